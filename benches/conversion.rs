@@ -0,0 +1,75 @@
+//! Compares the time spent per sample by output streams whose data callback produces the sample
+//! type of the device with streams that convert from `f32`.
+//!
+//! Run with `cargo bench --bench conversion`. The streams are driven by a `TestDevice`, which
+//! mixes their output into `f32` in the same way for all of them, so only the differences
+//! between the streams are meaningful.
+
+extern crate cpal;
+
+use std::time::{Duration, Instant};
+
+use cpal::test::TestDevice;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Dither, OutputCallbackInfo, Sample, SampleRate};
+
+const FRAMES: usize = 4096;
+const ITERATIONS: u32 = 500;
+
+fn write_ramp<T: Sample>(data: &mut [T]) {
+    for (i, sample) in data.iter_mut().enumerate() {
+        *sample = Sample::from(&((i % 64) as f32 / 64.0));
+    }
+}
+
+fn run<S: StreamTrait>(device: &TestDevice, stream: S) -> Duration {
+    stream.play().unwrap();
+    // Let the callbacks allocate their buffers before measuring.
+    device.process(FRAMES);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        device.process(FRAMES);
+        device.clear_recorded_output();
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let samples = (2 * FRAMES) as f64 * ITERATIONS as f64;
+    let nanos = elapsed.as_nanos() as f64 / samples;
+    println!("{:<32} {:>8.2} ns/sample", name, nanos);
+}
+
+fn main() {
+    let device = TestDevice::new(2, SampleRate(48_000));
+    let config = device.default_output_config().unwrap().config();
+
+    let stream = device
+        .build_output_stream(
+            &config,
+            |data: &mut [f32], _: &OutputCallbackInfo| write_ramp(data),
+            |err| panic!("{}", err),
+        )
+        .unwrap();
+    report("f32, no conversion", run(&device, stream));
+
+    let stream = device
+        .build_output_stream_dithered::<f32, _, _>(
+            &config,
+            Dither::Triangular,
+            |data: &mut [f32], _: &OutputCallbackInfo| write_ramp(data),
+            |err| panic!("{}", err),
+        )
+        .unwrap();
+    report("f32 dithered, no copy", run(&device, stream));
+
+    let stream = device
+        .build_output_stream_dithered::<i16, _, _>(
+            &config,
+            Dither::None,
+            |data: &mut [f32], _: &OutputCallbackInfo| write_ramp(data),
+            |err| panic!("{}", err),
+        )
+        .unwrap();
+    report("i16 converted from f32", run(&device, stream));
+}
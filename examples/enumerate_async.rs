@@ -0,0 +1,50 @@
+//! Enumerates the devices of the default host on a worker thread, as a UI would to stay
+//! responsive, and receives the results through a channel.
+
+extern crate anyhow;
+extern crate cpal;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::HostTrait;
+use cpal::DeviceInfo;
+
+fn main() -> Result<(), anyhow::Error> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let host = cpal::default_host();
+        // Querying the capabilities of every device can take hundreds of milliseconds.
+        let devices = host.enumerate_with_defaults();
+        sender.send(devices).ok();
+    });
+
+    // A UI would keep handling events while waiting.
+    let devices: Vec<DeviceInfo<cpal::Device>> = loop {
+        match receiver.recv_timeout(Duration::from_millis(16)) {
+            Ok(devices) => break devices?,
+            Err(mpsc::RecvTimeoutError::Timeout) => println!("Enumerating..."),
+            Err(mpsc::RecvTimeoutError::Disconnected) => anyhow::bail!("enumeration panicked"),
+        }
+    };
+
+    for info in &devices {
+        let mut defaults = Vec::new();
+        if info.is_default_input() {
+            defaults.push("default input");
+        }
+        if info.is_default_output() {
+            defaults.push("default output");
+        }
+        println!(
+            "{} ({:?}, {} in, {} out) {}",
+            info.name(),
+            info.direction(),
+            info.max_input_channels(),
+            info.max_output_channels(),
+            defaults.join(", "),
+        );
+    }
+    Ok(())
+}
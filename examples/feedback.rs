@@ -172,23 +172,23 @@ fn main() -> anyhow::Result<()> {
         "Attempting to build both streams with f32 samples and `{:?}`.",
         config
     );
-    let input_stream = input_device.build_input_stream(&config, input_data_fn, err_fn)?;
-    let output_stream = output_device.build_output_stream(&config, output_data_fn, err_fn)?;
-    println!("Successfully built streams.");
-
-    // Play the streams.
-    println!(
-        "Starting the input and output streams with `{}` milliseconds of latency.",
-        opt.latency
-    );
-    input_stream.play()?;
-    output_stream.play()?;
-
-    // Run for 3 seconds before closing.
-    println!("Playing for 3 seconds... ");
-    std::thread::sleep(std::time::Duration::from_secs(3));
-    drop(input_stream);
-    drop(output_stream);
+    {
+        let input_stream = input_device.build_input_stream(&config, input_data_fn, err_fn)?;
+        let output_stream = output_device.build_output_stream(&config, output_data_fn, err_fn)?;
+        println!("Successfully built streams.");
+
+        // Play the streams.
+        println!(
+            "Starting the input and output streams with `{}` milliseconds of latency.",
+            opt.latency
+        );
+        input_stream.play()?;
+        output_stream.play()?;
+
+        // Run for 3 seconds before closing the streams at the end of the block.
+        println!("Playing for 3 seconds... ");
+        std::thread::sleep(std::time::Duration::from_secs(3));
+    }
     println!("Done!");
     Ok(())
 }
@@ -110,29 +110,35 @@ fn main() -> Result<(), anyhow::Error> {
         eprintln!("an error occurred on stream: {}", err);
     };
 
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::F32 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| write_input_data::<f32, f32>(data, &writer_2),
-            err_fn,
-        )?,
-        cpal::SampleFormat::I16 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| write_input_data::<i16, i16>(data, &writer_2),
-            err_fn,
-        )?,
-        cpal::SampleFormat::U16 => device.build_input_stream(
-            &config.into(),
-            move |data, _: &_| write_input_data::<u16, i16>(data, &writer_2),
-            err_fn,
-        )?,
-    };
+    {
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| write_input_data::<f32, f32>(data, &writer_2),
+                err_fn,
+            )?,
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| write_input_data::<i16, i16>(data, &writer_2),
+                err_fn,
+            )?,
+            cpal::SampleFormat::U16 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| write_input_data::<u16, i16>(data, &writer_2),
+                err_fn,
+            )?,
+            cpal::SampleFormat::U8 => device.build_input_stream(
+                &config.into(),
+                move |data, _: &_| write_input_data::<u8, i16>(data, &writer_2),
+                err_fn,
+            )?,
+        };
 
-    stream.play()?;
+        stream.play()?;
 
-    // Let recording go for roughly three seconds.
-    std::thread::sleep(std::time::Duration::from_secs(3));
-    drop(stream);
+        // Let recording go for roughly three seconds, then stop it at the end of the block.
+        std::thread::sleep(std::time::Duration::from_secs(3));
+    }
     writer.lock().unwrap().take().unwrap().finalize()?;
     println!("Recording {} complete!", PATH);
     Ok(())
@@ -141,6 +147,7 @@ fn main() -> Result<(), anyhow::Error> {
 fn sample_format(format: cpal::SampleFormat) -> hound::SampleFormat {
     match format {
         cpal::SampleFormat::U16 => hound::SampleFormat::Int,
+        cpal::SampleFormat::U8 => hound::SampleFormat::Int,
         cpal::SampleFormat::I16 => hound::SampleFormat::Int,
         cpal::SampleFormat::F32 => hound::SampleFormat::Float,
     }
@@ -150,7 +157,11 @@ fn wav_spec_from_config(config: &cpal::SupportedStreamConfig) -> hound::WavSpec
     hound::WavSpec {
         channels: config.channels() as _,
         sample_rate: config.sample_rate().0 as _,
-        bits_per_sample: (config.sample_format().sample_size() * 8) as _,
+        // Unsigned samples are recorded as `i16`.
+        bits_per_sample: match config.sample_format() {
+            cpal::SampleFormat::U8 => 16,
+            sample_format => (sample_format.sample_size() * 8) as _,
+        },
         sample_format: sample_format(config.sample_format()),
     }
 }
@@ -0,0 +1,194 @@
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+use ring_buffer::{Consumer, Producer};
+use traits::StreamTrait;
+use {InputCallbackInfo, PauseStreamError, PlayStreamError, Sample, StreamConfig};
+
+// The state shared between the audio callback and the `AsyncInputStream`.
+pub(crate) struct Shared {
+    // The waker of the task waiting for samples, if any. The audio callback only ever tries to
+    // lock it, so that it never blocks.
+    waker: Mutex<Option<Waker>>,
+    dropped_frames: AtomicU64,
+}
+
+impl Shared {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Shared {
+            waker: Mutex::new(None),
+            dropped_frames: AtomicU64::new(0),
+        })
+    }
+}
+
+// The input data callback of an `AsyncInputStream`, pushing the captured frames that fit into
+// `producer` and waking the waiting task.
+pub(crate) fn input_callback<T>(
+    mut producer: Producer<T>,
+    shared: Arc<Shared>,
+    channels: usize,
+) -> impl FnMut(&[T], &InputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+{
+    move |data, _| {
+        let fitting = producer.free_len().min(data.len()) / channels * channels;
+        producer.push_slice(&data[..fitting]);
+        let dropped = (data.len() - fitting) / channels;
+        if dropped > 0 {
+            shared
+                .dropped_frames
+                .fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+        // Should the task be registering its waker right now, it checks for samples afterwards.
+        if let Ok(mut waker) = shared.waker.try_lock() {
+            if let Some(waker) = waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// An input stream whose captured samples are awaited as a `futures_core::Stream`, created via
+/// `DeviceTrait::build_input_stream_async`.
+///
+/// Each item holds the interleaved samples captured since the previous item, a whole number of
+/// frames. The stream of items never ends: drop the `AsyncInputStream` to stop capturing.
+///
+/// The audio callback hands samples over through a lock-free ring buffer holding a second of
+/// audio. When the task consuming the items falls behind that far, newly captured frames are
+/// dropped, rather than blocking the audio thread or queuing without bound, and counted by
+/// `dropped_frames`.
+pub struct AsyncInputStream<S, T> {
+    stream: S,
+    consumer: Consumer<T>,
+    shared: Arc<Shared>,
+}
+
+impl<S, T> AsyncInputStream<S, T>
+where
+    S: StreamTrait,
+    T: Sample,
+{
+    pub(crate) fn new(stream: S, consumer: Consumer<T>, shared: Arc<Shared>) -> Self {
+        AsyncInputStream {
+            stream,
+            consumer,
+            shared,
+        }
+    }
+
+    /// The underlying input stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// The number of frames dropped so far because the ring buffer was full.
+    pub fn dropped_frames(&self) -> u64 {
+        self.shared.dropped_frames.load(Ordering::Relaxed)
+    }
+}
+
+// Nothing is pinned structurally.
+impl<S, T> Unpin for AsyncInputStream<S, T> {}
+
+impl<S, T> Stream for AsyncInputStream<S, T>
+where
+    T: Sample,
+{
+    type Item = Vec<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.consumer.is_empty() {
+            *this.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+            // Samples pushed before the waker was registered did not wake the task.
+            if this.consumer.is_empty() {
+                return Poll::Pending;
+            }
+        }
+        let mut samples = vec![T::EQUILIBRIUM; this.consumer.len()];
+        let n = this.consumer.pop_slice(&mut samples);
+        samples.truncate(n);
+        Poll::Ready(Some(samples))
+    }
+}
+
+impl<S, T> StreamTrait for AsyncInputStream<S, T>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures_core::Stream;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use SampleRate;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn captured_samples_are_awaited() {
+        let input: Vec<f32> = (0..8).map(|i| i as f32 / 8.0).collect();
+        let device = TestDevice::new(2, SampleRate(4)).with_input(input.clone());
+        let config = device.default_input_config().unwrap().config();
+        let mut stream = device
+            .build_input_stream_async::<f32, _>(&config, |_| ())
+            .unwrap();
+        stream.play().unwrap();
+
+        let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Pending);
+
+        device.process(3);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        let item = Pin::new(&mut stream).poll_next(&mut cx);
+        assert_eq!(item, Poll::Ready(Some(input[..6].to_vec())));
+
+        // The ring buffer holds a second, i.e. four frames, so two of the next six are dropped.
+        device.process(6);
+        assert_eq!(stream.dropped_frames(), 2);
+        let item = Pin::new(&mut stream).poll_next(&mut cx);
+        assert_eq!(
+            item.map(|item| item.map(|item| item.len())),
+            Poll::Ready(Some(8))
+        );
+    }
+}
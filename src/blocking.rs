@@ -0,0 +1,327 @@
+//! Blocking read and write access to streams for programs that would rather not deal with
+//! callbacks.
+//!
+//! A blocking stream is an ordinary stream whose data callback moves samples between the device
+//! and an intermediate queue. The user reads from or writes to the queue from their own thread,
+//! either blocking until the requested number of samples could be transferred or transferring as
+//! many as possible and returning immediately, depending on the `BlockingMode`.
+//!
+//! The queue is protected by a mutex which is briefly locked by the audio callback. This is fine
+//! for simple programs but applications with strict latency requirements should prefer building
+//! their streams with a callback instead.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+use traits::StreamTrait;
+use {BufferSize, PauseStreamError, PlayStreamError, Sample, StreamConfig, WouldBlock};
+
+/// Whether reads and writes on a blocking stream wait for the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlockingMode {
+    /// Block the calling thread until all of the given samples have been transferred.
+    Block,
+    /// Transfer as many samples as possible without waiting, returning `WouldBlock` if none could
+    /// be transferred.
+    NonBlocking,
+}
+
+/// The default capacity of the intermediate queue in frames, used when the stream was not built
+/// with a fixed buffer size.
+const DEFAULT_CAPACITY_FRAMES: usize = 4096;
+
+// The queue shared between the user's thread and the audio callback.
+pub(crate) struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    // Notified by the audio callback each time samples are pushed to or popped from the queue.
+    changed: Condvar,
+    // The maximum number of samples held by the queue.
+    capacity: usize,
+}
+
+impl<T> Shared<T>
+where
+    T: Sample,
+{
+    // Creates the queue for a stream with the given configuration.
+    pub(crate) fn new(config: &StreamConfig) -> Arc<Self> {
+        let frames = match config.buffer_size {
+            BufferSize::Fixed(frames) => (frames as usize * 2).max(DEFAULT_CAPACITY_FRAMES),
+            BufferSize::Default => DEFAULT_CAPACITY_FRAMES,
+        };
+        let capacity = frames * config.channels as usize;
+        Arc::new(Shared {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            changed: Condvar::new(),
+            capacity,
+        })
+    }
+
+    // Fills `output` from the queue, writing silence in place of any samples that are missing.
+    pub(crate) fn pop_into(&self, output: &mut [T]) {
+        let mut queue = self.queue.lock().unwrap();
+        for sample in output.iter_mut() {
+            *sample = queue.pop_front().unwrap_or(T::EQUILIBRIUM);
+        }
+        self.changed.notify_all();
+    }
+
+    // Pushes as much of `input` to the queue as fits, dropping the remainder.
+    pub(crate) fn push_from(&self, input: &[T]) {
+        let mut queue = self.queue.lock().unwrap();
+        let space = self.capacity - queue.len();
+        queue.extend(input.iter().take(space));
+        self.changed.notify_all();
+    }
+
+    // Pushes `samples` to the queue on behalf of the user, returning the number of samples pushed.
+    fn write(&self, samples: &[T], mode: BlockingMode) -> Result<usize, WouldBlock> {
+        let mut written = 0;
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            let space = self.capacity - queue.len();
+            let n = space.min(samples.len() - written);
+            queue.extend(&samples[written..written + n]);
+            written += n;
+            if written == samples.len() {
+                return Ok(written);
+            }
+            match mode {
+                BlockingMode::Block => queue = self.changed.wait(queue).unwrap(),
+                BlockingMode::NonBlocking if written == 0 => return Err(WouldBlock),
+                BlockingMode::NonBlocking => return Ok(written),
+            }
+        }
+    }
+
+    // Pops samples from the queue into `samples` on behalf of the user, returning the number of
+    // samples popped.
+    fn read(&self, samples: &mut [T], mode: BlockingMode) -> Result<usize, WouldBlock> {
+        let mut read = 0;
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            while read < samples.len() {
+                match queue.pop_front() {
+                    Some(sample) => samples[read] = sample,
+                    None => break,
+                }
+                read += 1;
+            }
+            if read == samples.len() {
+                return Ok(read);
+            }
+            match mode {
+                BlockingMode::Block => queue = self.changed.wait(queue).unwrap(),
+                BlockingMode::NonBlocking if read == 0 => return Err(WouldBlock),
+                BlockingMode::NonBlocking => return Ok(read),
+            }
+        }
+    }
+}
+
+/// An output stream that is fed by writing samples to it, rather than from a callback.
+///
+/// Created via `DeviceTrait::build_blocking_output_stream`. Underruns are filled with silence.
+pub struct BlockingOutputStream<T, S> {
+    stream: S,
+    shared: Arc<Shared<T>>,
+    mode: BlockingMode,
+}
+
+/// An input stream whose samples are retrieved by reading from it, rather than from a callback.
+///
+/// Created via `DeviceTrait::build_blocking_input_stream`. Samples captured while the queue is
+/// full are discarded.
+pub struct BlockingInputStream<T, S> {
+    stream: S,
+    shared: Arc<Shared<T>>,
+    mode: BlockingMode,
+}
+
+impl<T, S> BlockingOutputStream<T, S>
+where
+    T: Sample,
+{
+    pub(crate) fn new(stream: S, shared: Arc<Shared<T>>) -> Self {
+        BlockingOutputStream {
+            stream,
+            shared,
+            mode: BlockingMode::Block,
+        }
+    }
+
+    /// The mode used by `write`. Defaults to `BlockingMode::Block`.
+    pub fn mode(&self) -> BlockingMode {
+        self.mode
+    }
+
+    /// Change the mode used by `write`.
+    pub fn set_mode(&mut self, mode: BlockingMode) {
+        self.mode = mode;
+    }
+
+    /// Queue interleaved samples for playback, returning the number of samples that were queued.
+    ///
+    /// In `BlockingMode::Block` this waits until all of `samples` have been queued. Note that it
+    /// will wait forever if the stream is paused while the queue is full.
+    pub fn write(&self, samples: &[T]) -> Result<usize, WouldBlock> {
+        self.shared.write(samples, self.mode)
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<T, S> BlockingInputStream<T, S>
+where
+    T: Sample,
+{
+    pub(crate) fn new(stream: S, shared: Arc<Shared<T>>) -> Self {
+        BlockingInputStream {
+            stream,
+            shared,
+            mode: BlockingMode::Block,
+        }
+    }
+
+    /// The mode used by `read`. Defaults to `BlockingMode::Block`.
+    pub fn mode(&self) -> BlockingMode {
+        self.mode
+    }
+
+    /// Change the mode used by `read`.
+    pub fn set_mode(&mut self, mode: BlockingMode) {
+        self.mode = mode;
+    }
+
+    /// Read captured interleaved samples into `samples`, returning the number of samples read.
+    ///
+    /// In `BlockingMode::Block` this waits until `samples` has been filled. Note that it will
+    /// wait forever if the stream is paused while the queue is empty.
+    pub fn read(&self, samples: &mut [T]) -> Result<usize, WouldBlock> {
+        self.shared.read(samples, self.mode)
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<T, S> StreamTrait for BlockingOutputStream<T, S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+impl<T, S> StreamTrait for BlockingInputStream<T, S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockingMode, Shared};
+    use std::thread;
+    use {BufferSize, SampleRate, StreamConfig, WouldBlock};
+
+    fn config() -> StreamConfig {
+        StreamConfig {
+            channels: 1,
+            sample_rate: SampleRate(44_100),
+            buffer_size: BufferSize::Default,
+        }
+    }
+
+    #[test]
+    fn output_underrun_is_silent() {
+        let shared = Shared::<i16>::new(&config());
+        assert_eq!(shared.write(&[1, 2, 3], BlockingMode::Block), Ok(3));
+        let mut output = [7i16; 5];
+        shared.pop_into(&mut output);
+        assert_eq!(output, [1, 2, 3, 0, 0]);
+    }
+
+    #[test]
+    fn non_blocking_write_when_full() {
+        let shared = Shared::<f32>::new(&config());
+        let samples = vec![0.5; shared.capacity + 10];
+        let mode = BlockingMode::NonBlocking;
+        assert_eq!(shared.write(&samples, mode), Ok(shared.capacity));
+        assert_eq!(shared.write(&samples, mode), Err(WouldBlock));
+    }
+
+    #[test]
+    fn non_blocking_read_when_empty() {
+        let shared = Shared::<f32>::new(&config());
+        let mut samples = [0.0; 4];
+        let mode = BlockingMode::NonBlocking;
+        assert_eq!(shared.read(&mut samples, mode), Err(WouldBlock));
+        shared.push_from(&[0.25, 0.5]);
+        assert_eq!(shared.read(&mut samples, mode), Ok(2));
+        assert_eq!(&samples[..2], &[0.25, 0.5]);
+    }
+
+    #[test]
+    fn blocking_write_waits_for_callback() {
+        let shared = Shared::<u16>::new(&config());
+        let samples = vec![1; shared.capacity * 3];
+        let consumer = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let mut output = vec![0; shared.capacity];
+                let mut consumed = 0;
+                while consumed < shared.capacity * 3 {
+                    shared.pop_into(&mut output);
+                    consumed += output.iter().filter(|&&s| s == 1).count();
+                }
+            })
+        };
+        assert_eq!(
+            shared.write(&samples, BlockingMode::Block),
+            Ok(samples.len())
+        );
+        consumer.join().unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use {ChannelCount, FrameCount, InputCallbackInfo, OutputCallbackInfo, Sample};
+
+// Regroups the buffers of a stream into blocks of a fixed number of samples, for
+// `StreamOptions::callback_buffer_size`.
+//
+// Input samples are held back until a block is complete, and output samples produced by the
+// data callback are held back until the host asks for them, so no sample is ever dropped
+// whether the buffers of the host are longer or shorter than a block.
+pub(crate) struct Blocks<T> {
+    // The number of samples per block, or `None` to pass the buffers of the host through.
+    len: Option<usize>,
+    // Input: the captured samples that do not yet make up a block.
+    // Output: the last block produced by the data callback, played from `start` on.
+    buffer: Vec<T>,
+    // Only used by output streams.
+    start: usize,
+}
+
+impl<T> Blocks<T>
+where
+    T: Sample,
+{
+    // Blocks of `frames` frames of `channels` samples each.
+    pub(crate) fn new(channels: ChannelCount, frames: Option<FrameCount>) -> Self {
+        let len = frames.map(|frames| frames as usize * channels as usize);
+        Blocks {
+            len,
+            buffer: Vec::with_capacity(len.unwrap_or(0)),
+            start: 0,
+        }
+    }
+
+    // Passes each block completed by the host buffer `data` to `data_callback`.
+    pub(crate) fn input<D>(&mut self, data: &[T], info: &InputCallbackInfo, data_callback: &mut D)
+    where
+        D: FnMut(&[T], &InputCallbackInfo),
+    {
+        let len = match self.len {
+            Some(len) => len,
+            None => return data_callback(data, info),
+        };
+        let mut data = data;
+        // Complete the block held back from the previous host buffer first.
+        if !self.buffer.is_empty() {
+            let n = data.len().min(len - self.buffer.len());
+            self.buffer.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.buffer.len() < len {
+                return;
+            }
+            data_callback(&self.buffer, info);
+            self.buffer.clear();
+        }
+        // Then pass the whole blocks of the host buffer without copying them.
+        let mut blocks = data.chunks_exact(len);
+        for block in &mut blocks {
+            data_callback(block, info);
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    // Fills the host buffer `data` with the blocks produced by `data_callback`.
+    pub(crate) fn output<D>(
+        &mut self,
+        data: &mut [T],
+        info: &OutputCallbackInfo,
+        data_callback: &mut D,
+    ) where
+        D: FnMut(&mut [T], &OutputCallbackInfo),
+    {
+        let len = match self.len {
+            Some(len) => len,
+            None => return data_callback(data, info),
+        };
+        let mut data = data;
+        while !data.is_empty() {
+            if self.start == self.buffer.len() {
+                self.buffer.resize(len, T::EQUILIBRIUM);
+                data_callback(&mut self.buffer, info);
+                self.start = 0;
+            }
+            let n = data.len().min(self.buffer.len() - self.start);
+            let (head, tail) = data.split_at_mut(n);
+            head.copy_from_slice(&self.buffer[self.start..self.start + n]);
+            self.start += n;
+            data = tail;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {InputCallbackInfo, OutputCallbackInfo, SampleRate, StreamOptions};
+
+    fn options(frames: u32) -> StreamOptions {
+        StreamOptions {
+            callback_buffer_size: Some(frames),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn input_blocks() {
+        let input: Vec<f32> = (0..40).map(|i| i as f32 / 64.0).collect();
+        let device = TestDevice::new(2, SampleRate(48_000)).with_input(input.clone());
+        let config = device.default_input_config().unwrap().config();
+        let blocks = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let blocks = blocks.clone();
+            device
+                .build_input_stream_with_options(
+                    &config,
+                    &options(4),
+                    move |data: &[f32], _: &InputCallbackInfo| {
+                        blocks.lock().unwrap().push(data.to_vec())
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        // Host buffers both shorter and longer than a block.
+        for &frames in &[3, 1, 9, 2, 5] {
+            device.process(frames);
+        }
+        let blocks = blocks.lock().unwrap();
+        assert_eq!(blocks.len(), 5);
+        assert!(blocks.iter().all(|block| block.len() == 8));
+        assert_eq!(blocks.concat(), &input[..40]);
+    }
+
+    #[test]
+    fn output_blocks() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let lens = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let lens = lens.clone();
+            let mut next = 0;
+            device
+                .build_output_stream_with_options(
+                    &config,
+                    &options(4),
+                    move |data: &mut [f32], _: &OutputCallbackInfo| {
+                        lens.lock().unwrap().push(data.len());
+                        for sample in data.iter_mut() {
+                            *sample = next as f32 / 64.0;
+                            next += 1;
+                        }
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        for &frames in &[3, 1, 9, 2, 5] {
+            device.process(frames);
+        }
+        let expected: Vec<f32> = (0..20).map(|i| i as f32 / 64.0).collect();
+        assert_eq!(device.recorded_output(), expected);
+        assert_eq!(*lens.lock().unwrap(), vec![4; 5]);
+    }
+}
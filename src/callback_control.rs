@@ -0,0 +1,279 @@
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle, Thread};
+use std::time::Duration;
+
+use traits::StreamTrait;
+use {BackendSpecificError, BuildStreamError, PauseStreamError, PlayStreamError, StreamConfig};
+/// Returned by the data callback of a stream built with `DeviceTrait::build_input_stream_with_control`
+/// or `DeviceTrait::build_output_stream_with_control` to indicate whether the stream should keep
+/// running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CallbackControl {
+    /// Keep invoking the data callback.
+    Continue,
+    /// Stop invoking the data callback. Output streams play silence from then on.
+    Stop,
+}
+
+// Tracks whether the data callback has asked for the stream to stop.
+//
+// The audio callback only sets `flag` and unparks the thread owning the stream, so that it never
+// blocks. The owner thread then pauses the stream and notifies `wait` through `paused`.
+pub(crate) struct Finished {
+    flag: AtomicBool,
+    owner: Thread,
+    // The outcome of pausing the stream, once the owner thread has done so.
+    paused: Mutex<Option<Result<(), PauseStreamError>>>,
+    condvar: Condvar,
+}
+
+impl Finished {
+    pub(crate) fn is_set(&self) -> bool {
+        self.flag.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set(&self) {
+        self.flag.store(true, Ordering::Release);
+        self.owner.unpark();
+    }
+
+    fn notify(&self, result: Result<(), PauseStreamError>) {
+        *self.paused.lock().unwrap() = Some(result);
+        self.condvar.notify_all();
+    }
+}
+
+// Runs on the owner thread, given the stream.
+type Command<S> = Box<dyn FnOnce(&S) + Send>;
+
+/// A stream whose data callback can stop the stream by returning `CallbackControl::Stop`.
+///
+/// Once the callback has returned `Stop` it is never invoked again, and the stream is paused
+/// shortly after. Use `wait` to block until this happens, e.g. to wait for a sound to finish
+/// playing before dropping the stream.
+///
+/// As streams cannot be paused from their audio thread, and are not `Send` on every host, the
+/// stream is built and owned by a dedicated thread, to which the methods of `ControlledStream`
+/// are forwarded.
+pub struct ControlledStream<S> {
+    commands: Option<mpsc::Sender<Command<S>>>,
+    thread: Option<JoinHandle<()>>,
+    finished: Arc<Finished>,
+    // The stream is only accessed from its owner thread.
+    stream: PhantomData<S>,
+}
+
+impl<S> ControlledStream<S>
+where
+    S: StreamTrait + 'static,
+{
+    // Spawns the owner thread, on which `build` builds the stream given the `Finished` to signal
+    // from its data callback.
+    pub(crate) fn spawn<B>(build: B) -> Result<Self, BuildStreamError>
+    where
+        B: FnOnce(Arc<Finished>) -> Result<S, BuildStreamError> + Send + 'static,
+    {
+        let (commands, commands_rx) = mpsc::channel::<Command<S>>();
+        let (built, built_rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("cpal_controlled_stream".to_owned())
+            .spawn(move || {
+                let finished = Arc::new(Finished {
+                    flag: AtomicBool::new(false),
+                    owner: thread::current(),
+                    paused: Mutex::new(None),
+                    condvar: Condvar::new(),
+                });
+                match build(finished.clone()) {
+                    Ok(stream) => {
+                        let _ = built.send(Ok(finished.clone()));
+                        run(stream, commands_rx, &finished);
+                    }
+                    Err(err) => {
+                        let _ = built.send(Err(err));
+                    }
+                }
+            })
+            .map_err(spawn_error)?;
+        match built_rx.recv() {
+            Ok(Ok(finished)) => Ok(ControlledStream {
+                commands: Some(commands),
+                thread: Some(thread),
+                finished,
+                stream: PhantomData,
+            }),
+            Ok(Err(err)) => Err(err),
+            Err(_) => {
+                let description = "the thread owning the stream panicked".to_string();
+                Err(BackendSpecificError { description }.into())
+            }
+        }
+    }
+
+    /// Whether the data callback has returned `CallbackControl::Stop`.
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_set()
+    }
+
+    /// Block until the data callback returns `CallbackControl::Stop` and the stream is paused.
+    ///
+    /// Returns the error of pausing the stream, if any, to the first caller.
+    pub fn wait(&self) -> Result<(), PauseStreamError> {
+        let mut paused = self.finished.paused.lock().unwrap();
+        while paused.is_none() {
+            paused = self.finished.condvar.wait(paused).unwrap();
+        }
+        paused.replace(Ok(())).unwrap()
+    }
+
+    /// Like `wait`, but gives up after `timeout`.
+    ///
+    /// Returns `Ok(true)` if the stream finished and was paused, or `Ok(false)` on timeout.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool, PauseStreamError> {
+        let paused = self.finished.paused.lock().unwrap();
+        let (mut paused, _) = self
+            .finished
+            .condvar
+            .wait_timeout_while(paused, timeout, |paused| paused.is_none())
+            .unwrap();
+        match paused.as_mut() {
+            Some(result) => mem::replace(result, Ok(())).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    // Runs `f` with the stream on the owner thread.
+    fn call<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&S) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, reply_rx) = mpsc::channel();
+        let command: Command<S> = Box::new(move |stream| {
+            let _ = reply.send(f(stream));
+        });
+        let commands = self.commands.as_ref().expect("the stream was dropped");
+        commands.send(command).expect("the stream thread panicked");
+        self.finished.owner.unpark();
+        reply_rx.recv().expect("the stream thread panicked")
+    }
+}
+
+// The loop of the owner thread, running commands and pausing the stream once the data callback
+// returns `Stop`, until the `ControlledStream` is dropped.
+fn run<S>(stream: S, commands: mpsc::Receiver<Command<S>>, finished: &Finished)
+where
+    S: StreamTrait,
+{
+    let mut paused = false;
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(command) => command(&stream),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        if !paused && finished.is_set() {
+            paused = true;
+            finished.notify(stream.pause());
+        }
+        thread::park();
+    }
+}
+
+fn spawn_error(err: io::Error) -> BuildStreamError {
+    let description = format!("failed to spawn the thread owning the stream: {}", err);
+    BackendSpecificError { description }.into()
+}
+
+impl<S> Drop for ControlledStream<S> {
+    fn drop(&mut self) {
+        // Disconnecting the commands stops the owner thread, which drops the stream.
+        self.commands = None;
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<S> StreamTrait for ControlledStream<S>
+where
+    S: StreamTrait + 'static,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.call(|stream: &S| stream.play())
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.call(|stream: &S| stream.pause())
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.call(|stream: &S| stream.config())
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.call(|stream: &S| stream.frame_position())
+    }
+
+    fn is_playing(&self) -> bool {
+        !self.is_finished() && self.call(|stream: &S| stream.is_playing())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {CallbackControl, OutputCallbackInfo, SampleRate};
+
+    #[test]
+    fn stop_ends_playback() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_with_control(
+                &config,
+                |data: &mut [f32], _: &OutputCallbackInfo| {
+                    data.iter_mut().for_each(|s| *s = 0.5);
+                    CallbackControl::Stop
+                },
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        assert!(stream.is_playing());
+        device.process(2);
+        assert!(stream.is_finished());
+        assert!(stream.wait_timeout(Duration::from_secs(1)).unwrap());
+        assert!(!stream.is_playing());
+        device.process(2);
+        assert_eq!(device.recorded_output(), vec![0.5, 0.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn wait_times_out_while_running() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_with_control(
+                &config,
+                |_: &mut [f32], _: &OutputCallbackInfo| CallbackControl::Continue,
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(2);
+        assert!(!stream.wait_timeout(Duration::from_millis(10)).unwrap());
+        assert!(stream.is_playing());
+        assert_eq!(stream.frame_position(), 2);
+    }
+}
@@ -0,0 +1,314 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use traits::StreamTrait;
+use {
+    BuildStreamError, ChannelCount, InvalidMatrixError, PauseStreamError, PlayStreamError, Sample,
+    StreamConfig,
+};
+
+/// Describes how the channels of the user's audio are mapped to the channels of a stream.
+///
+/// Used with `DeviceTrait::build_input_stream_with_channel_map` and
+/// `DeviceTrait::build_output_stream_with_channel_map` so that audio can be produced or
+/// consumed with a different number of channels than the device uses. Mixing is performed with
+/// `f32` intermediate values.
+///
+/// Channels are assumed to be in the usual WAVE order, e.g. `L, R, C, LFE, Ls, Rs` for 5.1.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelMap {
+    /// Mixes using a matrix chosen for the pair of channel counts:
+    ///
+    /// - Matching channel counts are passed through unchanged.
+    /// - Mono is copied to every channel.
+    /// - Stereo is averaged down to mono.
+    /// - 5.1 is downmixed to stereo with the ITU-R BS.775 coefficients, dropping the LFE channel.
+    /// - Otherwise, each channel is copied to the channel of the same index, if any.
+    Default,
+    /// Repeats the source channels across the target channels, e.g. `L, R` becomes
+    /// `L, R, L, R, L, R` when upmixing from stereo to 6 channels. Surplus source channels are
+    /// dropped when downmixing.
+    Duplicate,
+    /// A matrix of gains where `matrix[source][target]` is the gain applied to the source channel
+    /// when mixing into the target channel, like for `RoutedStream::set_output_matrix`.
+    ///
+    /// The matrix must have one row per source channel and one column per target channel.
+    Custom(Vec<Vec<f32>>),
+}
+
+impl ChannelMap {
+    /// The mixing matrix for mixing `source` channels into `target` channels, where
+    /// `matrix[source][target]` is the gain applied to the source channel when mixing into the
+    /// target channel.
+    ///
+    /// Returns `BuildStreamError::InvalidArgument` if either channel count is zero or if a
+    /// `Custom` matrix has the wrong dimensions.
+    pub fn matrix(
+        &self,
+        source: ChannelCount,
+        target: ChannelCount,
+    ) -> Result<Vec<Vec<f32>>, BuildStreamError> {
+        if source == 0 || target == 0 {
+            return Err(BuildStreamError::InvalidArgument);
+        }
+        let (source, target) = (source as usize, target as usize);
+        let identity = |s: usize, t: usize| if s == t { 1.0 } else { 0.0 };
+        let matrix = match *self {
+            ChannelMap::Default => match (source, target) {
+                (1, _) => vec![vec![1.0; target]],
+                (2, 1) => vec![vec![0.5], vec![0.5]],
+                // L, R, C, LFE, Ls, Rs
+                (6, 2) => vec![
+                    vec![1.0, 0.0],
+                    vec![0.0, 1.0],
+                    vec![FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+                    vec![0.0, 0.0],
+                    vec![FRAC_1_SQRT_2, 0.0],
+                    vec![0.0, FRAC_1_SQRT_2],
+                ],
+                _ => (0..source)
+                    .map(|s| (0..target).map(|t| identity(s, t)).collect())
+                    .collect(),
+            },
+            ChannelMap::Duplicate => (0..source)
+                .map(|s| (0..target).map(|t| identity(s, t % source)).collect())
+                .collect(),
+            ChannelMap::Custom(ref matrix) => {
+                if matrix.len() != source || matrix.iter().any(|row| row.len() != target) {
+                    return Err(BuildStreamError::InvalidArgument);
+                }
+                matrix.clone()
+            }
+        };
+        Ok(matrix)
+    }
+}
+
+// Mixes interleaved frames of `source` into `target` with the given `matrix[source][target]`.
+//
+// Both buffers must contain the same number of frames.
+pub(crate) fn mix<S, T>(matrix: &[Vec<f32>], source: &[S], target: &mut [T])
+where
+    S: Sample,
+    T: Sample,
+{
+    let source_channels = matrix.len();
+    let target_channels = matrix[0].len();
+    let source_frames = source.chunks(source_channels);
+    let target_frames = target.chunks_mut(target_channels);
+    for (source_frame, target_frame) in source_frames.zip(target_frames) {
+        for (t, target_sample) in target_frame.iter_mut().enumerate() {
+            let mixed: f32 = source_frame
+                .iter()
+                .zip(matrix)
+                .map(|(sample, gains)| sample.to_f32() * gains[t])
+                .sum();
+            *target_sample = T::from(&mixed);
+        }
+    }
+}
+
+// A mixing matrix that is replaced from other threads while the audio callback mixes with it.
+//
+// The matrix is double-buffered: the new matrix is stored in `pending` and swapped with the one
+// the audio callback uses, which then waits in `pending` to be freed by the next `set`. The audio
+// callback therefore never blocks nor deallocates.
+pub(crate) struct SharedMatrix {
+    // Whether `pending` holds a matrix that the audio callback has not picked up yet.
+    updated: AtomicBool,
+    pending: Mutex<Vec<Vec<f32>>>,
+}
+
+impl SharedMatrix {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(SharedMatrix {
+            updated: AtomicBool::new(false),
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn set(&self, matrix: Vec<Vec<f32>>) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = matrix;
+        self.updated.store(true, Ordering::Release);
+    }
+
+    // Called by the audio callback with the matrix it mixes with, which is swapped with the
+    // pending one, if any, unless `set` is running concurrently.
+    pub(crate) fn update(&self, current: &mut Vec<Vec<f32>>) {
+        if !self.updated.load(Ordering::Acquire) {
+            return;
+        }
+        if let Ok(mut pending) = self.pending.try_lock() {
+            mem::swap(current, &mut *pending);
+            self.updated.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// An output stream whose audio is mixed into the channels of the device through a routing
+/// matrix that can be replaced while the stream runs.
+///
+/// Created via `DeviceTrait::build_output_stream_routed`.
+pub struct RoutedStream<S> {
+    stream: S,
+    matrix: Arc<SharedMatrix>,
+    // The channels of the audio produced by the data callback.
+    source_channels: ChannelCount,
+}
+
+impl<S> RoutedStream<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(stream: S, matrix: Arc<SharedMatrix>, source_channels: ChannelCount) -> Self {
+        RoutedStream {
+            stream,
+            matrix,
+            source_channels,
+        }
+    }
+
+    /// Replace the routing matrix, where `matrix[source][target]` is the gain applied to the
+    /// source channel, produced by the data callback, when mixing into the target channel of the
+    /// device.
+    ///
+    /// The matrix must have one row per source channel and one column per channel of the stream's
+    /// config. It takes effect from the next buffer on, without blocking the audio callback.
+    pub fn set_output_matrix(&self, matrix: &[Vec<f32>]) -> Result<(), InvalidMatrixError> {
+        let target_channels = self.stream.config().channels as usize;
+        if matrix.len() != self.source_channels as usize
+            || matrix.iter().any(|row| row.len() != target_channels)
+        {
+            return Err(InvalidMatrixError);
+        }
+        self.matrix.set(matrix.to_vec());
+        Ok(())
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S> StreamTrait for RoutedStream<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{mix, ChannelMap};
+    use std::f32::consts::FRAC_1_SQRT_2;
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {BuildStreamError, InvalidMatrixError, OutputCallbackInfo, SampleRate};
+
+    #[test]
+    fn default_upmix_mono() {
+        let matrix = ChannelMap::Default.matrix(1, 6).unwrap();
+        let mut out = [0.0f32; 12];
+        mix(&matrix, &[0.5f32, -0.25], &mut out);
+        assert_eq!(&out[..6], &[0.5; 6]);
+        assert_eq!(&out[6..], &[-0.25; 6]);
+    }
+
+    #[test]
+    fn default_downmix_stereo() {
+        let matrix = ChannelMap::Default.matrix(2, 1).unwrap();
+        let mut out = [0.0f32; 2];
+        mix(&matrix, &[1.0f32, 0.0, 0.25, 0.75], &mut out);
+        assert_eq!(out, [0.5, 0.5]);
+    }
+
+    #[test]
+    fn default_downmix_5_1() {
+        let matrix = ChannelMap::Default.matrix(6, 2).unwrap();
+        // L, R, C, LFE, Ls, Rs
+        let frame = [0.1f32, 0.2, 0.3, 0.9, 0.4, 0.5];
+        let mut out = [0.0f32; 2];
+        mix(&matrix, &frame, &mut out);
+        let left = 0.1 + FRAC_1_SQRT_2 * 0.3 + FRAC_1_SQRT_2 * 0.4;
+        let right = 0.2 + FRAC_1_SQRT_2 * 0.3 + FRAC_1_SQRT_2 * 0.5;
+        assert!((out[0] - left).abs() < 1e-6);
+        assert!((out[1] - right).abs() < 1e-6);
+    }
+
+    #[test]
+    fn duplicate_upmix_stereo() {
+        let matrix = ChannelMap::Duplicate.matrix(2, 6).unwrap();
+        let mut out = [0i16; 6];
+        mix(&matrix, &[i16::MAX, 0], &mut out);
+        assert_eq!(out, [i16::MAX, 0, i16::MAX, 0, i16::MAX, 0]);
+    }
+
+    #[test]
+    fn custom_matrix() {
+        let map = ChannelMap::Custom(vec![vec![0.0, 1.0, 0.5], vec![1.0, 0.0, 0.5]]);
+        let matrix = map.matrix(2, 3).unwrap();
+        let mut out = [0.0f32; 3];
+        mix(&matrix, &[0.25f32, 0.75], &mut out);
+        assert_eq!(out, [0.75, 0.25, 0.5]);
+        assert!(matches!(
+            map.matrix(3, 2),
+            Err(BuildStreamError::InvalidArgument)
+        ));
+
+        // The same orientation as `RoutedStream::set_output_matrix`, see `routed_output_stream`.
+        let matrix = ChannelMap::Custom(vec![vec![0.0, 0.5]])
+            .matrix(1, 2)
+            .unwrap();
+        let mut out = [0.0f32; 2];
+        mix(&matrix, &[0.5f32], &mut out);
+        assert_eq!(out, [0.0, 0.25]);
+    }
+
+    #[test]
+    fn routed_output_stream() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_routed::<f32, _, _>(
+                &config,
+                1,
+                |data: &mut [f32], _: &OutputCallbackInfo| data.iter_mut().for_each(|s| *s = 0.5),
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(2);
+        assert_eq!(device.recorded_output(), [0.5; 4]);
+
+        stream.set_output_matrix(&[vec![0.0, 0.5]]).unwrap();
+        device.process(2);
+        assert_eq!(&device.recorded_output()[4..], &[0.0, 0.25, 0.0, 0.25]);
+
+        assert_eq!(
+            stream.set_output_matrix(&[vec![1.0], vec![1.0]]),
+            Err(InvalidMatrixError)
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use samples_formats;
+use traits::StreamTrait;
+use {OutputCallbackInfo, PauseStreamError, PlayStreamError, Sample, StreamConfig};
+
+/// How `DeviceTrait::build_output_stream_clipped` converts `f32` samples outside of
+/// `[-1.0, 1.0]` to the sample type of the stream.
+///
+/// This is a behavior choice rather than a correctness fix: clipping bounds the distortion of an
+/// overly loud signal, while wrapping turns it into full scale noise but reproduces the behavior
+/// of a plain integer cast, as other software may expect. Samples within the range are converted
+/// like `Sample::from` either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClipMode {
+    /// Out-of-range samples wrap around to the opposite end of the integer range. Floating point
+    /// samples are passed through unchanged.
+    Wrap,
+    /// Samples are hard-clipped to `[-1.0, 1.0]` before being quantized, whatever the sample
+    /// type. This is the default.
+    #[default]
+    Clip,
+}
+
+impl ClipMode {
+    /// Convert `sample` to `T` according to this mode.
+    pub(crate) fn convert<T>(self, sample: f32) -> T
+    where
+        T: Sample,
+    {
+        match self {
+            ClipMode::Clip => T::from_f32(sample.clamp(-1.0, 1.0)),
+            ClipMode::Wrap if T::FORMAT.is_float() => T::from_f32(sample),
+            ClipMode::Wrap => {
+                // Scale like `f32::to_i16`, then wrap rather than saturate.
+                let scale = if sample >= 0.0 {
+                    i16::MAX as f32
+                } else {
+                    -(i16::MIN as f32)
+                };
+                let wrapped = (sample * scale) as i64 as i16;
+                T::from(&wrapped)
+            }
+        }
+    }
+}
+
+// Wraps an `f32` output data callback into one for `T`, converting its output with `clip_mode`
+// and adding the number of out-of-range samples to `clipped`.
+pub(crate) fn clip_output<T, D>(
+    clip_mode: ClipMode,
+    clipped: Arc<AtomicU64>,
+    mut data_callback: D,
+) -> impl FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+    D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+{
+    let mut buffer = Vec::new();
+    move |data, info| {
+        let mut out_of_range = 0;
+        // Float samples are produced into the buffer of the host and clipped in place.
+        if let Some(data) = samples_formats::as_f32_slice_mut(data) {
+            data_callback(data, info);
+            for sample in data.iter_mut() {
+                if !(-1.0..=1.0).contains(sample) {
+                    out_of_range += 1;
+                }
+                *sample = clip_mode.convert(*sample);
+            }
+        } else {
+            buffer.clear();
+            buffer.resize(data.len(), 0.0);
+            data_callback(&mut buffer, info);
+            for (sample, &value) in data.iter_mut().zip(&buffer) {
+                if !(-1.0..=1.0).contains(&value) {
+                    out_of_range += 1;
+                }
+                *sample = clip_mode.convert(value);
+            }
+        }
+        if out_of_range > 0 {
+            clipped.fetch_add(out_of_range, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A stream counting the `f32` samples that were out of range, for a clip indicator.
+///
+/// Created via `DeviceTrait::build_output_stream_clipped`.
+pub struct ClippedStream<S> {
+    stream: S,
+    clipped: Arc<AtomicU64>,
+}
+
+impl<S> ClippedStream<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(stream: S, clipped: Arc<AtomicU64>) -> Self {
+        ClippedStream { stream, clipped }
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// The number of samples produced outside of `[-1.0, 1.0]` since the stream was built, which
+    /// were clipped or wrapped according to the `ClipMode`.
+    ///
+    /// A clip indicator can compare this with the value it last read.
+    pub fn clipped_samples(&self) -> u64 {
+        self.clipped.load(Ordering::Relaxed)
+    }
+}
+
+impl<S> StreamTrait for ClippedStream<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clip_output, ClipMode};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {OutputCallbackInfo, OutputStreamTimestamp, Sample, SampleRate, StreamInstant};
+
+    #[test]
+    fn in_range_samples_convert_like_sample_from() {
+        for &mode in &[ClipMode::Clip, ClipMode::Wrap] {
+            for &sample in &[0.0, 0.5, -0.5, 1.0, -1.0] {
+                assert_eq!(mode.convert::<i16>(sample), sample.to_i16());
+                assert_eq!(mode.convert::<u16>(sample), sample.to_u16());
+                assert_eq!(mode.convert::<f32>(sample), sample);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_samples() {
+        assert_eq!(ClipMode::Clip.convert::<i16>(1.5), i16::MAX);
+        assert_eq!(ClipMode::Clip.convert::<u16>(-1.5), 0);
+        assert_eq!(ClipMode::Clip.convert::<f32>(1.5), 1.0);
+        assert_eq!(
+            ClipMode::Wrap.convert::<i16>(1.5),
+            (1.5 * 32767.0) as i64 as i16
+        );
+        assert!(ClipMode::Wrap.convert::<i16>(1.5) < 0);
+        assert_eq!(ClipMode::Wrap.convert::<f32>(1.5), 1.5);
+        assert_eq!(ClipMode::default(), ClipMode::Clip);
+    }
+
+    #[test]
+    fn float_output_is_clipped_in_place() {
+        let instant = StreamInstant::new(0, 0);
+        let timestamp = OutputStreamTimestamp {
+            callback: instant,
+            playback: instant,
+        };
+        let info = OutputCallbackInfo::new(timestamp, 4, SampleRate(48_000));
+        let clipped = Arc::new(AtomicU64::new(0));
+        let mut data = [0.0f32; 4];
+        let host_buffer = data.as_ptr() as usize;
+        let mut callback = clip_output::<f32, _>(
+            ClipMode::Clip,
+            clipped.clone(),
+            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                assert_eq!(data.as_ptr() as usize, host_buffer);
+                data.copy_from_slice(&[2.0, 0.5, -0.5, -3.0]);
+            },
+        );
+        callback(&mut data, &info);
+        assert_eq!(data, [1.0, 0.5, -0.5, -1.0]);
+        assert_eq!(clipped.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn clipped_samples_are_counted() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_clipped::<i16, _, _>(
+                &config,
+                ClipMode::Clip,
+                |data: &mut [f32], _: &OutputCallbackInfo| {
+                    data.copy_from_slice(&[2.0, 0.5, -0.5, -3.0]);
+                },
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(2);
+        device.process(2);
+        assert_eq!(stream.clipped_samples(), 4);
+        let output = device.recorded_output();
+        assert_eq!(output[0], 1.0);
+        assert_eq!(output[3], -1.0);
+    }
+}
@@ -0,0 +1,179 @@
+use samples_formats;
+use {OutputCallbackInfo, Sample};
+
+/// The noise added to floating point samples before they are quantized to an integer format.
+///
+/// Quantizing without dither makes the rounding error depend on the signal, which is heard as
+/// distortion in quiet passages. Dither replaces it with a constant, signal independent noise
+/// floor. Converting to a floating point format is exact and never dithered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dither {
+    /// No dither: samples are converted with `Sample::from`.
+    None,
+    /// Noise uniformly distributed over one quantization step.
+    Rectangular,
+    /// Noise with a triangular distribution over two quantization steps (TPDF), which also
+    /// makes the power of the noise independent of the signal.
+    Triangular,
+}
+
+/// Quantizes `f32` samples with dither, keeping its pseudo-random state between buffers so that
+/// the noise of consecutive buffers is uncorrelated.
+pub(crate) struct Ditherer {
+    dither: Dither,
+    // The state of a xorshift32 generator, never zero.
+    state: u32,
+}
+
+impl Ditherer {
+    pub(crate) fn new(dither: Dither) -> Self {
+        Ditherer {
+            dither,
+            state: 0x9E37_79B9,
+        }
+    }
+
+    // A pseudo-random value uniformly distributed in `[-0.5, 0.5)`.
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+    }
+
+    // The noise to add to a sample, in quantization steps.
+    fn noise(&mut self) -> f32 {
+        match self.dither {
+            Dither::None => 0.0,
+            Dither::Rectangular => self.uniform(),
+            Dither::Triangular => self.uniform() + self.uniform(),
+        }
+    }
+
+    /// Convert `sample` to `T`, adding dither if `T` is an integer format.
+    pub(crate) fn quantize<T>(&mut self, sample: f32) -> T
+    where
+        T: Sample,
+    {
+        if self.dither == Dither::None || T::FORMAT.is_float() {
+            return T::from_f32(sample);
+        }
+        // Scale like `f32::to_i16`, then round to the nearest step rather than truncating.
+        let scale = if sample >= 0.0 {
+            i16::MAX as f32
+        } else {
+            -(i16::MIN as f32)
+        };
+        let quantized = (sample * scale + self.noise()).round();
+        let quantized = quantized.max(i16::MIN as f32).min(i16::MAX as f32) as i16;
+        T::from(&quantized)
+    }
+}
+
+// Wraps an `f32` output data callback into one for `T`, dithering its output.
+pub(crate) fn dither_output<T, D>(
+    dither: Dither,
+    mut data_callback: D,
+) -> impl FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+    D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+{
+    let mut ditherer = Ditherer::new(dither);
+    let mut buffer = Vec::new();
+    move |data, info| {
+        // Float samples are not dithered, so the data callback fills the buffer of the host.
+        if let Some(data) = samples_formats::as_f32_slice_mut(data) {
+            return data_callback(data, info);
+        }
+        buffer.clear();
+        buffer.resize(data.len(), 0.0);
+        data_callback(&mut buffer, info);
+        for (sample, &value) in data.iter_mut().zip(&buffer) {
+            *sample = ditherer.quantize(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dither_output, Dither, Ditherer};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {OutputCallbackInfo, OutputStreamTimestamp, Sample, SampleRate, StreamInstant};
+
+    // A constant signal of a third of a quantization step.
+    const THIRD_OF_A_STEP: f32 = 1.0 / (3.0 * 32767.0);
+
+    #[test]
+    fn no_dither_converts_as_before() {
+        let mut ditherer = Ditherer::new(Dither::None);
+        for &sample in &[0.0, 0.5, -0.5, 1.0, -1.0, THIRD_OF_A_STEP] {
+            assert_eq!(ditherer.quantize::<i16>(sample), sample.to_i16());
+            assert_eq!(ditherer.quantize::<u16>(sample), sample.to_u16());
+        }
+        assert_eq!(Ditherer::new(Dither::Triangular).quantize::<f32>(0.1), 0.1);
+    }
+
+    #[test]
+    fn dither_preserves_the_signal_below_a_step() {
+        for &dither in &[Dither::Rectangular, Dither::Triangular] {
+            let mut ditherer = Ditherer::new(dither);
+            let n = 30_000;
+            let sum: i64 = (0..n)
+                .map(|_| ditherer.quantize::<i16>(THIRD_OF_A_STEP) as i64)
+                .sum();
+            let mean = sum as f64 / n as f64;
+            assert!((mean - 1.0 / 3.0).abs() < 0.02, "{:?}: {}", dither, mean);
+        }
+    }
+
+    #[test]
+    fn dither_stays_within_range() {
+        let mut ditherer = Ditherer::new(Dither::Triangular);
+        for _ in 0..1_000 {
+            assert!(ditherer.quantize::<i16>(1.0) >= i16::MAX - 1);
+            assert!(ditherer.quantize::<i16>(-1.0) <= i16::MIN + 1);
+        }
+    }
+
+    #[test]
+    fn float_output_is_not_copied() {
+        let instant = StreamInstant::new(0, 0);
+        let timestamp = OutputStreamTimestamp {
+            callback: instant,
+            playback: instant,
+        };
+        let info = OutputCallbackInfo::new(timestamp, 4, SampleRate(48_000));
+        let mut data = [0.0f32; 4];
+        let host_buffer = data.as_ptr() as usize;
+        let mut callback = dither_output::<f32, _>(
+            Dither::Triangular,
+            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                assert_eq!(data.as_ptr() as usize, host_buffer);
+                data.iter_mut().for_each(|s| *s = 0.25);
+            },
+        );
+        callback(&mut data, &info);
+        assert_eq!(data, [0.25; 4]);
+    }
+
+    #[test]
+    fn dithered_output_stream() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_dithered::<i16, _, _>(
+                &config,
+                Dither::Triangular,
+                |data: &mut [f32], _: &OutputCallbackInfo| data.iter_mut().for_each(|s| *s = 0.5),
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(64);
+        let recorded = device.recorded_output();
+        assert_eq!(recorded.len(), 64);
+        assert!(recorded.iter().all(|s| (s - 0.5).abs() < 1e-4));
+    }
+}
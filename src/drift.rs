@@ -0,0 +1,276 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use duplex::StreamPair;
+use ring_buffer::Consumer;
+use traits::StreamTrait;
+use {PauseStreamError, PlayStreamError, Sample, SampleRate, StreamConfig};
+
+// How strongly the resampling ratio reacts to the ring buffer straying from its target fill, as
+// the correction applied when it is full or empty.
+const GAIN: f64 = 0.005;
+
+// The largest correction of the resampling ratio, i.e. 5000 ppm. Larger differences between the
+// clocks are not drift but a wrongly reported sample rate.
+const MAX_CORRECTION: f64 = 0.005;
+
+// The weight of each output buffer in the smoothed fill of the ring buffer.
+const SMOOTHING: f64 = 0.05;
+
+// Resamples the input of a duplex stream to the rate at which the output consumes it, adjusting
+// the ratio so that the ring buffer stays about half full however the two clocks drift apart.
+pub(crate) struct DriftCorrector<T> {
+    consumer: Consumer<T>,
+    channels: usize,
+    // The ratio of the input rate to the output rate reported by the devices.
+    nominal_ratio: f64,
+    // The fill of the ring buffer aimed for, in frames.
+    target: f64,
+    // The smoothed fill of the ring buffer, in frames.
+    queued: f64,
+    // Whether enough input was queued to start resampling. Cleared whenever the input runs dry
+    // so that the ring buffer fills up again.
+    primed: bool,
+    // The input frames being interpolated, with `position` counting frames from the first one.
+    history: Vec<f32>,
+    position: f64,
+    scratch: Vec<T>,
+    // The bits of the `f32` correction in ppm.
+    drift: Arc<AtomicU32>,
+}
+
+impl<T> DriftCorrector<T>
+where
+    T: Sample,
+{
+    pub(crate) fn new(
+        consumer: Consumer<T>,
+        channels: usize,
+        input_rate: SampleRate,
+        output_rate: SampleRate,
+    ) -> Self {
+        let capacity = consumer.capacity() / channels;
+        DriftCorrector {
+            consumer,
+            channels,
+            nominal_ratio: input_rate.0 as f64 / output_rate.0 as f64,
+            target: capacity as f64 / 2.0,
+            queued: 0.0,
+            primed: false,
+            history: Vec::new(),
+            position: 0.0,
+            scratch: Vec::new(),
+            drift: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+        }
+    }
+
+    pub(crate) fn drift(&self) -> Arc<AtomicU32> {
+        self.drift.clone()
+    }
+
+    // The input frames available to `process`, queued or not yet interpolated.
+    fn available(&self) -> f64 {
+        let history = (self.history.len() / self.channels) as f64;
+        (self.consumer.len() / self.channels) as f64 + (history - self.position).max(0.0)
+    }
+
+    // Moves the queued input to `history`, returning whether there was any.
+    fn pull(&mut self) -> bool {
+        let len = self.consumer.len() / self.channels * self.channels;
+        if len == 0 {
+            return false;
+        }
+        self.scratch.resize(len, T::EQUILIBRIUM);
+        let n = self.consumer.pop_slice(&mut self.scratch);
+        self.history
+            .extend(self.scratch[..n].iter().map(|sample| sample.to_f32()));
+        true
+    }
+
+    // Fills `output` with interleaved input frames, resampled to the rate of the output.
+    pub(crate) fn process(&mut self, output: &mut [T]) {
+        let available = self.available();
+        if !self.primed {
+            if available < self.target {
+                return output.iter_mut().for_each(|s| *s = T::EQUILIBRIUM);
+            }
+            self.primed = true;
+            self.queued = available;
+        }
+        self.queued += SMOOTHING * (available - self.queued);
+        let correction = ((self.queued - self.target) / self.target * GAIN)
+            .clamp(-MAX_CORRECTION, MAX_CORRECTION);
+        let drift = (correction * 1e6) as f32;
+        self.drift.store(drift.to_bits(), Ordering::Relaxed);
+        let step = self.nominal_ratio * (1.0 + correction);
+
+        let channels = self.channels;
+        let mut frames = output.chunks_exact_mut(channels);
+        for frame in &mut frames {
+            let index = self.position as usize;
+            while (index + 2) * channels > self.history.len() {
+                if !self.pull() {
+                    break;
+                }
+            }
+            if (index + 2) * channels > self.history.len() {
+                // The input ran dry: play silence until enough of it is queued again.
+                self.primed = false;
+                frame.iter_mut().for_each(|s| *s = T::EQUILIBRIUM);
+                break;
+            }
+            let fraction = (self.position - index as f64) as f32;
+            let current = &self.history[index * channels..(index + 1) * channels];
+            let next = &self.history[(index + 1) * channels..(index + 2) * channels];
+            for ((sample, &a), &b) in frame.iter_mut().zip(current).zip(next) {
+                *sample = T::from_f32(a + (b - a) * fraction);
+            }
+            self.position += step;
+        }
+        for frame in frames {
+            frame.iter_mut().for_each(|s| *s = T::EQUILIBRIUM);
+        }
+
+        let consumed = (self.position as usize).min(self.history.len() / channels);
+        self.history.drain(..consumed * channels);
+        self.position -= consumed as f64;
+    }
+}
+
+/// A duplex stream whose input is resampled to follow the clock of its output, created via
+/// `DeviceTrait::build_duplex_stream_with_drift_correction`.
+///
+/// The input and output streams may have different sample rates, and even streams reporting
+/// the same rate never run at exactly the same speed unless they share a clock. The input is
+/// resampled with linear interpolation at the ratio of the reported rates, corrected by up to
+/// 5000 ppm to keep the ring buffer between the streams half full, so that it neither underflows
+/// nor overflows over time. This varispeed adds half a ring buffer of latency.
+///
+/// Playing and pausing apply to both streams like for a `StreamPair`.
+pub struct DriftCorrectedStream<S> {
+    streams: StreamPair<S>,
+    // The bits of the `f32` drift in ppm.
+    drift: Arc<AtomicU32>,
+}
+
+impl<S> DriftCorrectedStream<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(streams: StreamPair<S>, drift: Arc<AtomicU32>) -> Self {
+        DriftCorrectedStream { streams, drift }
+    }
+
+    /// The underlying input and output streams.
+    pub fn streams(&self) -> &StreamPair<S> {
+        &self.streams
+    }
+
+    /// The measured drift of the input clock relative to the output clock beyond the ratio of
+    /// their reported sample rates, in parts per million, or `0.0` until the input was buffered.
+    ///
+    /// A positive drift means that the input runs faster than reported relative to the output.
+    pub fn clock_drift(&self) -> f32 {
+        f32::from_bits(self.drift.load(Ordering::Relaxed))
+    }
+}
+
+impl<S> StreamTrait for DriftCorrectedStream<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.streams.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.streams.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.streams.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.streams.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.streams.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DriftCorrector;
+    use ring_buffer;
+    use std::sync::atomic::Ordering;
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {OutputCallbackInfo, SampleRate};
+
+    #[test]
+    fn fast_input_is_tracked() {
+        let (mut producer, consumer) = ring_buffer::ring_buffer(512, 1);
+        let mut corrector =
+            DriftCorrector::new(consumer, 1, SampleRate(48_000), SampleRate(48_000));
+        let drift = corrector.drift();
+        // The input clock runs 1000 ppm fast: 64.064 frames per output buffer of 64 frames.
+        let mut produced = 0.0;
+        let mut output = vec![0.0f32; 64];
+        for i in 0..20_000 {
+            let due = ((i + 1) as f64 * 64.064) as usize;
+            while produced < due as f64 {
+                producer.push(0.5f32).unwrap();
+                produced += 1.0;
+            }
+            corrector.process(&mut output);
+            if i > 4 {
+                assert!(output.iter().all(|&sample| (sample - 0.5).abs() < 1e-6));
+            }
+        }
+        let ppm = f32::from_bits(drift.load(Ordering::Relaxed));
+        assert!((ppm - 1000.0).abs() < 50.0, "measured {} ppm", ppm);
+        // Half full, plus the offset needed to apply the correction.
+        assert!((corrector.queued - 307.0).abs() < 16.0);
+    }
+
+    #[test]
+    fn mismatched_rates_are_resampled() {
+        let (mut producer, consumer) = ring_buffer::ring_buffer(64, 1);
+        let mut corrector = DriftCorrector::new(consumer, 1, SampleRate(2), SampleRate(1));
+        // A ramp at twice the rate of the output comes out every other sample.
+        for i in 0..32 {
+            producer.push(i as f32 / 64.0).unwrap();
+        }
+        let mut output = vec![0.0f32; 8];
+        corrector.process(&mut output);
+        let expected: Vec<f32> = (0..8).map(|i| (2 * i) as f32 / 64.0).collect();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn duplex_stream() {
+        let device = TestDevice::new(1, SampleRate(48_000)).with_input(vec![0.25; 8192]);
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_duplex_stream_with_drift_correction(
+                &config,
+                &config,
+                |input: &[f32], output: &mut [f32], _: &OutputCallbackInfo| {
+                    output.copy_from_slice(input);
+                },
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        for _ in 0..64 {
+            device.process(64);
+        }
+        let output = device.recorded_output();
+        // Silence until half of the ring buffer is queued, then the input.
+        assert_eq!(output[0], 0.0);
+        assert_eq!(*output.last().unwrap(), 0.25);
+        assert_eq!(stream.clock_drift(), 0.0);
+    }
+}
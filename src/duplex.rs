@@ -0,0 +1,138 @@
+use std::sync::{Arc, Mutex};
+
+use traits::StreamTrait;
+use {PauseStreamError, PlayStreamError, StreamConfig, StreamError};
+
+/// The default capacity of the ring buffer between the input and output streams of a
+/// `StreamPair` in frames, used when the input stream was not built with a fixed buffer size.
+pub(crate) const DEFAULT_CAPACITY_FRAMES: usize = 4096;
+
+/// An input stream and an output stream whose audio is processed by a single data callback.
+///
+/// Created via `DeviceTrait::build_unsynchronized_duplex_stream`, and used by
+/// `DriftCorrectedStream` and `Monitor`. The data callback is invoked from the output
+/// stream, with the audio captured by the input stream since the previous invocation handed over
+/// through a lock-free ring buffer. This adds up to one input buffer of latency.
+///
+/// The two streams are not synchronized: each runs on the clock the host gives it. Unless the
+/// host happens to drive both from the same hardware clock, they drift apart and the data
+/// callback sees dropouts: whenever the input stream falls behind, the missing input frames are
+/// replaced with silence. Whenever it gets ahead by more than the ring buffer can hold, the
+/// surplus frames are dropped. `DriftCorrectedStream` resamples the input to avoid this.
+///
+/// Playing and pausing apply to both streams, starting with the input stream so that the data
+/// callback finds input from its first invocation. `config` and `frame_position` describe the
+/// output stream, which drives the data callback.
+pub struct StreamPair<S> {
+    input: S,
+    output: S,
+}
+
+impl<S> StreamPair<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(input: S, output: S) -> Self {
+        StreamPair { input, output }
+    }
+
+    /// The underlying input stream.
+    pub fn input(&self) -> &S {
+        &self.input
+    }
+
+    /// The underlying output stream.
+    pub fn output(&self) -> &S {
+        &self.output
+    }
+}
+
+impl<S> StreamTrait for StreamPair<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.input.play()?;
+        self.output.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.output.pause()?;
+        self.input.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.output.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.output.frame_position()
+    }
+
+    /// Whether both underlying streams are playing.
+    fn is_playing(&self) -> bool {
+        self.input.is_playing() && self.output.is_playing()
+    }
+}
+
+// Shares a single error callback between the input and output streams.
+pub(crate) fn shared_error_callback<E>(
+    error_callback: E,
+) -> (
+    impl FnMut(StreamError) + Send + 'static,
+    impl FnMut(StreamError) + Send + 'static,
+)
+where
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let input = Arc::new(Mutex::new(error_callback));
+    let output = input.clone();
+    (
+        move |err| (*input.lock().unwrap())(err),
+        move |err| (*output.lock().unwrap())(err),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {BuildStreamError, OutputCallbackInfo, SampleRate};
+
+    #[test]
+    fn output_is_computed_from_input() {
+        let device = TestDevice::new(1, SampleRate(48_000)).with_input(vec![0.1, 0.2, 0.3, 0.4]);
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_unsynchronized_duplex_stream(
+                &config,
+                &config,
+                |input: &[f32], output: &mut [f32], _: &OutputCallbackInfo| {
+                    for (output, input) in output.iter_mut().zip(input) {
+                        *output = input * 2.0;
+                    }
+                },
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(2);
+        device.process(4);
+        assert_eq!(device.recorded_output(), vec![0.2, 0.4, 0.6, 0.8, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn sample_rates_must_match() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let input_config = device.default_input_config().unwrap().config();
+        let mut output_config = input_config.clone();
+        output_config.sample_rate = SampleRate(44_100);
+        let result = device.build_unsynchronized_duplex_stream(
+            &input_config,
+            &output_config,
+            |_: &[f32], _: &mut [f32], _: &OutputCallbackInfo| (),
+            |_| (),
+        );
+        assert!(matches!(result, Err(BuildStreamError::InvalidArgument)));
+    }
+}
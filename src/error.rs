@@ -1,10 +1,32 @@
+use std::io;
 use thiserror::Error;
 
+use StreamConfig;
+
 /// The requested host, although supported on this platform, is unavailable.
 #[derive(Clone, Debug, Error)]
 #[error("the requested host is unavailable")]
 pub struct HostUnavailable;
 
+/// A read from or write to a non-blocking stream could not transfer any samples without blocking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[error("the operation would block")]
+pub struct WouldBlock;
+
+/// A routing matrix passed to `RoutedStream::set_output_matrix` does not have one row per source
+/// channel and one column per device channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[error("the dimensions of the routing matrix do not match the channels of the stream")]
+pub struct InvalidMatrixError;
+
+/// A string could not be parsed as a `SampleRate`, e.g. because it holds no positive number of
+/// Hz or kHz.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("invalid sample rate: {input:?}")]
+pub struct ParseSampleRateError {
+    pub input: String,
+}
+
 /// Some error has occurred that is specific to the backend from which it was produced.
 ///
 /// This error is often used as a catch-all in cases where:
@@ -45,6 +67,42 @@ pub enum DeviceNameError {
     },
 }
 
+/// Error that can happen when querying or changing the volume or mute state of a device.
+#[derive(Debug, Error)]
+pub enum DeviceVolumeError {
+    /// The device no longer exists. This can happen if the device is disconnected while the
+    /// program is running.
+    #[error("The requested device is no longer available. For example, it has been unplugged.")]
+    DeviceNotAvailable,
+    /// The host does not support device volumes, or the device has no volume or mute control.
+    #[error("the device has no volume or mute control that cpal can use")]
+    NotSupported,
+    /// See the `BackendSpecificError` docs for more information about this error variant.
+    #[error("{err}")]
+    BackendSpecific {
+        #[from]
+        err: BackendSpecificError,
+    },
+}
+
+/// Error that can happen when checking whether a device is in use.
+#[derive(Debug, Error)]
+pub enum DeviceInUseError {
+    /// The device no longer exists. This can happen if the device is disconnected while the
+    /// program is running.
+    #[error("The requested device is no longer available. For example, it has been unplugged.")]
+    DeviceNotAvailable,
+    /// The host cannot tell whether the device is in use.
+    #[error("the host cannot tell whether the device is in use")]
+    NotSupported,
+    /// See the `BackendSpecificError` docs for more information about this error variant.
+    #[error("{err}")]
+    BackendSpecific {
+        #[from]
+        err: BackendSpecificError,
+    },
+}
+
 /// Error that can happen when enumerating the list of supported formats.
 #[derive(Debug, Error)]
 pub enum SupportedStreamConfigsError {
@@ -93,6 +151,10 @@ pub enum BuildStreamError {
     /// The specified stream configuration is not supported.
     #[error("The requested stream configuration is not supported by the device.")]
     StreamConfigNotSupported,
+    /// The specified stream configuration has no channels or a sample rate of zero, which no
+    /// device supports. Returned before the request reaches the host.
+    #[error("The requested stream configuration is invalid as it has zero channels or a zero sample rate.")]
+    InvalidStreamConfig,
     /// We called something the C-Layer did not understand
     ///
     /// On ALSA device functions called with a feature they do not support will yield this. E.g.
@@ -102,6 +164,14 @@ pub enum BuildStreamError {
     /// Occurs if adding a new Stream ID would cause an integer overflow.
     #[error("Adding a new stream ID would cause an overflow")]
     StreamIdOverflow,
+    /// A bit-perfect stream was requested, but the host or device cannot guarantee that samples
+    /// reach the application or the device unconverted.
+    #[error("The host cannot guarantee bit-perfect streams for this device.")]
+    BitPerfectNotSupported,
+    /// A number of periods was requested via `StreamOptions::periods`, but it is outside of the
+    /// range supported by the device or the host cannot control it.
+    #[error("The requested number of periods is not supported by the device or host.")]
+    PeriodCountNotSupported,
     /// See the `BackendSpecificError` docs for more information about this error variant.
     #[error("{err}")]
     BackendSpecific {
@@ -110,11 +180,33 @@ pub enum BuildStreamError {
     },
 }
 
-/// Errors that might occur when calling `play_stream`.
+// Stream builders that query the supported configs of the device fail like the query did.
+impl From<SupportedStreamConfigsError> for BuildStreamError {
+    fn from(err: SupportedStreamConfigsError) -> Self {
+        match err {
+            SupportedStreamConfigsError::DeviceNotAvailable => BuildStreamError::DeviceNotAvailable,
+            SupportedStreamConfigsError::InvalidArgument => BuildStreamError::InvalidArgument,
+            SupportedStreamConfigsError::BackendSpecific { err } => err.into(),
+        }
+    }
+}
+
+impl From<DefaultStreamConfigError> for BuildStreamError {
+    fn from(err: DefaultStreamConfigError) -> Self {
+        match err {
+            DefaultStreamConfigError::DeviceNotAvailable => BuildStreamError::DeviceNotAvailable,
+            DefaultStreamConfigError::StreamTypeNotSupported => {
+                BuildStreamError::StreamConfigNotSupported
+            }
+            DefaultStreamConfigError::BackendSpecific { err } => err.into(),
+        }
+    }
+}
+
+/// Errors that might occur when calling `StreamTrait::play`.
 ///
-/// As of writing this, only macOS may immediately return an error while calling this method. This
-/// is because both the alsa and wasapi backends only enqueue these commands and do not process
-/// them immediately.
+/// Hosts that only enqueue the command for their audio thread, such as WASAPI, report failures
+/// through the stream's error callback instead.
 #[derive(Debug, Error)]
 pub enum PlayStreamError {
     /// The device associated with the stream is no longer available.
@@ -128,16 +220,19 @@ pub enum PlayStreamError {
     },
 }
 
-/// Errors that might occur when calling `pause_stream`.
+/// Errors that might occur when calling `StreamTrait::pause`.
 ///
-/// As of writing this, only macOS may immediately return an error while calling this method. This
-/// is because both the alsa and wasapi backends only enqueue these commands and do not process
-/// them immediately.
+/// Hosts that only enqueue the command for their audio thread, such as WASAPI, report failures
+/// through the stream's error callback instead.
 #[derive(Debug, Error)]
 pub enum PauseStreamError {
     /// The device associated with the stream is no longer available.
     #[error("the device associated with the stream is no longer available")]
     DeviceNotAvailable,
+    /// The device does not support pausing the stream and the host cannot emulate it. The stream
+    /// keeps running.
+    #[error("the device does not support pausing the stream")]
+    PauseNotSupported,
     /// See the `BackendSpecificError` docs for more information about this error variant.
     #[error("{err}")]
     BackendSpecific {
@@ -153,6 +248,27 @@ pub enum StreamError {
     /// program is running.
     #[error("The requested device is no longer available. For example, it has been unplugged.")]
     DeviceNotAvailable,
+    /// The configuration of the device changed while the stream was running, e.g. because the
+    /// user picked another sample rate in the system settings. The stream keeps running, but its
+    /// data may no longer match `new_config`: rebuild it to adapt.
+    ///
+    /// Currently only reported by CoreAudio on macOS, for changes of the sample rate.
+    #[error("the configuration of the device changed to {new_config:?}")]
+    StreamConfigChanged { new_config: StreamConfig },
+    /// The device ran out of data to play, or of room for captured data, because the data
+    /// callback did not keep up. Some of the audio was lost.
+    ///
+    /// Currently only reported by ALSA, depending on `XrunRecovery`, and by streams playing from
+    /// a ring buffer with `UnderrunPolicy::Report`.
+    #[error("an underrun or overrun occurred and some of the audio was lost")]
+    Xrun,
+    /// The data callback panicked with the given message. It is no longer invoked, and output
+    /// streams play silence from then on.
+    ///
+    /// Reported by the streams of the typed builders like `DeviceTrait::build_output_stream`,
+    /// whatever the host. Callbacks passed to the raw builders are not guarded.
+    #[error("the data callback panicked: {message}")]
+    CallbackPanic { message: String },
     /// See the `BackendSpecificError` docs for more information about this error variant.
     #[error("{err}")]
     BackendSpecific {
@@ -160,3 +276,65 @@ pub enum StreamError {
         err: BackendSpecificError,
     },
 }
+
+/// Errors that might occur while starting to play samples with `play_samples` or `play_file`.
+#[derive(Debug, Error)]
+pub enum PlaybackError {
+    /// The default host has no output device.
+    #[error("no output device is available")]
+    NoDevice,
+    /// The supported configs of the output device could not be queried.
+    #[error("{err}")]
+    SupportedStreamConfigs {
+        #[from]
+        err: SupportedStreamConfigsError,
+    },
+    /// The default config of the output device, to which a file is converted, could not be
+    /// queried.
+    #[error("{err}")]
+    DefaultStreamConfig {
+        #[from]
+        err: DefaultStreamConfigError,
+    },
+    /// The output stream could not be built.
+    #[error("{err}")]
+    BuildStream {
+        #[from]
+        err: BuildStreamError,
+    },
+    /// The output stream could not be played.
+    #[error("{err}")]
+    PlayStream {
+        #[from]
+        err: PlayStreamError,
+    },
+    /// Opening or decoding the WAV file failed.
+    #[error("failed to read the WAV file: {err}")]
+    Io {
+        #[from]
+        err: io::Error,
+    },
+}
+
+/// Errors that might occur while recording the input of a device with `record_to_wav`.
+#[derive(Debug, Error)]
+pub enum RecordError {
+    /// The input stream could not be built.
+    #[error("{err}")]
+    BuildStream {
+        #[from]
+        err: BuildStreamError,
+    },
+    /// The input stream could not be played.
+    #[error("{err}")]
+    PlayStream {
+        #[from]
+        err: PlayStreamError,
+    },
+    /// Creating or writing the WAV file failed.
+    #[error("failed to write the WAV file: {err}")]
+    Io {
+        #[from]
+        err: io::Error,
+    },
+}
@@ -0,0 +1,180 @@
+use std::slice::{ChunksExact, ChunksExactMut};
+
+use ChannelCount;
+
+// Whether `len` samples make up a whole number of frames of `channels` samples.
+fn is_whole_frames(len: usize, channels: ChannelCount) -> bool {
+    let channels = channels as usize;
+    channels != 0 && len / channels * channels == len
+}
+
+/// A buffer of interleaved samples viewed as a sequence of frames.
+///
+/// Each frame is a slice with one sample per channel, which avoids computing
+/// `frame * channels + channel` indices by hand, e.g. in a data callback:
+///
+/// ```
+/// # use cpal::Frames;
+/// let data = [0.1f32, 0.2, 0.3, 0.4];
+/// let frames = Frames::new(&data, 2).unwrap();
+/// let left: Vec<f32> = frames.iter().map(|frame| frame[0]).collect();
+/// assert_eq!(left, [0.1, 0.3]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Frames<'a, T> {
+    samples: &'a [T],
+    channels: ChannelCount,
+}
+
+impl<'a, T> Frames<'a, T> {
+    /// View `samples` as frames of `channels` samples.
+    ///
+    /// Returns `None` if `channels` is zero or the number of samples is not a multiple of it.
+    pub fn new(samples: &'a [T], channels: ChannelCount) -> Option<Self> {
+        if !is_whole_frames(samples.len(), channels) {
+            return None;
+        }
+        Some(Frames { samples, channels })
+    }
+
+    /// The number of samples in each frame.
+    pub fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    /// The number of frames.
+    pub fn len(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Whether there are no frames.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The frame at the given index, if any.
+    pub fn get(&self, frame: usize) -> Option<&'a [T]> {
+        let channels = self.channels as usize;
+        self.samples.get(frame * channels..(frame + 1) * channels)
+    }
+
+    /// An iterator over the frames.
+    pub fn iter(&self) -> ChunksExact<'a, T> {
+        self.samples.chunks_exact(self.channels as usize)
+    }
+
+    /// The underlying interleaved samples.
+    pub fn samples(&self) -> &'a [T] {
+        self.samples
+    }
+}
+
+impl<'a, T> IntoIterator for Frames<'a, T> {
+    type Item = &'a [T];
+    type IntoIter = ChunksExact<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A mutable buffer of interleaved samples viewed as a sequence of frames.
+///
+/// The mutable counterpart of `Frames`, e.g. for filling the buffer of an output stream one frame
+/// at a time.
+#[derive(Debug)]
+pub struct FramesMut<'a, T> {
+    samples: &'a mut [T],
+    channels: ChannelCount,
+}
+
+impl<'a, T> FramesMut<'a, T> {
+    /// View `samples` as frames of `channels` samples.
+    ///
+    /// Returns `None` if `channels` is zero or the number of samples is not a multiple of it.
+    pub fn new(samples: &'a mut [T], channels: ChannelCount) -> Option<Self> {
+        if !is_whole_frames(samples.len(), channels) {
+            return None;
+        }
+        Some(FramesMut { samples, channels })
+    }
+
+    /// The number of samples in each frame.
+    pub fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    /// The number of frames.
+    pub fn len(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Whether there are no frames.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The frame at the given index, if any.
+    pub fn get_mut(&mut self, frame: usize) -> Option<&mut [T]> {
+        let channels = self.channels as usize;
+        self.samples
+            .get_mut(frame * channels..(frame + 1) * channels)
+    }
+
+    /// An iterator over the frames.
+    pub fn iter(&self) -> ChunksExact<'_, T> {
+        self.samples.chunks_exact(self.channels as usize)
+    }
+
+    /// A mutable iterator over the frames.
+    pub fn iter_mut(&mut self) -> ChunksExactMut<'_, T> {
+        self.samples.chunks_exact_mut(self.channels as usize)
+    }
+
+    /// The underlying interleaved samples.
+    pub fn samples_mut(&mut self) -> &mut [T] {
+        self.samples
+    }
+}
+
+impl<'a, T> IntoIterator for FramesMut<'a, T> {
+    type Item = &'a mut [T];
+    type IntoIter = ChunksExactMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.samples.chunks_exact_mut(self.channels as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Frames, FramesMut};
+
+    #[test]
+    fn frames() {
+        let samples = [1, 2, 3, 4, 5, 6];
+        let frames = Frames::new(&samples, 3).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames.get(1), Some(&[4, 5, 6][..]));
+        assert_eq!(frames.get(2), None);
+        assert_eq!(frames.iter().collect::<Vec<_>>(), [&[1, 2, 3], &[4, 5, 6]]);
+    }
+
+    #[test]
+    fn frames_mut() {
+        let mut samples = [0; 4];
+        let mut frames = FramesMut::new(&mut samples, 2).unwrap();
+        for (i, frame) in frames.iter_mut().enumerate() {
+            frame[1] = i + 1;
+        }
+        assert_eq!(samples, [0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn invalid_channels() {
+        assert!(Frames::new(&[0; 4], 0).is_none());
+        assert!(Frames::new(&[0; 4], 3).is_none());
+        assert!(FramesMut::new(&mut [0; 4], 3).is_none());
+        assert!(Frames::new(&[0; 0], 2).unwrap().is_empty());
+    }
+}
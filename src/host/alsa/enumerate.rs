@@ -1,21 +1,33 @@
 use super::alsa;
 use super::parking_lot::Mutex;
-use super::{Device, DeviceHandles};
+use super::{AlsaAccess, Device, DeviceHandles, XrunRecovery};
+use std::sync::Arc;
 use {BackendSpecificError, DevicesError};
 
 /// ALSA's implementation for `Devices`.
 pub struct Devices {
     hint_iter: alsa::device_name::HintIter,
+    access: AlsaAccess,
 }
 
 impl Devices {
-    pub fn new() -> Result<Self, DevicesError> {
+    pub fn new(access: AlsaAccess) -> Result<Self, DevicesError> {
         Ok(Devices {
             hint_iter: alsa::device_name::HintIter::new_str(None, "pcm")?,
+            access,
         })
     }
 }
 
+// Whether the PCM with the given name is the variant of a hardware device not selected by
+// `access`.
+fn is_other_access(name: &str, access: AlsaAccess) -> bool {
+    match access {
+        AlsaAccess::Hw => name.starts_with("plughw:"),
+        AlsaAccess::Plughw => name.starts_with("hw:"),
+    }
+}
+
 unsafe impl Send for Devices {}
 unsafe impl Sync for Devices {}
 
@@ -31,13 +43,16 @@ impl Iterator for Devices {
                         None => continue,
                         // Ignoring the `null` device.
                         Some(name) if name == "null" => continue,
+                        Some(name) if is_other_access(&name, self.access) => continue,
                         Some(name) => name,
                     };
 
                     if let Ok(handles) = DeviceHandles::open(&name) {
                         return Some(Device {
                             name,
-                            handles: Mutex::new(handles),
+                            handles: Arc::new(Mutex::new(handles)),
+                            start_threshold: None,
+                            xrun_recovery: XrunRecovery::Silent,
                         });
                     }
                 }
@@ -46,11 +61,32 @@ impl Iterator for Devices {
     }
 }
 
+/// Create the device for the PCM with the given ALSA name, e.g. `hw:2,0` or `plughw:CARD=PCH`.
+pub fn device_from_name(name: &str) -> Result<Device, DevicesError> {
+    let handles = match DeviceHandles::open(name) {
+        Ok(handles) => handles,
+        // The PCM exists but is in use by another stream. Its handles are opened on demand.
+        Err(err) if err.errno() == Some(nix::errno::Errno::EBUSY) => Default::default(),
+        Err(err) => {
+            let description = format!("failed to open the ALSA PCM `{}`: {}", name, err);
+            return Err(BackendSpecificError { description }.into());
+        }
+    };
+    Ok(Device {
+        name: name.to_owned(),
+        handles: Arc::new(Mutex::new(handles)),
+        start_threshold: None,
+        xrun_recovery: XrunRecovery::Silent,
+    })
+}
+
 #[inline]
 pub fn default_input_device() -> Option<Device> {
     Some(Device {
         name: "default".to_owned(),
-        handles: Mutex::new(Default::default()),
+        handles: Default::default(),
+        start_threshold: None,
+        xrun_recovery: XrunRecovery::Silent,
     })
 }
 
@@ -58,7 +94,9 @@ pub fn default_input_device() -> Option<Device> {
 pub fn default_output_device() -> Option<Device> {
     Some(Device {
         name: "default".to_owned(),
-        handles: Mutex::new(Default::default()),
+        handles: Default::default(),
+        start_threshold: None,
+        xrun_recovery: XrunRecovery::Silent,
     })
 }
 
@@ -0,0 +1,159 @@
+use super::alsa;
+use super::alsa::mixer::{Mixer, Selem, SelemChannelId, SelemId};
+use {BackendSpecificError, DeviceVolumeError};
+
+// The simple mixer controls tried, in order, for the volume of a card's playback.
+const PLAYBACK_CONTROLS: [&str; 2] = ["Master", "PCM"];
+
+// The simple mixer control for the volume of a card's capture.
+const CAPTURE_CONTROL: &str = "Capture";
+
+// The name of the mixer of the card that the PCM named `pcm` is bound to, e.g. `hw:PCH` for
+// `plughw:CARD=PCH,DEV=0` or `hw:0` for `hw:0,0`. PCMs without a card use the default mixer.
+fn mixer_name(pcm: &str) -> String {
+    let card = pcm.split_once(':').and_then(|(_, args)| {
+        let mut args = args.split(',');
+        let first = args.clone().next().filter(|arg| !arg.contains('='));
+        args.find_map(|arg| arg.strip_prefix("CARD=")).or(first)
+    });
+    match card {
+        Some(card) => format!("hw:{}", card),
+        None => "default".to_string(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Playback,
+    Capture,
+}
+
+// The volume control of the card of a PCM.
+pub(super) struct Control {
+    mixer: Mixer,
+    id: SelemId,
+    direction: Direction,
+}
+
+impl Control {
+    // Finds the playback volume control of the card of the PCM named `pcm`, or else its capture
+    // volume control.
+    pub(super) fn open(pcm: &str) -> Result<Self, DeviceVolumeError> {
+        let mixer = Mixer::new(&mixer_name(pcm), false)?;
+        let controls = PLAYBACK_CONTROLS
+            .iter()
+            .map(|&name| (name, Direction::Playback))
+            .chain(Some((CAPTURE_CONTROL, Direction::Capture)));
+        for (name, direction) in controls {
+            let id = SelemId::new(name, 0);
+            let has_volume = match mixer.find_selem(&id) {
+                Some(selem) if direction == Direction::Playback => selem.has_playback_volume(),
+                Some(selem) => selem.has_capture_volume(),
+                None => false,
+            };
+            if has_volume {
+                return Ok(Control {
+                    mixer,
+                    id,
+                    direction,
+                });
+            }
+        }
+        Err(DeviceVolumeError::NotSupported)
+    }
+
+    fn selem(&self) -> Result<Selem<'_>, DeviceVolumeError> {
+        self.mixer.find_selem(&self.id).ok_or_else(|| {
+            let description = "the mixer control disappeared".to_string();
+            BackendSpecificError { description }.into()
+        })
+    }
+
+    // The channels of the control, to which changes are applied. The first is read from.
+    fn channels(&self, selem: &Selem) -> Vec<SelemChannelId> {
+        SelemChannelId::all()
+            .iter()
+            .cloned()
+            .filter(|&channel| match self.direction {
+                Direction::Playback => selem.has_playback_channel(channel),
+                Direction::Capture => selem.has_capture_channel(channel),
+            })
+            .collect()
+    }
+
+    pub(super) fn volume(&self) -> Result<f32, DeviceVolumeError> {
+        let selem = self.selem()?;
+        let channel = self.channels(&selem).first().cloned();
+        let channel = channel.unwrap_or_else(SelemChannelId::mono);
+        let ((min, max), volume) = match self.direction {
+            Direction::Playback => (
+                selem.get_playback_volume_range(),
+                selem.get_playback_volume(channel)?,
+            ),
+            Direction::Capture => (
+                selem.get_capture_volume_range(),
+                selem.get_capture_volume(channel)?,
+            ),
+        };
+        if max <= min {
+            return Ok(1.0);
+        }
+        Ok((volume - min) as f32 / (max - min) as f32)
+    }
+
+    pub(super) fn set_volume(&self, volume: f32) -> Result<(), DeviceVolumeError> {
+        let selem = self.selem()?;
+        let (min, max) = match self.direction {
+            Direction::Playback => selem.get_playback_volume_range(),
+            Direction::Capture => selem.get_capture_volume_range(),
+        };
+        let volume = volume.clamp(0.0, 1.0);
+        let value = min + ((max - min) as f32 * volume).round() as i64;
+        for channel in self.channels(&selem) {
+            match self.direction {
+                Direction::Playback => selem.set_playback_volume(channel, value)?,
+                Direction::Capture => selem.set_capture_volume(channel, value)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn is_muted(&self) -> Result<bool, DeviceVolumeError> {
+        let selem = self.selem()?;
+        let channel = self.channels(&selem).first().cloned();
+        let channel = channel.unwrap_or_else(SelemChannelId::mono);
+        // The switch of a control is on when the control is not muted.
+        let switch = match self.direction {
+            Direction::Playback if selem.has_playback_switch() => {
+                selem.get_playback_switch(channel)?
+            }
+            Direction::Capture if selem.has_capture_switch() => {
+                selem.get_capture_switch(channel)?
+            }
+            _ => return Err(DeviceVolumeError::NotSupported),
+        };
+        Ok(switch == 0)
+    }
+
+    pub(super) fn set_muted(&self, muted: bool) -> Result<(), DeviceVolumeError> {
+        let selem = self.selem()?;
+        let switch = if muted { 0 } else { 1 };
+        match self.direction {
+            Direction::Playback if selem.has_playback_switch() => {
+                selem.set_playback_switch_all(switch)?
+            }
+            Direction::Capture if selem.has_capture_switch() => {
+                selem.set_capture_switch_all(switch)?
+            }
+            _ => return Err(DeviceVolumeError::NotSupported),
+        }
+        Ok(())
+    }
+}
+
+impl From<alsa::Error> for DeviceVolumeError {
+    fn from(err: alsa::Error) -> Self {
+        let err: BackendSpecificError = err.into();
+        err.into()
+    }
+}
@@ -6,32 +6,106 @@ use self::alsa::poll::Descriptors;
 use self::parking_lot::Mutex;
 use crate::{
     BackendSpecificError, BufferSize, BuildStreamError, ChannelCount, Data,
-    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo,
-    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    DefaultStreamConfigError, DeviceInUseError, DeviceNameError, DeviceVolumeError, DevicesError,
+    FrameCount, InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError,
+    SampleFormat, SampleRate, StreamConfig, StreamError, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 use std::cmp;
 use std::convert::TryInto;
+use std::ffi::CStr;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::vec::IntoIter as VecIntoIter;
 use traits::{DeviceTrait, HostTrait, StreamTrait};
 
-pub use self::enumerate::{default_input_device, default_output_device, Devices};
+pub use self::enumerate::{default_input_device, default_output_device, device_from_name, Devices};
 
 pub type SupportedInputConfigs = VecIntoIter<SupportedStreamConfigRange>;
 pub type SupportedOutputConfigs = VecIntoIter<SupportedStreamConfigRange>;
 
 mod enumerate;
+mod mixer;
+
+/// How hardware devices are opened by the ALSA host.
+///
+/// ALSA exposes each sound card both directly, as `hw:CARD=...`, and through the plug layer, as
+/// `plughw:CARD=...`. The raw `hw` device only accepts the exact sample formats, rates and channel
+/// counts supported by the hardware, so building a stream with any other configuration fails with
+/// `StreamConfigNotSupported`. The `plughw` device accepts nearly any configuration and converts
+/// it in software, at the cost of some CPU time, a little latency and, when resampling, a loss of
+/// quality.
+///
+/// This only affects which of the two variants of each card are returned by `Host::devices`.
+/// Other PCMs such as `default` or `sysdefault` are always returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlsaAccess {
+    /// Open hardware devices directly, for bit-perfect playback and capture.
+    Hw,
+    /// Open hardware devices through the plug layer, which converts unsupported configurations.
+    /// This is the default.
+    Plughw,
+}
+
+/// How streams react to an xrun: an underrun of an output stream, whose data callback was too late
+/// to fill the buffer, an overrun of an input stream, or the system being suspended.
+///
+/// ALSA stops the stream on an xrun, and further reads and writes fail until it is recovered.
+/// Every xrun is counted by `Stream::xrun_count` whichever the setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum XrunRecovery {
+    /// Recover and restart the stream without notifying the error callback. This is the default.
+    Silent,
+    /// Recover and restart the stream, passing `StreamError::Xrun` to the error callback.
+    Report,
+    /// Pass `StreamError::Xrun` to the error callback and stop the stream, which must then be
+    /// rebuilt to go on.
+    Disabled,
+}
 
 /// The default linux, dragonfly and freebsd host type.
 #[derive(Debug)]
-pub struct Host;
+pub struct Host {
+    /// How hardware devices are opened (default is `AlsaAccess::Plughw`).
+    access: AlsaAccess,
+}
 
 impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
-        Ok(Host)
+        Ok(Host {
+            access: AlsaAccess::Plughw,
+        })
+    }
+
+    // The version of libasound, as reported by the library loaded at runtime.
+    pub(crate) fn library_version() -> Option<String> {
+        extern "C" {
+            fn snd_asoundlib_version() -> *const libc::c_char;
+        }
+        let version = unsafe { CStr::from_ptr(snd_asoundlib_version()) };
+        Some(version.to_string_lossy().into_owned())
+    }
+
+    /// How hardware devices returned by `devices` are opened.
+    pub fn access(&self) -> AlsaAccess {
+        self.access
+    }
+
+    /// Set how hardware devices returned by `devices` are opened.
+    /// (default is `AlsaAccess::Plughw`)
+    pub fn set_access(&mut self, access: AlsaAccess) {
+        self.access = access;
+    }
+
+    /// The device for the PCM with the given ALSA name, e.g. `hw:2,0` or `plughw:CARD=PCH`.
+    ///
+    /// This allows opening a device that is known by name without relying on the order in
+    /// which devices are enumerated. The name is used as is, regardless of `access`. Returns an
+    /// error describing the failure if no such PCM could be opened.
+    pub fn device_from_name(&self, name: &str) -> Result<Device, DevicesError> {
+        device_from_name(name)
     }
 }
 
@@ -45,7 +119,7 @@ impl HostTrait for Host {
     }
 
     fn devices(&self) -> Result<Self::Devices, DevicesError> {
-        Devices::new()
+        Devices::new(self.access)
     }
 
     fn default_input_device(&self) -> Option<Self::Device> {
@@ -78,6 +152,26 @@ impl DeviceTrait for Device {
         Device::supported_output_configs(self)
     }
 
+    fn volume(&self) -> Result<f32, DeviceVolumeError> {
+        mixer::Control::open(&self.name)?.volume()
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<(), DeviceVolumeError> {
+        mixer::Control::open(&self.name)?.set_volume(volume)
+    }
+
+    fn is_muted(&self) -> Result<bool, DeviceVolumeError> {
+        mixer::Control::open(&self.name)?.is_muted()
+    }
+
+    fn set_muted(&self, muted: bool) -> Result<(), DeviceVolumeError> {
+        mixer::Control::open(&self.name)?.set_muted(muted)
+    }
+
+    fn is_in_use(&self) -> Result<bool, DeviceInUseError> {
+        Device::is_in_use(self)
+    }
+
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_input_config(self)
     }
@@ -86,6 +180,11 @@ impl DeviceTrait for Device {
         Device::default_output_config(self)
     }
 
+    fn default_buffer_size(&self, config: &SupportedStreamConfig) -> Option<FrameCount> {
+        self.default_period_size(config, alsa::Direction::Playback)
+            .or_else(|| self.default_period_size(config, alsa::Direction::Capture))
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         conf: &StreamConfig,
@@ -98,7 +197,7 @@ impl DeviceTrait for Device {
         E: FnMut(StreamError) + Send + 'static,
     {
         let stream_inner =
-            self.build_stream_inner(conf, sample_format, alsa::Direction::Capture)?;
+            self.build_stream_inner(conf, sample_format, alsa::Direction::Capture, None)?;
         let stream = Stream::new_input(Arc::new(stream_inner), data_callback, error_callback);
         Ok(stream)
     }
@@ -115,10 +214,82 @@ impl DeviceTrait for Device {
         E: FnMut(StreamError) + Send + 'static,
     {
         let stream_inner =
-            self.build_stream_inner(conf, sample_format, alsa::Direction::Playback)?;
+            self.build_stream_inner(conf, sample_format, alsa::Direction::Playback, None)?;
         let stream = Stream::new_output(Arc::new(stream_inner), data_callback, error_callback);
         Ok(stream)
     }
+
+    fn build_input_stream_raw_with_periods<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        periods: u32,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner =
+            self.build_stream_inner(conf, sample_format, alsa::Direction::Capture, Some(periods))?;
+        let stream = Stream::new_input(Arc::new(stream_inner), data_callback, error_callback);
+        Ok(stream)
+    }
+
+    fn build_output_stream_raw_with_periods<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        periods: u32,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner = self.build_stream_inner(
+            conf,
+            sample_format,
+            alsa::Direction::Playback,
+            Some(periods),
+        )?;
+        let stream = Stream::new_output(Arc::new(stream_inner), data_callback, error_callback);
+        Ok(stream)
+    }
+
+    fn build_input_stream_bit_perfect<D, E>(
+        &self,
+        conf: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (stream_inner, sample_format) =
+            self.build_bit_perfect_stream_inner(conf, alsa::Direction::Capture)?;
+        let stream = Stream::new_input(Arc::new(stream_inner), data_callback, error_callback);
+        Ok((stream, sample_format))
+    }
+
+    fn build_output_stream_bit_perfect<D, E>(
+        &self,
+        conf: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (stream_inner, sample_format) =
+            self.build_bit_perfect_stream_inner(conf, alsa::Direction::Playback)?;
+        let stream = Stream::new_output(Arc::new(stream_inner), data_callback, error_callback);
+        Ok((stream, sample_format))
+    }
 }
 
 struct TriggerSender(libc::c_int);
@@ -200,7 +371,14 @@ impl DeviceHandles {
         };
 
         if handle.is_none() {
-            *handle = Some(alsa::pcm::PCM::new(name, stream_type, true)?);
+            let pcm = alsa::pcm::PCM::new(name, stream_type, true);
+            trace!(
+                "alsa: open {:?} for {:?}: {:?}",
+                name,
+                stream_type,
+                pcm.as_ref().map(|_| ())
+            );
+            *handle = Some(pcm?);
         }
 
         Ok(handle)
@@ -223,17 +401,69 @@ impl DeviceHandles {
     }
 }
 
+// Clones share the PCM handles opened during enumeration, as hardware devices can only be opened
+// once.
+#[derive(Clone)]
 pub struct Device {
     name: String,
-    handles: Mutex<DeviceHandles>,
+    handles: Arc<Mutex<DeviceHandles>>,
+    // The number of frames written before output streams start playing, if not ALSA's default.
+    start_threshold: Option<FrameCount>,
+    // How streams built from this device react to xruns.
+    xrun_recovery: XrunRecovery,
+}
+
+// Devices are identified by their PCM name (e.g. `hw:CARD=PCH,DEV=0`), which refers to the card
+// by its ID rather than by a handle and is therefore stable across enumerations.
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Device {}
+
+impl Hash for Device {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
 }
 
 impl Device {
+    /// The number of frames that output streams built from this device buffer before the device
+    /// starts playing, or `None` to start once all but one period of the buffer is filled.
+    pub fn start_threshold(&self) -> Option<FrameCount> {
+        self.start_threshold
+    }
+
+    /// Set the number of frames that output streams built from this device from now on buffer
+    /// before the device starts playing. (default is `None`)
+    ///
+    /// A higher threshold makes it less likely for the first buffers to underrun while the data
+    /// callback warms up, at the cost of a longer delay before playback begins. The threshold is
+    /// limited to the size of the stream's buffer. It does not apply to input streams, which
+    /// start capturing as soon as they are built.
+    pub fn set_start_threshold(&mut self, frames: Option<FrameCount>) {
+        self.start_threshold = frames;
+    }
+
+    /// How streams built from this device react to xruns.
+    pub fn xrun_recovery(&self) -> XrunRecovery {
+        self.xrun_recovery
+    }
+
+    /// Set how streams built from this device from now on react to xruns. (default is
+    /// `XrunRecovery::Silent`)
+    pub fn set_xrun_recovery(&mut self, xrun_recovery: XrunRecovery) {
+        self.xrun_recovery = xrun_recovery;
+    }
+
     fn build_stream_inner(
         &self,
         conf: &StreamConfig,
         sample_format: SampleFormat,
         stream_type: alsa::Direction,
+        periods: Option<u32>,
     ) -> Result<StreamInner, BuildStreamError> {
         let handle_result = self
             .handles
@@ -251,8 +481,30 @@ impl Device {
             Err((e, _)) => return Err(e.into()),
             Ok(handle) => handle,
         };
-        let can_pause = set_hw_params_from_format(&handle, conf, sample_format)?;
-        let period_len = set_sw_params_from_format(&handle, conf, stream_type)?;
+        let can_pause = set_hw_params_from_format(&handle, conf, sample_format, periods);
+        trace!(
+            "alsa: hw params {:?} {:?} on {:?}: {:?}",
+            conf,
+            sample_format,
+            self.name,
+            can_pause
+        );
+        let can_pause = can_pause?;
+        let period_len =
+            set_sw_params_from_format(&handle, conf, stream_type, self.start_threshold);
+        trace!("alsa: sw params on {:?}: {:?}", self.name, period_len);
+        let period_len = period_len?;
+
+        // The sample rate is set to the nearest one supported by the device, and the buffer size
+        // is chosen by ALSA unless a fixed size was requested.
+        let conf = {
+            let hw_params = handle.hw_params_current()?;
+            StreamConfig {
+                channels: conf.channels,
+                sample_rate: SampleRate(hw_params.get_rate()?),
+                buffer_size: BufferSize::Fixed(hw_params.get_buffer_size()? as FrameCount),
+            }
+        };
 
         handle.prepare()?;
 
@@ -278,19 +530,101 @@ impl Device {
             handle.start()?;
         }
 
+        trace!(
+            "alsa: built {:?} stream on {:?} with {:?}",
+            stream_type,
+            self.name,
+            conf
+        );
         let stream_inner = StreamInner {
             channel: handle,
             sample_format,
             num_descriptors,
-            conf: conf.clone(),
+            conf,
             period_len,
             can_pause,
             creation_instant,
+            frames: AtomicU64::new(0),
+            paused_in_software: AtomicBool::new(false),
+            xrun_recovery: self.xrun_recovery,
+            xruns: AtomicU64::new(0),
         };
 
         Ok(stream_inner)
     }
 
+    fn is_in_use(&self) -> Result<bool, DeviceInUseError> {
+        // Handles that are already open are held by this device, keeping others from opening the
+        // PCM. The handles that are opened now are kept for later use too.
+        let mut handles = self.handles.lock();
+        let mut opened = false;
+        for &stream_type in &[alsa::Direction::Playback, alsa::Direction::Capture] {
+            match handles.try_open(&self.name, stream_type) {
+                Ok(_) => opened = true,
+                Err(err) if err.errno() == Some(nix::errno::Errno::EBUSY) => return Ok(true),
+                // The PCM may not support this direction.
+                Err(_) => (),
+            }
+        }
+        if !opened {
+            return Err(DeviceInUseError::DeviceNotAvailable);
+        }
+        Ok(false)
+    }
+
+    // Builds a stream on the hardware device of this device, in a format native to the hardware.
+    fn build_bit_perfect_stream_inner(
+        &self,
+        conf: &StreamConfig,
+        stream_type: alsa::Direction,
+    ) -> Result<(StreamInner, SampleFormat), BuildStreamError> {
+        let hw_device;
+        let device = if self.name.starts_with("hw:") {
+            self
+        } else if let Some(args) = self.name.strip_prefix("plughw:") {
+            // The handles of the plug device keep the hardware device busy.
+            *self.handles.lock() = DeviceHandles::default();
+            hw_device = Device {
+                name: format!("hw:{}", args),
+                handles: Default::default(),
+                start_threshold: self.start_threshold,
+                xrun_recovery: self.xrun_recovery,
+            };
+            &hw_device
+        } else {
+            return Err(BuildStreamError::BitPerfectNotSupported);
+        };
+
+        // The formats of a hardware device are those of the hardware, in order of preference.
+        let supported: Vec<_> = device
+            .supported_configs(stream_type)?
+            .filter(|range| {
+                range.channels == conf.channels
+                    && range.min_sample_rate <= conf.sample_rate
+                    && conf.sample_rate <= range.max_sample_rate
+            })
+            .map(|range| range.sample_format)
+            .collect();
+        let sample_format = [
+            SampleFormat::F32,
+            SampleFormat::I16,
+            SampleFormat::U16,
+            SampleFormat::U8,
+        ]
+        .iter()
+        .cloned()
+        .find(|sample_format| supported.contains(sample_format))
+        .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+
+        let stream_inner = device.build_stream_inner(conf, sample_format, stream_type, None)?;
+        // The sample rate is set to the nearest one supported, which hardware devices do not
+        // resample to.
+        if stream_inner.conf.sample_rate != conf.sample_rate {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+        Ok((stream_inner, sample_format))
+    }
+
     #[inline]
     fn name(&self) -> Result<String, DeviceNameError> {
         Ok(self.name.clone())
@@ -320,9 +654,9 @@ impl Device {
         let hw_params = alsa::pcm::HwParams::any(handle)?;
 
         // TODO: check endianess
-        const FORMATS: [(SampleFormat, alsa::pcm::Format); 3] = [
+        const FORMATS: [(SampleFormat, alsa::pcm::Format); 4] = [
             //SND_PCM_FORMAT_S8,
-            //SND_PCM_FORMAT_U8,
+            (SampleFormat::U8, alsa::pcm::Format::U8),
             (SampleFormat::I16, alsa::pcm::Format::S16LE),
             //SND_PCM_FORMAT_S16_BE,
             (SampleFormat::U16, alsa::pcm::Format::U16LE),
@@ -423,8 +757,8 @@ impl Device {
                 for &(min_rate, max_rate) in sample_rates.iter() {
                     output.push(SupportedStreamConfigRange {
                         channels,
-                        min_sample_rate: SampleRate(min_rate as u32),
-                        max_sample_rate: SampleRate(max_rate as u32),
+                        min_sample_rate: SampleRate(min_rate),
+                        max_sample_rate: SampleRate(max_rate),
                         buffer_size: buffer_size_range.clone(),
                         sample_format,
                     });
@@ -432,6 +766,12 @@ impl Device {
             }
         }
 
+        trace!(
+            "alsa: {:?} supports for {:?}: {:?}",
+            self.name,
+            stream_t,
+            output
+        );
         Ok(output.into_iter())
     }
 
@@ -494,6 +834,31 @@ impl Device {
     fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         self.default_config(alsa::Direction::Playback)
     }
+
+    // The period size that `set_hw_params_from_format` picks for `BufferSize::Default`.
+    fn default_period_size(
+        &self,
+        config: &SupportedStreamConfig,
+        stream_t: alsa::Direction,
+    ) -> Option<FrameCount> {
+        let mut guard = self.handles.lock();
+        let handle = guard.get_mut(&self.name, stream_t).ok()?;
+        let hw_params = alsa::pcm::HwParams::any(handle).ok()?;
+        hw_params
+            .set_access(alsa::pcm::Access::RWInterleaved)
+            .ok()?;
+        hw_params
+            .set_rate(config.sample_rate.0, alsa::ValueOr::Nearest)
+            .ok()?;
+        hw_params.set_channels(config.channels as u32).ok()?;
+        hw_params
+            .set_period_time_near(25_000, alsa::ValueOr::Nearest)
+            .ok()?;
+        hw_params
+            .get_period_size()
+            .ok()
+            .map(|frames| frames as FrameCount)
+    }
 }
 
 struct StreamInner {
@@ -507,15 +872,13 @@ struct StreamInner {
     // Format of the samples.
     sample_format: SampleFormat,
 
-    // The configuration used to open this stream.
+    // The configuration negotiated with the device when opening this stream.
     conf: StreamConfig,
 
     // Minimum number of samples to put in the buffer.
     period_len: usize,
 
-    #[allow(dead_code)]
     // Whether or not the hardware supports pausing the stream.
-    // TODO: We need an API to expose this. See #197, #284.
     can_pause: bool,
 
     // In the case that the device does not return valid timestamps via `get_htstamp`, this field
@@ -527,6 +890,17 @@ struct StreamInner {
     // If this field is `None` then the elapsed duration between `get_trigger_htstamp` and
     // `get_htstamp` is used.
     creation_instant: Option<std::time::Instant>,
+
+    // The number of frames read from or written to the channel so far.
+    frames: AtomicU64,
+
+    // Whether the stream is paused while the hardware, which cannot pause, keeps running. Output
+    // streams then write silence and input streams discard what they read.
+    paused_in_software: AtomicBool,
+
+    // How to react to xruns, and the number of xruns so far.
+    xrun_recovery: XrunRecovery,
+    xruns: AtomicU64,
 }
 
 // Assume that the ALSA library is built with thread safe option.
@@ -574,8 +948,10 @@ fn input_stream_worker(
             PollDescriptorsFlow::Continue => {
                 continue;
             }
-            PollDescriptorsFlow::XRun => {
-                report_error(stream.channel.prepare(), error_callback);
+            PollDescriptorsFlow::XRun(err) => {
+                if !handle_xrun(stream, StreamType::Input, err, error_callback) {
+                    return;
+                }
                 continue;
             }
             PollDescriptorsFlow::Return => return,
@@ -596,8 +972,11 @@ fn input_stream_worker(
                     status,
                     delay_frames,
                     data_callback,
+                    error_callback,
                 );
-                report_error(res, error_callback);
+                if let Some(false) = report_error(res, error_callback) {
+                    return;
+                }
             }
         }
     }
@@ -619,8 +998,10 @@ fn output_stream_worker(
 
         match flow {
             PollDescriptorsFlow::Continue => continue,
-            PollDescriptorsFlow::XRun => {
-                report_error(stream.channel.prepare(), error_callback);
+            PollDescriptorsFlow::XRun(err) => {
+                if !handle_xrun(stream, StreamType::Output, err, error_callback) {
+                    return;
+                }
                 continue;
             }
             PollDescriptorsFlow::Return => return,
@@ -644,7 +1025,9 @@ fn output_stream_worker(
                     data_callback,
                     error_callback,
                 );
-                report_error(res, error_callback);
+                if let Some(false) = report_error(res, error_callback) {
+                    return;
+                }
             }
         }
     }
@@ -666,6 +1049,53 @@ where
     }
 }
 
+// Whether `err` reports an xrun, including the suspension of the system.
+fn is_xrun(err: &alsa::Error) -> bool {
+    matches!(
+        err.errno(),
+        Some(nix::errno::Errno::EPIPE) | Some(nix::errno::Errno::ESTRPIPE)
+    )
+}
+
+// Counts the xrun reported with `err` and recovers from it as configured for the stream.
+//
+// Returns `false` if the stream is stopped instead, in which case its worker must return.
+fn handle_xrun(
+    stream: &StreamInner,
+    stream_type: StreamType,
+    err: alsa::Error,
+    error_callback: &mut dyn FnMut(StreamError),
+) -> bool {
+    stream.xruns.fetch_add(1, Ordering::Relaxed);
+    trace!(
+        "alsa: xrun, recovering with {:?}: {}",
+        stream.xrun_recovery,
+        err
+    );
+    match stream.xrun_recovery {
+        XrunRecovery::Silent => (),
+        XrunRecovery::Report => error_callback(StreamError::Xrun),
+        XrunRecovery::Disabled => {
+            error_callback(StreamError::Xrun);
+            return false;
+        }
+    }
+    let recovered = stream.channel.try_recover(err, true);
+    trace!("alsa: recover from xrun: {:?}", recovered);
+    if let Err(err) = recovered {
+        error_callback(err.into());
+        return false;
+    }
+    // Output streams restart once enough frames are written, but input streams must be started.
+    if stream_type == StreamType::Input {
+        if let Err(err) = stream.channel.start() {
+            error_callback(err.into());
+            return false;
+        }
+    }
+    true
+}
+
 enum PollDescriptorsFlow {
     Continue,
     Return,
@@ -675,7 +1105,7 @@ enum PollDescriptorsFlow {
         avail_frames: usize,
         delay_frames: usize,
     },
-    XRun,
+    XRun(alsa::Error),
 }
 
 // This block is shared between both input and output stream worker functions.
@@ -735,9 +1165,7 @@ fn poll_descriptors_and_prepare_buffer(
 
     let status = stream.channel.status()?;
     let avail_frames = match stream.channel.avail() {
-        Err(err) if err.errno() == Some(nix::errno::Errno::EPIPE) => {
-            return Ok(PollDescriptorsFlow::XRun)
-        }
+        Err(err) if is_xrun(&err) => return Ok(PollDescriptorsFlow::XRun(err)),
         res => res,
     }? as usize;
     let delay_frames = match status.get_delay() {
@@ -765,17 +1193,30 @@ fn poll_descriptors_and_prepare_buffer(
 }
 
 // Read input data from ALSA and deliver it to the user.
+//
+// Returns `false` if the stream was stopped after an xrun.
 fn process_input(
     stream: &StreamInner,
     buffer: &mut [u8],
     status: alsa::pcm::Status,
     delay_frames: usize,
     data_callback: &mut (dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static),
-) -> Result<(), BackendSpecificError> {
-    stream.channel.io_bytes().readi(buffer)?;
+    error_callback: &mut dyn FnMut(StreamError),
+) -> Result<bool, BackendSpecificError> {
+    let frames = match stream.channel.io_bytes().readi(buffer) {
+        Err(err) if is_xrun(&err) => {
+            return Ok(handle_xrun(stream, StreamType::Input, err, error_callback))
+        }
+        res => res,
+    }?;
+    if stream.paused_in_software.load(Ordering::SeqCst) {
+        return Ok(true);
+    }
     let sample_format = stream.sample_format;
     let data = buffer.as_mut_ptr() as *mut ();
-    let len = buffer.len() / sample_format.sample_size();
+    // Only pass the frames actually read, with one sample per channel of the stream, e.g. a
+    // single sample per frame for mono capture.
+    let len = frames * stream.conf.channels as usize;
     let data = unsafe { Data::from_parts(data, len, sample_format) };
     let callback = stream_timestamp(&status, stream.creation_instant)?;
     let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
@@ -783,15 +1224,16 @@ fn process_input(
         .sub(delay_duration)
         .expect("`capture` is earlier than representation supported by `StreamInstant`");
     let timestamp = crate::InputStreamTimestamp { callback, capture };
-    let info = crate::InputCallbackInfo { timestamp };
+    let info = crate::InputCallbackInfo::new(timestamp, frames, stream.conf.sample_rate);
     data_callback(&data, &info);
+    stream.frames.fetch_add(frames as u64, Ordering::Relaxed);
 
-    Ok(())
+    Ok(true)
 }
 
 // Request data from the user's function and write it via ALSA.
 //
-// Returns `true`
+// Returns `false` if the stream was stopped after an xrun.
 fn process_output(
     stream: &StreamInner,
     buffer: &mut [u8],
@@ -800,8 +1242,13 @@ fn process_output(
     delay_frames: usize,
     data_callback: &mut (dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static),
     error_callback: &mut dyn FnMut(StreamError),
-) -> Result<(), BackendSpecificError> {
-    {
+) -> Result<bool, BackendSpecificError> {
+    let paused = stream.paused_in_software.load(Ordering::SeqCst);
+    if paused {
+        let len = buffer.len() / stream.sample_format.sample_size();
+        let data = buffer.as_mut_ptr() as *mut ();
+        unsafe { Data::from_parts(data, len, stream.sample_format) }.write_silence();
+    } else {
         // We're now sure that we're ready to write data.
         let sample_format = stream.sample_format;
         let data = buffer.as_mut_ptr() as *mut ();
@@ -813,15 +1260,16 @@ fn process_output(
             .add(delay_duration)
             .expect("`playback` occurs beyond representation supported by `StreamInstant`");
         let timestamp = crate::OutputStreamTimestamp { callback, playback };
-        let info = crate::OutputCallbackInfo { timestamp };
+        let frames = len / stream.conf.channels as usize;
+        let info = crate::OutputCallbackInfo::new(timestamp, frames, stream.conf.sample_rate);
         data_callback(&mut data, &info);
     }
     loop {
         match stream.channel.io_bytes().writei(buffer) {
-            Err(err) if err.errno() == Some(nix::errno::Errno::EPIPE) => {
-                // buffer underrun
-                // TODO: Notify the user of this.
-                let _ = stream.channel.try_recover(err, false);
+            Err(err) if is_xrun(&err) => {
+                if !handle_xrun(stream, StreamType::Output, err, error_callback) {
+                    return Ok(false);
+                }
             }
             Err(err) => {
                 error_callback(err.into());
@@ -837,11 +1285,16 @@ fn process_output(
                 continue;
             }
             _ => {
+                if !paused {
+                    stream
+                        .frames
+                        .fetch_add(available_frames as u64, Ordering::Relaxed);
+                }
                 break;
             }
         }
     }
-    Ok(())
+    Ok(true)
 }
 
 // Use the elapsed duration since the start of the stream.
@@ -876,6 +1329,8 @@ fn stream_timestamp(
 
 // Adapted from `timestamp2ns` here:
 // https://fossies.org/linux/alsa-lib/test/audio_time.c
+// `time_t` and `c_long` are not `i64` on every target.
+#[allow(clippy::unnecessary_cast)]
 fn timespec_to_nanos(ts: libc::timespec) -> i64 {
     ts.tv_sec as i64 * 1_000_000_000 + ts.tv_nsec as i64
 }
@@ -910,7 +1365,7 @@ impl Stream {
         let thread = thread::Builder::new()
             .name("cpal_alsa_in".to_owned())
             .spawn(move || {
-                input_stream_worker(rx, &*stream, &mut data_callback, &mut error_callback);
+                input_stream_worker(rx, &stream, &mut data_callback, &mut error_callback);
             })
             .unwrap();
         Stream {
@@ -935,7 +1390,7 @@ impl Stream {
         let thread = thread::Builder::new()
             .name("cpal_alsa_out".to_owned())
             .spawn(move || {
-                output_stream_worker(rx, &*stream, &mut data_callback, &mut error_callback);
+                output_stream_worker(rx, &stream, &mut data_callback, &mut error_callback);
             })
             .unwrap();
         Stream {
@@ -944,6 +1399,19 @@ impl Stream {
             trigger: tx,
         }
     }
+
+    /// Whether `pause` is emulated in software because the hardware cannot pause.
+    ///
+    /// While such a stream is paused the device keeps running: output streams play silence and
+    /// input streams discard the captured audio, without invoking the data callback.
+    pub fn pauses_in_software(&self) -> bool {
+        !self.inner.can_pause
+    }
+
+    /// The number of xruns of the stream so far, including those it recovered from.
+    pub fn xrun_count(&self) -> u64 {
+        self.inner.xruns.load(Ordering::Relaxed)
+    }
 }
 
 impl Drop for Stream {
@@ -954,21 +1422,44 @@ impl Drop for Stream {
 }
 
 impl StreamTrait for Stream {
+    // Streams run from the moment they are built, and ALSA fails to resume or pause a PCM that is
+    // not paused or running respectively.
     fn play(&self) -> Result<(), PlayStreamError> {
-        self.inner.channel.pause(false).ok();
+        if !self.inner.can_pause {
+            self.inner.paused_in_software.store(false, Ordering::SeqCst);
+        } else if self.inner.channel.state() == alsa::pcm::State::Paused {
+            self.inner.channel.pause(false)?;
+        }
         Ok(())
     }
     fn pause(&self) -> Result<(), PauseStreamError> {
-        self.inner.channel.pause(true).ok();
+        if !self.inner.can_pause {
+            self.inner.paused_in_software.store(true, Ordering::SeqCst);
+        } else if self.inner.channel.state() != alsa::pcm::State::Paused {
+            self.inner.channel.pause(true)?;
+        }
         Ok(())
     }
+    fn config(&self) -> StreamConfig {
+        self.inner.conf.clone()
+    }
+    fn frame_position(&self) -> u64 {
+        self.inner.frames.load(Ordering::Relaxed)
+    }
+    fn is_playing(&self) -> bool {
+        if !self.inner.can_pause {
+            return !self.inner.paused_in_software.load(Ordering::SeqCst);
+        }
+        self.inner.channel.state() != alsa::pcm::State::Paused
+    }
 }
 
 fn set_hw_params_from_format(
     pcm_handle: &alsa::pcm::PCM,
     config: &StreamConfig,
     sample_format: SampleFormat,
-) -> Result<bool, BackendSpecificError> {
+    periods: Option<u32>,
+) -> Result<bool, BuildStreamError> {
     let hw_params = alsa::pcm::HwParams::any(pcm_handle)?;
     hw_params.set_access(alsa::pcm::Access::RWInterleaved)?;
 
@@ -976,12 +1467,14 @@ fn set_hw_params_from_format(
         match sample_format {
             SampleFormat::I16 => alsa::pcm::Format::S16BE,
             SampleFormat::U16 => alsa::pcm::Format::U16BE,
+            SampleFormat::U8 => alsa::pcm::Format::U8,
             SampleFormat::F32 => alsa::pcm::Format::FloatBE,
         }
     } else {
         match sample_format {
             SampleFormat::I16 => alsa::pcm::Format::S16LE,
             SampleFormat::U16 => alsa::pcm::Format::U16LE,
+            SampleFormat::U8 => alsa::pcm::Format::U8,
             SampleFormat::F32 => alsa::pcm::Format::FloatLE,
         }
     };
@@ -990,12 +1483,27 @@ fn set_hw_params_from_format(
     hw_params.set_rate(config.sample_rate.0, alsa::ValueOr::Nearest)?;
     hw_params.set_channels(config.channels as u32)?;
 
-    match config.buffer_size {
-        BufferSize::Fixed(v) => {
-            hw_params.set_period_size_near((v / 4) as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
-            hw_params.set_buffer_size(v as alsa::pcm::Frames)?;
+    // The requested number of periods must be supported exactly, before the period size is
+    // derived from it.
+    if let Some(periods) = periods {
+        hw_params
+            .set_periods(periods, alsa::ValueOr::Nearest)
+            .map_err(|_| BuildStreamError::PeriodCountNotSupported)?;
+    }
+
+    match (&config.buffer_size, periods) {
+        (BufferSize::Fixed(v), None) => {
+            hw_params
+                .set_period_size_near((*v / 4) as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
+            hw_params.set_buffer_size(*v as alsa::pcm::Frames)?;
+        }
+        (BufferSize::Fixed(v), Some(_)) => {
+            hw_params.set_buffer_size(*v as alsa::pcm::Frames)?;
+        }
+        (BufferSize::Default, Some(_)) => {
+            hw_params.set_period_time_near(25_000, alsa::ValueOr::Nearest)?;
         }
-        BufferSize::Default => {
+        (BufferSize::Default, None) => {
             // These values together represent a moderate latency and wakeup interval.
             // Without them, we are at the mercy of the device
             hw_params.set_period_time_near(25_000, alsa::ValueOr::Nearest)?;
@@ -1012,6 +1520,7 @@ fn set_sw_params_from_format(
     pcm_handle: &alsa::pcm::PCM,
     config: &StreamConfig,
     stream_type: alsa::Direction,
+    start_threshold: Option<FrameCount>,
 ) -> Result<usize, BackendSpecificError> {
     let sw_params = pcm_handle.sw_params_current()?;
 
@@ -1024,9 +1533,10 @@ fn set_sw_params_from_format(
         }
         sw_params.set_avail_min(period as alsa::pcm::Frames)?;
 
-        let start_threshold = match stream_type {
-            alsa::Direction::Playback => buffer - period,
-            alsa::Direction::Capture => 1,
+        let start_threshold = match (stream_type, start_threshold) {
+            (alsa::Direction::Playback, Some(frames)) => (frames as u64).max(1).min(buffer),
+            (alsa::Direction::Playback, None) => buffer - period,
+            (alsa::Direction::Capture, _) => 1,
         };
         sw_params.set_start_threshold(start_threshold.try_into().unwrap())?;
 
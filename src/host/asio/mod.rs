@@ -3,10 +3,10 @@ extern crate parking_lot;
 
 use crate::{
     BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleFormat,
-    StreamConfig, StreamError, SupportedStreamConfig, SupportedStreamConfigsError,
+    InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError, Sample, SampleFormat,
+    StreamConfig, StreamError, StreamOptions, SupportedStreamConfig, SupportedStreamConfigsError,
 };
-use traits::{DeviceTrait, HostTrait, StreamTrait};
+use traits::{self, CallbackThread, DeviceTrait, HostTrait, StreamTrait};
 
 pub use self::device::{Device, Devices, SupportedInputConfigs, SupportedOutputConfigs};
 pub use self::stream::Stream;
@@ -27,15 +27,20 @@ impl Host {
         let host = Host { asio };
         Ok(host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl HostTrait for Host {
     type Devices = Devices;
     type Device = Device;
 
+    /// ASIO is available if at least one ASIO driver is installed.
     fn is_available() -> bool {
-        true
-        //unimplemented!("check how to do this using asio-sys")
+        !sys::Asio::new().driver_names().is_empty()
     }
 
     fn devices(&self) -> Result<Self::Devices, DevicesError> {
@@ -109,6 +114,54 @@ impl DeviceTrait for Device {
     {
         Device::build_output_stream_raw(self, config, sample_format, data_callback, error_callback)
     }
+
+    /// The data callback runs on a thread of the ASIO driver, so the `realtime` and `cpu_affinity`
+    /// options are ignored.
+    fn build_input_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        traits::build_input_stream_with_options(
+            self,
+            config,
+            options,
+            CallbackThread::Foreign,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    /// The data callback runs on a thread of the ASIO driver, so the `realtime` and `cpu_affinity`
+    /// options are ignored.
+    fn build_output_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        traits::build_output_stream_with_options(
+            self,
+            config,
+            options,
+            CallbackThread::Foreign,
+            data_callback,
+            error_callback,
+        )
+    }
 }
 
 impl StreamTrait for Stream {
@@ -119,4 +172,16 @@ impl StreamTrait for Stream {
     fn pause(&self) -> Result<(), PauseStreamError> {
         Stream::pause(self)
     }
+
+    fn config(&self) -> StreamConfig {
+        Stream::config(self)
+    }
+
+    fn frame_position(&self) -> u64 {
+        Stream::frame_position(self)
+    }
+
+    fn is_playing(&self) -> bool {
+        Stream::is_playing(self)
+    }
 }
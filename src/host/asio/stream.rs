@@ -10,7 +10,7 @@ use crate::{
     StreamError,
 };
 use std;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 /// Sample types whose constant silent value is known.
@@ -38,6 +38,10 @@ pub struct Stream {
     driver: Arc<sys::Driver>,
     asio_streams: Arc<Mutex<sys::AsioStreams>>,
     callback_id: sys::CallbackId,
+    // The configuration of the stream along with the buffer size used by the driver.
+    config: StreamConfig,
+    // The number of frames processed by the data callback.
+    frames: Arc<AtomicU64>,
 }
 
 impl Stream {
@@ -50,6 +54,18 @@ impl Stream {
         self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+
+    pub fn config(&self) -> StreamConfig {
+        self.config.clone()
+    }
+
+    pub fn frame_position(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
 }
 
 impl Device {
@@ -83,6 +99,8 @@ impl Device {
 
         let stream_playing = Arc::new(AtomicBool::new(false));
         let playing = Arc::clone(&stream_playing);
+        let stream_frames = Arc::new(AtomicU64::new(0));
+        let frames = Arc::clone(&stream_frames);
         let asio_streams = self.asio_streams.clone();
 
         // Set the input callback.
@@ -138,7 +156,7 @@ impl Device {
                     .sub(delay)
                     .expect("`capture` occurs before origin of alsa `StreamInstant`");
                 let timestamp = crate::InputStreamTimestamp { callback, capture };
-                let info = InputCallbackInfo { timestamp };
+                let info = InputCallbackInfo::new(timestamp, n_frames, sample_rate);
                 data_callback(&data, &info);
             }
 
@@ -221,6 +239,7 @@ impl Device {
                     unsupported_format_pair
                 ),
             }
+            frames.fetch_add(asio_stream.buffer_size as u64, Ordering::Relaxed);
         });
 
         let driver = self.driver.clone();
@@ -234,6 +253,12 @@ impl Device {
             driver,
             asio_streams,
             callback_id,
+            config: StreamConfig {
+                channels: num_channels,
+                sample_rate: config.sample_rate,
+                buffer_size: BufferSize::Fixed(buffer_size as u32),
+            },
+            frames: stream_frames,
         })
     }
 
@@ -268,6 +293,8 @@ impl Device {
 
         let stream_playing = Arc::new(AtomicBool::new(false));
         let playing = Arc::clone(&stream_playing);
+        let stream_frames = Arc::new(AtomicU64::new(0));
+        let frames = Arc::clone(&stream_frames);
         let asio_streams = self.asio_streams.clone();
 
         let config = config.clone();
@@ -336,7 +363,7 @@ impl Device {
                     .add(delay)
                     .expect("`playback` occurs beyond representation supported by `StreamInstant`");
                 let timestamp = crate::OutputStreamTimestamp { callback, playback };
-                let info = OutputCallbackInfo { timestamp };
+                let info = OutputCallbackInfo::new(timestamp, n_frames, sample_rate);
                 data_callback(&mut data, &info);
 
                 // 2. Silence ASIO channels if necessary.
@@ -447,6 +474,7 @@ impl Device {
                     unsupported_format_pair
                 ),
             }
+            frames.fetch_add(asio_stream.buffer_size as u64, Ordering::Relaxed);
         });
 
         let driver = self.driver.clone();
@@ -460,6 +488,12 @@ impl Device {
             driver,
             asio_streams,
             callback_id,
+            config: StreamConfig {
+                channels: num_channels,
+                sample_rate: config.sample_rate,
+                buffer_size: BufferSize::Fixed(buffer_size as u32),
+            },
+            frames: stream_frames,
         })
     }
 
@@ -676,7 +710,9 @@ fn check_config(
     // unsigned formats are not supported by asio
     match sample_format {
         SampleFormat::I16 | SampleFormat::F32 => (),
-        SampleFormat::U16 => return Err(BuildStreamError::StreamConfigNotSupported),
+        SampleFormat::U16 | SampleFormat::U8 => {
+            return Err(BuildStreamError::StreamConfigNotSupported)
+        }
     }
     if *channels > num_asio_channels {
         return Err(BuildStreamError::StreamConfigNotSupported);
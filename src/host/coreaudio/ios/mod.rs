@@ -34,13 +34,15 @@ use self::enumerate::{
     SupportedOutputConfigs,
 };
 use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 pub mod enumerate;
 
 // These days the default of iOS is now F32 and no longer I16
 const SUPPORTED_SAMPLE_FORMAT: SampleFormat = SampleFormat::F32;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
 pub struct Host;
@@ -49,6 +51,11 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl HostTrait for Host {
@@ -204,6 +211,8 @@ impl DeviceTrait for Device {
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
         let sample_rate = config.sample_rate;
+        let frames = Arc::new(AtomicU64::new(0));
+        let callback_frames = frames.clone();
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_input_callback(move |args: Args| unsafe {
             let ptr = (*args.data.data).mBuffers.as_ptr() as *const AudioBuffer;
@@ -236,8 +245,9 @@ impl DeviceTrait for Device {
                 .expect("`capture` occurs before origin of alsa `StreamInstant`");
             let timestamp = crate::InputStreamTimestamp { callback, capture };
 
-            let info = InputCallbackInfo { timestamp };
+            let info = InputCallbackInfo::new(timestamp, buffer_frames, sample_rate);
             data_callback(&data, &info);
+            callback_frames.fetch_add(buffer_frames as u64, Ordering::Relaxed);
             Ok(())
         })?;
 
@@ -246,6 +256,8 @@ impl DeviceTrait for Device {
         Ok(Stream::new(StreamInner {
             playing: true,
             audio_unit,
+            config: config.clone(),
+            frames,
         }))
     }
 
@@ -282,6 +294,8 @@ impl DeviceTrait for Device {
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
         let sample_rate = config.sample_rate;
+        let frames = Arc::new(AtomicU64::new(0));
+        let callback_frames = frames.clone();
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_render_callback(move |args: Args| unsafe {
             // If `run()` is currently running, then a callback will be available from this list.
@@ -312,8 +326,9 @@ impl DeviceTrait for Device {
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
 
-            let info = OutputCallbackInfo { timestamp };
+            let info = OutputCallbackInfo::new(timestamp, buffer_frames, sample_rate);
             data_callback(&mut data, &info);
+            callback_frames.fetch_add(buffer_frames as u64, Ordering::Relaxed);
             Ok(())
         })?;
 
@@ -322,6 +337,8 @@ impl DeviceTrait for Device {
         Ok(Stream::new(StreamInner {
             playing: true,
             audio_unit,
+            config: config.clone(),
+            frames,
         }))
     }
 }
@@ -367,11 +384,27 @@ impl StreamTrait for Stream {
         }
         Ok(())
     }
+
+    fn config(&self) -> StreamConfig {
+        self.inner.borrow().config.clone()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.inner.borrow().frames.load(Ordering::Relaxed)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.inner.borrow().playing
+    }
 }
 
 struct StreamInner {
     playing: bool,
     audio_unit: AudioUnit,
+    // The configuration with which the stream was created.
+    config: StreamConfig,
+    // The number of frames passed to or received from the data callback.
+    frames: Arc<AtomicU64>,
 }
 
 fn create_audio_unit() -> Result<AudioUnit, coreaudio::Error> {
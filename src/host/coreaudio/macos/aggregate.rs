@@ -0,0 +1,258 @@
+use super::check_os_status;
+use super::core_foundation_sys::array::{kCFTypeArrayCallBacks, CFArrayCreate};
+use super::core_foundation_sys::base::{kCFAllocatorDefault, CFRelease, CFTypeRef};
+use super::core_foundation_sys::dictionary::{
+    kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionaryCreateMutable,
+    CFDictionaryRef, CFDictionarySetValue, CFMutableDictionaryRef,
+};
+use super::core_foundation_sys::number::{kCFNumberSInt32Type, CFNumberCreate};
+use super::core_foundation_sys::string::{CFStringCreateWithCString, CFStringRef};
+use super::coreaudio::sys::{
+    kAudioDeviceTransportTypeAggregate, kAudioHardwarePropertyPlugInForBundleID,
+    kAudioObjectPropertyElementMaster, kAudioObjectPropertyScopeGlobal, kAudioObjectSystemObject,
+    kAudioPlugInCreateAggregateDevice, kAudioPlugInDestroyAggregateDevice, kCFStringEncodingUTF8,
+    AudioDeviceID, AudioObjectGetPropertyData, AudioObjectID, AudioObjectPropertyAddress,
+    AudioObjectPropertySelector, AudioValueTranslation,
+};
+use super::{Device, Host};
+use crate::BackendSpecificError;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr::null;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// The keys of the description of an aggregate device, and of its sub-devices, as defined by
+// `AudioHardware.h`.
+const AGGREGATE_UID_KEY: &str = "uid";
+const AGGREGATE_NAME_KEY: &str = "name";
+const AGGREGATE_SUB_DEVICES_KEY: &str = "subdevices";
+const AGGREGATE_MASTER_KEY: &str = "master";
+const AGGREGATE_PRIVATE_KEY: &str = "private";
+const SUB_DEVICE_UID_KEY: &str = "uid";
+const SUB_DEVICE_DRIFT_COMPENSATION_KEY: &str = "drift";
+
+// The bundle of the HAL plug-in managing aggregate devices.
+const CORE_AUDIO_BUNDLE_ID: &str = "com.apple.audio.CoreAudio";
+
+// Makes the UIDs of the aggregate devices created by this process unique.
+static AGGREGATE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+impl Host {
+    /// Create a transient aggregate device combining `devices`, e.g. to record from or play to
+    /// several interfaces in sync through a single stream.
+    ///
+    /// The channels of the aggregate device are those of `devices`, in order. The first device
+    /// provides the clock, to which the others are resampled with drift compensation. The
+    /// aggregate device is private to this process, and destroyed when the returned
+    /// `AggregateDevice` is dropped.
+    ///
+    /// Aggregate devices created in Audio MIDI Setup are enumerated by `devices` like any other
+    /// device, see `Device::is_aggregate`.
+    pub fn create_aggregate_device(
+        &self,
+        name: &str,
+        devices: &[Device],
+    ) -> Result<AggregateDevice, BackendSpecificError> {
+        let uids = devices
+            .iter()
+            .map(|device| device.uid())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| BackendSpecificError {
+                description: "cannot aggregate a device without UID".to_string(),
+            })?;
+        let master = uids.first().ok_or_else(|| BackendSpecificError {
+            description: "cannot create an aggregate device without sub-devices".to_string(),
+        })?;
+        let uid = format!(
+            "cpal.aggregate.{}.{}",
+            std::process::id(),
+            AGGREGATE_COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+
+        unsafe {
+            let plugin_id = core_audio_plugin()?;
+            let description = CFDictionary::new();
+            description.set(AGGREGATE_UID_KEY, CFObject::string(&uid)?);
+            description.set(AGGREGATE_NAME_KEY, CFObject::string(name)?);
+            description.set(AGGREGATE_MASTER_KEY, CFObject::string(master)?);
+            description.set(AGGREGATE_PRIVATE_KEY, CFObject::number(1));
+            let mut sub_devices = Vec::with_capacity(uids.len());
+            for (i, sub_device_uid) in uids.iter().enumerate() {
+                let sub_device = CFDictionary::new();
+                sub_device.set(SUB_DEVICE_UID_KEY, CFObject::string(sub_device_uid)?);
+                let drift_compensation = if i == 0 { 0 } else { 1 };
+                sub_device.set(
+                    SUB_DEVICE_DRIFT_COMPENSATION_KEY,
+                    CFObject::number(drift_compensation),
+                );
+                sub_devices.push(sub_device.0);
+            }
+            description.set(AGGREGATE_SUB_DEVICES_KEY, CFObject::array(&sub_devices));
+
+            let address = global_address(kAudioPlugInCreateAggregateDevice);
+            let qualifier = (description.0).0 as CFDictionaryRef;
+            let device_id: AudioDeviceID = 0;
+            let data_size = mem::size_of::<AudioDeviceID>() as u32;
+            let status = AudioObjectGetPropertyData(
+                plugin_id,
+                &address as *const _,
+                mem::size_of::<CFDictionaryRef>() as u32,
+                &qualifier as *const _ as *const c_void,
+                &data_size as *const _ as *mut _,
+                &device_id as *const _ as *mut _,
+            );
+            check_os_status(status)?;
+            Ok(AggregateDevice {
+                plugin_id,
+                device: Device::new(device_id),
+            })
+        }
+    }
+}
+
+impl Device {
+    /// Whether this is an aggregate device, combining several devices, either set up by the user
+    /// in Audio MIDI Setup or created via `Host::create_aggregate_device`.
+    pub fn is_aggregate(&self) -> bool {
+        self.get_property::<u32, BackendSpecificError>(&super::TRANSPORT_TYPE, 0)
+            .map(|transport_type| transport_type == kAudioDeviceTransportTypeAggregate)
+            .unwrap_or(false)
+    }
+}
+
+/// A transient aggregate device, created via `Host::create_aggregate_device`.
+///
+/// The aggregate device is destroyed when this is dropped, after which streams built from it
+/// report `StreamError::DeviceNotAvailable`.
+pub struct AggregateDevice {
+    plugin_id: AudioObjectID,
+    device: Device,
+}
+
+impl AggregateDevice {
+    /// The aggregate device, from which streams are built like from any other device.
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl Drop for AggregateDevice {
+    fn drop(&mut self) {
+        let address = global_address(kAudioPlugInDestroyAggregateDevice);
+        let device_id = self.device.audio_device_id;
+        let data_size = mem::size_of::<AudioDeviceID>() as u32;
+        unsafe {
+            AudioObjectGetPropertyData(
+                self.plugin_id,
+                &address as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+                &device_id as *const _ as *mut _,
+            );
+        }
+    }
+}
+
+fn global_address(selector: AudioObjectPropertySelector) -> AudioObjectPropertyAddress {
+    AudioObjectPropertyAddress {
+        mSelector: selector,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    }
+}
+
+// The HAL plug-in through which aggregate devices are created and destroyed.
+unsafe fn core_audio_plugin() -> Result<AudioObjectID, BackendSpecificError> {
+    let bundle_id = CFObject::string(CORE_AUDIO_BUNDLE_ID)?;
+    let bundle_id_ref = bundle_id.0 as CFStringRef;
+    let plugin_id: AudioObjectID = 0;
+    let translation = AudioValueTranslation {
+        mInputData: &bundle_id_ref as *const _ as *mut c_void,
+        mInputDataSize: mem::size_of::<CFStringRef>() as u32,
+        mOutputData: &plugin_id as *const _ as *mut c_void,
+        mOutputDataSize: mem::size_of::<AudioObjectID>() as u32,
+    };
+    let address = global_address(kAudioHardwarePropertyPlugInForBundleID);
+    let data_size = mem::size_of::<AudioValueTranslation>() as u32;
+    let status = AudioObjectGetPropertyData(
+        kAudioObjectSystemObject,
+        &address as *const _,
+        0,
+        null(),
+        &data_size as *const _ as *mut _,
+        &translation as *const _ as *mut _,
+    );
+    check_os_status(status)?;
+    if plugin_id == 0 {
+        let description = "the CoreAudio HAL plug-in is not available".to_string();
+        return Err(BackendSpecificError { description });
+    }
+    Ok(plugin_id)
+}
+
+// An owned Core Foundation object, released when dropped.
+#[repr(transparent)]
+struct CFObject(CFTypeRef);
+
+impl CFObject {
+    unsafe fn string(s: &str) -> Result<Self, BackendSpecificError> {
+        let c_string = CString::new(s).map_err(|_| BackendSpecificError {
+            description: format!("invalid string {:?}", s),
+        })?;
+        let string = CFStringCreateWithCString(
+            kCFAllocatorDefault,
+            c_string.as_ptr(),
+            kCFStringEncodingUTF8,
+        );
+        Ok(CFObject(string as CFTypeRef))
+    }
+
+    unsafe fn number(value: i32) -> Self {
+        let number = CFNumberCreate(
+            kCFAllocatorDefault,
+            kCFNumberSInt32Type,
+            &value as *const _ as *const c_void,
+        );
+        CFObject(number as CFTypeRef)
+    }
+
+    unsafe fn array(values: &[CFObject]) -> Self {
+        let array = CFArrayCreate(
+            kCFAllocatorDefault,
+            values.as_ptr() as *const *const c_void,
+            values.len() as _,
+            &kCFTypeArrayCallBacks,
+        );
+        CFObject(array as CFTypeRef)
+    }
+}
+
+impl Drop for CFObject {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { CFRelease(self.0) }
+        }
+    }
+}
+
+struct CFDictionary(CFObject);
+
+impl CFDictionary {
+    unsafe fn new() -> Self {
+        let dictionary = CFDictionaryCreateMutable(
+            kCFAllocatorDefault,
+            0,
+            &kCFTypeDictionaryKeyCallBacks,
+            &kCFTypeDictionaryValueCallBacks,
+        );
+        CFDictionary(CFObject(dictionary as CFTypeRef))
+    }
+
+    // Retains `key` and `value`, which are released by the caller.
+    unsafe fn set(&self, key: &str, value: CFObject) {
+        let key = CFObject::string(key).expect("keys do not contain nul bytes");
+        CFDictionarySetValue((self.0).0 as CFMutableDictionaryRef, key.0, value.0);
+    }
+}
@@ -81,9 +81,7 @@ unsafe impl Sync for Devices {}
 impl Iterator for Devices {
     type Item = Device;
     fn next(&mut self) -> Option<Device> {
-        self.0.next().map(|id| Device {
-            audio_device_id: id,
-        })
+        self.0.next().map(Device::new)
     }
 }
 
@@ -110,7 +108,7 @@ pub fn default_input_device() -> Option<Device> {
         return None;
     }
 
-    let device = Device { audio_device_id };
+    let device = Device::new(audio_device_id);
     Some(device)
 }
 
@@ -137,7 +135,7 @@ pub fn default_output_device() -> Option<Device> {
         return None;
     }
 
-    let device = Device { audio_device_id };
+    let device = Device::new(audio_device_id);
     Some(device)
 }
 
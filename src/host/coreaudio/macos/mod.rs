@@ -3,46 +3,57 @@ extern crate coreaudio;
 
 use super::{asbd_from_config, check_os_status, frames_to_duration, host_time_to_stream_instant};
 
+use self::core_foundation_sys::base::CFRelease;
 use self::core_foundation_sys::string::{CFStringGetCString, CFStringGetCStringPtr, CFStringRef};
 use self::coreaudio::audio_unit::render_callback::{self, data};
 use self::coreaudio::audio_unit::{AudioUnit, Element, Scope};
 use self::coreaudio::sys::{
     kAudioDevicePropertyAvailableNominalSampleRates, kAudioDevicePropertyBufferFrameSize,
-    kAudioDevicePropertyBufferFrameSizeRange, kAudioDevicePropertyDeviceNameCFString,
-    kAudioDevicePropertyNominalSampleRate, kAudioDevicePropertyScopeOutput,
-    kAudioDevicePropertyStreamConfiguration, kAudioDevicePropertyStreamFormat,
-    kAudioObjectPropertyElementMaster, kAudioObjectPropertyScopeGlobal,
-    kAudioObjectPropertyScopeInput, kAudioObjectPropertyScopeOutput,
-    kAudioOutputUnitProperty_CurrentDevice, kAudioOutputUnitProperty_EnableIO,
-    kAudioUnitProperty_StreamFormat, kCFStringEncodingUTF8, AudioBuffer, AudioBufferList,
-    AudioDeviceID, AudioObjectAddPropertyListener, AudioObjectGetPropertyData,
-    AudioObjectGetPropertyDataSize, AudioObjectID, AudioObjectPropertyAddress,
-    AudioObjectPropertyScope, AudioObjectRemovePropertyListener, AudioObjectSetPropertyData,
+    kAudioDevicePropertyBufferFrameSizeRange, kAudioDevicePropertyDataSource,
+    kAudioDevicePropertyDeviceNameCFString, kAudioDevicePropertyDeviceUID,
+    kAudioDevicePropertyHogMode, kAudioDevicePropertyMute, kAudioDevicePropertyNominalSampleRate,
+    kAudioDevicePropertyScopeOutput, kAudioDevicePropertyStreamConfiguration,
+    kAudioDevicePropertyStreamFormat, kAudioDevicePropertyStreams,
+    kAudioDevicePropertyTransportType, kAudioDevicePropertyVolumeScalar,
+    kAudioDeviceTransportTypeBuiltIn, kAudioDeviceTransportTypeDisplayPort,
+    kAudioDeviceTransportTypeHDMI, kAudioObjectPropertyElementMaster,
+    kAudioObjectPropertyScopeGlobal, kAudioObjectPropertyScopeInput,
+    kAudioObjectPropertyScopeOutput, kAudioOutputUnitProperty_CurrentDevice,
+    kAudioOutputUnitProperty_EnableIO, kAudioUnitProperty_StreamFormat, kCFStringEncodingUTF8,
+    AudioBuffer, AudioBufferList, AudioDeviceID, AudioObjectAddPropertyListener,
+    AudioObjectGetPropertyData, AudioObjectGetPropertyDataSize, AudioObjectHasProperty,
+    AudioObjectID, AudioObjectPropertyAddress, AudioObjectPropertyScope,
+    AudioObjectPropertySelector, AudioObjectRemovePropertyListener, AudioObjectSetPropertyData,
     AudioStreamBasicDescription, AudioValueRange, OSStatus,
 };
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
     BackendSpecificError, BufferSize, BuildStreamError, ChannelCount, Data,
-    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo,
-    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    DefaultStreamConfigError, DeviceInUseError, DeviceNameError, DeviceVolumeError, DevicesError,
+    FormFactor, FrameCount, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
+    PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 use std::cell::RefCell;
 use std::ffi::CStr;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
 use std::ptr::null;
 use std::slice;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+pub use self::aggregate::AggregateDevice;
 pub use self::enumerate::{
     default_input_device, default_output_device, Devices, SupportedInputConfigs,
     SupportedOutputConfigs,
 };
 
+mod aggregate;
 pub mod enumerate;
 
 /// Coreaudio host, the default host on macOS.
@@ -53,6 +64,11 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl HostTrait for Host {
@@ -98,10 +114,91 @@ impl DeviceTrait for Device {
         Device::supported_output_configs(self)
     }
 
+    fn supports_input(&self) -> bool {
+        self.has_streams(kAudioObjectPropertyScopeInput)
+    }
+
+    fn supports_output(&self) -> bool {
+        self.has_streams(kAudioObjectPropertyScopeOutput)
+    }
+
+    fn volume(&self) -> Result<f32, DeviceVolumeError> {
+        let address = self.volume_property_address(kAudioDevicePropertyVolumeScalar)?;
+        self.get_property(&address, 0f32)
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<(), DeviceVolumeError> {
+        let address = self.volume_property_address(kAudioDevicePropertyVolumeScalar)?;
+        self.set_property(&address, volume.clamp(0.0, 1.0))
+    }
+
+    fn is_muted(&self) -> Result<bool, DeviceVolumeError> {
+        let address = self.volume_property_address(kAudioDevicePropertyMute)?;
+        let muted = self.get_property::<u32, DeviceVolumeError>(&address, 0)?;
+        Ok(muted != 0)
+    }
+
+    fn set_muted(&self, muted: bool) -> Result<(), DeviceVolumeError> {
+        let address = self.volume_property_address(kAudioDevicePropertyMute)?;
+        self.set_property(&address, muted as u32)
+    }
+
+    fn is_in_use(&self) -> Result<bool, DeviceInUseError> {
+        if unsafe { AudioObjectHasProperty(self.audio_device_id, &HOG_MODE as *const _) } == 0 {
+            return Err(DeviceInUseError::NotSupported);
+        }
+        let owner = self.get_property::<i32, DeviceInUseError>(&HOG_MODE, -1)?;
+        Ok(owner != -1 && owner != std::process::id() as i32)
+    }
+
+    fn form_factor(&self) -> Option<FormFactor> {
+        let transport_type = self
+            .get_property::<u32, BackendSpecificError>(&TRANSPORT_TYPE, 0)
+            .ok()?;
+        if transport_type == kAudioDeviceTransportTypeHDMI
+            || transport_type == kAudioDeviceTransportTypeDisplayPort
+        {
+            return Some(FormFactor::Hdmi);
+        }
+        if transport_type != kAudioDeviceTransportTypeBuiltIn {
+            return None;
+        }
+        // Built-in devices switch between data sources, e.g. when headphones are plugged in.
+        let scope = if self.has_streams(kAudioObjectPropertyScopeOutput) {
+            kAudioObjectPropertyScopeOutput
+        } else {
+            kAudioObjectPropertyScopeInput
+        };
+        let address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyDataSource,
+            mScope: scope,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let data_source = self
+            .get_property::<u32, BackendSpecificError>(&address, 0)
+            .ok()?;
+        match data_source {
+            DATA_SOURCE_INTERNAL_SPEAKERS => Some(FormFactor::Speakers),
+            DATA_SOURCE_HEADPHONES => Some(FormFactor::Headphones),
+            DATA_SOURCE_INTERNAL_MICROPHONE => Some(FormFactor::Microphone),
+            _ => None,
+        }
+    }
+
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_input_config(self)
     }
 
+    fn default_buffer_size(&self, _config: &SupportedStreamConfig) -> Option<FrameCount> {
+        let address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyBufferFrameSize,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        self.get_property::<u32, BackendSpecificError>(&address, 0)
+            .ok()
+    }
+
     fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_output_config(self)
     }
@@ -135,12 +232,118 @@ impl DeviceTrait for Device {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 pub struct Device {
     pub(crate) audio_device_id: AudioDeviceID,
+    // Whether streams are built with the device in hog mode.
+    hog_mode: bool,
+}
+
+// The `AudioDeviceID` of a device may change when it is reconnected, so devices are identified by
+// their UID where possible.
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        if self.audio_device_id == other.audio_device_id {
+            return true;
+        }
+        match (self.uid(), other.uid()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Device {}
+
+impl Hash for Device {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.uid() {
+            Some(uid) => uid.hash(state),
+            None => self.audio_device_id.hash(state),
+        }
+    }
 }
 
 impl Device {
+    pub(crate) fn new(audio_device_id: AudioDeviceID) -> Self {
+        Device {
+            audio_device_id,
+            hog_mode: false,
+        }
+    }
+
+    /// Whether streams built from this device take exclusive access of it, see `set_hog_mode`.
+    pub fn hog_mode(&self) -> bool {
+        self.hog_mode
+    }
+
+    /// Set whether streams built from this device from now on hold it in hog mode, giving this
+    /// process exclusive access to the device for as long as the stream lives, similar to the
+    /// exclusive mode of WASAPI. (default is `false`)
+    ///
+    /// Building a stream returns `BuildStreamError::DeviceNotAvailable` if another process
+    /// holds the device in hog mode. Hog mode is released when the stream is dropped, and by the
+    /// system if the process exits without dropping it.
+    pub fn set_hog_mode(&mut self, hog_mode: bool) {
+        self.hog_mode = hog_mode;
+    }
+
+    // Acquires hog mode for the streams of a device that asks for it.
+    fn acquire_hog_mode(&self) -> Result<Option<HogMode>, BuildStreamError> {
+        if !self.hog_mode {
+            return Ok(None);
+        }
+        let owner = self.get_property::<i32, BuildStreamError>(&HOG_MODE, -1)?;
+        let pid = std::process::id() as i32;
+        if owner == pid {
+            // Another stream of this process already holds the device, and releases it.
+            return Ok(None);
+        }
+        if owner != -1 {
+            return Err(BuildStreamError::DeviceNotAvailable);
+        }
+        // Setting the property toggles hog mode, after which it holds the owner.
+        self.set_property::<i32, BuildStreamError>(&HOG_MODE, pid)?;
+        if self.get_property::<i32, BuildStreamError>(&HOG_MODE, -1)? != pid {
+            return Err(BuildStreamError::DeviceNotAvailable);
+        }
+        Ok(Some(HogMode {
+            device_id: self.audio_device_id,
+        }))
+    }
+
+    // The persistent unique identifier of the device, if it could be retrieved.
+    fn uid(&self) -> Option<String> {
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyDeviceUID,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let uid: CFStringRef = null();
+        let data_size = mem::size_of::<CFStringRef>();
+        unsafe {
+            let status = AudioObjectGetPropertyData(
+                self.audio_device_id,
+                &property_address as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+                &uid as *const _ as *mut _,
+            );
+            if check_os_status(status).is_err() || uid.is_null() {
+                return None;
+            }
+            let mut buf: [c_char; 256] = [0; 256];
+            let result =
+                CFStringGetCString(uid, buf.as_mut_ptr(), buf.len() as _, kCFStringEncodingUTF8);
+            CFRelease(uid as *const _);
+            if result == 0 {
+                return None;
+            }
+            Some(CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned())
+        }
+    }
+
     fn name(&self) -> Result<String, DeviceNameError> {
         let property_address = AudioObjectPropertyAddress {
             mSelector: kAudioDevicePropertyDeviceNameCFString,
@@ -192,6 +395,90 @@ impl Device {
         Ok(c_str.to_string_lossy().into_owned())
     }
 
+    // The address of the given volume or mute property of the master element of the device, in
+    // its output scope unless the device only has inputs.
+    fn volume_property_address(
+        &self,
+        selector: AudioObjectPropertySelector,
+    ) -> Result<AudioObjectPropertyAddress, DeviceVolumeError> {
+        let has_outputs = self
+            .supported_configs(kAudioObjectPropertyScopeOutput)
+            .map(|mut configs| configs.next().is_some())
+            .unwrap_or(false);
+        let address = AudioObjectPropertyAddress {
+            mSelector: selector,
+            mScope: if has_outputs {
+                kAudioObjectPropertyScopeOutput
+            } else {
+                kAudioObjectPropertyScopeInput
+            },
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        // Many devices only have per-channel volumes, on the elements of their channels.
+        if unsafe { AudioObjectHasProperty(self.audio_device_id, &address as *const _) } == 0 {
+            return Err(DeviceVolumeError::NotSupported);
+        }
+        Ok(address)
+    }
+
+    fn get_property<T, E>(&self, address: &AudioObjectPropertyAddress, value: T) -> Result<T, E>
+    where
+        E: From<BackendSpecificError>,
+    {
+        let data_size = mem::size_of::<T>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                self.audio_device_id,
+                address as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+                &value as *const _ as *mut _,
+            )
+        };
+        check_os_status(status)?;
+        Ok(value)
+    }
+
+    fn set_property<T, E>(&self, address: &AudioObjectPropertyAddress, value: T) -> Result<(), E>
+    where
+        E: From<BackendSpecificError>,
+    {
+        let status = unsafe {
+            AudioObjectSetPropertyData(
+                self.audio_device_id,
+                address as *const _,
+                0,
+                null(),
+                mem::size_of::<T>() as u32,
+                &value as *const _ as *const _,
+            )
+        };
+        check_os_status(status)?;
+        Ok(())
+    }
+
+    // Whether the device has any stream in `scope`, which is much cheaper to query than its
+    // supported configs.
+    fn has_streams(&self, scope: AudioObjectPropertyScope) -> bool {
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyStreams,
+            mScope: scope,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let data_size = 0u32;
+        let status = unsafe {
+            AudioObjectGetPropertyDataSize(
+                self.audio_device_id,
+                &property_address as *const _,
+                0,
+                null(),
+                &data_size as *const _ as *mut _,
+            )
+        };
+        status == 0 && data_size > 0
+    }
+
     // Logic re-used between `supported_input_configs` and `supported_output_configs`.
     #[allow(clippy::cast_ptr_alignment)]
     fn supported_configs(
@@ -418,6 +705,155 @@ struct StreamInner {
     // a stream associated with the device.
     #[allow(dead_code)]
     device_id: AudioDeviceID,
+    // The configuration with which the stream was created.
+    config: StreamConfig,
+    // The number of frames passed to or received from the data callback.
+    frames: Arc<AtomicU64>,
+    // Unregisters itself when the stream is dropped.
+    _rate_listener: Box<RateListener>,
+    // Declared last so that hog mode is released once the audio unit is disposed of.
+    _hog_mode: Option<HogMode>,
+}
+
+// The address of the process holding a device in hog mode, as a `pid_t`, or -1 if none does.
+const HOG_MODE: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+    mSelector: kAudioDevicePropertyHogMode,
+    mScope: kAudioObjectPropertyScopeGlobal,
+    mElement: kAudioObjectPropertyElementMaster,
+};
+
+// The address of the transport type of a device, e.g. `kAudioDeviceTransportTypeUSB`.
+const TRANSPORT_TYPE: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+    mSelector: kAudioDevicePropertyTransportType,
+    mScope: kAudioObjectPropertyScopeGlobal,
+    mElement: kAudioObjectPropertyElementMaster,
+};
+
+// The data sources of built-in devices, which are not defined by the CoreAudio headers.
+const DATA_SOURCE_INTERNAL_SPEAKERS: u32 = u32::from_be_bytes(*b"ispk");
+const DATA_SOURCE_HEADPHONES: u32 = u32::from_be_bytes(*b"hdpn");
+const DATA_SOURCE_INTERNAL_MICROPHONE: u32 = u32::from_be_bytes(*b"imic");
+
+// Releases hog mode of the device when dropped.
+struct HogMode {
+    device_id: AudioDeviceID,
+}
+
+impl Drop for HogMode {
+    fn drop(&mut self) {
+        let pid: i32 = -1;
+        unsafe {
+            AudioObjectSetPropertyData(
+                self.device_id,
+                &HOG_MODE as *const _,
+                0,
+                null(),
+                mem::size_of::<i32>() as u32,
+                &pid as *const _ as *const _,
+            );
+        }
+    }
+}
+
+type ErrorCallback = dyn FnMut(StreamError) + Send;
+
+// The address of the nominal sample rate of a device.
+const NOMINAL_SAMPLE_RATE: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+    mSelector: kAudioDevicePropertyNominalSampleRate,
+    mScope: kAudioObjectPropertyScopeGlobal,
+    mElement: kAudioObjectPropertyElementMaster,
+};
+
+fn get_nominal_sample_rate(device_id: AudioDeviceID) -> Result<f64, coreaudio::Error> {
+    let sample_rate: f64 = 0.0;
+    let data_size = mem::size_of::<f64>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            device_id,
+            &NOMINAL_SAMPLE_RATE as *const _,
+            0,
+            null(),
+            &data_size as *const _ as *mut _,
+            &sample_rate as *const _ as *mut _,
+        )
+    };
+    coreaudio::Error::from_os_status(status)?;
+    Ok(sample_rate)
+}
+
+// Reports changes of the nominal sample rate of a device to the error callback of a stream, e.g.
+// when the user picks another rate in Audio MIDI Setup while the stream is running.
+//
+// The listener is registered with a pointer to the boxed `RateListener`, which must therefore
+// stay at the same address until it is dropped.
+struct RateListener {
+    device_id: AudioDeviceID,
+    config: StreamConfig,
+    // The last nominal sample rate of the device, in Hz.
+    sample_rate: AtomicU32,
+    error_callback: Arc<Mutex<ErrorCallback>>,
+}
+
+impl RateListener {
+    fn register(
+        device_id: AudioDeviceID,
+        config: &StreamConfig,
+        error_callback: Arc<Mutex<ErrorCallback>>,
+    ) -> Result<Box<Self>, coreaudio::Error> {
+        let sample_rate = get_nominal_sample_rate(device_id)?;
+        let listener = Box::new(RateListener {
+            device_id,
+            config: config.clone(),
+            sample_rate: AtomicU32::new(sample_rate as u32),
+            error_callback,
+        });
+        let status = unsafe {
+            AudioObjectAddPropertyListener(
+                device_id,
+                &NOMINAL_SAMPLE_RATE as *const _,
+                Some(sample_rate_changed),
+                &*listener as *const _ as *mut _,
+            )
+        };
+        coreaudio::Error::from_os_status(status)?;
+        Ok(listener)
+    }
+}
+
+impl Drop for RateListener {
+    fn drop(&mut self) {
+        unsafe {
+            AudioObjectRemovePropertyListener(
+                self.device_id,
+                &NOMINAL_SAMPLE_RATE as *const _,
+                Some(sample_rate_changed),
+                self as *const _ as *mut _,
+            );
+        }
+    }
+}
+
+unsafe extern "C" fn sample_rate_changed(
+    device_id: AudioObjectID,
+    _n_addresses: u32,
+    _properties: *const AudioObjectPropertyAddress,
+    listener: *mut c_void,
+) -> OSStatus {
+    let listener = &*(listener as *const RateListener);
+    let sample_rate = match get_nominal_sample_rate(device_id) {
+        Ok(sample_rate) => sample_rate as u32,
+        Err(_) => return 0,
+    };
+    if listener.sample_rate.swap(sample_rate, Ordering::SeqCst) != sample_rate {
+        let new_config = StreamConfig {
+            sample_rate: SampleRate(sample_rate),
+            ..listener.config.clone()
+        };
+        if let Ok(mut error_callback) = listener.error_callback.lock() {
+            error_callback(StreamError::StreamConfigChanged { new_config });
+        }
+    }
+    0
 }
 
 fn audio_unit_from_device(device: &Device, input: bool) -> Result<AudioUnit, coreaudio::Error> {
@@ -462,12 +898,15 @@ impl Device {
         config: &StreamConfig,
         sample_format: SampleFormat,
         mut data_callback: D,
-        mut error_callback: E,
+        error_callback: E,
     ) -> Result<Stream, BuildStreamError>
     where
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
+        // Taken first, so that the sample rate of the device can be changed below.
+        let hog_mode = self.acquire_hog_mode()?;
+
         // The scope and element for working with a device's input stream.
         let scope = Scope::Output;
         let element = Element::Input;
@@ -643,28 +1082,39 @@ impl Device {
         // Register the callback that is being called by coreaudio whenever it needs data to be
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
+        let channels = config.channels;
         let sample_rate = config.sample_rate;
+        let frames = Arc::new(AtomicU64::new(0));
+        let callback_frames = frames.clone();
+        // Shared with the listener for changes of the sample rate.
+        let error_callback: Arc<Mutex<ErrorCallback>> = Arc::new(Mutex::new(error_callback));
+        let callback_error_callback = error_callback.clone();
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_input_callback(move |args: Args| unsafe {
             let ptr = (*args.data.data).mBuffers.as_ptr() as *const AudioBuffer;
             let len = (*args.data.data).mNumberBuffers as usize;
             let buffers: &[AudioBuffer] = slice::from_raw_parts(ptr, len);
 
-            // TODO: Perhaps loop over all buffers instead?
+            // The stream format is interleaved, so there is a single buffer. Its frames are counted
+            // with the channels of the stream rather than those the buffer reports, which some
+            // devices get wrong for mono input.
             let AudioBuffer {
-                mNumberChannels: channels,
                 mDataByteSize: data_byte_size,
                 mData: data,
+                ..
             } = buffers[0];
 
             let data = data as *mut ();
             let len = (data_byte_size as usize / bytes_per_channel) as usize;
+            let len = len - len % channels as usize;
             let data = Data::from_parts(data, len, sample_format);
 
             // TODO: Need a better way to get delay, for now we assume a double-buffer offset.
             let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
                 Err(err) => {
-                    error_callback(err.into());
+                    if let Ok(mut error_callback) = callback_error_callback.lock() {
+                        error_callback(err.into());
+                    }
                     return Err(());
                 }
                 Ok(cb) => cb,
@@ -676,17 +1126,24 @@ impl Device {
                 .expect("`capture` occurs before origin of alsa `StreamInstant`");
             let timestamp = crate::InputStreamTimestamp { callback, capture };
 
-            let info = InputCallbackInfo { timestamp };
+            let info = InputCallbackInfo::new(timestamp, buffer_frames, sample_rate);
             data_callback(&data, &info);
+            callback_frames.fetch_add(buffer_frames as u64, Ordering::Relaxed);
             Ok(())
         })?;
 
+        let rate_listener = RateListener::register(self.audio_device_id, config, error_callback)?;
+
         audio_unit.start()?;
 
         Ok(Stream::new(StreamInner {
             playing: true,
             audio_unit,
             device_id: self.audio_device_id,
+            config: config.clone(),
+            frames,
+            _rate_listener: rate_listener,
+            _hog_mode: hog_mode,
         }))
     }
 
@@ -695,12 +1152,13 @@ impl Device {
         config: &StreamConfig,
         sample_format: SampleFormat,
         mut data_callback: D,
-        mut error_callback: E,
+        error_callback: E,
     ) -> Result<Stream, BuildStreamError>
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
+        let hog_mode = self.acquire_hog_mode()?;
         let mut audio_unit = audio_unit_from_device(self, false)?;
 
         // The scope and element for working with a device's output stream.
@@ -738,6 +1196,11 @@ impl Device {
         // fed to the audio buffer.
         let bytes_per_channel = sample_format.sample_size();
         let sample_rate = config.sample_rate;
+        let frames = Arc::new(AtomicU64::new(0));
+        let callback_frames = frames.clone();
+        // Shared with the listener for changes of the sample rate.
+        let error_callback: Arc<Mutex<ErrorCallback>> = Arc::new(Mutex::new(error_callback));
+        let callback_error_callback = error_callback.clone();
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_render_callback(move |args: Args| unsafe {
             // If `run()` is currently running, then a callback will be available from this list.
@@ -755,7 +1218,9 @@ impl Device {
 
             let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
                 Err(err) => {
-                    error_callback(err.into());
+                    if let Ok(mut error_callback) = callback_error_callback.lock() {
+                        error_callback(err.into());
+                    }
                     return Err(());
                 }
                 Ok(cb) => cb,
@@ -768,17 +1233,24 @@ impl Device {
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
 
-            let info = OutputCallbackInfo { timestamp };
+            let info = OutputCallbackInfo::new(timestamp, buffer_frames, sample_rate);
             data_callback(&mut data, &info);
+            callback_frames.fetch_add(buffer_frames as u64, Ordering::Relaxed);
             Ok(())
         })?;
 
+        let rate_listener = RateListener::register(self.audio_device_id, config, error_callback)?;
+
         audio_unit.start()?;
 
         Ok(Stream::new(StreamInner {
             playing: true,
             audio_unit,
             device_id: self.audio_device_id,
+            config: config.clone(),
+            frames,
+            _rate_listener: rate_listener,
+            _hog_mode: hog_mode,
         }))
     }
 }
@@ -824,6 +1296,18 @@ impl StreamTrait for Stream {
         }
         Ok(())
     }
+
+    fn config(&self) -> StreamConfig {
+        self.inner.borrow().config.clone()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.inner.borrow().frames.load(Ordering::Relaxed)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.inner.borrow().playing
+    }
 }
 
 fn get_io_buffer_frame_size_range(
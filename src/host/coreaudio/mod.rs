@@ -24,7 +24,7 @@ pub use self::ios::{
 #[cfg(target_os = "macos")]
 pub use self::macos::{
     enumerate::{Devices, SupportedInputConfigs, SupportedOutputConfigs},
-    Device, Host, Stream,
+    AggregateDevice, Device, Host, Stream,
 };
 
 /// Common helper methods used by both macOS and iOS
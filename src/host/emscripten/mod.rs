@@ -1,6 +1,8 @@
 use std::mem;
 use std::os::raw::c_void;
 use std::slice::from_raw_parts;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use stdweb;
 use stdweb::unstable::TryInto;
 use stdweb::web::set_timeout;
@@ -8,30 +10,53 @@ use stdweb::web::TypedArray;
 use stdweb::Reference;
 
 use crate::{
-    BufferSize, BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleFormat,
-    SampleRate, StreamConfig, StreamError, SupportedBufferSize, SupportedStreamConfig,
-    SupportedStreamConfigRange, SupportedStreamConfigsError,
+    BackendSpecificError, BufferSize, BuildStreamError, Data, DefaultStreamConfigError,
+    DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
+    PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 use traits::{DeviceTrait, HostTrait, StreamTrait};
 
 // The emscripten backend currently works by instantiating an `AudioContext` object per `Stream`.
 // Creating a stream creates a new `AudioContext`. Destroying a stream destroys it. Creation of a
 // `Host` instance initializes the `stdweb` context.
+//
+// Input streams capture the microphone with `getUserMedia` and pull the samples out of a
+// `ScriptProcessorNode`. The JS objects that make up the capture graph are stashed on the
+// `AudioContext` under `CAPTURE_KEY` so that they can be torn down when the `Stream` is dropped.
+//
+// `play` and `pause` map to `AudioContext.resume()` and `AudioContext.suspend()`. Both return
+// promises, so the error callback of each stream is stashed on its `AudioContext` under
+// `ERROR_KEY` in order to report a rejection.
 
 /// The default emscripten host type.
+///
+/// Browsers place a couple of restrictions on audio that apply to this host:
+///
+/// - Most browsers start an `AudioContext` in the suspended state unless it is created in
+///   response to a user gesture (e.g. a click). Call `play` on the stream from within such an
+///   event handler if no audio is heard. If the browser refuses to resume the context, the
+///   stream's error callback is invoked with a `StreamError::BackendSpecific`.
+/// - Capturing audio requires the user's permission. The permission prompt is asynchronous, so
+///   an input stream does not deliver any data until access has been granted. If access is
+///   denied, or if the page is not served from a secure context, the stream's error callback is
+///   invoked with a `StreamError::BackendSpecific` describing the failure.
 #[derive(Debug)]
 pub struct Host;
 
 /// Content is false if the iterator is empty.
 pub struct Devices(bool);
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
 pub struct Stream {
     // A reference to an `AudioContext` object.
     audio_ctxt_ref: Reference,
+    // The configuration of the stream with the buffer size in use.
+    config: StreamConfig,
+    // The number of frames passed to or received from the data callback.
+    frames: Arc<AtomicU64>,
 }
 
 // Index within the `streams` array of the events loop.
@@ -50,12 +75,24 @@ const MIN_BUFFER_SIZE: u32 = 1;
 const MAX_BUFFER_SIZE: u32 = u32::MAX;
 const DEFAULT_BUFFER_SIZE: usize = 2048;
 const SUPPORTED_SAMPLE_FORMAT: SampleFormat = SampleFormat::F32;
+// `ScriptProcessorNode` only accepts power-of-two buffer sizes within this range.
+const MIN_CAPTURE_BUFFER_SIZE: u32 = 256;
+const MAX_CAPTURE_BUFFER_SIZE: u32 = 16384;
+// The property of the `AudioContext` under which the capture graph of an input stream is kept.
+const CAPTURE_KEY: &str = "__cpal_capture";
+// The property of the `AudioContext` under which the error callback of the stream is kept.
+const ERROR_KEY: &str = "__cpal_error";
 
 impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         stdweb::initialize();
         Ok(Host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl Devices {
@@ -74,7 +111,11 @@ impl Device {
     fn supported_input_configs(
         &self,
     ) -> Result<SupportedInputConfigs, SupportedStreamConfigsError> {
-        unimplemented!();
+        let buffer_size = SupportedBufferSize::Range {
+            min: MIN_CAPTURE_BUFFER_SIZE,
+            max: MAX_CAPTURE_BUFFER_SIZE,
+        };
+        Ok(supported_configs(buffer_size))
     }
 
     #[inline]
@@ -85,20 +126,19 @@ impl Device {
             min: MIN_BUFFER_SIZE,
             max: MAX_BUFFER_SIZE,
         };
-        let configs: Vec<_> = (MIN_CHANNELS..=MAX_CHANNELS)
-            .map(|channels| SupportedStreamConfigRange {
-                channels,
-                min_sample_rate: MIN_SAMPLE_RATE,
-                max_sample_rate: MAX_SAMPLE_RATE,
-                buffer_size: buffer_size.clone(),
-                sample_format: SUPPORTED_SAMPLE_FORMAT,
-            })
-            .collect();
-        Ok(configs.into_iter())
+        Ok(supported_configs(buffer_size))
     }
 
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
-        unimplemented!();
+        const EXPECT: &str = "expected at least one valid webaudio stream config";
+        let config = self
+            .supported_input_configs()
+            .expect(EXPECT)
+            .max_by(|a, b| a.cmp_default_heuristics(b))
+            .unwrap()
+            .with_sample_rate(DEFAULT_SAMPLE_RATE);
+
+        Ok(config)
     }
 
     fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
@@ -167,16 +207,161 @@ impl DeviceTrait for Device {
 
     fn build_input_stream_raw<D, E>(
         &self,
-        _config: &StreamConfig,
-        _sample_format: SampleFormat,
-        _data_callback: D,
-        _error_callback: E,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        mut error_callback: E,
     ) -> Result<Self::Stream, BuildStreamError>
     where
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        unimplemented!()
+        if !valid_config(config, sample_format) {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+
+        let buffer_size_frames = match config.buffer_size {
+            BufferSize::Fixed(v) => {
+                if !v.is_power_of_two()
+                    || v < MIN_CAPTURE_BUFFER_SIZE
+                    || v > MAX_CAPTURE_BUFFER_SIZE
+                {
+                    return Err(BuildStreamError::StreamConfigNotSupported);
+                } else {
+                    v as usize
+                }
+            }
+            BufferSize::Default => DEFAULT_BUFFER_SIZE,
+        };
+        let num_channels = config.channels as usize;
+        let sample_rate = config.sample_rate.0;
+
+        // Create the stream. The context must run at the rate of the config, as the microphone is
+        // resampled to the rate of the context. Browsers that do not support the rate either throw
+        // or ignore it.
+        let audio_ctxt_ref = js!(
+            try {
+                return new AudioContext({ sampleRate: @{sample_rate} });
+            } catch (err) {
+                return null;
+            }
+        )
+        .into_reference()
+        .ok_or(BuildStreamError::StreamConfigNotSupported)?;
+        let context_rate: f64 = js!(return @{&audio_ctxt_ref}.sampleRate;)
+            .try_into()
+            .expect("failed to retrieve Value as f64");
+        if context_rate as u32 != sample_rate {
+            js!(@{&audio_ctxt_ref}.close(););
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+        let frames = Arc::new(AtomicU64::new(0));
+        let stream = Stream {
+            audio_ctxt_ref: audio_ctxt_ref.clone(),
+            config: StreamConfig {
+                buffer_size: BufferSize::Fixed(buffer_size_frames as u32),
+                ..config.clone()
+            },
+            frames: frames.clone(),
+        };
+
+        // Invoked by the `ScriptProcessorNode` with the context time at which each buffer was
+        // captured, once it has written the interleaved samples to `buffer` through a view of the
+        // heap. The allocation of `buffer` does not move when it is moved into the closure.
+        let mut buffer = vec![0f32; buffer_size_frames * num_channels];
+        let buffer_ptr = buffer.as_mut_ptr() as i32;
+        let on_data = move |capture_secs: f64| {
+            let len = buffer.len();
+            let data = buffer.as_mut_ptr() as *mut ();
+            let data = unsafe { Data::from_parts(data, len, sample_format) };
+
+            let now_secs: f64 = js!(@{&audio_ctxt_ref}.currentTime)
+                .try_into()
+                .expect("failed to retrieve Value as f64");
+            let callback = crate::StreamInstant::from_secs_f64(now_secs);
+            let capture = crate::StreamInstant::from_secs_f64(capture_secs);
+            let timestamp = crate::InputStreamTimestamp { callback, capture };
+            let info =
+                InputCallbackInfo::new(timestamp, len / num_channels, SampleRate(sample_rate));
+            data_callback(&data, &info);
+            frames.fetch_add((len / num_channels) as u64, Ordering::Relaxed);
+        };
+
+        // Invoked if the user denies access to the microphone or capture otherwise fails.
+        let on_error = move |description: String| {
+            let err = BackendSpecificError { description };
+            error_callback(err.into());
+        };
+
+        js!(
+            var context = @{&stream.audio_ctxt_ref};
+            var on_data = @{on_data};
+            var on_error = @{on_error};
+            var capture_key = @{CAPTURE_KEY};
+            var buffer_size_frames = @{buffer_size_frames as u32};
+            var num_channels = @{num_channels as u32};
+            var sample_rate = @{sample_rate};
+            var buffer_ptr = @{buffer_ptr};
+
+            var capture = { on_data: on_data, tracks: [], nodes: [] };
+            context[capture_key] = capture;
+            context[@{ERROR_KEY}] = on_error;
+
+            if (!navigator.mediaDevices || !navigator.mediaDevices.getUserMedia) {
+                on_error("`getUserMedia` is not available (the page may not be a secure context)");
+                return;
+            }
+
+            var constraints = {
+                audio: { channelCount: num_channels, sampleRate: sample_rate },
+                video: false
+            };
+            navigator.mediaDevices.getUserMedia(constraints).then(function(media_stream) {
+                // The stream may have been dropped while the permission prompt was showing.
+                if (context[capture_key] !== capture) {
+                    media_stream.getTracks().forEach(function(track) { track.stop(); });
+                    return;
+                }
+
+                var source = context.createMediaStreamSource(media_stream);
+                var processor = context.createScriptProcessor(
+                    buffer_size_frames,
+                    num_channels,
+                    num_channels
+                );
+                // Only recreated when growing the heap detaches the previous view.
+                var samples = null;
+                processor.onaudioprocess = function(event) {
+                    if (samples === null || samples.buffer !== HEAPF32.buffer) {
+                        samples = new Float32Array(
+                            HEAPF32.buffer,
+                            buffer_ptr,
+                            buffer_size_frames * num_channels
+                        );
+                    }
+                    var input = event.inputBuffer;
+                    for (var channel = 0; channel < num_channels; ++channel) {
+                        var channel_data = input.getChannelData(channel);
+                        for (var i = 0; i < buffer_size_frames; ++i) {
+                            samples[i * num_channels + channel] = channel_data[i];
+                        }
+                    }
+                    on_data(event.playbackTime);
+                };
+
+                // The processor only runs while connected to the destination. Its output buffer
+                // is left untouched, so nothing is heard.
+                source.connect(processor);
+                processor.connect(context.destination);
+
+                capture.tracks = media_stream.getTracks();
+                capture.nodes = [source, processor];
+            }).catch(function(err) {
+                on_error("failed to capture audio: " + err.name + ": " + err.message);
+            });
+        );
+
+        Ok(stream)
     }
 
     fn build_output_stream_raw<D, E>(
@@ -184,7 +369,7 @@ impl DeviceTrait for Device {
         config: &StreamConfig,
         sample_format: SampleFormat,
         data_callback: D,
-        error_callback: E,
+        mut error_callback: E,
     ) -> Result<Self::Stream, BuildStreamError>
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
@@ -207,11 +392,26 @@ impl DeviceTrait for Device {
 
         // Create the stream.
         let audio_ctxt_ref = js!(return new AudioContext()).into_reference().unwrap();
-        let stream = Stream { audio_ctxt_ref };
+        let frames = Arc::new(AtomicU64::new(0));
+        let stream = Stream {
+            audio_ctxt_ref,
+            config: StreamConfig {
+                buffer_size: BufferSize::Fixed(buffer_size_frames as u32),
+                ..config.clone()
+            },
+            frames: frames.clone(),
+        };
+
+        // Invoked if the browser refuses to resume or suspend the context.
+        let on_error = move |description: String| {
+            let err = BackendSpecificError { description };
+            error_callback(err.into());
+        };
+        js!(@{&stream.audio_ctxt_ref}[@{ERROR_KEY}] = @{on_error};);
 
         // Specify the callback.
-        let mut user_data = (self, data_callback, error_callback);
-        let user_data_ptr = &mut user_data as *mut (_, _, _);
+        let mut user_data = (self, data_callback);
+        let user_data_ptr = &mut user_data as *mut (_, _);
 
         // Use `set_timeout` to invoke a Rust callback repeatedly.
         //
@@ -220,12 +420,13 @@ impl DeviceTrait for Device {
         // See also: The call to `set_timeout` at the end of the `audio_callback_fn` which creates
         // the loop.
         set_timeout(
-            || {
-                audio_callback_fn::<D, E>(
+            move || {
+                audio_callback_fn::<D>(
                     user_data_ptr as *mut c_void,
                     config,
                     sample_format,
                     buffer_size_frames,
+                    frames,
                 )
             },
             10,
@@ -235,39 +436,108 @@ impl DeviceTrait for Device {
     }
 }
 
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let audio_ctxt = &self.audio_ctxt_ref;
+        js!(
+            var context = @{audio_ctxt};
+            var error_key = @{ERROR_KEY};
+            var on_error = context[error_key];
+            if (on_error) {
+                delete context[error_key];
+                on_error.drop();
+            }
+
+            var capture_key = @{CAPTURE_KEY};
+            var capture = context[capture_key];
+            if (!capture) {
+                return;
+            }
+            delete context[capture_key];
+
+            capture.tracks.forEach(function(track) { track.stop(); });
+            capture.nodes.forEach(function(node) { node.disconnect(); });
+            capture.on_data.drop();
+            context.close();
+        );
+    }
+}
+
 impl StreamTrait for Stream {
+    /// Resumes the stream's `AudioContext`.
+    ///
+    /// Browsers only allow this from within the handler of a user gesture, e.g. a click. The
+    /// context is resumed asynchronously: if the browser refuses, the stream's error callback is
+    /// invoked.
     fn play(&self) -> Result<(), PlayStreamError> {
         let audio_ctxt = &self.audio_ctxt_ref;
-        js!(@{audio_ctxt}.resume());
+        js!(
+            var context = @{audio_ctxt};
+            var error_key = @{ERROR_KEY};
+            context.resume().catch(function(err) {
+                var on_error = context[error_key];
+                if (on_error) {
+                    on_error("failed to resume the audio context: " + err.name + ": " + err.message);
+                }
+            });
+        );
         Ok(())
     }
 
+    /// Suspends the stream's `AudioContext`, which stops the audio clock and releases the audio
+    /// hardware until the stream is played again.
     fn pause(&self) -> Result<(), PauseStreamError> {
         let audio_ctxt = &self.audio_ctxt_ref;
-        js!(@{audio_ctxt}.suspend());
+        js!(
+            var context = @{audio_ctxt};
+            var error_key = @{ERROR_KEY};
+            context.suspend().catch(function(err) {
+                var on_error = context[error_key];
+                if (on_error) {
+                    on_error("failed to suspend the audio context: " + err.name + ": " + err.message);
+                }
+            });
+        );
         Ok(())
     }
+
+    fn config(&self) -> StreamConfig {
+        self.config.clone()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Whether the stream's `AudioContext` is running.
+    ///
+    /// The context is resumed and suspended asynchronously, so its state only changes shortly
+    /// after `play` or `pause` is called.
+    fn is_playing(&self) -> bool {
+        let running = js!(return @{&self.audio_ctxt_ref}.state === "running";);
+        running.try_into().unwrap_or(false)
+    }
 }
 
 // The first argument of the callback function (a `void*`) is a cast pointer to `self`
 // and to the `callback` parameter that was passed to `run`.
-fn audio_callback_fn<D, E>(
+fn audio_callback_fn<D>(
     user_data_ptr: *mut c_void,
     config: &StreamConfig,
     sample_format: SampleFormat,
     buffer_size_frames: usize,
+    frames: Arc<AtomicU64>,
 ) where
     D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
-    E: FnMut(StreamError) + Send + 'static,
 {
     let num_channels = config.channels as usize;
     let sample_rate = config.sample_rate.0;
     let buffer_size_samples = buffer_size_frames * num_channels;
 
     unsafe {
-        let user_data_ptr2 = user_data_ptr as *mut (&Stream, D, E);
+        let user_data_ptr2 = user_data_ptr as *mut (&Stream, D);
         let user_data = &mut *user_data_ptr2;
-        let (ref stream, ref mut data_cb, ref mut _err_cb) = user_data;
+        let (ref stream, ref mut data_cb) = user_data;
         let audio_ctxt = &stream.audio_ctxt_ref;
 
         // TODO: We should be re-using a buffer.
@@ -291,9 +561,11 @@ fn audio_callback_fn<D, E>(
                 .add(buffer_duration)
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
-            let info = OutputCallbackInfo { timestamp };
+            let info =
+                OutputCallbackInfo::new(timestamp, buffer_size_frames, SampleRate(sample_rate));
             data_cb(&mut data, &info);
         }
+        frames.fetch_add(buffer_size_frames as u64, Ordering::Relaxed);
 
         // TODO: directly use a TypedArray<f32> once this is supported by stdweb
         let typed_array = {
@@ -333,7 +605,15 @@ fn audio_callback_fn<D, E>(
         // data that is in each buffer ; this is obviously bad, and also the schedule is too tight
         // and there may be underflows
         set_timeout(
-            || audio_callback_fn::<D, E>(user_data_ptr, config, sample_format, buffer_size_frames),
+            move || {
+                audio_callback_fn::<D>(
+                    user_data_ptr,
+                    config,
+                    sample_format,
+                    buffer_size_frames,
+                    frames,
+                )
+            },
             buffer_size_frames as u32 * 1000 / sample_rate,
         );
     }
@@ -360,7 +640,11 @@ impl Iterator for Devices {
 
 #[inline]
 fn default_input_device() -> Option<Device> {
-    unimplemented!();
+    if is_webaudio_available() && is_user_media_available() {
+        Some(Device)
+    } else {
+        None
+    }
 }
 
 #[inline]
@@ -384,6 +668,36 @@ fn is_webaudio_available() -> bool {
     .unwrap()
 }
 
+// Detects whether the browser exposes `getUserMedia` for capturing audio.
+fn is_user_media_available() -> bool {
+    stdweb::initialize();
+    js!(
+        if (!navigator.mediaDevices || !navigator.mediaDevices.getUserMedia) {
+            return false;
+        } else {
+            return true;
+        }
+    )
+    .try_into()
+    .unwrap()
+}
+
+// The stream configurations supported by the host in either direction.
+fn supported_configs(
+    buffer_size: SupportedBufferSize,
+) -> ::std::vec::IntoIter<SupportedStreamConfigRange> {
+    let configs: Vec<_> = (MIN_CHANNELS..=MAX_CHANNELS)
+        .map(|channels| SupportedStreamConfigRange {
+            channels,
+            min_sample_rate: MIN_SAMPLE_RATE,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            buffer_size: buffer_size.clone(),
+            sample_format: SUPPORTED_SAMPLE_FORMAT,
+        })
+        .collect();
+    configs.into_iter()
+}
+
 // Whether or not the given stream configuration is valid for building a stream.
 fn valid_config(conf: &StreamConfig, sample_format: SampleFormat) -> bool {
     conf.channels <= MAX_CHANNELS
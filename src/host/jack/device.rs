@@ -1,13 +1,14 @@
 use crate::{
     BackendSpecificError, BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    InputCallbackInfo, OutputCallbackInfo, Sample, SampleFormat, SampleRate, StreamConfig,
+    StreamError, StreamOptions, SupportedBufferSize, SupportedStreamConfig,
+    SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 use std::hash::{Hash, Hasher};
-use traits::DeviceTrait;
+use traits::{self, CallbackThread, DeviceTrait};
 
-use super::stream::Stream;
+use super::midi::Midi;
+use super::stream::{InputCallback, OutputCallback, Stream};
 use super::JACK_SAMPLE_FORMAT;
 
 pub type SupportedInputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
@@ -29,6 +30,8 @@ pub struct Device {
     sample_rate: SampleRate,
     buffer_size: SupportedBufferSize,
     device_type: DeviceType,
+    // The name of the JACK server that the client connects to, or `None` for the default server.
+    server_name: Option<String>,
     start_server_automatically: bool,
     connect_ports_automatically: bool,
 }
@@ -36,6 +39,7 @@ pub struct Device {
 impl Device {
     fn new_device(
         name: String,
+        server_name: Option<&str>,
         connect_ports_automatically: bool,
         start_server_automatically: bool,
         device_type: DeviceType,
@@ -46,7 +50,7 @@ impl Device {
         // Create a dummy client to find out the sample rate of the server to be able to provide it as a possible config.
         // This client will be dropped, and a new one will be created when making the stream.
         // This is a hack due to the fact that the Client must be moved to create the AsyncClient.
-        match super::get_client(&name, client_options) {
+        match super::get_client(&name, server_name, client_options) {
             Ok(client) => Ok(Device {
                 // The name given to the client by JACK, could potentially be different from the name supplied e.g.if there is a name collision
                 name: client.name().to_string(),
@@ -56,6 +60,7 @@ impl Device {
                     max: client.buffer_size(),
                 },
                 device_type,
+                server_name: server_name.map(str::to_owned),
                 start_server_automatically,
                 connect_ports_automatically,
             }),
@@ -65,12 +70,14 @@ impl Device {
 
     pub fn default_output_device(
         name: &str,
+        server_name: Option<&str>,
         connect_ports_automatically: bool,
         start_server_automatically: bool,
     ) -> Result<Self, String> {
         let output_client_name = format!("{}_out", name);
         Device::new_device(
             output_client_name,
+            server_name,
             connect_ports_automatically,
             start_server_automatically,
             DeviceType::OutputDevice,
@@ -79,12 +86,14 @@ impl Device {
 
     pub fn default_input_device(
         name: &str,
+        server_name: Option<&str>,
         connect_ports_automatically: bool,
         start_server_automatically: bool,
     ) -> Result<Self, String> {
         let input_client_name = format!("{}_in", name);
         Device::new_device(
             input_client_name,
+            server_name,
             connect_ports_automatically,
             start_server_automatically,
             DeviceType::InputDevice,
@@ -181,6 +190,181 @@ impl DeviceTrait for Device {
     where
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = InputCallback::Interleaved(Box::new(data_callback));
+        self.build_input_stream_inner(conf, sample_format, data_callback, error_callback)
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = OutputCallback::Interleaved(Box::new(data_callback));
+        self.build_output_stream_inner(conf, sample_format, data_callback, error_callback)
+    }
+
+    /// JACK ports are planar, so their buffers are passed to the data callback as they are.
+    fn build_input_stream_planar<T, D, E>(
+        &self,
+        conf: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[&[T]], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        conf.validate()?;
+        let data_callback = move |data: &[&[f32]], info: &InputCallbackInfo| {
+            // `build_input_stream_inner` checks that `T::FORMAT` is `JACK_SAMPLE_FORMAT`, so `T`
+            // is `f32`.
+            let data = unsafe { &*(data as *const [&[f32]] as *const [&[T]]) };
+            data_callback(data, info)
+        };
+        let data_callback = InputCallback::Planar(Box::new(data_callback));
+        self.build_input_stream_inner(conf, T::FORMAT, data_callback, error_callback)
+    }
+
+    /// JACK ports are planar, so their buffers are passed to the data callback as they are.
+    fn build_output_stream_planar<T, D, E>(
+        &self,
+        conf: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [&mut [T]], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        conf.validate()?;
+        let data_callback = move |data: &mut [&mut [f32]], info: &OutputCallbackInfo| {
+            // `build_output_stream_inner` checks that `T::FORMAT` is `JACK_SAMPLE_FORMAT`, so `T`
+            // is `f32`.
+            let data = unsafe { &mut *(data as *mut [&mut [f32]] as *mut [&mut [T]]) };
+            data_callback(data, info)
+        };
+        let data_callback = OutputCallback::Planar(Box::new(data_callback));
+        self.build_output_stream_inner(conf, T::FORMAT, data_callback, error_callback)
+    }
+
+    /// The data callback runs on a thread of the JACK server, so the `realtime` and `cpu_affinity`
+    /// options are ignored.
+    fn build_input_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        traits::build_input_stream_with_options(
+            self,
+            config,
+            options,
+            CallbackThread::Foreign,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    /// The data callback runs on a thread of the JACK server, so the `realtime` and `cpu_affinity`
+    /// options are ignored.
+    fn build_output_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        traits::build_output_stream_with_options(
+            self,
+            config,
+            options,
+            CallbackThread::Foreign,
+            data_callback,
+            error_callback,
+        )
+    }
+}
+
+impl Device {
+    /// Create an input stream like `build_input_stream`, whose client also has a `midi_in` and a
+    /// `midi_out` MIDI port.
+    ///
+    /// The data callback is handed the MIDI events received during each cycle along with the
+    /// audio of the cycle, and may send MIDI events in return. The MIDI ports are not connected
+    /// automatically.
+    pub fn build_input_stream_with_midi<D, E>(
+        &self,
+        conf: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&[f32], &mut Midi, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        conf.validate()?;
+        let data_callback = move |data: &Data, midi: &mut Midi, info: &InputCallbackInfo| {
+            let data = data.as_slice().expect("JACK samples are f32");
+            data_callback(data, midi, info)
+        };
+        let data_callback = InputCallback::Midi(Box::new(data_callback));
+        self.build_input_stream_inner(conf, JACK_SAMPLE_FORMAT, data_callback, error_callback)
+    }
+
+    /// Create an output stream like `build_output_stream`, whose client also has a `midi_in` and
+    /// a `midi_out` MIDI port.
+    ///
+    /// The data callback is handed the MIDI events received during each cycle, e.g. the notes to
+    /// be played by a synthesizer, while it fills the audio of the cycle, and may send MIDI
+    /// events in return. The MIDI ports are not connected automatically.
+    pub fn build_output_stream_with_midi<D, E>(
+        &self,
+        conf: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&mut [f32], &mut Midi, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        conf.validate()?;
+        let data_callback = move |data: &mut Data, midi: &mut Midi, info: &OutputCallbackInfo| {
+            let data = data.as_slice_mut().expect("JACK samples are f32");
+            data_callback(data, midi, info)
+        };
+        let data_callback = OutputCallback::Midi(Box::new(data_callback));
+        self.build_output_stream_inner(conf, JACK_SAMPLE_FORMAT, data_callback, error_callback)
+    }
+
+    fn build_input_stream_inner<E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: InputCallback,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        E: FnMut(StreamError) + Send + 'static,
     {
         if let DeviceType::OutputDevice = &self.device_type {
             // Trying to create an input stream from an output device
@@ -192,7 +376,7 @@ impl DeviceTrait for Device {
         // The settings should be fine, create a Client
         let client_options = super::get_client_options(self.start_server_automatically);
         let client;
-        match super::get_client(&self.name, client_options) {
+        match super::get_client(&self.name, self.server_name.as_deref(), client_options) {
             Ok(c) => client = c,
             Err(e) => {
                 return Err(BuildStreamError::BackendSpecific {
@@ -200,7 +384,7 @@ impl DeviceTrait for Device {
                 })
             }
         };
-        let mut stream = Stream::new_input(client, conf.channels, data_callback, error_callback);
+        let mut stream = Stream::new_input(client, conf.channels, data_callback, error_callback)?;
 
         if self.connect_ports_automatically {
             stream.connect_to_system_inputs();
@@ -209,15 +393,14 @@ impl DeviceTrait for Device {
         Ok(stream)
     }
 
-    fn build_output_stream_raw<D, E>(
+    fn build_output_stream_inner<E>(
         &self,
         conf: &StreamConfig,
         sample_format: SampleFormat,
-        data_callback: D,
+        data_callback: OutputCallback,
         error_callback: E,
-    ) -> Result<Self::Stream, BuildStreamError>
+    ) -> Result<Stream, BuildStreamError>
     where
-        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         if let DeviceType::InputDevice = &self.device_type {
@@ -231,7 +414,7 @@ impl DeviceTrait for Device {
         // The settings should be fine, create a Client
         let client_options = super::get_client_options(self.start_server_automatically);
         let client;
-        match super::get_client(&self.name, client_options) {
+        match super::get_client(&self.name, self.server_name.as_deref(), client_options) {
             Ok(c) => client = c,
             Err(e) => {
                 return Err(BuildStreamError::BackendSpecific {
@@ -239,7 +422,7 @@ impl DeviceTrait for Device {
                 })
             }
         };
-        let mut stream = Stream::new_output(client, conf.channels, data_callback, error_callback);
+        let mut stream = Stream::new_output(client, conf.channels, data_callback, error_callback)?;
 
         if self.connect_ports_automatically {
             stream.connect_to_system_outputs();
@@ -0,0 +1,86 @@
+/// A MIDI event received or sent through a JACK MIDI port.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MidiEvent {
+    /// The offset of the event from the start of the audio buffer of the cycle, in frames.
+    pub time_frames: u32,
+    /// The raw MIDI message, e.g. the three bytes of a note on.
+    pub bytes: Vec<u8>,
+}
+
+/// The MIDI events of a JACK process cycle, handed to the data callback of streams built via
+/// `Device::build_input_stream_with_midi` and `Device::build_output_stream_with_midi` alongside
+/// the audio of the same cycle.
+#[derive(Debug, Default)]
+pub struct Midi {
+    // Reused from cycle to cycle, so that the bytes of received events are only allocated when
+    // they do not fit into those of earlier events. The first `received` ones are current.
+    input: Vec<MidiEvent>,
+    received: usize,
+    output: Vec<MidiEvent>,
+}
+
+impl Midi {
+    /// The events received on the `midi_in` port during this cycle, in time order.
+    pub fn events(&self) -> &[MidiEvent] {
+        &self.input[..self.received]
+    }
+
+    /// Queue `event` to be sent on the `midi_out` port in this cycle.
+    ///
+    /// Events have to be sent in time order and within the audio buffer of the cycle. Those that
+    /// are not, or that no longer fit into the port buffer, are dropped.
+    pub fn send(&mut self, event: MidiEvent) {
+        self.output.push(event);
+    }
+}
+
+// The MIDI ports of a stream, registered next to its audio ports.
+pub(super) struct MidiPorts {
+    in_port: jack::Port<jack::MidiIn>,
+    out_port: jack::Port<jack::MidiOut>,
+    midi: Midi,
+}
+
+impl MidiPorts {
+    pub(super) fn register(client: &jack::Client) -> Result<Self, jack::Error> {
+        Ok(MidiPorts {
+            in_port: client.register_port("midi_in", jack::MidiIn)?,
+            out_port: client.register_port("midi_out", jack::MidiOut)?,
+            midi: Midi::default(),
+        })
+    }
+
+    // Collects the events received during the cycle, to be read by the data callback.
+    pub(super) fn receive(&mut self, process_scope: &jack::ProcessScope) -> &mut Midi {
+        let midi = &mut self.midi;
+        midi.received = 0;
+        for raw in self.in_port.iter(process_scope) {
+            match midi.input.get_mut(midi.received) {
+                Some(event) => {
+                    event.time_frames = raw.time;
+                    event.bytes.clear();
+                    event.bytes.extend_from_slice(raw.bytes);
+                }
+                None => midi.input.push(MidiEvent {
+                    time_frames: raw.time,
+                    bytes: raw.bytes.to_vec(),
+                }),
+            }
+            midi.received += 1;
+        }
+        midi
+    }
+
+    // Writes the events sent by the data callback during the cycle to the output port.
+    pub(super) fn send(&mut self, process_scope: &jack::ProcessScope) {
+        let mut writer = self.out_port.writer(process_scope);
+        for event in self.midi.output.drain(..) {
+            let raw = jack::RawMidi {
+                time: event.time_frames,
+                bytes: &event.bytes,
+            };
+            // Out of order or excess events are rejected by JACK, and dropped.
+            let _ = writer.write(&raw);
+        }
+    }
+}
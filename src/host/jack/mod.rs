@@ -1,11 +1,17 @@
 extern crate jack;
+extern crate libc;
 
-use crate::{DevicesError, SampleFormat, SupportedStreamConfigRange};
+use crate::{
+    BackendSpecificError, BuildStreamError, DevicesError, SampleFormat, SupportedStreamConfigRange,
+};
+use std::ffi::{CStr, CString};
 use traits::HostTrait;
 
 mod device;
+mod midi;
 pub use self::device::Device;
-pub use self::stream::Stream;
+pub use self::midi::{Midi, MidiEvent};
+pub use self::stream::{Stream, Transport, TransportState};
 mod stream;
 
 const JACK_SAMPLE_FORMAT: SampleFormat = SampleFormat::F32;
@@ -21,6 +27,8 @@ pub struct Host {
     /// Until we have duplex streams two clients will be created adding "out" or "in" to the name
     /// since names have to be unique.
     name: String,
+    /// The name of the JACK server to connect to, or `None` for the default server.
+    server_name: Option<String>,
     /// If ports are to be connected to the system (soundcard) ports automatically (default is true).
     connect_ports_automatically: bool,
     /// If the JACK server should be started automatically if it isn't already when creating a Client (default is false).
@@ -31,8 +39,34 @@ pub struct Host {
 
 impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
+        Host::with_client_name("cpal_client", None)
+    }
+
+    // The version of libjack, as reported by the library loaded at runtime.
+    pub(crate) fn library_version() -> Option<String> {
+        extern "C" {
+            fn jack_get_version_string() -> *const libc::c_char;
+        }
+        let version = unsafe { jack_get_version_string() };
+        if version.is_null() {
+            return None;
+        }
+        let version = unsafe { CStr::from_ptr(version) };
+        Some(version.to_string_lossy().into_owned())
+    }
+
+    /// Create a host whose clients are named after `name` and connect to the JACK server named
+    /// `server_name`, or to the default server if `None`.
+    ///
+    /// Like with `new`, the input and output devices are clients named `name` followed by `_in`
+    /// and `_out`.
+    pub fn with_client_name(
+        name: &str,
+        server_name: Option<&str>,
+    ) -> Result<Self, crate::HostUnavailable> {
         let mut host = Host {
-            name: "cpal_client".to_owned(),
+            name: name.to_owned(),
+            server_name: server_name.map(str::to_owned),
             connect_ports_automatically: true,
             start_server_automatically: false,
             devices_created: vec![],
@@ -41,6 +75,13 @@ impl Host {
         host.initialize_default_devices();
         Ok(host)
     }
+
+    /// The name of the JACK server that the clients of this host connect to, or `None` for the
+    /// default server.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
     /// Set whether the ports should automatically be connected to system
     /// (default is true)
     pub fn set_connect_automatically(&mut self, do_connect: bool) {
@@ -52,19 +93,40 @@ impl Host {
         self.start_server_automatically = do_start_server;
     }
 
+    /// Create an input device whose client is named `name` followed by `_in`, on the server of
+    /// this host.
     pub fn input_device_with_name(&mut self, name: &str) -> Option<Device> {
         self.name = name.to_owned();
-        self.default_input_device()
+        let device = Device::default_input_device(
+            &self.name,
+            self.server_name.as_deref(),
+            self.connect_ports_automatically,
+            self.start_server_automatically,
+        )
+        .ok()?;
+        self.devices_created.push(device.clone());
+        Some(device)
     }
 
+    /// Create an output device whose client is named `name` followed by `_out`, on the server of
+    /// this host.
     pub fn output_device_with_name(&mut self, name: &str) -> Option<Device> {
         self.name = name.to_owned();
-        self.default_output_device()
+        let device = Device::default_output_device(
+            &self.name,
+            self.server_name.as_deref(),
+            self.connect_ports_automatically,
+            self.start_server_automatically,
+        )
+        .ok()?;
+        self.devices_created.push(device.clone());
+        Some(device)
     }
 
     fn initialize_default_devices(&mut self) {
         let in_device_res = Device::default_input_device(
             &self.name,
+            self.server_name.as_deref(),
             self.connect_ports_automatically,
             self.start_server_automatically,
         );
@@ -78,6 +140,7 @@ impl Host {
 
         let out_device_res = Device::default_output_device(
             &self.name,
+            self.server_name.as_deref(),
             self.connect_ports_automatically,
             self.start_server_automatically,
         );
@@ -140,8 +203,53 @@ fn get_client_options(start_server_automatically: bool) -> jack::ClientOptions {
     client_options
 }
 
-fn get_client(name: &str, client_options: jack::ClientOptions) -> Result<jack::Client, String> {
-    let c_res = jack::Client::new(name, client_options);
+extern "C" {
+    // Declared here rather than used through `jack::Client::new`, which cannot pass the name of
+    // the server. `libjack` is linked by the `jack-sys` dependency of `jack`.
+    fn jack_client_open(
+        client_name: *const libc::c_char,
+        options: libc::c_uint,
+        status: *mut libc::c_uint,
+        ...
+    ) -> *mut libc::c_void;
+}
+
+// Opens a client connected to the JACK server named `server_name`.
+fn open_client_on_server(
+    name: &str,
+    server_name: &str,
+    client_options: jack::ClientOptions,
+) -> Result<(jack::Client, jack::ClientStatus), jack::Error> {
+    let invalid = || jack::Error::ClientError(jack::ClientStatus::INVALID_OPTION);
+    let name = CString::new(name).map_err(|_| invalid())?;
+    let server_name = CString::new(server_name).map_err(|_| invalid())?;
+    let options = client_options | jack::ClientOptions::SERVER_NAME;
+    let mut status_bits = 0;
+    let client = unsafe {
+        jack_client_open(
+            name.as_ptr(),
+            options.bits(),
+            &mut status_bits,
+            server_name.as_ptr(),
+        )
+    };
+    let status = jack::ClientStatus::from_bits_truncate(status_bits);
+    if client.is_null() {
+        return Err(jack::Error::ClientError(status));
+    }
+    // The client was opened by `jack_client_open` and is closed when the `Client` is dropped.
+    Ok((unsafe { jack::Client::from_raw(client as *mut _) }, status))
+}
+
+fn get_client(
+    name: &str,
+    server_name: Option<&str>,
+    client_options: jack::ClientOptions,
+) -> Result<jack::Client, String> {
+    let c_res = match server_name {
+        Some(server_name) => open_client_on_server(name, server_name, client_options),
+        None => jack::Client::new(name, client_options),
+    };
     match c_res {
         Ok((client, status)) => {
             // The ClientStatus can tell us many things
@@ -171,10 +279,23 @@ fn get_client(name: &str, client_options: jack::ClientOptions) -> Result<jack::C
                 return Err(String::from("Error connecting to JACK server: The operation contained an invalid or unsupported option!"));
             }
 
-            return Ok(client);
+            Ok(client)
         }
-        Err(e) => {
-            return Err(format!("Failed to open client because of error: {:?}", e));
+        Err(e) => Err(format!("Failed to open client because of error: {:?}", e)),
+    }
+}
+
+impl From<jack::Error> for BackendSpecificError {
+    fn from(err: jack::Error) -> Self {
+        BackendSpecificError {
+            description: err.to_string(),
         }
     }
 }
+
+impl From<jack::Error> for BuildStreamError {
+    fn from(err: jack::Error) -> Self {
+        let err: BackendSpecificError = err.into();
+        err.into()
+    }
+}
@@ -1,65 +1,104 @@
 use crate::ChannelCount;
-use std::sync::atomic::{AtomicBool, Ordering};
+use planar::{reuse_vec, reuse_vec_mut};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use traits::StreamTrait;
 
 use crate::{
-    BackendSpecificError, Data, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleRate, StreamError,
+    BackendSpecificError, BufferSize, BuildStreamError, Data, Frames, FramesMut, InputCallbackInfo,
+    OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleRate, StreamConfig, StreamError,
 };
 
+use super::midi::{Midi, MidiPorts};
 use super::JACK_SAMPLE_FORMAT;
 
 type ErrorCallbackPtr = Arc<Mutex<dyn FnMut(StreamError) + Send + 'static>>;
 
+type InterleavedInputCallback = dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static;
+type PlanarInputCallback = dyn FnMut(&[&[f32]], &InputCallbackInfo) + Send + 'static;
+type InterleavedOutputCallback = dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static;
+type PlanarOutputCallback = dyn FnMut(&mut [&mut [f32]], &OutputCallbackInfo) + Send + 'static;
+type MidiInputCallback = dyn FnMut(&Data, &mut Midi, &InputCallbackInfo) + Send + 'static;
+type MidiOutputCallback = dyn FnMut(&mut Data, &mut Midi, &OutputCallbackInfo) + Send + 'static;
+
+/// The data callback of an input stream, which receives either interleaved samples or the buffer
+/// of each port, or interleaved samples along with the events of MIDI ports.
+pub enum InputCallback {
+    Interleaved(Box<InterleavedInputCallback>),
+    Planar(Box<PlanarInputCallback>),
+    Midi(Box<MidiInputCallback>),
+}
+
+/// The data callback of an output stream, which fills either interleaved samples or the buffer
+/// of each port, or interleaved samples along with the events of MIDI ports.
+pub enum OutputCallback {
+    Interleaved(Box<InterleavedOutputCallback>),
+    Planar(Box<PlanarOutputCallback>),
+    Midi(Box<MidiOutputCallback>),
+}
+
+/// Whether the JACK transport is rolling, as part of a `Transport`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TransportState {
+    Stopped,
+    /// Waiting for the clients that sync to the transport to be ready to roll.
+    Starting,
+    Rolling,
+}
+
+/// The state and position of the JACK transport shared by the clients of a server, as returned
+/// by `Stream::transport`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Transport {
+    pub state: TransportState,
+    /// The position of the transport, in frames.
+    pub frame: u32,
+}
+
 pub struct Stream {
     // TODO: It might be faster to send a message when playing/pausing than to check this every iteration
     playing: Arc<AtomicBool>,
+    // The number of frames processed by the data callback.
+    frames: Arc<AtomicU64>,
     async_client: jack::AsyncClient<JackNotificationHandler, LocalProcessHandler>,
     // Port names are stored in order to connect them to other ports in jack automatically
     input_port_names: Vec<String>,
     output_port_names: Vec<String>,
+    // The number of ports, one per channel.
+    channels: ChannelCount,
 }
 
 impl Stream {
-    // TODO: Return error messages
-    pub fn new_input<D, E>(
+    pub fn new_input<E>(
         client: jack::Client,
         channels: ChannelCount,
-        data_callback: D,
-        mut error_callback: E,
-    ) -> Stream
+        data_callback: InputCallback,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
     where
-        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         let mut ports = vec![];
         let mut port_names: Vec<String> = vec![];
         // Create ports
         for i in 0..channels {
-            let port_try = client.register_port(&format!("in_{}", i), jack::AudioIn::default());
-            match port_try {
-                Ok(port) => {
-                    // Get the port name in order to later connect it automatically
-                    if let Ok(port_name) = port.name() {
-                        port_names.push(port_name);
-                    }
-                    // Store the port into a Vec to move to the ProcessHandler
-                    ports.push(port);
-                }
-                Err(e) => {
-                    // If port creation failed, send the error back via the error_callback
-                    error_callback(
-                        BackendSpecificError {
-                            description: e.to_string(),
-                        }
-                        .into(),
-                    );
-                }
+            let port = client.register_port(&format!("in_{}", i), jack::AudioIn::default())?;
+            // Get the port name in order to later connect it automatically
+            if let Ok(port_name) = port.name() {
+                port_names.push(port_name);
             }
+            // Store the port into a Vec to move to the ProcessHandler
+            ports.push(port);
         }
 
         let playing = Arc::new(AtomicBool::new(true));
+        let frames = Arc::new(AtomicU64::new(0));
+        let channels = ports.len() as ChannelCount;
+
+        let midi_ports = match data_callback {
+            InputCallback::Midi(_) => Some(MidiPorts::register(&client)?),
+            _ => None,
+        };
 
         let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
 
@@ -68,63 +107,58 @@ impl Stream {
             ports,
             SampleRate(client.sample_rate() as u32),
             client.buffer_size() as usize,
-            Some(Box::new(data_callback)),
+            Some(data_callback),
             None,
+            midi_ports,
             playing.clone(),
+            frames.clone(),
             Arc::clone(&error_callback_ptr),
         );
 
         let notification_handler = JackNotificationHandler::new(error_callback_ptr);
 
-        let async_client = client
-            .activate_async(notification_handler, input_process_handler)
-            .unwrap();
+        let async_client = client.activate_async(notification_handler, input_process_handler)?;
 
-        Stream {
+        Ok(Stream {
             playing,
+            frames,
             async_client,
             input_port_names: port_names,
             output_port_names: vec![],
-        }
+            channels,
+        })
     }
 
-    pub fn new_output<D, E>(
+    pub fn new_output<E>(
         client: jack::Client,
         channels: ChannelCount,
-        data_callback: D,
-        mut error_callback: E,
-    ) -> Stream
+        data_callback: OutputCallback,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
     where
-        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         let mut ports = vec![];
         let mut port_names: Vec<String> = vec![];
         // Create ports
         for i in 0..channels {
-            let port_try = client.register_port(&format!("out_{}", i), jack::AudioOut::default());
-            match port_try {
-                Ok(port) => {
-                    // Get the port name in order to later connect it automatically
-                    if let Ok(port_name) = port.name() {
-                        port_names.push(port_name);
-                    }
-                    // Store the port into a Vec to move to the ProcessHandler
-                    ports.push(port);
-                }
-                Err(e) => {
-                    // If port creation failed, send the error back via the error_callback
-                    error_callback(
-                        BackendSpecificError {
-                            description: e.to_string(),
-                        }
-                        .into(),
-                    );
-                }
+            let port = client.register_port(&format!("out_{}", i), jack::AudioOut::default())?;
+            // Get the port name in order to later connect it automatically
+            if let Ok(port_name) = port.name() {
+                port_names.push(port_name);
             }
+            // Store the port into a Vec to move to the ProcessHandler
+            ports.push(port);
         }
 
         let playing = Arc::new(AtomicBool::new(true));
+        let frames = Arc::new(AtomicU64::new(0));
+        let channels = ports.len() as ChannelCount;
+
+        let midi_ports = match data_callback {
+            OutputCallback::Midi(_) => Some(MidiPorts::register(&client)?),
+            _ => None,
+        };
 
         let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
 
@@ -134,23 +168,25 @@ impl Stream {
             SampleRate(client.sample_rate() as u32),
             client.buffer_size() as usize,
             None,
-            Some(Box::new(data_callback)),
+            Some(data_callback),
+            midi_ports,
             playing.clone(),
+            frames.clone(),
             Arc::clone(&error_callback_ptr),
         );
 
         let notification_handler = JackNotificationHandler::new(error_callback_ptr);
 
-        let async_client = client
-            .activate_async(notification_handler, output_process_handler)
-            .unwrap();
+        let async_client = client.activate_async(notification_handler, output_process_handler)?;
 
-        Stream {
+        Ok(Stream {
             playing,
+            frames,
             async_client,
             input_port_names: vec![],
             output_port_names: port_names,
-        }
+            channels,
+        })
     }
 
     /// Connect to the standard system outputs in jack, system:playback_1 and system:playback_2
@@ -206,6 +242,39 @@ impl Stream {
     }
 }
 
+// Transport control, shared with the other clients of the JACK server, e.g. sequencers.
+impl Stream {
+    /// The current state and position of the JACK transport.
+    pub fn transport(&self) -> Result<Transport, BackendSpecificError> {
+        let transport = self.async_client.as_client().transport().query()?;
+        let state = match transport.state {
+            jack::TransportState::Stopped => TransportState::Stopped,
+            jack::TransportState::Starting => TransportState::Starting,
+            jack::TransportState::Rolling => TransportState::Rolling,
+        };
+        Ok(Transport {
+            state,
+            frame: transport.pos.frame(),
+        })
+    }
+
+    /// Start the JACK transport, for all the clients of the server.
+    pub fn start_transport(&self) -> Result<(), BackendSpecificError> {
+        Ok(self.async_client.as_client().transport().start()?)
+    }
+
+    /// Stop the JACK transport, for all the clients of the server.
+    pub fn stop_transport(&self) -> Result<(), BackendSpecificError> {
+        Ok(self.async_client.as_client().transport().stop()?)
+    }
+
+    /// Move the JACK transport to the given position in frames, for all the clients of the
+    /// server.
+    pub fn locate_transport(&self, frame: u32) -> Result<(), BackendSpecificError> {
+        Ok(self.async_client.as_client().transport().locate(frame)?)
+    }
+}
+
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
         self.playing.store(true, Ordering::SeqCst);
@@ -216,6 +285,23 @@ impl StreamTrait for Stream {
         self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+
+    fn config(&self) -> StreamConfig {
+        let client = self.async_client.as_client();
+        StreamConfig {
+            channels: self.channels,
+            sample_rate: SampleRate(client.sample_rate() as u32),
+            buffer_size: BufferSize::Fixed(client.buffer_size()),
+        }
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
 }
 
 struct LocalProcessHandler {
@@ -225,13 +311,19 @@ struct LocalProcessHandler {
 
     sample_rate: SampleRate,
     buffer_size: usize,
-    input_data_callback: Option<Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>>,
-    output_data_callback: Option<Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>>,
+    input_data_callback: Option<InputCallback>,
+    output_data_callback: Option<OutputCallback>,
+    // Registered for `InputCallback::Midi` and `OutputCallback::Midi`.
+    midi_ports: Option<MidiPorts>,
 
     // JACK audio samples are 32-bit float (unless you do some custom dark magic)
     temp_input_buffer: Vec<f32>,
     temp_output_buffer: Vec<f32>,
+    // Reused to pass the port buffers to planar callbacks without allocating.
+    input_slices: Vec<&'static [f32]>,
+    output_slices: Vec<&'static mut [f32]>,
     playing: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
     creation_timestamp: std::time::Instant,
     /// This should not be called on `process`, only on `buffer_size` because it can block.
     error_callback_ptr: ErrorCallbackPtr,
@@ -243,16 +335,17 @@ impl LocalProcessHandler {
         in_ports: Vec<jack::Port<jack::AudioIn>>,
         sample_rate: SampleRate,
         buffer_size: usize,
-        input_data_callback: Option<Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>>,
-        output_data_callback: Option<
-            Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>,
-        >,
+        input_data_callback: Option<InputCallback>,
+        output_data_callback: Option<OutputCallback>,
+        midi_ports: Option<MidiPorts>,
         playing: Arc<AtomicBool>,
+        frames: Arc<AtomicU64>,
         error_callback_ptr: ErrorCallbackPtr,
     ) -> Self {
         // These may be reallocated in the `buffer_size` callback.
         let temp_input_buffer = vec![0.0; in_ports.len() * buffer_size];
         let temp_output_buffer = vec![0.0; out_ports.len() * buffer_size];
+        let (channels_in, channels_out) = (in_ports.len(), out_ports.len());
 
         LocalProcessHandler {
             out_ports,
@@ -261,9 +354,13 @@ impl LocalProcessHandler {
             buffer_size,
             input_data_callback,
             output_data_callback,
+            midi_ports,
+            input_slices: Vec::with_capacity(channels_in),
+            output_slices: Vec::with_capacity(channels_out),
             temp_input_buffer,
             temp_output_buffer,
             playing,
+            frames,
             creation_timestamp: std::time::Instant::now(),
             error_callback_ptr,
         }
@@ -281,6 +378,10 @@ fn temp_buffer_to_data(temp_input_buffer: &mut Vec<f32>, total_buffer_size: usiz
 impl jack::ProcessHandler for LocalProcessHandler {
     fn process(&mut self, _: &jack::Client, process_scope: &jack::ProcessScope) -> jack::Control {
         if !self.playing.load(Ordering::SeqCst) {
+            // Clear the MIDI output, whose buffer would otherwise keep the events of the last cycle.
+            if let Some(ports) = &mut self.midi_ports {
+                ports.send(process_scope);
+            }
             return jack::Control::Continue;
         }
 
@@ -308,24 +409,16 @@ impl jack::ProcessHandler for LocalProcessHandler {
             ))
             .expect("`playback` occurs beyond representation supported by `StreamInstant`");
 
+        let mut midi = self
+            .midi_ports
+            .as_mut()
+            .map(|ports| ports.receive(process_scope));
+
         if let Some(input_callback) = &mut self.input_data_callback {
             // Let's get the data from the input ports and run the callback
 
             let num_in_channels = self.in_ports.len();
 
-            // Read the data from the input ports into the temporary buffer
-            // Go through every channel and store its data in the temporary input buffer
-            for ch_ix in 0..num_in_channels {
-                let input_channel = &self.in_ports[ch_ix].as_slice(process_scope);
-                for i in 0..current_frame_count {
-                    self.temp_input_buffer[ch_ix + i * num_in_channels] = input_channel[i];
-                }
-            }
-            // Create a slice of exactly current_frame_count frames
-            let data = temp_buffer_to_data(
-                &mut self.temp_input_buffer,
-                current_frame_count * num_in_channels,
-            );
             // Create timestamp
             let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
             let duration_since_cycle_start =
@@ -335,18 +428,50 @@ impl jack::ProcessHandler for LocalProcessHandler {
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let capture = start_callback_instant;
             let timestamp = crate::InputStreamTimestamp { callback, capture };
-            let info = crate::InputCallbackInfo { timestamp };
-            input_callback(&data, &info);
+            let info =
+                crate::InputCallbackInfo::new(timestamp, current_frame_count, self.sample_rate);
+
+            match input_callback {
+                InputCallback::Interleaved(_) | InputCallback::Midi(_) => {
+                    // Read the data from the input ports into the temporary buffer
+                    // Go through every channel and store its data in the temporary input buffer
+                    let samples =
+                        &mut self.temp_input_buffer[..current_frame_count * num_in_channels];
+                    if let Some(mut frames) = FramesMut::new(samples, num_in_channels as _) {
+                        for (ch_ix, port) in self.in_ports.iter().enumerate() {
+                            let input_channel = port.as_slice(process_scope);
+                            for (frame, &sample) in frames.iter_mut().zip(input_channel) {
+                                frame[ch_ix] = sample;
+                            }
+                        }
+                    }
+                    // Create a slice of exactly current_frame_count frames
+                    let data = temp_buffer_to_data(
+                        &mut self.temp_input_buffer,
+                        current_frame_count * num_in_channels,
+                    );
+                    match (input_callback, midi.as_mut()) {
+                        (InputCallback::Midi(input_callback), Some(midi)) => {
+                            input_callback(&data, midi, &info)
+                        }
+                        (InputCallback::Interleaved(input_callback), _) => {
+                            input_callback(&data, &info)
+                        }
+                        _ => unreachable!("MIDI ports are registered for MIDI callbacks"),
+                    }
+                }
+                InputCallback::Planar(input_callback) => {
+                    let mut channels = reuse_vec(std::mem::take(&mut self.input_slices));
+                    channels.extend(self.in_ports.iter().map(|p| p.as_slice(process_scope)));
+                    input_callback(&channels, &info);
+                    self.input_slices = reuse_vec(channels);
+                }
+            }
         }
 
         if let Some(output_callback) = &mut self.output_data_callback {
             let num_out_channels = self.out_ports.len();
 
-            // Create a slice of exactly current_frame_count frames
-            let mut data = temp_buffer_to_data(
-                &mut self.temp_output_buffer,
-                current_frame_count * num_out_channels,
-            );
             // Create timestamp
             let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
             let duration_since_cycle_start =
@@ -359,18 +484,58 @@ impl jack::ProcessHandler for LocalProcessHandler {
                 .add(buffer_duration)
                 .expect("`playback` occurs beyond representation supported by `StreamInstant`");
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
-            let info = crate::OutputCallbackInfo { timestamp };
-            output_callback(&mut data, &info);
-
-            // Deinterlace
-            for ch_ix in 0..num_out_channels {
-                let output_channel = &mut self.out_ports[ch_ix].as_mut_slice(process_scope);
-                for i in 0..current_frame_count {
-                    output_channel[i] = self.temp_output_buffer[ch_ix + i * num_out_channels];
+            let info =
+                crate::OutputCallbackInfo::new(timestamp, current_frame_count, self.sample_rate);
+
+            match output_callback {
+                OutputCallback::Interleaved(_) | OutputCallback::Midi(_) => {
+                    // Create a slice of exactly current_frame_count frames
+                    let mut data = temp_buffer_to_data(
+                        &mut self.temp_output_buffer,
+                        current_frame_count * num_out_channels,
+                    );
+                    match (output_callback, midi.as_mut()) {
+                        (OutputCallback::Midi(output_callback), Some(midi)) => {
+                            output_callback(&mut data, midi, &info)
+                        }
+                        (OutputCallback::Interleaved(output_callback), _) => {
+                            output_callback(&mut data, &info)
+                        }
+                        _ => unreachable!("MIDI ports are registered for MIDI callbacks"),
+                    }
+
+                    // Deinterlace
+                    let samples =
+                        &self.temp_output_buffer[..current_frame_count * num_out_channels];
+                    if let Some(frames) = Frames::new(samples, num_out_channels as _) {
+                        for (ch_ix, port) in self.out_ports.iter_mut().enumerate() {
+                            let output_channel = port.as_mut_slice(process_scope);
+                            for (sample, frame) in output_channel.iter_mut().zip(frames) {
+                                *sample = frame[ch_ix];
+                            }
+                        }
+                    }
+                }
+                OutputCallback::Planar(output_callback) => {
+                    let mut channels = reuse_vec_mut(std::mem::take(&mut self.output_slices));
+                    channels.extend(
+                        self.out_ports
+                            .iter_mut()
+                            .map(|p| p.as_mut_slice(process_scope)),
+                    );
+                    output_callback(&mut channels, &info);
+                    self.output_slices = reuse_vec_mut(channels);
                 }
             }
         }
 
+        if let Some(ports) = &mut self.midi_ports {
+            ports.send(process_scope);
+        }
+
+        self.frames
+            .fetch_add(current_frame_count as u64, Ordering::Relaxed);
+
         // Continue as normal
         jack::Control::Continue
     }
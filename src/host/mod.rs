@@ -1,10 +1,17 @@
-#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+#[cfg(all(
+    any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
+    feature = "alsa",
+    not(feature = "null-backend")
+))]
 pub(crate) mod alsa;
 #[cfg(all(windows, feature = "asio"))]
 pub(crate) mod asio;
-#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[cfg(all(
+    any(target_os = "macos", target_os = "ios"),
+    not(feature = "null-backend")
+))]
 pub(crate) mod coreaudio;
-#[cfg(target_os = "emscripten")]
+#[cfg(all(target_os = "emscripten", not(feature = "null-backend")))]
 pub(crate) mod emscripten;
 #[cfg(all(
     any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
@@ -12,9 +19,18 @@ pub(crate) mod emscripten;
 ))]
 pub(crate) mod jack;
 pub(crate) mod null;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", not(feature = "null-backend")))]
 pub(crate) mod oboe;
-#[cfg(windows)]
+#[cfg(all(
+    any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
+    feature = "pulseaudio"
+))]
+pub(crate) mod pulseaudio;
+#[cfg(all(windows, feature = "wasapi", not(feature = "null-backend")))]
 pub(crate) mod wasapi;
-#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+#[cfg(all(
+    target_arch = "wasm32",
+    feature = "wasm-bindgen",
+    not(feature = "null-backend")
+))]
 pub(crate) mod webaudio;
@@ -9,13 +9,15 @@ use traits::{DeviceTrait, HostTrait, StreamTrait};
 #[derive(Default)]
 pub struct Devices;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
 pub struct Host;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Stream;
+pub struct Stream {
+    config: StreamConfig,
+}
 
 pub struct SupportedInputConfigs;
 pub struct SupportedOutputConfigs;
@@ -25,6 +27,12 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    // The null host is implemented by CPAL itself.
+    #[allow(dead_code)]
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl Devices {
@@ -126,6 +134,18 @@ impl StreamTrait for Stream {
     fn pause(&self) -> Result<(), PauseStreamError> {
         unimplemented!()
     }
+
+    fn config(&self) -> StreamConfig {
+        self.config.clone()
+    }
+
+    fn frame_position(&self) -> u64 {
+        0
+    }
+
+    fn is_playing(&self) -> bool {
+        false
+    }
 }
 
 impl Iterator for Devices {
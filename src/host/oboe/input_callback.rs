@@ -3,8 +3,10 @@ use std::time::Instant;
 
 extern crate oboe;
 
+use self::oboe::AudioStreamBase;
+
 use super::convert::{stream_instant, to_stream_instant};
-use crate::{Data, InputCallbackInfo, InputStreamTimestamp, Sample, StreamError};
+use crate::{Data, InputCallbackInfo, InputStreamTimestamp, Sample, SampleRate, StreamError};
 
 pub struct CpalInputCallback<I, C> {
     data_cb: Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>,
@@ -32,13 +34,14 @@ impl<I, C> CpalInputCallback<I, C> {
     fn make_callback_info(
         &self,
         audio_stream: &mut dyn oboe::AudioInputStreamSafe,
+        frames: usize,
     ) -> InputCallbackInfo {
-        InputCallbackInfo {
-            timestamp: InputStreamTimestamp {
-                callback: to_stream_instant(self.created.elapsed()),
-                capture: stream_instant(audio_stream),
-            },
-        }
+        let timestamp = InputStreamTimestamp {
+            callback: to_stream_instant(self.created.elapsed()),
+            capture: stream_instant(audio_stream),
+        };
+        let sample_rate = SampleRate(audio_stream.get_sample_rate() as u32);
+        InputCallbackInfo::new(timestamp, frames, sample_rate)
     }
 }
 
@@ -69,7 +72,7 @@ where
         audio_stream: &mut dyn oboe::AudioInputStreamSafe,
         audio_data: &[<<Self as oboe::AudioInputCallback>::FrameType as oboe::IsFrameType>::Type],
     ) -> oboe::DataCallbackResult {
-        let cb_info = self.make_callback_info(audio_stream);
+        let cb_info = self.make_callback_info(audio_stream, audio_data.len());
         let channel_count = if C::CHANNEL_COUNT == oboe::ChannelCount::Mono {
             1
         } else {
@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::cmp;
 use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
 use std::vec::IntoIter as VecIntoIter;
 
 extern crate oboe;
@@ -21,7 +22,9 @@ mod output_callback;
 
 use self::android_media::{get_audio_record_min_buffer_size, get_audio_track_min_buffer_size};
 use self::input_callback::CpalInputCallback;
-use self::oboe::{AudioInputStream, AudioOutputStream};
+use self::oboe::{
+    AudioInputStream, AudioInputStreamSafe, AudioOutputStream, AudioOutputStreamSafe, StreamState,
+};
 use self::output_callback::CpalOutputCallback;
 
 // Android Java API supports up to 8 channels, but oboe API
@@ -36,10 +39,32 @@ const SAMPLE_RATES: [i32; 13] = [
 ];
 
 pub struct Host;
+#[derive(Clone)]
 pub struct Device(Option<oboe::AudioDeviceInfo>);
+
+impl Device {
+    // The ID assigned to the device by Android, or `None` for the default device.
+    fn id(&self) -> Option<i32> {
+        self.0.as_ref().map(|info| info.id)
+    }
+}
+
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Device {}
+
+impl Hash for Device {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
 pub enum Stream {
-    Input(Box<RefCell<dyn AudioInputStream>>),
-    Output(Box<RefCell<dyn AudioOutputStream>>),
+    Input(Box<RefCell<dyn AudioInputStream>>, StreamConfig),
+    Output(Box<RefCell<dyn AudioOutputStream>>, StreamConfig),
 }
 pub type SupportedInputConfigs = VecIntoIter<SupportedStreamConfigRange>;
 pub type SupportedOutputConfigs = VecIntoIter<SupportedStreamConfigRange>;
@@ -49,6 +74,11 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl HostTrait for Host {
@@ -246,7 +276,10 @@ where
             error_callback,
         ))
         .open_stream()?;
-    Ok(Stream::Input(Box::new(RefCell::new(stream))))
+    Ok(Stream::Input(
+        Box::new(RefCell::new(stream)),
+        config.clone(),
+    ))
 }
 
 fn build_output_stream<D, E, C, T>(
@@ -270,7 +303,10 @@ where
             error_callback,
         ))
         .open_stream()?;
-    Ok(Stream::Output(Box::new(RefCell::new(stream))))
+    Ok(Stream::Output(
+        Box::new(RefCell::new(stream)),
+        config.clone(),
+    ))
 }
 
 impl DeviceTrait for Device {
@@ -393,8 +429,8 @@ impl DeviceTrait for Device {
                     .into())
                 }
             }
-            SampleFormat::U16 => Err(BackendSpecificError {
-                description: "U16 format is not supported on Android.".to_owned(),
+            SampleFormat::U16 | SampleFormat::U8 => Err(BackendSpecificError {
+                description: format!("{:?} format is not supported on Android.", sample_format),
             }
             .into()),
         }
@@ -466,8 +502,8 @@ impl DeviceTrait for Device {
                     .into())
                 }
             }
-            SampleFormat::U16 => Err(BackendSpecificError {
-                description: "U16 format is not supported on Android.".to_owned(),
+            SampleFormat::U16 | SampleFormat::U8 => Err(BackendSpecificError {
+                description: format!("{:?} format is not supported on Android.", sample_format),
             }
             .into()),
         }
@@ -477,11 +513,11 @@ impl DeviceTrait for Device {
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
         match self {
-            Self::Input(stream) => stream
+            Self::Input(stream, _) => stream
                 .borrow_mut()
                 .request_start()
                 .map_err(PlayStreamError::from),
-            Self::Output(stream) => stream
+            Self::Output(stream, _) => stream
                 .borrow_mut()
                 .request_start()
                 .map_err(PlayStreamError::from),
@@ -490,14 +526,38 @@ impl StreamTrait for Stream {
 
     fn pause(&self) -> Result<(), PauseStreamError> {
         match self {
-            Self::Input(_) => Err(BackendSpecificError {
-                description: "Pause called on the input stream.".to_owned(),
-            }
-            .into()),
-            Self::Output(stream) => stream
+            // Oboe cannot pause input streams, so they are stopped instead, which discards the
+            // audio captured until they are started again.
+            Self::Input(stream, _) => stream
+                .borrow_mut()
+                .request_stop()
+                .map_err(PauseStreamError::from),
+            Self::Output(stream, _) => stream
                 .borrow_mut()
                 .request_pause()
                 .map_err(PauseStreamError::from),
         }
     }
+
+    fn config(&self) -> StreamConfig {
+        match self {
+            Self::Input(_, config) | Self::Output(_, config) => config.clone(),
+        }
+    }
+
+    fn frame_position(&self) -> u64 {
+        let frames = match self {
+            Self::Input(stream, _) => stream.borrow_mut().get_frames_read(),
+            Self::Output(stream, _) => stream.borrow_mut().get_frames_written(),
+        };
+        frames.max(0) as u64
+    }
+
+    fn is_playing(&self) -> bool {
+        let state = match self {
+            Self::Input(stream, _) => stream.borrow().get_state(),
+            Self::Output(stream, _) => stream.borrow().get_state(),
+        };
+        matches!(state, StreamState::Starting | StreamState::Started)
+    }
 }
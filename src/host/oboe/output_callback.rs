@@ -3,8 +3,10 @@ use std::time::Instant;
 
 extern crate oboe;
 
+use self::oboe::AudioStreamBase;
+
 use super::convert::{stream_instant, to_stream_instant};
-use crate::{Data, OutputCallbackInfo, OutputStreamTimestamp, Sample, StreamError};
+use crate::{Data, OutputCallbackInfo, OutputStreamTimestamp, Sample, SampleRate, StreamError};
 
 pub struct CpalOutputCallback<I, C> {
     data_cb: Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>,
@@ -32,13 +34,14 @@ impl<I, C> CpalOutputCallback<I, C> {
     fn make_callback_info(
         &self,
         audio_stream: &mut dyn oboe::AudioOutputStreamSafe,
+        frames: usize,
     ) -> OutputCallbackInfo {
-        OutputCallbackInfo {
-            timestamp: OutputStreamTimestamp {
-                callback: to_stream_instant(self.created.elapsed()),
-                playback: stream_instant(audio_stream),
-            },
-        }
+        let timestamp = OutputStreamTimestamp {
+            callback: to_stream_instant(self.created.elapsed()),
+            playback: stream_instant(audio_stream),
+        };
+        let sample_rate = SampleRate(audio_stream.get_sample_rate() as u32);
+        OutputCallbackInfo::new(timestamp, frames, sample_rate)
     }
 }
 
@@ -69,7 +72,7 @@ where
         audio_stream: &mut dyn oboe::AudioOutputStreamSafe,
         audio_data: &mut [<<Self as oboe::AudioOutputCallback>::FrameType as oboe::IsFrameType>::Type],
     ) -> oboe::DataCallbackResult {
-        let cb_info = self.make_callback_info(audio_stream);
+        let cb_info = self.make_callback_info(audio_stream, audio_data.len());
         let channel_count = if C::CHANNEL_COUNT == oboe::ChannelCount::Mono {
             1
         } else {
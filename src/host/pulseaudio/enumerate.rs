@@ -0,0 +1,188 @@
+use super::pulse::callbacks::ListResult;
+use super::pulse::context::{self, Context};
+use super::pulse::mainloop::standard::{IterateResult, Mainloop};
+use super::pulse::operation::{self, Operation};
+use super::{Device, Direction, CLIENT_NAME};
+use crate::{BackendSpecificError, ChannelCount, SampleRate};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// A connection to the server, used to query its sinks and sources.
+//
+// The mainloop is only ever iterated from the thread that owns the connection, blocking until
+// the pending operation completes.
+pub(super) struct Connection {
+    mainloop: Mainloop,
+    context: Context,
+}
+
+impl Connection {
+    // Connects to the default server without spawning one if none is running.
+    pub(super) fn new() -> Result<Self, BackendSpecificError> {
+        let failed = |what: &str| BackendSpecificError {
+            description: format!("failed to {} for the PulseAudio server", what),
+        };
+        let mainloop = Mainloop::new().ok_or_else(|| failed("create a mainloop"))?;
+        let context =
+            Context::new(&mainloop, CLIENT_NAME).ok_or_else(|| failed("create a context"))?;
+        let mut connection = Connection { mainloop, context };
+        connection
+            .context
+            .connect(None, context::FlagSet::NOAUTOSPAWN, None)?;
+        loop {
+            connection.iterate()?;
+            match connection.context.get_state() {
+                context::State::Ready => return Ok(connection),
+                context::State::Failed | context::State::Terminated => {
+                    let description = "failed to connect to the PulseAudio server".to_string();
+                    return Err(BackendSpecificError { description });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn iterate(&mut self) -> Result<(), BackendSpecificError> {
+        match self.mainloop.iterate(true) {
+            IterateResult::Success(_) => Ok(()),
+            IterateResult::Err(err) => Err(err.into()),
+            IterateResult::Quit(_) => {
+                let description = "the PulseAudio mainloop quit unexpectedly".to_string();
+                Err(BackendSpecificError { description })
+            }
+        }
+    }
+
+    // Runs the mainloop until the operation is complete.
+    fn wait<C>(&mut self, operation: Operation<C>) -> Result<(), BackendSpecificError>
+    where
+        C: ?Sized,
+    {
+        while operation.get_state() == operation::State::Running {
+            self.iterate()?;
+        }
+        Ok(())
+    }
+
+    // The names of the default sink and source.
+    fn default_names(&mut self) -> Result<(Option<String>, Option<String>), BackendSpecificError> {
+        let names = Rc::new(RefCell::new((None, None)));
+        let operation = {
+            let names = names.clone();
+            self.context.introspect().get_server_info(move |info| {
+                *names.borrow_mut() = (
+                    to_owned(&info.default_sink_name),
+                    to_owned(&info.default_source_name),
+                );
+            })
+        };
+        self.wait(operation)?;
+        let names = names.borrow().clone();
+        Ok(names)
+    }
+
+    fn devices(&mut self, direction: Direction) -> Result<Vec<Device>, BackendSpecificError> {
+        let devices = Rc::new(RefCell::new(Vec::new()));
+        let introspector = self.context.introspect();
+        match direction {
+            Direction::Sink => {
+                let devices = devices.clone();
+                let operation = introspector.get_sink_info_list(move |result| {
+                    if let ListResult::Item(info) = result {
+                        let device = device(
+                            &info.name,
+                            &info.description,
+                            direction,
+                            info.sample_spec.channels,
+                            info.sample_spec.rate,
+                            None,
+                        );
+                        devices.borrow_mut().extend(device);
+                    }
+                });
+                self.wait(operation)?;
+            }
+            Direction::Source => {
+                let devices = devices.clone();
+                let operation = introspector.get_source_info_list(move |result| {
+                    if let ListResult::Item(info) = result {
+                        let device = device(
+                            &info.name,
+                            &info.description,
+                            direction,
+                            info.sample_spec.channels,
+                            info.sample_spec.rate,
+                            to_owned(&info.monitor_of_sink_name),
+                        );
+                        devices.borrow_mut().extend(device);
+                    }
+                });
+                self.wait(operation)?;
+            }
+        }
+        let devices = devices.replace(Vec::new());
+        Ok(devices)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.context.disconnect();
+    }
+}
+
+fn to_owned(string: &Option<Cow<str>>) -> Option<String> {
+    string.as_ref().map(|string| string.to_string())
+}
+
+fn device(
+    name: &Option<Cow<str>>,
+    description: &Option<Cow<str>>,
+    direction: Direction,
+    channels: u8,
+    sample_rate: u32,
+    monitor_of_sink: Option<String>,
+) -> Option<Device> {
+    let name = to_owned(name)?;
+    let description = to_owned(description).unwrap_or_else(|| name.clone());
+    Some(Device {
+        name,
+        description,
+        direction,
+        channels: channels as ChannelCount,
+        sample_rate: SampleRate(sample_rate),
+        monitor_of_sink,
+    })
+}
+
+// All sinks followed by all sources.
+pub(super) fn devices() -> Result<Vec<Device>, BackendSpecificError> {
+    let mut connection = Connection::new()?;
+    let mut devices = connection.devices(Direction::Sink)?;
+    devices.extend(connection.devices(Direction::Source)?);
+    Ok(devices)
+}
+
+// The monitor sources of all sinks.
+pub(super) fn monitor_sources() -> Result<Vec<Device>, BackendSpecificError> {
+    let mut connection = Connection::new()?;
+    let mut sources = connection.devices(Direction::Source)?;
+    sources.retain(Device::is_monitor);
+    Ok(sources)
+}
+
+// The sink or source that the server currently uses by default.
+pub(super) fn default_device(direction: Direction) -> Option<Device> {
+    let mut connection = Connection::new().ok()?;
+    let (sink, source) = connection.default_names().ok()?;
+    let name = match direction {
+        Direction::Sink => sink?,
+        Direction::Source => source?,
+    };
+    connection
+        .devices(direction)
+        .ok()?
+        .into_iter()
+        .find(|device| device.name == name)
+}
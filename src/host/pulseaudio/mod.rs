@@ -0,0 +1,310 @@
+extern crate libpulse_binding as pulse;
+extern crate libpulse_simple_binding as psimple;
+
+use self::pulse::error::PAErr;
+use self::pulse::sample;
+use crate::{
+    BackendSpecificError, BuildStreamError, ChannelCount, Data, DefaultStreamConfigError,
+    DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate,
+    StreamConfig, StreamError, SupportedBufferSize, SupportedStreamConfig,
+    SupportedStreamConfigRange, SupportedStreamConfigsError,
+};
+use std::cmp;
+use std::hash::{Hash, Hasher};
+use std::vec::IntoIter as VecIntoIter;
+use traits::{DeviceTrait, HostTrait};
+
+pub use self::stream::Stream;
+
+mod enumerate;
+mod stream;
+
+pub type SupportedInputConfigs = VecIntoIter<SupportedStreamConfigRange>;
+pub type SupportedOutputConfigs = VecIntoIter<SupportedStreamConfigRange>;
+pub type Devices = VecIntoIter<Device>;
+
+// The name under which streams and enumeration connections appear in the server.
+const CLIENT_NAME: &str = "cpal";
+
+// The prefix of the names of monitor sources.
+const MONITOR_PREFIX: &str = "Monitor of ";
+
+// The sample formats supported natively by both PulseAudio and cpal.
+const SAMPLE_FORMATS: [SampleFormat; 2] = [SampleFormat::I16, SampleFormat::F32];
+
+/// The PulseAudio host, also usable with PipeWire through its PulseAudio compatibility layer.
+///
+/// Devices are the sinks and sources of the server. Unlike ALSA devices they can be used by
+/// several streams at once, and the server converts between their native configuration and that
+/// of each stream.
+#[derive(Debug)]
+pub struct Host;
+
+impl Host {
+    pub fn new() -> Result<Self, crate::HostUnavailable> {
+        if Self::is_available() {
+            Ok(Host)
+        } else {
+            Err(crate::HostUnavailable)
+        }
+    }
+
+    // The version of libpulse, as reported by the library loaded at runtime.
+    pub(crate) fn library_version() -> Option<String> {
+        let version = pulse::version::get_library_version();
+        Some(version.to_string_lossy().into_owned())
+    }
+
+    /// The monitor source of each sink, which captures the audio played by the sink.
+    ///
+    /// These sources are also part of `input_devices`. Recording from one captures the system's
+    /// output, like loopback recording on WASAPI.
+    pub fn monitor_sources(&self) -> Result<Devices, DevicesError> {
+        Ok(enumerate::monitor_sources()?.into_iter())
+    }
+}
+
+impl HostTrait for Host {
+    type Devices = Devices;
+    type Device = Device;
+
+    /// PulseAudio is available if a server accepts connections.
+    fn is_available() -> bool {
+        enumerate::Connection::new().is_ok()
+    }
+
+    fn devices(&self) -> Result<Self::Devices, DevicesError> {
+        Ok(enumerate::devices()?.into_iter())
+    }
+
+    fn default_input_device(&self) -> Option<Self::Device> {
+        enumerate::default_device(Direction::Source)
+    }
+
+    fn default_output_device(&self) -> Option<Self::Device> {
+        enumerate::default_device(Direction::Sink)
+    }
+}
+
+// Whether a device is a sink, which plays audio, or a source, which captures it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Direction {
+    Sink,
+    Source,
+}
+
+#[derive(Clone, Debug)]
+pub struct Device {
+    // The name of the sink or source, e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo`.
+    name: String,
+    // The human readable description, e.g. `Built-in Audio Analog Stereo`.
+    description: String,
+    direction: Direction,
+    // The configuration the server uses for the device.
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    // For the monitor source of a sink, the name of the sink.
+    monitor_of_sink: Option<String>,
+}
+
+// Devices are identified by the name of the sink or source, which is stable for the lifetime of
+// the server and usually across restarts.
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.direction == other.direction
+    }
+}
+
+impl Eq for Device {}
+
+impl Hash for Device {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.direction.hash(state);
+    }
+}
+
+impl Device {
+    /// The name of the sink or source in the PulseAudio server, as shown by `pactl list short`.
+    ///
+    /// `DeviceTrait::name` returns the human readable description instead.
+    pub fn pulse_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the device is the monitor source of a sink, capturing the audio it plays.
+    pub fn is_monitor(&self) -> bool {
+        self.monitor_of_sink.is_some()
+    }
+
+    /// For a monitor source, the name of the sink it captures, as returned by `pulse_name`.
+    pub fn monitor_of_sink(&self) -> Option<&str> {
+        self.monitor_of_sink.as_deref()
+    }
+
+    fn supported_configs(&self, direction: Direction) -> Vec<SupportedStreamConfigRange> {
+        if self.direction != direction {
+            return Vec::new();
+        }
+        // The server remixes and resamples as needed, so channel counts up to the device's own
+        // and any sample rate are supported.
+        let mut configs = Vec::new();
+        for channels in 1..=self.channels.max(1) {
+            for &sample_format in &SAMPLE_FORMATS {
+                configs.push(SupportedStreamConfigRange {
+                    channels,
+                    min_sample_rate: SampleRate(1),
+                    max_sample_rate: SampleRate(sample::Spec::RATE_MAX),
+                    buffer_size: SupportedBufferSize::Unknown,
+                    sample_format,
+                });
+            }
+        }
+        configs
+    }
+
+    fn default_config(
+        &self,
+        direction: Direction,
+    ) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        if self.direction != direction {
+            return Err(DefaultStreamConfigError::StreamTypeNotSupported);
+        }
+        Ok(SupportedStreamConfig {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            buffer_size: SupportedBufferSize::Unknown,
+            sample_format: SampleFormat::F32,
+        })
+    }
+
+    // The sample spec with which to open a stream, if the device supports the configuration.
+    fn sample_spec(
+        &self,
+        direction: Direction,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<sample::Spec, BuildStreamError> {
+        let format = match sample_format {
+            SampleFormat::I16 => sample::Format::S16NE,
+            SampleFormat::F32 => sample::Format::FLOAT32NE,
+            SampleFormat::U8 => sample::Format::U8,
+            SampleFormat::U16 => return Err(BuildStreamError::StreamConfigNotSupported),
+        };
+        let spec = sample::Spec {
+            format,
+            rate: conf.sample_rate.0,
+            channels: cmp::min(conf.channels, u8::MAX as ChannelCount) as u8,
+        };
+        if self.direction != direction
+            || !spec.is_valid()
+            || spec.channels as ChannelCount != conf.channels
+        {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+        Ok(spec)
+    }
+}
+
+impl DeviceTrait for Device {
+    type SupportedInputConfigs = SupportedInputConfigs;
+    type SupportedOutputConfigs = SupportedOutputConfigs;
+    type Stream = Stream;
+
+    /// The description of the sink or source.
+    ///
+    /// The server describes monitor sources as e.g. `Monitor of Built-in Audio Analog Stereo`. The
+    /// prefix is added if the description lacks it, so that monitors can be told apart by name.
+    fn name(&self) -> Result<String, DeviceNameError> {
+        if self.is_monitor() && !self.description.starts_with(MONITOR_PREFIX) {
+            return Ok(format!("{}{}", MONITOR_PREFIX, self.description));
+        }
+        Ok(self.description.clone())
+    }
+
+    fn supported_input_configs(
+        &self,
+    ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
+        Ok(self.supported_configs(Direction::Source).into_iter())
+    }
+
+    fn supported_output_configs(
+        &self,
+    ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError> {
+        Ok(self.supported_configs(Direction::Sink).into_iter())
+    }
+
+    fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        self.default_config(Direction::Source)
+    }
+
+    fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        self.default_config(Direction::Sink)
+    }
+
+    fn build_input_stream_raw<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let spec = self.sample_spec(Direction::Source, conf, sample_format)?;
+        Stream::new_input(
+            &self.name,
+            conf,
+            spec,
+            sample_format,
+            data_callback,
+            error_callback,
+        )
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let spec = self.sample_spec(Direction::Sink, conf, sample_format)?;
+        Stream::new_output(
+            &self.name,
+            conf,
+            spec,
+            sample_format,
+            data_callback,
+            error_callback,
+        )
+    }
+}
+
+impl From<PAErr> for BackendSpecificError {
+    fn from(err: PAErr) -> Self {
+        BackendSpecificError {
+            description: format!("{}", err),
+        }
+    }
+}
+
+impl From<PAErr> for BuildStreamError {
+    fn from(err: PAErr) -> Self {
+        let err: BackendSpecificError = err.into();
+        err.into()
+    }
+}
+
+impl From<PAErr> for StreamError {
+    fn from(err: PAErr) -> Self {
+        let err: BackendSpecificError = err.into();
+        err.into()
+    }
+}
@@ -0,0 +1,262 @@
+use super::psimple::Simple;
+use super::pulse::def::BufferAttr;
+use super::pulse::sample;
+use super::pulse::stream::Direction;
+use super::CLIENT_NAME;
+use crate::{
+    BackendSpecificError, BufferSize, BuildStreamError, Data, FrameCount, InputCallbackInfo,
+    InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp, PauseStreamError,
+    PlayStreamError, SampleFormat, StreamConfig, StreamError, StreamInstant,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use traits::StreamTrait;
+
+// The duration of each buffer passed to the data callback when no buffer size is requested.
+const DEFAULT_BUFFER_DURATION: Duration = Duration::from_millis(10);
+
+pub struct Stream {
+    inner: Arc<StreamInner>,
+    // `None` once the stream has been dropped.
+    thread: Option<JoinHandle<()>>,
+}
+
+struct StreamInner {
+    state: Mutex<State>,
+    // Notified whenever `state` changes.
+    condvar: Condvar,
+    // The number of frames processed by the data callback.
+    frames: AtomicU64,
+    config: StreamConfig,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Playing,
+    Paused,
+    Dropped,
+}
+
+impl StreamInner {
+    // Blocks while the stream is paused. Returns the state it left `Paused` for, along with
+    // whether it had to wait.
+    fn wait_while_paused(&self) -> (State, bool) {
+        let mut state = self.state.lock().unwrap();
+        let mut waited = false;
+        while *state == State::Paused {
+            state = self.condvar.wait(state).unwrap();
+            waited = true;
+        }
+        (*state, waited)
+    }
+
+    fn set_state(&self, new_state: State) {
+        *self.state.lock().unwrap() = new_state;
+        self.condvar.notify_all();
+    }
+}
+
+// The number of frames in each buffer passed to the data callback.
+fn buffer_frames(conf: &StreamConfig) -> usize {
+    match conf.buffer_size {
+        BufferSize::Fixed(frames) => frames.max(1) as usize,
+        BufferSize::Default => {
+            let frames =
+                conf.sample_rate.0 as u128 * DEFAULT_BUFFER_DURATION.as_micros() / 1_000_000;
+            (frames as usize).max(1)
+        }
+    }
+}
+
+// Opens a connection to the sink or source named `device`.
+//
+// The server side buffer is kept at two buffers of the data callback, so that one is played or
+// captured while the other is processed, instead of the server's default of around two seconds.
+fn open(
+    device: &str,
+    direction: Direction,
+    spec: &sample::Spec,
+    buffer_bytes: usize,
+) -> Result<Simple, BuildStreamError> {
+    let buffer_bytes = buffer_bytes as u32;
+    let attr = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: buffer_bytes.saturating_mul(2),
+        prebuf: u32::MAX,
+        minreq: u32::MAX,
+        fragsize: buffer_bytes,
+    };
+    let simple = Simple::new(
+        None,
+        CLIENT_NAME,
+        direction,
+        Some(device),
+        CLIENT_NAME,
+        spec,
+        None,
+        Some(&attr),
+    )?;
+    Ok(simple)
+}
+
+fn stream_instant(created: Instant) -> StreamInstant {
+    let elapsed = created.elapsed();
+    StreamInstant::new(elapsed.as_secs() as i64, elapsed.subsec_nanos())
+}
+
+// The latency of the stream, treating failures to query it as no latency.
+fn latency(simple: &Simple) -> Duration {
+    match simple.get_latency() {
+        Ok(latency) => Duration::from_micros(latency.0),
+        Err(_) => Duration::from_secs(0),
+    }
+}
+
+impl Stream {
+    pub(super) fn new_input<D, E>(
+        device: &str,
+        conf: &StreamConfig,
+        spec: sample::Spec,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        mut error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let frames = buffer_frames(conf);
+        let mut buffer = vec![0u8; frames * spec.frame_size()];
+        let simple = open(device, Direction::Record, &spec, buffer.len())?;
+        Stream::spawn(conf, frames, move |inner| {
+            let created = Instant::now();
+            loop {
+                match inner.wait_while_paused() {
+                    (State::Dropped, _) => return,
+                    // Discard what was captured while paused.
+                    (_, true) => {
+                        if let Err(err) = simple.flush() {
+                            error_callback(err.into());
+                        }
+                    }
+                    _ => (),
+                }
+                if let Err(err) = simple.read(&mut buffer) {
+                    error_callback(err.into());
+                    return;
+                }
+                let callback = stream_instant(created);
+                let capture = callback.sub(latency(&simple)).unwrap_or(callback);
+                let timestamp = InputStreamTimestamp { callback, capture };
+                let info = InputCallbackInfo::new(timestamp, frames, inner.config.sample_rate);
+                let len = buffer.len() / sample_format.sample_size();
+                let data =
+                    unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), len, sample_format) };
+                data_callback(&data, &info);
+                inner.frames.fetch_add(frames as u64, Ordering::Relaxed);
+            }
+        })
+    }
+
+    pub(super) fn new_output<D, E>(
+        device: &str,
+        conf: &StreamConfig,
+        spec: sample::Spec,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        mut error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let frames = buffer_frames(conf);
+        let mut buffer = vec![0u8; frames * spec.frame_size()];
+        let simple = open(device, Direction::Playback, &spec, buffer.len())?;
+        Stream::spawn(conf, frames, move |inner| {
+            let created = Instant::now();
+            loop {
+                if let (State::Dropped, _) = inner.wait_while_paused() {
+                    return;
+                }
+                let callback = stream_instant(created);
+                let playback = callback.add(latency(&simple)).unwrap_or(callback);
+                let timestamp = OutputStreamTimestamp { callback, playback };
+                let info = OutputCallbackInfo::new(timestamp, frames, inner.config.sample_rate);
+                let len = buffer.len() / sample_format.sample_size();
+                let mut data =
+                    unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), len, sample_format) };
+                data_callback(&mut data, &info);
+                if let Err(err) = simple.write(&buffer) {
+                    error_callback(err.into());
+                    return;
+                }
+                inner.frames.fetch_add(frames as u64, Ordering::Relaxed);
+            }
+        })
+    }
+
+    // Runs `worker` on a new thread, which must return once the stream is dropped.
+    fn spawn<F>(conf: &StreamConfig, frames: usize, worker: F) -> Result<Stream, BuildStreamError>
+    where
+        F: FnOnce(&StreamInner) + Send + 'static,
+    {
+        let inner = Arc::new(StreamInner {
+            state: Mutex::new(State::Playing),
+            condvar: Condvar::new(),
+            frames: AtomicU64::new(0),
+            config: StreamConfig {
+                buffer_size: BufferSize::Fixed(frames as FrameCount),
+                ..conf.clone()
+            },
+        });
+        let thread = {
+            let inner = inner.clone();
+            thread::Builder::new()
+                .name("cpal_pulseaudio".to_owned())
+                .spawn(move || worker(&inner))
+                .map_err(|err| BackendSpecificError {
+                    description: format!("failed to spawn the stream thread: {}", err),
+                })?
+        };
+        Ok(Stream {
+            inner,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.inner.set_state(State::Dropped);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl StreamTrait for Stream {
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.inner.set_state(State::Playing);
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.inner.set_state(State::Paused);
+        Ok(())
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.inner.config.clone()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.inner.frames.load(Ordering::Relaxed)
+    }
+
+    fn is_playing(&self) -> bool {
+        *self.inner.state.lock().unwrap() == State::Playing
+    }
+}
@@ -3,32 +3,52 @@
 use super::check_result;
 use std::ptr;
 
+use super::winapi::shared::winerror::RPC_E_CHANGED_MODE;
 use super::winapi::um::combaseapi::{CoInitializeEx, CoUninitialize};
 use super::winapi::um::objbase::COINIT_MULTITHREADED;
 
 thread_local!(static COM_INITIALIZED: ComInitialized = {
     unsafe {
-        // this call can fail if another library initialized COM in single-threaded mode
-        // handling this situation properly would make the API more annoying, so we just don't care
-        check_result(CoInitializeEx(ptr::null_mut(), COINIT_MULTITHREADED)).unwrap();
-        ComInitialized(ptr::null_mut())
+        // This call fails if the application, e.g. its GUI framework, already initialized COM in
+        // a single-threaded apartment on this thread. WASAPI works from either apartment, so COM
+        // is used as it is then, and left for the application to uninitialize.
+        let result = CoInitializeEx(ptr::null_mut(), COINIT_MULTITHREADED);
+        if result == RPC_E_CHANGED_MODE {
+            ComInitialized {
+                uninitialize: false,
+                _not_send: ptr::null_mut(),
+            }
+        } else {
+            check_result(result).unwrap();
+            ComInitialized {
+                uninitialize: true,
+                _not_send: ptr::null_mut(),
+            }
+        }
     }
 });
 
 /// RAII object that guards the fact that COM is initialized.
-///
-// We store a raw pointer because it's the only way at the moment to remove `Send`/`Sync` from the
-// object.
-struct ComInitialized(*mut ());
+struct ComInitialized {
+    // Whether `CoInitializeEx` succeeded, and has to be balanced by `CoUninitialize`.
+    uninitialize: bool,
+    // We store a raw pointer because it's the only way at the moment to remove `Send`/`Sync` from
+    // the object.
+    _not_send: *mut (),
+}
 
 impl Drop for ComInitialized {
     #[inline]
     fn drop(&mut self) {
-        unsafe { CoUninitialize() };
+        if self.uninitialize {
+            unsafe { CoUninitialize() };
+        }
     }
 }
 
 /// Ensures that COM is initialized in this thread.
+///
+/// If the thread already belongs to a single-threaded apartment, it is kept in it.
 #[inline]
 pub fn com_initialized() {
     COM_INITIALIZED.with(|_| {});
@@ -1,19 +1,23 @@
 use crate::{
-    BackendSpecificError, BufferSize, Data, DefaultStreamConfigError, DeviceNameError,
-    DevicesError, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
+    BackendSpecificError, BufferSize, ChannelLayout, Data, DefaultStreamConfigError,
+    DeviceInUseError, DeviceNameError, DeviceVolumeError, DevicesError, FormFactor, FrameCount,
+    InputCallbackInfo, OutputCallbackInfo, Role, SampleFormat, SampleRate, StreamConfig,
     SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
     SupportedStreamConfigsError, COMMON_SAMPLE_RATES,
 };
 use std;
 use std::ffi::OsString;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io::Error as IoError;
 use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::os::windows::ffi::OsStringExt;
 use std::ptr;
 use std::slice;
+use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 use super::check_result;
 use super::check_result_backend_specific;
@@ -26,31 +30,51 @@ use super::winapi::shared::minwindef::{DWORD, WORD};
 use super::winapi::shared::mmreg;
 use super::winapi::shared::winerror;
 use super::winapi::shared::wtypes;
+use super::winapi::um::strmif::REFERENCE_TIME;
 use super::winapi::Interface;
 
 // https://msdn.microsoft.com/en-us/library/cc230355.aspx
 use super::winapi::um::audioclient::{
-    self, IAudioClient, IID_IAudioClient, AUDCLNT_E_DEVICE_INVALIDATED,
+    self, IAudioClient, IID_IAudioClient, AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED,
+    AUDCLNT_E_DEVICE_INVALIDATED, AUDCLNT_E_DEVICE_IN_USE, AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED,
 };
 use super::winapi::um::audiosessiontypes::{
-    AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_EVENTCALLBACK, AUDCLNT_STREAMFLAGS_LOOPBACK,
+    AUDCLNT_SHAREMODE, AUDCLNT_SHAREMODE_EXCLUSIVE, AUDCLNT_SHAREMODE_SHARED,
+    AUDCLNT_STREAMFLAGS_EVENTCALLBACK, AUDCLNT_STREAMFLAGS_LOOPBACK,
 };
 use super::winapi::um::combaseapi::{
     CoCreateInstance, CoTaskMemFree, PropVariantClear, CLSCTX_ALL,
 };
 use super::winapi::um::coml2api;
+use super::winapi::um::endpointvolume::IAudioEndpointVolume;
 use super::winapi::um::mmdeviceapi::{
-    eAll, eCapture, eConsole, eRender, CLSID_MMDeviceEnumerator, EDataFlow, IMMDevice,
-    IMMDeviceCollection, IMMDeviceEnumerator, IMMEndpoint, DEVICE_STATE_ACTIVE,
+    self, eAll, eCapture, eCommunications, eConsole, eMultimedia, eRender,
+    CLSID_MMDeviceEnumerator, EDataFlow, IMMDevice, IMMDeviceCollection, IMMDeviceEnumerator,
+    IMMEndpoint, DEVICE_STATE_ACTIVE,
 };
-use super::winapi::um::winnt::{LPWSTR, WCHAR};
+use super::winapi::um::winnt::{self, LPWSTR, WCHAR};
 
 use super::{
     stream::{AudioClientFlow, Stream, StreamInner},
-    winapi::um::synchapi,
+    winapi::um::{handleapi, synchapi},
+    WasapiServiceMode,
 };
 use crate::{traits::DeviceTrait, BuildStreamError, StreamError};
 
+// The buffer duration requested in `WasapiServiceMode::Polling`, in 100-nanosecond units. The buffer
+// must be large enough to survive the thread oversleeping, as it is not woken up by the device.
+const POLLING_BUFFER_DURATION: REFERENCE_TIME = 400_000;
+
+// Lets the audio engine convert the format of a shared mode stream to and from the mix format,
+// resampling with its default quality. Not defined by `winapi`.
+const AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM: DWORD = 0x8000_0000;
+const AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY: DWORD = 0x0800_0000;
+
+// The longest buffer that may be requested via `StreamOptions::periods` or
+// `Device::set_buffer_duration`, in 100-nanosecond units. Shared mode audio clients refuse buffers
+// longer than two seconds.
+const MAX_PERIODS_BUFFER_DURATION: REFERENCE_TIME = 20_000_000;
+
 pub type SupportedInputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
 pub type SupportedOutputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
 
@@ -66,6 +90,10 @@ pub struct Device {
     /// We cache an uninitialized `IAudioClient` so that we can call functions from it without
     /// having to create/destroy audio clients all the time.
     future_audio_client: Arc<Mutex<Option<IAudioClientWrapper>>>, // TODO: add NonZero around the ptr
+    /// How streams built from this device are serviced.
+    service_mode: WasapiServiceMode,
+    /// The buffer duration requested for streams built from this device, if not the default.
+    buffer_duration: Option<Duration>,
 }
 
 impl DeviceTrait for Device {
@@ -89,6 +117,38 @@ impl DeviceTrait for Device {
         Device::supported_output_configs(self)
     }
 
+    fn supports_input(&self) -> bool {
+        self.data_flow() == eCapture
+    }
+
+    fn supports_output(&self) -> bool {
+        self.data_flow() == eRender
+    }
+
+    fn volume(&self) -> Result<f32, DeviceVolumeError> {
+        Device::volume(self)
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<(), DeviceVolumeError> {
+        Device::set_volume(self, volume)
+    }
+
+    fn is_muted(&self) -> Result<bool, DeviceVolumeError> {
+        Device::is_muted(self)
+    }
+
+    fn set_muted(&self, muted: bool) -> Result<(), DeviceVolumeError> {
+        Device::set_muted(self, muted)
+    }
+
+    fn is_in_use(&self) -> Result<bool, DeviceInUseError> {
+        Device::is_in_use(self)
+    }
+
+    fn form_factor(&self) -> Option<FormFactor> {
+        Device::form_factor(self)
+    }
+
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_input_config(self)
     }
@@ -97,6 +157,38 @@ impl DeviceTrait for Device {
         Device::default_output_config(self)
     }
 
+    fn default_input_channel_layout(
+        &self,
+    ) -> Result<Option<ChannelLayout>, DefaultStreamConfigError> {
+        if self.data_flow() == eCapture {
+            self.default_channel_layout()
+        } else {
+            Err(DefaultStreamConfigError::StreamTypeNotSupported)
+        }
+    }
+
+    fn default_output_channel_layout(
+        &self,
+    ) -> Result<Option<ChannelLayout>, DefaultStreamConfigError> {
+        if self.data_flow() == eRender {
+            self.default_channel_layout()
+        } else {
+            Err(DefaultStreamConfigError::StreamTypeNotSupported)
+        }
+    }
+
+    fn default_buffer_size(&self, config: &SupportedStreamConfig) -> Option<FrameCount> {
+        com::com_initialized();
+        let lock = self.ensure_future_audio_client().ok()?;
+        let client = lock.as_ref()?.0;
+        let mut period = 0;
+        let hresult = unsafe { (*client).GetDevicePeriod(&mut period, ptr::null_mut()) };
+        check_result(hresult).ok()?;
+        // The period is in 100-nanosecond units.
+        let frames = period as u64 * config.sample_rate.0 as u64 / 10_000_000;
+        Some(frames as FrameCount)
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         config: &StreamConfig,
@@ -108,7 +200,12 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        let stream_inner = self.build_input_stream_raw_inner(config, sample_format)?;
+        let stream_inner = self.build_input_stream_raw_inner(
+            config,
+            sample_format,
+            AUDCLNT_SHAREMODE_SHARED,
+            None,
+        )?;
         Ok(Stream::new_input(
             stream_inner,
             data_callback,
@@ -127,13 +224,114 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        let stream_inner = self.build_output_stream_raw_inner(config, sample_format)?;
+        let stream_inner = self.build_output_stream_raw_inner(
+            config,
+            sample_format,
+            AUDCLNT_SHAREMODE_SHARED,
+            None,
+        )?;
+        Ok(Stream::new_output(
+            stream_inner,
+            data_callback,
+            error_callback,
+        ))
+    }
+
+    fn build_input_stream_raw_with_periods<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        periods: u32,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner = self.build_input_stream_raw_inner(
+            config,
+            sample_format,
+            AUDCLNT_SHAREMODE_SHARED,
+            Some(periods),
+        )?;
+        Ok(Stream::new_input(
+            stream_inner,
+            data_callback,
+            error_callback,
+        ))
+    }
+
+    fn build_output_stream_raw_with_periods<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        periods: u32,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let stream_inner = self.build_output_stream_raw_inner(
+            config,
+            sample_format,
+            AUDCLNT_SHAREMODE_SHARED,
+            Some(periods),
+        )?;
         Ok(Stream::new_output(
             stream_inner,
             data_callback,
             error_callback,
         ))
     }
+
+    fn build_input_stream_bit_perfect<D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        // Loopback capture is only available in shared mode.
+        if self.data_flow() == eRender {
+            return Err(BuildStreamError::BitPerfectNotSupported);
+        }
+        let sample_format = self.exclusive_sample_format(config)?;
+        let stream_inner = self.build_input_stream_raw_inner(
+            config,
+            sample_format,
+            AUDCLNT_SHAREMODE_EXCLUSIVE,
+            None,
+        )?;
+        let stream = Stream::new_input(stream_inner, data_callback, error_callback);
+        Ok((stream, sample_format))
+    }
+
+    fn build_output_stream_bit_perfect<D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let sample_format = self.exclusive_sample_format(config)?;
+        let stream_inner = self.build_output_stream_raw_inner(
+            config,
+            sample_format,
+            AUDCLNT_SHAREMODE_EXCLUSIVE,
+            None,
+        )?;
+        let stream = Stream::new_output(stream_inner, data_callback, error_callback);
+        Ok((stream, sample_format))
+    }
 }
 
 struct Endpoint {
@@ -214,9 +412,11 @@ unsafe fn data_flow_from_immendpoint(endpoint: *const IMMEndpoint) -> EDataFlow
     data_flow.assume_init()
 }
 
-// Given the audio client and format, returns whether or not the format is supported.
+// Given the audio client and format, returns whether or not the format is supported in the given
+// share mode.
 pub unsafe fn is_format_supported(
     client: *const IAudioClient,
+    share_mode: AUDCLNT_SHAREMODE,
     waveformatex_ptr: *const mmreg::WAVEFORMATEX,
 ) -> Result<bool, SupportedStreamConfigsError> {
     /*
@@ -253,10 +453,22 @@ pub unsafe fn is_format_supported(
 
     // Check if the given format is supported.
     let is_supported = |waveformatex_ptr, mut closest_waveformatex_ptr| {
-        let result = (*client).IsFormatSupported(
-            AUDCLNT_SHAREMODE_SHARED,
-            waveformatex_ptr,
-            &mut closest_waveformatex_ptr,
+        // There is no closest match in exclusive mode, which requires passing null instead.
+        let closest_waveformatex_ptr_ptr = if share_mode == AUDCLNT_SHAREMODE_SHARED {
+            &mut closest_waveformatex_ptr as *mut _
+        } else {
+            ptr::null_mut()
+        };
+        let result =
+            (*client).IsFormatSupported(share_mode, waveformatex_ptr, closest_waveformatex_ptr_ptr);
+        trace!(
+            "wasapi: IsFormatSupported({}, tag {:#x}, {} channels, {} Hz, {} bits): {:#010x}",
+            share_mode,
+            { (*waveformatex_ptr).wFormatTag },
+            { (*waveformatex_ptr).nChannels },
+            { (*waveformatex_ptr).nSamplesPerSec },
+            { (*waveformatex_ptr).wBitsPerSample },
+            result
         );
         // `IsFormatSupported` can return `S_FALSE` (which means that a compatible format
         // has been found, but not an exact match) so we also treat this as unsupported.
@@ -292,6 +504,30 @@ pub unsafe fn is_format_supported(
     }
 }
 
+// Given the audio client and format, returns the format itself if it is supported, or else the
+// closest match suggested by `IsFormatSupported`, if any.
+unsafe fn closest_supported_format(
+    client: *const IAudioClient,
+    waveformatex_ptr: *const mmreg::WAVEFORMATEX,
+) -> Result<Option<SupportedStreamConfig>, SupportedStreamConfigsError> {
+    // The closest match is allocated by WASAPI and freed when this is dropped.
+    let mut closest_waveformatex_ptr = WaveFormatExPtr(ptr::null_mut());
+    let result = (*client).IsFormatSupported(
+        AUDCLNT_SHAREMODE_SHARED,
+        waveformatex_ptr,
+        &mut closest_waveformatex_ptr.0,
+    );
+    match (result, check_result(result)) {
+        (_, Err(ref e)) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
+            Err(SupportedStreamConfigsError::DeviceNotAvailable)
+        }
+        (_, Err(_)) => Ok(None),
+        (winerror::S_FALSE, _) if closest_waveformatex_ptr.0.is_null() => Ok(None),
+        (winerror::S_FALSE, _) => Ok(format_from_waveformatex_ptr(closest_waveformatex_ptr.0)),
+        (_, Ok(())) => Ok(format_from_waveformatex_ptr(waveformatex_ptr)),
+    }
+}
+
 // Get a cpal Format from a WAVEFORMATEX.
 unsafe fn format_from_waveformatex_ptr(
     waveformatex_ptr: *const mmreg::WAVEFORMATEX,
@@ -333,7 +569,191 @@ unsafe impl Send for Device {}
 unsafe impl Sync for Device {}
 
 impl Device {
+    /// How streams built from this device learn that the device buffer needs servicing.
+    pub fn service_mode(&self) -> WasapiServiceMode {
+        self.service_mode
+    }
+
+    /// Set how streams built from this device are serviced from now on.
+    /// (default is `WasapiServiceMode::Event`)
+    pub fn set_service_mode(&mut self, mode: WasapiServiceMode) {
+        self.service_mode = mode;
+    }
+
+    /// The buffer duration requested for streams built from this device, see
+    /// `set_buffer_duration`.
+    pub fn buffer_duration(&self) -> Option<Duration> {
+        self.buffer_duration
+    }
+
+    /// Set the duration of the buffer of streams built from this device from now on, e.g. 10
+    /// milliseconds as a latency target, or `None` for the default of the service mode.
+    /// (default is `None`)
+    ///
+    /// This is the `hnsBufferDuration` of `IAudioClient::Initialize`, which WASAPI rounds to a
+    /// valid buffer: in shared mode, it is at least the buffer of the audio engine, and in
+    /// exclusive mode, at least the minimum device period and aligned to the requirements of the
+    /// device. Durations longer than two seconds are clamped. The duration the stream actually
+    /// got is returned by `Stream::buffer_duration`. In shared mode, `StreamOptions::periods` takes
+    /// precedence.
+    pub fn set_buffer_duration(&mut self, duration: Option<Duration>) {
+        self.buffer_duration = duration;
+    }
+
+    // The flags and buffer duration with which to initialize audio clients for the service mode.
+    fn stream_flags_and_duration(&self) -> (DWORD, REFERENCE_TIME) {
+        let (flags, duration) = match self.service_mode {
+            WasapiServiceMode::Event => (AUDCLNT_STREAMFLAGS_EVENTCALLBACK, 0),
+            WasapiServiceMode::Polling => (0, POLLING_BUFFER_DURATION),
+        };
+        match self.buffer_duration {
+            Some(duration) => (flags, reference_time(duration)),
+            None => (flags, duration),
+        }
+    }
+
+    // The sample format in which the device supports `config` in exclusive mode, in which the
+    // samples reach the device unconverted.
+    fn exclusive_sample_format(
+        &self,
+        config: &StreamConfig,
+    ) -> Result<SampleFormat, BuildStreamError> {
+        com::com_initialized();
+        let lock = match self.ensure_future_audio_client() {
+            Ok(lock) => lock,
+            Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
+                return Err(BuildStreamError::DeviceNotAvailable)
+            }
+            Err(e) => {
+                let description = format!("{}", e);
+                let err = BackendSpecificError { description };
+                return Err(err.into());
+            }
+        };
+        let client = lock.unwrap().0;
+        for &sample_format in &[SampleFormat::F32, SampleFormat::I16] {
+            let format = match config_to_waveformatextensible(config, sample_format) {
+                Some(format) => format,
+                None => continue,
+            };
+            if unsafe { is_format_supported(client, AUDCLNT_SHAREMODE_EXCLUSIVE, &format.Format)? }
+            {
+                return Ok(sample_format);
+            }
+        }
+        Err(BuildStreamError::StreamConfigNotSupported)
+    }
+
+    // Initializes `audio_client`, releasing it on failure.
+    //
+    // In exclusive mode, the device is serviced once per device period, and in event mode the
+    // buffer holds exactly one period. The period is the default one of the device unless a buffer
+    // duration was set, in which case it is the duration, as it is serviced once per buffer.
+    // Should the device require the buffer to be aligned, the audio client is replaced by a new
+    // one initialized for the aligned buffer, and returned.
+    unsafe fn initialize_audio_client(
+        &self,
+        audio_client: *mut IAudioClient,
+        share_mode: AUDCLNT_SHAREMODE,
+        stream_flags: DWORD,
+        buffer_duration: REFERENCE_TIME,
+        format: &mmreg::WAVEFORMATEX,
+    ) -> Result<*mut IAudioClient, BuildStreamError> {
+        if share_mode == AUDCLNT_SHAREMODE_SHARED {
+            let hresult = (*audio_client).Initialize(
+                share_mode,
+                stream_flags,
+                buffer_duration,
+                0,
+                format,
+                ptr::null(),
+            );
+            return initialized_audio_client(audio_client, hresult);
+        }
+
+        let event = stream_flags & AUDCLNT_STREAMFLAGS_EVENTCALLBACK != 0;
+        let mut period = 0;
+        let mut min_period = 0;
+        let hresult = (*audio_client).GetDevicePeriod(&mut period, &mut min_period);
+        if let Err(e) = check_result(hresult) {
+            (*audio_client).Release();
+            return Err(initialize_error(e));
+        }
+        if let (true, Some(duration)) = (event, self.buffer_duration) {
+            period = reference_time(duration).max(min_period);
+        }
+        let hresult = (*audio_client).Initialize(
+            share_mode,
+            stream_flags,
+            if event {
+                period
+            } else {
+                buffer_duration.max(period)
+            },
+            period,
+            format,
+            ptr::null(),
+        );
+        if hresult != AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED {
+            return initialized_audio_client(audio_client, hresult);
+        }
+
+        // The buffer size of the failed attempt is the aligned one.
+        let mut frames = 0u32;
+        let hresult = (*audio_client).GetBufferSize(&mut frames);
+        (*audio_client).Release();
+        check_result(hresult).map_err(initialize_error)?;
+        let period = (10_000_000.0 * frames as f64 / format.nSamplesPerSec as f64).round();
+        let period = period as REFERENCE_TIME;
+        let audio_client = self.build_audioclient().map_err(initialize_error)?;
+        let hresult = (*audio_client).Initialize(
+            share_mode,
+            stream_flags,
+            if event {
+                period
+            } else {
+                buffer_duration.max(period)
+            },
+            period,
+            format,
+            ptr::null(),
+        );
+        initialized_audio_client(audio_client, hresult)
+    }
+
+    // The endpoint ID string of the device, without the null terminator.
+    //
+    // Unlike the `IMMDevice` pointer, the ID stays the same when the device is enumerated again,
+    // cf. https://docs.microsoft.com/en-us/windows/desktop/api/mmdeviceapi/nf-mmdeviceapi-immdevice-getid
+    fn id(&self) -> Vec<WCHAR> {
+        unsafe {
+            struct IdRAII(LPWSTR);
+            /// RAII for device IDs.
+            impl Drop for IdRAII {
+                fn drop(&mut self) {
+                    unsafe { CoTaskMemFree(self.0 as *mut c_void) }
+                }
+            }
+            let mut id: LPWSTR = ptr::null_mut();
+            let rc = (*self.device).GetId(&mut id);
+            // GetId only fails with E_OUTOFMEMORY and if it does, we're probably dead already.
+            // Plus it won't do to change the device comparison logic unexpectedly.
+            if rc != winerror::S_OK {
+                panic!("cpal: GetId failure: {}", rc)
+            }
+            let id = IdRAII(id);
+            // 16-bit null-terminated string.
+            let mut len = 0;
+            while *id.0.offset(len) != 0 {
+                len += 1;
+            }
+            slice::from_raw_parts(id.0, len as usize).to_vec()
+        }
+    }
+
     pub fn name(&self) -> Result<String, DeviceNameError> {
+        // initializing COM because we call `PropVariantClear` to release the name.
+        com::com_initialized();
         unsafe {
             // Open the device's property store.
             let mut property_store = ptr::null_mut();
@@ -382,11 +802,50 @@ impl Device {
         }
     }
 
+    /// The form factor of the endpoint, see `DeviceTrait::form_factor`.
+    pub fn form_factor(&self) -> Option<FormFactor> {
+        // Initializing COM because we call `PropVariantClear` to release the value.
+        com::com_initialized();
+        unsafe {
+            let mut property_store = ptr::null_mut();
+            let hresult =
+                (*self.device).OpenPropertyStore(coml2api::STGM_READ, &mut property_store);
+            check_result(hresult).ok()?;
+
+            let mut property_value = mem::zeroed();
+            let hresult = (*property_store).GetValue(
+                &mmdeviceapi::PKEY_AudioEndpoint_FormFactor as *const _ as *const _,
+                &mut property_value,
+            );
+            (*property_store).Release();
+            check_result(hresult).ok()?;
+            let form_factor = if property_value.vt == wtypes::VT_UI4 as _ {
+                Some(*(&property_value.data as *const _ as *const u32))
+            } else {
+                None
+            };
+            PropVariantClear(&mut property_value);
+
+            match form_factor? {
+                mmdeviceapi::Speakers => Some(FormFactor::Speakers),
+                mmdeviceapi::Headphones => Some(FormFactor::Headphones),
+                mmdeviceapi::Headset | mmdeviceapi::Handset => Some(FormFactor::Headset),
+                mmdeviceapi::Microphone => Some(FormFactor::Microphone),
+                mmdeviceapi::LineLevel => Some(FormFactor::LineLevel),
+                mmdeviceapi::DigitalAudioDisplayDevice => Some(FormFactor::Hdmi),
+                mmdeviceapi::SPDIF => Some(FormFactor::Spdif),
+                _ => None,
+            }
+        }
+    }
+
     #[inline]
     fn from_immdevice(device: *mut IMMDevice) -> Self {
         Device {
             device,
             future_audio_client: Arc::new(Mutex::new(None)),
+            service_mode: WasapiServiceMode::Event,
+            buffer_duration: None,
         }
     }
 
@@ -408,6 +867,10 @@ impl Device {
                 &mut audio_client,
             );
 
+            trace!(
+                "wasapi: IMMDevice::Activate(IAudioClient): {:#010x}",
+                hresult
+            );
             // can fail if the device has been disconnected since we enumerated it, or if
             // the device doesn't support playback for some reason
             check_result(hresult)?;
@@ -474,7 +937,7 @@ impl Device {
 
             // If the default format can't succeed we have no hope of finding other formats.
             assert_eq!(
-                is_format_supported(client, default_waveformatex_ptr.0)?,
+                is_format_supported(client, AUDCLNT_SHAREMODE_SHARED, default_waveformatex_ptr.0)?,
                 true
             );
 
@@ -498,7 +961,7 @@ impl Device {
                 test_format.nSamplesPerSec = rate;
                 test_format.nAvgBytesPerSec =
                     rate * u32::from((*default_waveformatex_ptr.0).nBlockAlign);
-                if is_format_supported(client, test_format.as_ptr())? {
+                if is_format_supported(client, AUDCLNT_SHAREMODE_SHARED, test_format.as_ptr())? {
                     supported_sample_rates.push(rate);
                 }
             }
@@ -540,11 +1003,254 @@ impl Device {
         }
     }
 
+    /// The supported config closest to `config` with samples of `sample_format`, as suggested by
+    /// `IsFormatSupported`.
+    ///
+    /// This is `config` itself if it is supported, or else the closest match found by WASAPI,
+    /// which may differ in its sample format, channel count or sample rate. If there is none, the
+    /// default config of the device is returned. Fails with `InvalidArgument` if `config` cannot
+    /// be described to WASAPI at all, e.g. because of the `U16` sample format.
+    pub fn closest_supported_config(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedStreamConfig, SupportedStreamConfigsError> {
+        let format = config_to_waveformatextensible(config, sample_format)
+            .ok_or(SupportedStreamConfigsError::InvalidArgument)?;
+
+        // initializing COM because we call `CoTaskMemFree` to release the closest format.
+        com::com_initialized();
+
+        // The lock must be released before falling back to `default_format`, which takes it too.
+        let closest = {
+            let lock = match self.ensure_future_audio_client() {
+                Ok(lock) => lock,
+                Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
+                    return Err(SupportedStreamConfigsError::DeviceNotAvailable)
+                }
+                Err(e) => {
+                    let description = format!("{}", e);
+                    let err = BackendSpecificError { description };
+                    return Err(err.into());
+                }
+            };
+            let client = lock.unwrap().0;
+            unsafe { closest_supported_format(client, &format.Format)? }
+        };
+
+        match closest {
+            Some(closest) => Ok(closest),
+            None => self.default_format().map_err(|err| match err {
+                DefaultStreamConfigError::DeviceNotAvailable => {
+                    SupportedStreamConfigsError::DeviceNotAvailable
+                }
+                DefaultStreamConfigError::StreamTypeNotSupported => {
+                    SupportedStreamConfigsError::InvalidArgument
+                }
+                DefaultStreamConfigError::BackendSpecific { err } => err.into(),
+            }),
+        }
+    }
+
+    /// Like `DeviceTrait::build_input_stream_raw`, but builds the stream with the config returned
+    /// by `closest_supported_config` instead of failing if `config` is not supported.
+    ///
+    /// Returns the stream along with that config, whose sample format is the one of the data
+    /// passed to the data callback.
+    pub fn build_input_stream_raw_closest<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Stream, SupportedStreamConfig), BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let closest = self.closest_supported_config(config, sample_format)?;
+        let closest_config = StreamConfig {
+            buffer_size: config.buffer_size.clone(),
+            ..closest.config()
+        };
+        let stream = DeviceTrait::build_input_stream_raw(
+            self,
+            &closest_config,
+            closest.sample_format(),
+            data_callback,
+            error_callback,
+        )?;
+        Ok((stream, closest))
+    }
+
+    /// Like `DeviceTrait::build_output_stream_raw`, but builds the stream with the config
+    /// returned by `closest_supported_config` instead of failing if `config` is not supported.
+    ///
+    /// Returns the stream along with that config, whose sample format is the one of the data
+    /// that the data callback must write.
+    pub fn build_output_stream_raw_closest<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Stream, SupportedStreamConfig), BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let closest = self.closest_supported_config(config, sample_format)?;
+        let closest_config = StreamConfig {
+            buffer_size: config.buffer_size.clone(),
+            ..closest.config()
+        };
+        let stream = DeviceTrait::build_output_stream_raw(
+            self,
+            &closest_config,
+            closest.sample_format(),
+            data_callback,
+            error_callback,
+        )?;
+        Ok((stream, closest))
+    }
+
+    // Calls `f` with the `IAudioEndpointVolume` interface of the device, which controls the master
+    // volume of the endpoint.
+    fn with_endpoint_volume<T, F>(&self, f: F) -> Result<T, DeviceVolumeError>
+    where
+        F: FnOnce(&IAudioEndpointVolume) -> Result<T, IoError>,
+    {
+        let volume_error = |e: IoError| match e.raw_os_error() {
+            Some(AUDCLNT_E_DEVICE_INVALIDATED) => DeviceVolumeError::DeviceNotAvailable,
+            _ => {
+                let description = format!("{}", e);
+                BackendSpecificError { description }.into()
+            }
+        };
+        com::com_initialized();
+        unsafe {
+            let mut endpoint_volume: *mut IAudioEndpointVolume = ptr::null_mut();
+            let hresult = (*self.device).Activate(
+                &IAudioEndpointVolume::uuidof(),
+                CLSCTX_ALL,
+                ptr::null_mut(),
+                &mut endpoint_volume as *mut *mut IAudioEndpointVolume as *mut _,
+            );
+            check_result(hresult).map_err(volume_error)?;
+            let result = f(&*endpoint_volume);
+            (*endpoint_volume).Release();
+            result.map_err(volume_error)
+        }
+    }
+
+    pub fn volume(&self) -> Result<f32, DeviceVolumeError> {
+        self.with_endpoint_volume(|endpoint_volume| unsafe {
+            let mut volume = 0.0;
+            check_result(endpoint_volume.GetMasterVolumeLevelScalar(&mut volume))?;
+            Ok(volume)
+        })
+    }
+
+    pub fn set_volume(&self, volume: f32) -> Result<(), DeviceVolumeError> {
+        let volume = volume.clamp(0.0, 1.0);
+        self.with_endpoint_volume(|endpoint_volume| unsafe {
+            check_result(endpoint_volume.SetMasterVolumeLevelScalar(volume, ptr::null()))
+        })
+    }
+
+    pub fn is_muted(&self) -> Result<bool, DeviceVolumeError> {
+        self.with_endpoint_volume(|endpoint_volume| unsafe {
+            let mut muted = 0;
+            check_result(endpoint_volume.GetMute(&mut muted))?;
+            Ok(muted != 0)
+        })
+    }
+
+    pub fn set_muted(&self, muted: bool) -> Result<(), DeviceVolumeError> {
+        self.with_endpoint_volume(|endpoint_volume| unsafe {
+            check_result(endpoint_volume.SetMute(muted as _, ptr::null()))
+        })
+    }
+
+    /// Whether the endpoint cannot be opened in exclusive mode because it is in use, see
+    /// `DeviceTrait::is_in_use`.
+    pub fn is_in_use(&self) -> Result<bool, DeviceInUseError> {
+        let config = match self.default_format() {
+            Ok(format) => format.config(),
+            Err(DefaultStreamConfigError::DeviceNotAvailable) => {
+                return Err(DeviceInUseError::DeviceNotAvailable)
+            }
+            Err(DefaultStreamConfigError::StreamTypeNotSupported) => {
+                return Err(DeviceInUseError::NotSupported)
+            }
+            Err(DefaultStreamConfigError::BackendSpecific { err }) => return Err(err.into()),
+        };
+        let format = match self.exclusive_sample_format(&config) {
+            Ok(sample_format) => config_to_waveformatextensible(&config, sample_format),
+            Err(BuildStreamError::DeviceNotAvailable) => {
+                return Err(DeviceInUseError::DeviceNotAvailable)
+            }
+            Err(BuildStreamError::BackendSpecific { err }) => return Err(err.into()),
+            Err(_) => None,
+        };
+        let format = match format {
+            Some(format) => format,
+            None => return Ok(false),
+        };
+
+        unsafe {
+            let audio_client = match self.build_audioclient() {
+                Ok(audio_client) => audio_client,
+                Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
+                    return Err(DeviceInUseError::DeviceNotAvailable)
+                }
+                Err(e) => {
+                    let description = format!("{}", e);
+                    return Err(BackendSpecificError { description }.into());
+                }
+            };
+            let mut period = 0;
+            let hresult = (*audio_client).GetDevicePeriod(&mut period, ptr::null_mut());
+            let hresult = if hresult < 0 {
+                hresult
+            } else {
+                (*audio_client).Initialize(
+                    AUDCLNT_SHAREMODE_EXCLUSIVE,
+                    0,
+                    period,
+                    period,
+                    &format.Format,
+                    ptr::null(),
+                )
+            };
+            // Releasing the audio client closes the endpoint again.
+            (*audio_client).Release();
+            match hresult {
+                AUDCLNT_E_DEVICE_IN_USE => Ok(true),
+                AUDCLNT_E_DEVICE_INVALIDATED => Err(DeviceInUseError::DeviceNotAvailable),
+                _ => Ok(false),
+            }
+        }
+    }
+
     pub fn supported_input_configs(
         &self,
     ) -> Result<SupportedInputConfigs, SupportedStreamConfigsError> {
         if self.data_flow() == eCapture {
-            self.supported_formats()
+            // Shared-mode streams whose format differs from the mix format are converted with
+            // `AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM`, so mono capture is available even when the mix
+            // format has more channels, e.g. for microphones exposed as stereo.
+            let mut configs: Vec<_> = self.supported_formats()?.collect();
+            let mono: Vec<_> = configs
+                .iter()
+                .filter(|config| config.channels > 1)
+                .map(|config| SupportedStreamConfigRange {
+                    channels: 1,
+                    ..config.clone()
+                })
+                .collect();
+            configs.extend(mono);
+            Ok(configs.into_iter())
         // If it's an output device, assume no input formats.
         } else {
             Ok(vec![].into_iter())
@@ -562,11 +1268,8 @@ impl Device {
         }
     }
 
-    // We always create voices in shared mode, therefore all samples go through an audio
-    // processor to mix them together.
-    //
-    // One format is guaranteed to be supported, the one returned by `GetMixFormat`.
-    fn default_format(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+    // The format at which the system mixer currently runs in shared mode.
+    fn mix_format(&self) -> Result<WaveFormatExPtr, DefaultStreamConfigError> {
         // initializing COM because we call `CoTaskMemFree`
         com::com_initialized();
 
@@ -596,12 +1299,38 @@ impl Device {
                 }
                 Ok(()) => (),
             };
+            Ok(format_ptr)
+        }
+    }
 
+    // We always create voices in shared mode, therefore all samples go through an audio
+    // processor to mix them together.
+    //
+    // One format is guaranteed to be supported, the one returned by `GetMixFormat`.
+    fn default_format(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        let format_ptr = self.mix_format()?;
+        unsafe {
             format_from_waveformatex_ptr(format_ptr.0)
                 .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)
         }
     }
 
+    // The channel mask of the mix format, or the usual layout of its channels if it has none.
+    fn default_channel_layout(&self) -> Result<Option<ChannelLayout>, DefaultStreamConfigError> {
+        let format_ptr = self.mix_format()?;
+        unsafe {
+            let format = format_ptr.0;
+            if (*format).wFormatTag == mmreg::WAVE_FORMAT_EXTENSIBLE {
+                let extensible = format as *const mmreg::WAVEFORMATEXTENSIBLE;
+                let mask = (*extensible).dwChannelMask;
+                if mask != 0 {
+                    return Ok(Some(ChannelLayout(mask)));
+                }
+            }
+            Ok(ChannelLayout::for_channels((*format).nChannels))
+        }
+    }
+
     pub(crate) fn data_flow(&self) -> EDataFlow {
         let endpoint = Endpoint::from(self.device as *const _);
         endpoint.data_flow()
@@ -628,6 +1357,8 @@ impl Device {
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: AUDCLNT_SHAREMODE,
+        periods: Option<u32>,
     ) -> Result<StreamInner, BuildStreamError> {
         unsafe {
             // Making sure that COM is initialized.
@@ -635,7 +1366,7 @@ impl Device {
             com::com_initialized();
 
             // Obtaining a `IAudioClient`.
-            let audio_client = match self.build_audioclient() {
+            let mut audio_client = match self.build_audioclient() {
                 Ok(client) => client,
                 Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
                     return Err(BuildStreamError::DeviceNotAvailable)
@@ -657,7 +1388,10 @@ impl Device {
                 BufferSize::Default => (),
             };
 
-            let mut stream_flags: DWORD = AUDCLNT_STREAMFLAGS_EVENTCALLBACK;
+            let (mut stream_flags, mut buffer_duration) = self.stream_flags_and_duration();
+            if let Some(periods) = periods {
+                buffer_duration = periods_buffer_duration(audio_client, periods)?;
+            }
 
             if self.data_flow() == eRender {
                 stream_flags |= AUDCLNT_STREAMFLAGS_LOOPBACK;
@@ -667,37 +1401,26 @@ impl Device {
             let waveformatex = {
                 let format_attempt = config_to_waveformatextensible(config, sample_format)
                     .ok_or(BuildStreamError::StreamConfigNotSupported)?;
-                let share_mode = AUDCLNT_SHAREMODE_SHARED;
 
-                // Ensure the format is supported.
-                match super::device::is_format_supported(audio_client, &format_attempt.Format) {
+                // Ensure the format is supported, or else have it converted in shared mode.
+                match is_format_supported(audio_client, share_mode, &format_attempt.Format) {
+                    Ok(false) if share_mode == AUDCLNT_SHAREMODE_SHARED => {
+                        stream_flags |= AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM
+                            | AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY;
+                    }
                     Ok(false) => return Err(BuildStreamError::StreamConfigNotSupported),
                     Err(_) => return Err(BuildStreamError::DeviceNotAvailable),
                     _ => (),
                 }
 
                 // Finally, initializing the audio client
-                let hresult = (*audio_client).Initialize(
+                audio_client = self.initialize_audio_client(
+                    audio_client,
                     share_mode,
                     stream_flags,
-                    0,
-                    0,
+                    buffer_duration,
                     &format_attempt.Format,
-                    ptr::null(),
-                );
-                match check_result(hresult) {
-                    Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
-                        (*audio_client).Release();
-                        return Err(BuildStreamError::DeviceNotAvailable);
-                    }
-                    Err(e) => {
-                        (*audio_client).Release();
-                        let description = format!("{}", e);
-                        let err = BackendSpecificError { description };
-                        return Err(err.into());
-                    }
-                    Ok(()) => (),
-                };
+                )?;
 
                 format_attempt.Format
             };
@@ -724,26 +1447,6 @@ impl Device {
                 max_frames_in_buffer
             };
 
-            // Creating the event that will be signalled whenever we need to submit some samples.
-            let event = {
-                let event = synchapi::CreateEventA(ptr::null_mut(), 0, 0, ptr::null());
-                if event.is_null() {
-                    (*audio_client).Release();
-                    let description = "failed to create event".to_string();
-                    let err = BackendSpecificError { description };
-                    return Err(err.into());
-                }
-
-                if let Err(e) = check_result((*audio_client).SetEventHandle(event)) {
-                    (*audio_client).Release();
-                    let description = format!("failed to call SetEventHandle: {}", e);
-                    let err = BackendSpecificError { description };
-                    return Err(err.into());
-                }
-
-                event
-            };
-
             // Building a `IAudioCaptureClient` that will be used to read captured samples.
             let capture_client = {
                 let mut capture_client: *mut audioclient::IAudioCaptureClient = ptr::null_mut();
@@ -778,17 +1481,26 @@ impl Device {
                 err
             })?;
 
-            Ok(StreamInner {
+            let mut stream = StreamInner {
                 audio_client,
                 audio_clock,
                 client_flow,
-                event,
+                event: ptr::null_mut(),
                 playing: false,
                 max_frames_in_buffer,
                 bytes_per_frame: waveformatex.nBlockAlign,
                 config: config.clone(),
                 sample_format,
-            })
+                frames: Arc::new(AtomicU64::new(0)),
+            };
+
+            // Creating the event that will be signalled whenever we need to submit or retrieve
+            // some samples. Should this fail, dropping `stream` releases the audio client.
+            if self.service_mode == WasapiServiceMode::Event {
+                stream.event = create_event(audio_client)?;
+            }
+
+            Ok(stream)
         }
     }
 
@@ -796,6 +1508,8 @@ impl Device {
         &self,
         config: &StreamConfig,
         sample_format: SampleFormat,
+        share_mode: AUDCLNT_SHAREMODE,
+        periods: Option<u32>,
     ) -> Result<StreamInner, BuildStreamError> {
         unsafe {
             // Making sure that COM is initialized.
@@ -803,7 +1517,7 @@ impl Device {
             com::com_initialized();
 
             // Obtaining a `IAudioClient`.
-            let audio_client = match self.build_audioclient() {
+            let mut audio_client = match self.build_audioclient() {
                 Ok(client) => client,
                 Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
                     return Err(BuildStreamError::DeviceNotAvailable)
@@ -829,62 +1543,35 @@ impl Device {
             let waveformatex = {
                 let format_attempt = config_to_waveformatextensible(config, sample_format)
                     .ok_or(BuildStreamError::StreamConfigNotSupported)?;
-                let share_mode = AUDCLNT_SHAREMODE_SHARED;
 
-                // Ensure the format is supported.
-                match super::device::is_format_supported(audio_client, &format_attempt.Format) {
+                let (mut stream_flags, mut buffer_duration) = self.stream_flags_and_duration();
+                if let Some(periods) = periods {
+                    buffer_duration = periods_buffer_duration(audio_client, periods)?;
+                }
+
+                // Ensure the format is supported, or else have it converted in shared mode.
+                match is_format_supported(audio_client, share_mode, &format_attempt.Format) {
+                    Ok(false) if share_mode == AUDCLNT_SHAREMODE_SHARED => {
+                        stream_flags |= AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM
+                            | AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY;
+                    }
                     Ok(false) => return Err(BuildStreamError::StreamConfigNotSupported),
                     Err(_) => return Err(BuildStreamError::DeviceNotAvailable),
                     _ => (),
                 }
 
                 // Finally, initializing the audio client
-                let hresult = (*audio_client).Initialize(
+                audio_client = self.initialize_audio_client(
+                    audio_client,
                     share_mode,
-                    AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
-                    0,
-                    0,
+                    stream_flags,
+                    buffer_duration,
                     &format_attempt.Format,
-                    ptr::null(),
-                );
-
-                match check_result(hresult) {
-                    Err(ref e) if e.raw_os_error() == Some(AUDCLNT_E_DEVICE_INVALIDATED) => {
-                        (*audio_client).Release();
-                        return Err(BuildStreamError::DeviceNotAvailable);
-                    }
-                    Err(e) => {
-                        (*audio_client).Release();
-                        let description = format!("{}", e);
-                        let err = BackendSpecificError { description };
-                        return Err(err.into());
-                    }
-                    Ok(()) => (),
-                };
+                )?;
 
                 format_attempt.Format
             };
 
-            // Creating the event that will be signalled whenever we need to submit some samples.
-            let event = {
-                let event = synchapi::CreateEventA(ptr::null_mut(), 0, 0, ptr::null());
-                if event.is_null() {
-                    (*audio_client).Release();
-                    let description = "failed to create event".to_string();
-                    let err = BackendSpecificError { description };
-                    return Err(err.into());
-                }
-
-                if let Err(e) = check_result((*audio_client).SetEventHandle(event)) {
-                    (*audio_client).Release();
-                    let description = format!("failed to call SetEventHandle: {}", e);
-                    let err = BackendSpecificError { description };
-                    return Err(err.into());
-                };
-
-                event
-            };
-
             // obtaining the size of the samples buffer in number of frames
             let max_frames_in_buffer = {
                 let mut max_frames_in_buffer = 0u32;
@@ -941,21 +1628,107 @@ impl Device {
                 err
             })?;
 
-            Ok(StreamInner {
+            let mut stream = StreamInner {
                 audio_client,
                 audio_clock,
                 client_flow,
-                event,
+                event: ptr::null_mut(),
                 playing: false,
                 max_frames_in_buffer,
                 bytes_per_frame: waveformatex.nBlockAlign,
                 config: config.clone(),
                 sample_format,
-            })
+                frames: Arc::new(AtomicU64::new(0)),
+            };
+
+            // Creating the event that will be signalled whenever we need to submit or retrieve
+            // some samples. Should this fail, dropping `stream` releases the audio client.
+            if self.service_mode == WasapiServiceMode::Event {
+                stream.event = create_event(audio_client)?;
+            }
+
+            Ok(stream)
+        }
+    }
+}
+
+// The audio client if it was initialized, as indicated by `hresult`, or else the error after
+// releasing it.
+unsafe fn initialized_audio_client(
+    audio_client: *mut IAudioClient,
+    hresult: winnt::HRESULT,
+) -> Result<*mut IAudioClient, BuildStreamError> {
+    trace!("wasapi: IAudioClient::Initialize: {:#010x}", hresult);
+    match check_result(hresult) {
+        Ok(()) => Ok(audio_client),
+        Err(e) => {
+            (*audio_client).Release();
+            Err(initialize_error(e))
         }
     }
 }
 
+// The shared mode buffer duration of `periods` default device periods, or else the error after
+// releasing `audio_client`.
+unsafe fn periods_buffer_duration(
+    audio_client: *mut IAudioClient,
+    periods: u32,
+) -> Result<REFERENCE_TIME, BuildStreamError> {
+    let mut period = 0;
+    let hresult = (*audio_client).GetDevicePeriod(&mut period, ptr::null_mut());
+    if let Err(e) = check_result(hresult) {
+        (*audio_client).Release();
+        return Err(initialize_error(e));
+    }
+    match period.checked_mul(periods as REFERENCE_TIME) {
+        Some(duration) if duration <= MAX_PERIODS_BUFFER_DURATION => Ok(duration),
+        _ => {
+            (*audio_client).Release();
+            Err(BuildStreamError::PeriodCountNotSupported)
+        }
+    }
+}
+
+// `duration` in the 100-nanosecond units of WASAPI, clamped to the longest buffer it accepts.
+fn reference_time(duration: Duration) -> REFERENCE_TIME {
+    let units = duration.as_nanos() / 100;
+    units.min(MAX_PERIODS_BUFFER_DURATION as u128) as REFERENCE_TIME
+}
+
+// The error with which building a stream fails if an audio client could not be initialized.
+fn initialize_error(e: IoError) -> BuildStreamError {
+    match e.raw_os_error() {
+        Some(AUDCLNT_E_DEVICE_INVALIDATED) | Some(AUDCLNT_E_DEVICE_IN_USE) => {
+            BuildStreamError::DeviceNotAvailable
+        }
+        Some(AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED) => BuildStreamError::BitPerfectNotSupported,
+        _ => {
+            let description = format!("{}", e);
+            BackendSpecificError { description }.into()
+        }
+    }
+}
+
+// Creates an event and registers it with `audio_client` to be signalled whenever a buffer is
+// ready to be processed.
+unsafe fn create_event(audio_client: *mut IAudioClient) -> Result<winnt::HANDLE, BuildStreamError> {
+    let event = synchapi::CreateEventA(ptr::null_mut(), 0, 0, ptr::null());
+    if event.is_null() {
+        let description = "failed to create event".to_string();
+        let err = BackendSpecificError { description };
+        return Err(err.into());
+    }
+
+    if let Err(e) = check_result((*audio_client).SetEventHandle(event)) {
+        handleapi::CloseHandle(event);
+        let description = format!("failed to call SetEventHandle: {}", e);
+        let err = BackendSpecificError { description };
+        return Err(err.into());
+    }
+
+    Ok(event)
+}
+
 impl PartialEq for Device {
     #[inline]
     fn eq(&self, other: &Device) -> bool {
@@ -963,50 +1736,18 @@ impl PartialEq for Device {
         // the client code might need to compare the previous default device with the current one.
         // The pointer comparison (`self.device == other.device`) don't work there,
         // because the pointers are different even when the default device stays the same.
-        //
-        // In this code section we're trying to use the GetId method for the device comparison, cf.
-        // https://docs.microsoft.com/en-us/windows/desktop/api/mmdeviceapi/nf-mmdeviceapi-immdevice-getid
-        unsafe {
-            struct IdRAII(LPWSTR);
-            /// RAII for device IDs.
-            impl Drop for IdRAII {
-                fn drop(&mut self) {
-                    unsafe { CoTaskMemFree(self.0 as *mut c_void) }
-                }
-            }
-            let mut id1: LPWSTR = ptr::null_mut();
-            let rc1 = (*self.device).GetId(&mut id1);
-            // GetId only fails with E_OUTOFMEMORY and if it does, we're probably dead already.
-            // Plus it won't do to change the device comparison logic unexpectedly.
-            if rc1 != winerror::S_OK {
-                panic!("cpal: GetId failure: {}", rc1)
-            }
-            let id1 = IdRAII(id1);
-            let mut id2: LPWSTR = ptr::null_mut();
-            let rc2 = (*other.device).GetId(&mut id2);
-            if rc2 != winerror::S_OK {
-                panic!("cpal: GetId failure: {}", rc1)
-            }
-            let id2 = IdRAII(id2);
-            // 16-bit null-terminated comparison.
-            let mut offset = 0;
-            loop {
-                let w1: WCHAR = *id1.0.offset(offset);
-                let w2: WCHAR = *id2.0.offset(offset);
-                if w1 == 0 && w2 == 0 {
-                    return true;
-                }
-                if w1 != w2 {
-                    return false;
-                }
-                offset += 1;
-            }
-        }
+        self.id() == other.id()
     }
 }
 
 impl Eq for Device {}
 
+impl Hash for Device {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
 impl Clone for Device {
     #[inline]
     fn clone(&self) -> Device {
@@ -1017,6 +1758,8 @@ impl Clone for Device {
         Device {
             device: self.device,
             future_audio_client: self.future_audio_client.clone(),
+            service_mode: self.service_mode,
+            buffer_duration: self.buffer_duration,
         }
     }
 }
@@ -1026,6 +1769,8 @@ impl fmt::Debug for Device {
         f.debug_struct("Device")
             .field("device", &self.device)
             .field("name", &self.name())
+            .field("service_mode", &self.service_mode)
+            .field("buffer_duration", &self.buffer_duration)
             .finish()
     }
 }
@@ -1116,6 +1861,8 @@ pub struct Devices {
 
 impl Devices {
     pub fn new() -> Result<Self, DevicesError> {
+        // The enumerator is shared by all threads, but this one may not have initialized COM yet.
+        com::com_initialized();
         unsafe {
             let mut collection: *mut IMMDeviceCollection = ptr::null_mut();
             // can fail because of wrong parameters (should never happen) or out of memory
@@ -1176,10 +1923,16 @@ impl Iterator for Devices {
     }
 }
 
-fn default_device(data_flow: EDataFlow) -> Option<Device> {
+fn default_device(data_flow: EDataFlow, role: Role) -> Option<Device> {
+    let role = match role {
+        Role::Console => eConsole,
+        Role::Multimedia => eMultimedia,
+        Role::Communications => eCommunications,
+    };
+    com::com_initialized();
     unsafe {
         let mut device = ptr::null_mut();
-        let hres = (*ENUMERATOR.0).GetDefaultAudioEndpoint(data_flow, eConsole, &mut device);
+        let hres = (*ENUMERATOR.0).GetDefaultAudioEndpoint(data_flow, role, &mut device);
         if let Err(_err) = check_result(hres) {
             return None; // TODO: check specifically for `E_NOTFOUND`, and panic otherwise
         }
@@ -1188,11 +1941,19 @@ fn default_device(data_flow: EDataFlow) -> Option<Device> {
 }
 
 pub fn default_input_device() -> Option<Device> {
-    default_device(eCapture)
+    default_input_device_for_role(Role::Console)
+}
+
+pub fn default_input_device_for_role(role: Role) -> Option<Device> {
+    default_device(eCapture, role)
 }
 
 pub fn default_output_device() -> Option<Device> {
-    default_device(eRender)
+    default_output_device_for_role(Role::Console)
+}
+
+pub fn default_output_device_for_role(role: Role) -> Option<Device> {
+    default_device(eRender, role)
 }
 
 /// Get the audio clock used to produce `StreamInstant`s.
@@ -1228,7 +1989,7 @@ fn config_to_waveformatextensible(
     let format_tag = match sample_format {
         SampleFormat::I16 => mmreg::WAVE_FORMAT_PCM,
         SampleFormat::F32 => mmreg::WAVE_FORMAT_EXTENSIBLE,
-        SampleFormat::U16 => return None,
+        SampleFormat::U16 | SampleFormat::U8 => return None,
     };
     let channels = config.channels as WORD;
     let sample_rate = config.sample_rate.0 as DWORD;
@@ -1243,7 +2004,7 @@ fn config_to_waveformatextensible(
             let ex_size = mem::size_of::<mmreg::WAVEFORMATEX>();
             (extensible_size - ex_size) as WORD
         }
-        SampleFormat::U16 => return None,
+        SampleFormat::U16 | SampleFormat::U8 => return None,
     };
     let waveformatex = mmreg::WAVEFORMATEX {
         wFormatTag: format_tag,
@@ -1263,7 +2024,7 @@ fn config_to_waveformatextensible(
     let sub_format = match sample_format {
         SampleFormat::I16 => ksmedia::KSDATAFORMAT_SUBTYPE_PCM,
         SampleFormat::F32 => ksmedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
-        SampleFormat::U16 => return None,
+        SampleFormat::U16 | SampleFormat::U8 => return None,
     };
     let waveformatextensible = mmreg::WAVEFORMATEXTENSIBLE {
         Format: waveformatex,
@@ -1,8 +1,8 @@
 extern crate winapi;
 
 pub use self::device::{
-    default_input_device, default_output_device, Device, Devices, SupportedInputConfigs,
-    SupportedOutputConfigs,
+    default_input_device, default_input_device_for_role, default_output_device,
+    default_output_device_for_role, Device, Devices, SupportedInputConfigs, SupportedOutputConfigs,
 };
 pub use self::stream::Stream;
 use self::winapi::um::winnt::HRESULT;
@@ -10,16 +10,42 @@ use std::io::Error as IoError;
 use traits::HostTrait;
 use BackendSpecificError;
 use DevicesError;
+use Role;
 
 mod com;
 mod device;
 mod stream;
 
+/// How a WASAPI stream's audio thread learns that the device buffer needs servicing.
+///
+/// Set per device with `Device::set_service_mode` before building streams.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WasapiServiceMode {
+    /// WASAPI signals an event each time a period of audio has been consumed or captured
+    /// (`AUDCLNT_STREAMFLAGS_EVENTCALLBACK`). This gives the lowest latency with the fewest
+    /// wakeups and is the default.
+    Event,
+    /// The audio thread wakes up on a timer, a few times per buffer, and services whatever part
+    /// of the buffer is ready. This uses a larger buffer, and therefore has more latency, but
+    /// works around drivers that signal the event unreliably.
+    Polling,
+}
+
 /// The WASAPI host, the default windows host type.
 ///
 /// Note: If you use a WASAPI output device as an input device it will
 /// transparently enable loopback mode (see
 /// https://docs.microsoft.com/en-us/windows/win32/coreaudio/loopback-recording).
+///
+/// Shared mode streams whose config differs from the format of the system mixer, e.g. capturing
+/// at 16 kHz from a 48 kHz microphone, are converted by the audio engine of Windows
+/// (`AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM`), resampling with its own converter rather than cpal's.
+/// The supported configs only list the formats the mixer accepts without conversion, and typed
+/// builders still require the channel count of one of them.
+///
+/// COM is initialized in the multithreaded apartment on the threads that call into the host,
+/// unless the application already initialized it there, in either apartment. Threads of a
+/// single-threaded apartment, like the UI thread of most GUI frameworks, are left in it.
 #[derive(Debug)]
 pub struct Host;
 
@@ -27,6 +53,11 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl HostTrait for Host {
@@ -49,6 +80,14 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn default_input_device_for_role(&self, role: Role) -> Option<Self::Device> {
+        default_input_device_for_role(role)
+    }
+
+    fn default_output_device_for_role(&self, role: Role) -> Option<Self::Device> {
+        default_output_device_for_role(role)
+    }
 }
 
 #[inline]
@@ -1,6 +1,7 @@
 use super::check_result;
 use super::winapi::shared::basetsd::{UINT32, UINT64};
-use super::winapi::shared::minwindef::{BYTE, FALSE, WORD};
+use super::winapi::shared::minwindef::{BYTE, DWORD, FALSE, WORD};
+use super::winapi::shared::winerror;
 use super::winapi::um::audioclient::{self, AUDCLNT_E_DEVICE_INVALIDATED, AUDCLNT_S_BUFFER_EMPTY};
 use super::winapi::um::handleapi;
 use super::winapi::um::synchapi;
@@ -8,12 +9,14 @@ use super::winapi::um::winbase;
 use super::winapi::um::winnt;
 use crate::traits::StreamTrait;
 use crate::{
-    BackendSpecificError, Data, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleFormat, StreamError,
+    BackendSpecificError, BufferSize, Data, InputCallbackInfo, OutputCallbackInfo,
+    PauseStreamError, PlayStreamError, SampleFormat, StreamConfig, StreamError,
 };
 use std::mem;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 
 pub struct Stream {
@@ -31,6 +34,16 @@ pub struct Stream {
     // This event is signalled after a new entry is added to `commands`, so that the `run()`
     // method can be notified.
     pending_scheduled_event: winnt::HANDLE,
+
+    // The configuration of the stream along with the size of the buffer allocated by WASAPI.
+    config: StreamConfig,
+
+    // The number of frames processed by the data callback, shared with `StreamInner`.
+    frames: Arc<AtomicU64>,
+
+    // Whether `play` was called more recently than `pause`. The state of the audio thread itself
+    // only changes once it processes the command.
+    playing: AtomicBool,
 }
 
 struct RunContext {
@@ -42,6 +55,35 @@ struct RunContext {
     handles: Vec<winnt::HANDLE>,
 
     commands: Receiver<Command>,
+
+    // How long to wait for a handle to be signalled before servicing the stream anyway, in
+    // milliseconds. `INFINITE` unless the stream is polled rather than signalled by WASAPI.
+    timeout: DWORD,
+}
+
+impl RunContext {
+    fn new(
+        stream: StreamInner,
+        pending_scheduled_event: winnt::HANDLE,
+        commands: Receiver<Command>,
+    ) -> Self {
+        let mut handles = vec![pending_scheduled_event];
+        let timeout = if stream.event.is_null() {
+            // Polling: wake up four times per buffer so that it never runs dry.
+            let buffer_ms =
+                stream.max_frames_in_buffer as u64 * 1_000 / stream.config.sample_rate.0 as u64;
+            (buffer_ms / 4).max(1) as DWORD
+        } else {
+            handles.push(stream.event);
+            winbase::INFINITE
+        };
+        RunContext {
+            stream,
+            handles,
+            commands,
+            timeout,
+        }
+    }
 }
 
 // Once we start running the eventloop, the RunContext will not be moved.
@@ -66,7 +108,8 @@ pub struct StreamInner {
     pub audio_client: *mut audioclient::IAudioClient,
     pub audio_clock: *mut audioclient::IAudioClock,
     pub client_flow: AudioClientFlow,
-    // Event that is signalled by WASAPI whenever audio data must be written.
+    // Event that is signalled by WASAPI whenever audio data must be written. Null if the stream
+    // is serviced in `WasapiServiceMode::Polling`.
     pub event: winnt::HANDLE,
     // True if the stream is currently playing. False if paused.
     pub playing: bool,
@@ -75,11 +118,17 @@ pub struct StreamInner {
     // Number of bytes that each frame occupies.
     pub bytes_per_frame: WORD,
     // The configuration with which the stream was created.
-    pub config: crate::StreamConfig,
+    pub config: StreamConfig,
     // The sample format with which the stream was created.
     pub sample_format: SampleFormat,
+    // The number of frames read from or written to the buffer so far.
+    pub frames: Arc<AtomicU64>,
 }
 
+// Commands are sent to the audio thread over a channel, and the event handle that wakes it up may
+// be signalled from any thread.
+unsafe impl Send for Stream {}
+
 impl Stream {
     pub(crate) fn new_input<D, E>(
         stream_inner: StreamInner,
@@ -93,12 +142,14 @@ impl Stream {
         let pending_scheduled_event =
             unsafe { synchapi::CreateEventA(ptr::null_mut(), 0, 0, ptr::null()) };
         let (tx, rx) = channel();
-
-        let run_context = RunContext {
-            handles: vec![pending_scheduled_event, stream_inner.event],
-            stream: stream_inner,
-            commands: rx,
+        let config = StreamConfig {
+            buffer_size: BufferSize::Fixed(stream_inner.max_frames_in_buffer),
+            ..stream_inner.config.clone()
         };
+        let frames = stream_inner.frames.clone();
+        let playing = AtomicBool::new(stream_inner.playing);
+
+        let run_context = RunContext::new(stream_inner, pending_scheduled_event, rx);
 
         let thread = thread::Builder::new()
             .name("cpal_wasapi_in".to_owned())
@@ -109,6 +160,9 @@ impl Stream {
             thread: Some(thread),
             commands: tx,
             pending_scheduled_event,
+            config,
+            frames,
+            playing,
         }
     }
 
@@ -124,12 +178,14 @@ impl Stream {
         let pending_scheduled_event =
             unsafe { synchapi::CreateEventA(ptr::null_mut(), 0, 0, ptr::null()) };
         let (tx, rx) = channel();
-
-        let run_context = RunContext {
-            handles: vec![pending_scheduled_event, stream_inner.event],
-            stream: stream_inner,
-            commands: rx,
+        let config = StreamConfig {
+            buffer_size: BufferSize::Fixed(stream_inner.max_frames_in_buffer),
+            ..stream_inner.config.clone()
         };
+        let frames = stream_inner.frames.clone();
+        let playing = AtomicBool::new(stream_inner.playing);
+
+        let run_context = RunContext::new(stream_inner, pending_scheduled_event, rx);
 
         let thread = thread::Builder::new()
             .name("cpal_wasapi_out".to_owned())
@@ -140,6 +196,9 @@ impl Stream {
             thread: Some(thread),
             commands: tx,
             pending_scheduled_event,
+            config,
+            frames,
+            playing,
         }
     }
 
@@ -154,6 +213,17 @@ impl Stream {
     }
 }
 
+impl Stream {
+    /// The duration of the buffer allocated by WASAPI, i.e. the latency the stream actually got,
+    /// e.g. after `Device::set_buffer_duration`.
+    pub fn buffer_duration(&self) -> std::time::Duration {
+        match self.config.buffer_size {
+            BufferSize::Fixed(frames) => frames_to_duration(frames, self.config.sample_rate),
+            BufferSize::Default => std::time::Duration::from_secs(0),
+        }
+    }
+}
+
 impl Drop for Stream {
     #[inline]
     fn drop(&mut self) {
@@ -168,12 +238,23 @@ impl Drop for Stream {
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), PlayStreamError> {
         self.push_command(Command::PlayStream);
+        self.playing.store(true, Ordering::SeqCst);
         Ok(())
     }
     fn pause(&self) -> Result<(), PauseStreamError> {
         self.push_command(Command::PauseStream);
+        self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+    fn config(&self) -> StreamConfig {
+        self.config.clone()
+    }
+    fn frame_position(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+    fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
 }
 
 impl Drop for AudioClientFlow {
@@ -193,7 +274,9 @@ impl Drop for StreamInner {
         unsafe {
             (*self.audio_client).Release();
             (*self.audio_clock).Release();
-            handleapi::CloseHandle(self.event);
+            if !self.event.is_null() {
+                handleapi::CloseHandle(self.event);
+            }
         }
     }
 }
@@ -231,25 +314,31 @@ fn process_commands(run_context: &mut RunContext) -> Result<bool, StreamError> {
 
     Ok(true)
 }
-// Wait for any of the given handles to be signalled.
+// Wait for any of the given handles to be signalled, or for `timeout` milliseconds to elapse.
 //
-// Returns the index of the `handle` that was signalled, or an `Err` if
-// `WaitForMultipleObjectsEx` fails.
+// Returns the index of the `handle` that was signalled, `handles.len()` on timeout, or an `Err`
+// if `WaitForMultipleObjectsEx` fails.
 //
 // This is called when the `run` thread is ready to wait for the next event. The
 // next event might be some command submitted by the user (the first handle) or
 // might indicate that one of the streams is ready to deliver or receive audio.
-fn wait_for_handle_signal(handles: &[winnt::HANDLE]) -> Result<usize, BackendSpecificError> {
+fn wait_for_handle_signal(
+    handles: &[winnt::HANDLE],
+    timeout: DWORD,
+) -> Result<usize, BackendSpecificError> {
     debug_assert!(handles.len() <= winnt::MAXIMUM_WAIT_OBJECTS as usize);
     let result = unsafe {
         synchapi::WaitForMultipleObjectsEx(
             handles.len() as u32,
             handles.as_ptr(),
-            FALSE,             // Don't wait for all, just wait for the first
-            winbase::INFINITE, // TODO: allow setting a timeout
-            FALSE,             // irrelevant parameter here
+            FALSE, // Don't wait for all, just wait for the first
+            timeout,
+            FALSE, // irrelevant parameter here
         )
     };
+    if result == winerror::WAIT_TIMEOUT {
+        return Ok(handles.len());
+    }
     if result == winbase::WAIT_FAILED {
         let err = unsafe { winapi::um::errhandlingapi::GetLastError() };
         let description = format!("`WaitForMultipleObjectsEx failed: {}", err);
@@ -358,7 +447,7 @@ fn process_commands_and_await_signal(
     };
 
     // Wait for any of the handles to be signalled.
-    let handle_idx = match wait_for_handle_signal(&run_context.handles) {
+    let handle_idx = match wait_for_handle_signal(&run_context.handles, run_context.timeout) {
         Ok(idx) => idx,
         Err(err) => {
             error_callback(err.into());
@@ -367,10 +456,15 @@ fn process_commands_and_await_signal(
     };
 
     // If `handle_idx` is 0, then it's `pending_scheduled_event` that was signalled in
-    // order for us to pick up the pending commands. Otherwise, a stream needs data.
+    // order for us to pick up the pending commands. Otherwise, a stream needs data or, when
+    // polling, the timeout elapsed.
     if handle_idx == 0 {
         return Some(ControlFlow::Continue);
     }
+    // A paused stream has no data to process, even though the poll timeout keeps elapsing.
+    if !run_context.stream.playing {
+        return Some(ControlFlow::Continue);
+    }
 
     None
 }
@@ -428,7 +522,11 @@ fn process_input(
                     return ControlFlow::Break;
                 }
             };
-            let info = InputCallbackInfo { timestamp };
+            let info = InputCallbackInfo::new(
+                timestamp,
+                frames_available as usize,
+                stream.config.sample_rate,
+            );
             data_callback(&data, &info);
 
             // Release the buffer.
@@ -437,6 +535,9 @@ fn process_input(
                 error_callback(err);
                 return ControlFlow::Break;
             }
+            stream
+                .frames
+                .fetch_add(frames_available as u64, Ordering::Relaxed);
         }
     }
 }
@@ -481,7 +582,7 @@ fn process_output(
                 return ControlFlow::Break;
             }
         };
-        let info = OutputCallbackInfo { timestamp };
+        let info = OutputCallbackInfo::new(timestamp, frames_available as usize, sample_rate);
         data_callback(&mut data, &info);
 
         let hresult = (*render_client).ReleaseBuffer(frames_available as u32, 0);
@@ -489,6 +590,9 @@ fn process_output(
             error_callback(err);
             return ControlFlow::Break;
         }
+        stream
+            .frames
+            .fetch_add(frames_available as u64, Ordering::Relaxed);
     }
 
     ControlFlow::Continue
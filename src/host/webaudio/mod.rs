@@ -8,18 +8,20 @@ use self::wasm_bindgen::JsCast;
 use self::web_sys::{AudioContext, AudioContextOptions};
 use crate::{
     BackendSpecificError, BufferSize, BuildStreamError, Data, DefaultStreamConfigError,
-    DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError, SupportedBufferSize,
-    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigsError,
+    DeviceNameError, DevicesError, FrameCount, InputCallbackInfo, OutputCallbackInfo,
+    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
+    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    SupportedStreamConfigsError,
 };
 use std::ops::DerefMut;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use traits::{DeviceTrait, HostTrait, StreamTrait};
 
 /// Content is false if the iterator is empty.
 pub struct Devices(bool);
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
 pub struct Host;
@@ -29,6 +31,9 @@ pub struct Stream {
     on_ended_closures: Vec<Arc<RwLock<Option<Closure<dyn FnMut()>>>>>,
     config: StreamConfig,
     buffer_size_frames: usize,
+    frames: Arc<AtomicU64>,
+    // Whether `play` was called more recently than `pause`.
+    playing: AtomicBool,
 }
 
 pub type SupportedInputConfigs = ::std::vec::IntoIter<SupportedStreamConfigRange>;
@@ -48,6 +53,11 @@ impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
         Ok(Host)
     }
+
+    // The version of the library implementing this host, which cannot be queried.
+    pub(crate) fn library_version() -> Option<String> {
+        None
+    }
 }
 
 impl HostTrait for Host {
@@ -211,6 +221,7 @@ impl DeviceTrait for Device {
         };
         let buffer_size_samples = buffer_size_frames * n_channels;
         let buffer_time_step_secs = buffer_time_step_secs(buffer_size_frames, config.sample_rate);
+        let sample_rate = config.sample_rate;
 
         let data_callback = Arc::new(Mutex::new(Box::new(data_callback)));
 
@@ -233,6 +244,9 @@ impl DeviceTrait for Device {
         // A cursor keeping track of the current time at which new frames should be scheduled.
         let time = Arc::new(RwLock::new(0f64));
 
+        // The number of frames produced by the data callback.
+        let frames = Arc::new(AtomicU64::new(0));
+
         // Create a set of closures / callbacks which will continuously fetch and schedule sample
         // playback. Starting with two workers, e.g. a front and back buffer so that audio frames
         // can be fetched in the background.
@@ -240,6 +254,7 @@ impl DeviceTrait for Device {
             let data_callback_handle = data_callback.clone();
             let ctx_handle = ctx.clone();
             let time_handle = time.clone();
+            let frames_handle = frames.clone();
 
             // A set of temporary buffers to be used for intermediate sample transformation steps.
             let mut temporary_buffer = vec![0f32; buffer_size_samples];
@@ -292,9 +307,11 @@ impl DeviceTrait for Device {
                         let callback = crate::StreamInstant::from_secs_f64(now);
                         let playback = crate::StreamInstant::from_secs_f64(time_at_start_of_buffer);
                         let timestamp = crate::OutputStreamTimestamp { callback, playback };
-                        let info = OutputCallbackInfo { timestamp };
+                        let info =
+                            OutputCallbackInfo::new(timestamp, buffer_size_frames, sample_rate);
                         (data_callback.deref_mut())(&mut data, &info);
                     }
+                    frames_handle.fetch_add(buffer_size_frames as u64, Ordering::Relaxed);
 
                     // Deinterleave the sample data and copy into the audio context buffer.
                     // We do not reference the audio context buffer directly e.g. getChannelData.
@@ -346,6 +363,8 @@ impl DeviceTrait for Device {
             on_ended_closures,
             config: config.clone(),
             buffer_size_frames,
+            frames,
+            playing: AtomicBool::new(false),
         })
     }
 }
@@ -376,6 +395,7 @@ impl StreamTrait for Stream {
                         .unwrap();
                     offset_ms += time_step_ms;
                 }
+                self.playing.store(true, Ordering::SeqCst);
                 Ok(())
             }
             Err(err) => {
@@ -388,7 +408,10 @@ impl StreamTrait for Stream {
 
     fn pause(&self) -> Result<(), PauseStreamError> {
         match self.ctx.suspend() {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.playing.store(false, Ordering::SeqCst);
+                Ok(())
+            }
             Err(err) => {
                 let description = format!("{:?}", err);
                 let err = BackendSpecificError { description };
@@ -396,6 +419,21 @@ impl StreamTrait for Stream {
             }
         }
     }
+
+    fn config(&self) -> StreamConfig {
+        StreamConfig {
+            buffer_size: BufferSize::Fixed(self.buffer_size_frames as FrameCount),
+            ..self.config.clone()
+        }
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
 }
 
 impl Drop for Stream {
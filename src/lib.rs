@@ -100,6 +100,7 @@
 //!     SampleFormat::F32 => device.build_output_stream(&config, write_silence::<f32>, err_fn),
 //!     SampleFormat::I16 => device.build_output_stream(&config, write_silence::<i16>, err_fn),
 //!     SampleFormat::U16 => device.build_output_stream(&config, write_silence::<u16>, err_fn),
+//!     SampleFormat::U8 => device.build_output_stream(&config, write_silence::<u8>, err_fn),
 //! }.unwrap();
 //!
 //! fn write_silence<T: Sample>(data: &mut [T], _: &cpal::OutputCallbackInfo) {
@@ -140,33 +141,141 @@
 //! # let stream = device.build_output_stream_raw(&config, sample_format, data_fn, err_fn).unwrap();
 //! stream.pause().unwrap();
 //! ```
+//!
+//! ## Thread safety
+//!
+//! `Host`, `Device` and `HostId` are `Send` and `Sync` on all platforms, so devices may be
+//! enumerated on one thread and used on another. Enumerating devices and querying their configs
+//! can take a while on some systems, and may run on a worker thread to keep a UI responsive, as
+//! in the `enumerate_async` example. Hosts set up whatever the thread needs, like COM for WASAPI.
+//!
+//! The dynamically dispatched `Stream` is neither `Send` nor `Sync` as some hosts, e.g. AAudio on
+//! Android, do not allow their streams to be used from other threads. Where the host allows it,
+//! the host-specific stream types in the `platform` module can be moved between threads, e.g. to
+//! build a stream on one thread and pause it from another:
+//!
+//! - `AlsaStream` is `Send` and `Sync`.
+//! - `JackStream` and `WasapiStream` are `Send`.
+//!
+//! The data and error callbacks must be `Send` as they are called on a thread owned by the host.
+//!
+//! ## Releasing audio resources
+//!
+//! CPAL keeps no global state that would need tearing down, which is why there is no `shutdown`
+//! function: every OS resource belongs to a `Host`, `Device` or `Stream` and is released when
+//! that value is dropped. To stop all audio before exiting, drop the streams, keeping in mind
+//! that `std::process::exit` does not run destructors.
+//!
+//! - Dropping a stream stops it. Hosts whose streams run on a thread of their own (ALSA, WASAPI
+//!   and PulseAudio) join that thread before `drop` returns, so the data callback is never
+//!   invoked afterwards. JACK streams deactivate and close their JACK client.
+//! - Streams own the OS handles they were opened with, so hosts, devices and streams can be
+//!   dropped in any order without leaving a device locked. The handles that an ALSA device opens
+//!   to query its configs are closed along with the device.
+//! - On Windows, COM is initialized on each thread that uses WASAPI, and uninitialized when that
+//!   thread exits.
 
 #![recursion_limit = "512"]
 
-#[cfg(target_os = "windows")]
+#[cfg(all(
+    target_os = "windows",
+    feature = "wasapi",
+    not(feature = "null-backend")
+))]
 #[macro_use]
 extern crate lazy_static;
 // Extern crate declarations with `#[macro_use]` must unfortunately be at crate root.
-#[cfg(target_os = "emscripten")]
+#[cfg(all(target_os = "emscripten", not(feature = "null-backend")))]
 #[macro_use]
 extern crate stdweb;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(feature = "log")]
+extern crate log;
 extern crate thiserror;
 
+// Records a backend call and its outcome, e.g. the OS error code, for diagnosing devices and
+// streams that misbehave. With the `log` feature, this logs at the trace level with the `cpal`
+// target. Without it, the arguments are only type-checked and nothing is evaluated. Hosts without
+// traced calls leave it unused.
+#[cfg(feature = "log")]
+#[allow(unused_macros)]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        ::log::trace!(target: "cpal", $($arg)+)
+    };
+}
+#[cfg(not(feature = "log"))]
+#[allow(unused_macros)]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+}
+
+#[cfg(feature = "async")]
+pub use async_input::AsyncInputStream;
+pub use callback_control::{CallbackControl, ControlledStream};
+pub use channel_map::{ChannelMap, RoutedStream};
+pub use clip::{ClipMode, ClippedStream};
+pub use dither::Dither;
+pub use drift::DriftCorrectedStream;
+pub use duplex::StreamPair;
 pub use error::*;
+pub use frames::{Frames, FramesMut};
+pub use meter::{ChannelLevels, MeteredStream};
+pub use monitor::{monitor, Monitor};
 pub use platform::{
-    available_hosts, default_host, host_from_id, Device, Devices, Host, HostId, Stream,
-    SupportedInputConfigs, SupportedOutputConfigs, ALL_HOSTS,
+    available_hosts, backend_info, default_host, enumerate_with_defaults, host_from_id,
+    BackendInfo, Device, Devices, Host, HostId, Stream, SupportedInputConfigs,
+    SupportedOutputConfigs, ALL_HOSTS,
 };
+#[cfg(feature = "wav")]
+pub use playback::play_file;
+pub use playback::{play_samples, PlaybackHandle};
+pub use record::{record_to_wav, WavRecording};
 pub use samples_formats::{Sample, SampleFormat};
+pub use stats::{InstrumentedStream, StreamStats};
 use std::convert::TryInto;
+use std::fmt;
 use std::ops::{Div, Mul};
+use std::str::FromStr;
 use std::time::Duration;
-
+pub use stream_event::{NotifyingStream, StreamEvent};
+use traits::DeviceTrait;
+pub use underrun::UnderrunPolicy;
+
+#[cfg(feature = "async")]
+mod async_input;
+pub mod blocking;
+mod blocks;
+mod callback_control;
+mod channel_map;
+mod clip;
+mod dither;
+mod drift;
+mod duplex;
 mod error;
+mod frames;
 mod host;
+mod meter;
+mod monitor;
+mod planar;
 pub mod platform;
+mod playback;
+mod realtime;
+mod record;
+pub mod ring_buffer;
 mod samples_formats;
+mod stats;
+mod stream_event;
+pub mod test;
 pub mod traits;
+mod underrun;
+mod unwind;
+mod wav;
 
 /// A host's device iterator yielding only *input* devices.
 pub type InputDevices<I> = std::iter::Filter<I, fn(&<I as Iterator>::Item) -> bool>;
@@ -177,8 +286,82 @@ pub type OutputDevices<I> = std::iter::Filter<I, fn(&<I as Iterator>::Item) -> b
 /// Number of channels.
 pub type ChannelCount = u16;
 
+/// The speaker positions of the channels of a device, retrieved via
+/// `DeviceTrait::default_input_channel_layout` or `DeviceTrait::default_output_channel_layout`.
+///
+/// This is a mask of the speaker bits below, in the order of the channels, with the same values
+/// as the `dwChannelMask` of a Windows `WAVEFORMATEXTENSIBLE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelLayout(pub u32);
+
+impl ChannelLayout {
+    pub const FRONT_LEFT: u32 = 0x1;
+    pub const FRONT_RIGHT: u32 = 0x2;
+    pub const FRONT_CENTER: u32 = 0x4;
+    pub const LOW_FREQUENCY: u32 = 0x8;
+    pub const BACK_LEFT: u32 = 0x10;
+    pub const BACK_RIGHT: u32 = 0x20;
+    pub const SIDE_LEFT: u32 = 0x200;
+    pub const SIDE_RIGHT: u32 = 0x400;
+
+    pub const MONO: Self = ChannelLayout(Self::FRONT_CENTER);
+    pub const STEREO: Self = ChannelLayout(Self::FRONT_LEFT | Self::FRONT_RIGHT);
+    pub const SURROUND_5_1: Self = ChannelLayout(
+        Self::FRONT_LEFT
+            | Self::FRONT_RIGHT
+            | Self::FRONT_CENTER
+            | Self::LOW_FREQUENCY
+            | Self::BACK_LEFT
+            | Self::BACK_RIGHT,
+    );
+    pub const SURROUND_7_1: Self =
+        ChannelLayout(Self::SURROUND_5_1.0 | Self::SIDE_LEFT | Self::SIDE_RIGHT);
+
+    /// The usual layout of the given number of channels, if there is one.
+    pub fn for_channels(channels: ChannelCount) -> Option<Self> {
+        match channels {
+            1 => Some(Self::MONO),
+            2 => Some(Self::STEREO),
+            6 => Some(Self::SURROUND_5_1),
+            8 => Some(Self::SURROUND_7_1),
+            _ => None,
+        }
+    }
+
+    /// The number of channels with a speaker position.
+    pub fn channels(&self) -> ChannelCount {
+        self.0.count_ones() as ChannelCount
+    }
+
+    /// A name for the layout to show to users, like `"Stereo"`, if it is a common one.
+    pub fn name(&self) -> Option<&'static str> {
+        match *self {
+            Self::MONO => Some("Mono"),
+            Self::STEREO => Some("Stereo"),
+            Self::SURROUND_5_1 => Some("5.1 Surround"),
+            Self::SURROUND_7_1 => Some("7.1 Surround"),
+            _ => None,
+        }
+    }
+}
+
+/// The purpose for which a default device is requested.
+///
+/// Some hosts, notably WASAPI, keep a separate default device per role. For example, a VoIP
+/// application should prefer the `Communications` device, which is often a headset. Hosts
+/// without the concept return the same default device for every role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Games, system notification sounds and voice commands.
+    Console,
+    /// Music, movies, narration and live music recording.
+    Multimedia,
+    /// Voice communications, e.g. talking to another person.
+    Communications,
+}
+
 /// The number of samples processed per second for a single channel of audio.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SampleRate(pub u32);
 
 impl<T> Mul<T> for SampleRate
@@ -201,6 +384,58 @@ where
     }
 }
 
+impl SampleRate {
+    /// The sample rate of CDs.
+    pub const HZ_44100: SampleRate = SampleRate(44_100);
+    /// The usual sample rate of video and professional audio.
+    pub const HZ_48000: SampleRate = SampleRate(48_000);
+    /// The usual high resolution sample rate.
+    pub const HZ_96000: SampleRate = SampleRate(96_000);
+
+    /// The sample rate of `khz` kHz, rounded to the nearest Hz, e.g. `44_100` for `44.1`.
+    ///
+    /// Negative rates saturate to zero, which is not a valid sample rate.
+    pub fn from_khz(khz: f32) -> Self {
+        SampleRate((khz as f64 * 1000.0).round() as u32)
+    }
+}
+
+/// Formats the sample rate in Hz, e.g. `48000 Hz`, which `FromStr` parses back.
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} Hz", self.0)
+    }
+}
+
+/// Parses a positive sample rate in Hz, like `48000` or `48000 Hz`, or in kHz, like `48kHz` or
+/// `44.1 kHz`, ignoring case and surrounding whitespace. Rates in kHz are rounded to the nearest
+/// Hz.
+impl FromStr for SampleRate {
+    type Err = ParseSampleRateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseSampleRateError {
+            input: s.to_owned(),
+        };
+        let lower = s.trim().to_ascii_lowercase();
+        let hz = if let Some(khz) = lower.strip_suffix("khz") {
+            let khz: f64 = khz.trim_end().parse().map_err(|_| error())?;
+            let hz = (khz * 1000.0).round();
+            if !(hz >= 1.0 && hz <= u32::MAX as f64) {
+                return Err(error());
+            }
+            hz as u32
+        } else {
+            let hz = lower.strip_suffix("hz").unwrap_or(&lower).trim_end();
+            hz.parse().map_err(|_| error())?
+        };
+        if hz == 0 {
+            return Err(error());
+        }
+        Ok(SampleRate(hz))
+    }
+}
+
 /// The desired number of frames for the hardware buffer.
 pub type FrameCount = u32;
 
@@ -211,7 +446,7 @@ pub type FrameCount = u32;
 /// large, leading to latency issues. If low latency is desired, Fixed(BufferSize)
 /// should be used in accordance with the SupportedBufferSize range produced by
 /// the SupportedStreamConfig API.  
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum BufferSize {
     Default,
     Fixed(FrameCount),
@@ -220,7 +455,7 @@ pub enum BufferSize {
 /// The set of parameters used to describe how to open a stream.
 ///
 /// The sample format is omitted in favour of using a sample type.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct StreamConfig {
     pub channels: ChannelCount,
     pub sample_rate: SampleRate,
@@ -264,6 +499,168 @@ pub struct SupportedStreamConfig {
     sample_format: SampleFormat,
 }
 
+/// An owned snapshot of the capabilities of a device, retrieved via `Device::capabilities`.
+///
+/// Querying a device asks the OS every time and fails once the device is gone. A snapshot is
+/// queried once and can then be inspected any number of times, or sent to another thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    pub(crate) name: String,
+    pub(crate) supported_input_configs: Vec<SupportedStreamConfigRange>,
+    pub(crate) supported_output_configs: Vec<SupportedStreamConfigRange>,
+    pub(crate) default_input_config: Option<SupportedStreamConfig>,
+    pub(crate) default_output_config: Option<SupportedStreamConfig>,
+}
+
+/// A device along with its capabilities and whether it is a default device, as listed by
+/// `HostTrait::enumerate_with_defaults`, e.g. to populate a device picker.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo<D> {
+    device: D,
+    capabilities: DeviceCapabilities,
+    direction: Direction,
+    is_default_input: bool,
+    is_default_output: bool,
+}
+
+/// Requirements for selecting the devices able to open a particular kind of stream, used with
+/// `HostTrait::input_devices_supporting` and `HostTrait::output_devices_supporting`.
+///
+/// Fields left as `None` match anything. A device matches if any single one of its supported
+/// configurations satisfies all of the requirements.
+///
+/// ```no_run
+/// use cpal::traits::HostTrait;
+/// use cpal::{DeviceQuery, SampleFormat};
+///
+/// let query = DeviceQuery {
+///     min_channels: Some(8),
+///     sample_format: Some(SampleFormat::F32),
+///     ..Default::default()
+/// };
+/// for device in cpal::default_host().output_devices_supporting(&query).unwrap() {
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceQuery {
+    /// The minimum number of channels.
+    pub min_channels: Option<ChannelCount>,
+    /// A sample rate that must lie within the supported range.
+    pub sample_rate: Option<SampleRate>,
+    /// The sample format.
+    pub sample_format: Option<SampleFormat>,
+}
+
+/// The directions in which a device supports streams, as returned by `DeviceTrait::direction`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The device only supports input streams.
+    Input,
+    /// The device only supports output streams.
+    Output,
+    /// The device supports both input and output streams.
+    Duplex,
+}
+
+/// The physical kind of a device, as returned by `DeviceTrait::form_factor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FormFactor {
+    /// Loudspeakers, built into the computer or external.
+    Speakers,
+    /// Headphones, without a microphone.
+    Headphones,
+    /// A headset or handset, combining headphones and a microphone.
+    Headset,
+    /// A microphone, built into the computer or external.
+    Microphone,
+    /// A line level connector, e.g. to an amplifier or mixing desk.
+    LineLevel,
+    /// A display with audio, connected through HDMI or DisplayPort.
+    Hdmi,
+    /// A digital S/PDIF connector, optical or coaxial.
+    Spdif,
+}
+
+/// Options for building a stream with `DeviceTrait::build_input_stream_with_options` or
+/// `DeviceTrait::build_output_stream_with_options`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// Promote the thread running the data callback to realtime scheduling, so that other
+    /// processes under load do not cause underruns or overruns. (default is `false`)
+    ///
+    /// - On Linux, dragonfly and freebsd, the thread is switched to `SCHED_FIFO`. This requires
+    ///   the `CAP_SYS_NICE` capability or a sufficient `RLIMIT_RTPRIO` limit, usually granted to
+    ///   the `audio` group in `/etc/security/limits.conf`. Threads that already run with a
+    ///   realtime policy keep their priority.
+    /// - On Windows, the thread is registered with MMCSS as a "Pro Audio" task, which requires no
+    ///   privileges.
+    /// - The threads of JACK and ASIO belong to the JACK server and the ASIO driver, and are left
+    ///   alone. CoreAudio already runs its data callbacks on realtime threads, and other hosts
+    ///   have no thread to promote.
+    ///
+    /// Failing to promote the thread is reported to the error callback, and the stream keeps
+    /// running at normal priority.
+    pub realtime: bool,
+    /// Restrict the thread running the data callback to the CPUs with these indices, e.g. to
+    /// dedicate a core to audio and reduce jitter and cache contention. (default is `None`)
+    ///
+    /// This only applies to the threads that cpal owns, like the poll thread of ALSA and the
+    /// thread servicing WASAPI streams, on Linux and Windows. The threads of CoreAudio, JACK and
+    /// ASIO belong to the OS, the JACK server or the ASIO driver and are left alone, and other
+    /// platforms are not supported.
+    ///
+    /// Building the stream fails with `BuildStreamError::InvalidArgument` for an empty list.
+    /// Failing to set the affinity, e.g. for CPUs that do not exist, is reported to the error
+    /// callback, and the stream keeps running on any CPU.
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// Invoke the data callback with buffers of exactly this many frames, whatever the period of
+    /// the device. (default is `None`, passing the buffers of the host through)
+    ///
+    /// Input is held back until a buffer is complete, and output until the host asks for it, so
+    /// no samples are dropped but up to one buffer of latency is added. Each `InputCallbackInfo`
+    /// or `OutputCallbackInfo` is that of the host buffer during which the data callback is
+    /// invoked, so its timestamps are only approximate. Building the stream fails with
+    /// `BuildStreamError::InvalidArgument` for a size of zero.
+    pub callback_buffer_size: Option<FrameCount>,
+    /// Split the buffer of the device into this many periods, trading latency for resilience to
+    /// scheduling jitter. (default is `None`, letting the host choose)
+    ///
+    /// The device is serviced once per period, so fewer periods leave less audio queued while more
+    /// periods survive longer delays of the audio thread. Each host maps it differently:
+    ///
+    /// - ALSA: the `periods` hardware parameter. With `BufferSize::Fixed`, the buffer of that size
+    ///   is split into this many periods, and otherwise each period lasts about 25 milliseconds.
+    /// - WASAPI: a buffer of this many default device periods, of usually 10 milliseconds, in
+    ///   shared mode. Buffers longer than two seconds are not supported.
+    ///
+    /// Building the stream fails with `BuildStreamError::InvalidArgument` for zero periods and
+    /// with `BuildStreamError::PeriodCountNotSupported` if the device does not support the count,
+    /// or on other hosts.
+    pub periods: Option<u32>,
+}
+
+/// The parts of a stream configuration that a `SupportedStreamConfigRange` does not support,
+/// returned by `SupportedStreamConfigRange::why_unsupported`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConfigMismatch {
+    /// The channel count differs from that of the range.
+    pub channels: bool,
+    /// The sample rate lies outside `min_sample_rate..=max_sample_rate`.
+    pub sample_rate: bool,
+    /// The buffer size is fixed to a size outside the supported range.
+    pub buffer_size: bool,
+    /// The sample format differs from that of the range.
+    pub sample_format: bool,
+}
+
+/// A host's device iterator yielding only the devices matching a `DeviceQuery`.
+pub struct QueriedDevices<I> {
+    devices: I,
+    query: DeviceQuery,
+    input: bool,
+}
+
 /// A buffer of dynamically typed audio data, passed to raw stream callbacks.
 ///
 /// Raw input stream callbacks receive `&Data`, while raw output stream callbacks expect `&mut
@@ -325,12 +722,36 @@ pub struct OutputStreamTimestamp {
 #[derive(Debug, Clone, PartialEq)]
 pub struct InputCallbackInfo {
     timestamp: InputStreamTimestamp,
+    budget: Duration,
 }
 
 /// Information relevant to a single call to the user's output stream data callback.
 #[derive(Debug, Clone, PartialEq)]
 pub struct OutputCallbackInfo {
     timestamp: OutputStreamTimestamp,
+    budget: Duration,
+}
+
+impl StreamConfig {
+    // Rejects the configurations that no device supports, whatever the host.
+    pub(crate) fn validate(&self) -> Result<(), BuildStreamError> {
+        if self.channels == 0 || self.sample_rate.0 == 0 {
+            return Err(BuildStreamError::InvalidStreamConfig);
+        }
+        Ok(())
+    }
+
+    /// The duration of `frames` frames at the sample rate of the configuration.
+    ///
+    /// The result is rounded down to the nanosecond, and zero if the sample rate is zero.
+    pub fn duration_of_frames(&self, frames: u64) -> Duration {
+        frames_duration(frames, self.sample_rate)
+    }
+
+    /// The number of whole frames that fit in `duration` at the sample rate of the configuration.
+    pub fn frames_in_duration(&self, duration: Duration) -> u64 {
+        (duration.as_nanos() * self.sample_rate.0 as u128 / 1_000_000_000) as u64
+    }
 }
 
 impl SupportedStreamConfig {
@@ -357,6 +778,33 @@ impl SupportedStreamConfig {
             buffer_size: BufferSize::Default,
         }
     }
+
+    /// The size in bytes of one frame, i.e. one sample for each channel.
+    pub fn bytes_per_frame(&self) -> usize {
+        self.channels as usize * self.sample_format.sample_size()
+    }
+
+    /// The size in bytes of `frames` frames.
+    pub fn frames_to_bytes(&self, frames: usize) -> usize {
+        frames * self.bytes_per_frame()
+    }
+
+    /// The number of whole frames in `bytes` bytes.
+    ///
+    /// Returns zero if the configuration has no channels.
+    pub fn bytes_to_frames(&self, bytes: usize) -> usize {
+        bytes.checked_div(self.bytes_per_frame()).unwrap_or(0)
+    }
+
+    /// The duration of `frames` frames. See `StreamConfig::duration_of_frames`.
+    pub fn duration_of_frames(&self, frames: u64) -> Duration {
+        frames_duration(frames, self.sample_rate)
+    }
+
+    /// The number of whole frames that fit in `duration`.
+    pub fn frames_in_duration(&self, duration: Duration) -> u64 {
+        self.config().frames_in_duration(duration)
+    }
 }
 
 impl StreamInstant {
@@ -403,7 +851,7 @@ impl StreamInstant {
     fn from_nanos(nanos: i64) -> Self {
         let secs = nanos / 1_000_000_000;
         let subsec_nanos = nanos - secs * 1_000_000_000;
-        Self::new(secs as i64, subsec_nanos as u32)
+        Self::new(secs, subsec_nanos as u32)
     }
 
     #[allow(dead_code)]
@@ -431,17 +879,121 @@ impl StreamInstant {
 }
 
 impl InputCallbackInfo {
+    // Internal constructor for host implementations to use, given the number of frames passed to
+    // the callback.
+    pub(crate) fn new(
+        timestamp: InputStreamTimestamp,
+        frames: usize,
+        sample_rate: SampleRate,
+    ) -> Self {
+        InputCallbackInfo {
+            timestamp,
+            budget: frames_duration(frames as u64, sample_rate),
+        }
+    }
+
     /// The timestamp associated with the call to an input stream's data callback.
     pub fn timestamp(&self) -> InputStreamTimestamp {
         self.timestamp
     }
+
+    /// The time available to the data callback, i.e. the duration of the audio in the buffer.
+    ///
+    /// The next buffer arrives about this long after the current one, so a callback that takes
+    /// longer than its budget will eventually cause samples to be dropped.
+    ///
+    /// In debug builds with the `log` feature, setting the `CPAL_WARN_ON_OVERRUN` environment
+    /// variable makes streams built with the typed `DeviceTrait` methods trace the first time
+    /// their data callback exceeds its budget.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
 }
 
 impl OutputCallbackInfo {
+    // Internal constructor for host implementations to use, given the number of frames passed to
+    // the callback.
+    pub(crate) fn new(
+        timestamp: OutputStreamTimestamp,
+        frames: usize,
+        sample_rate: SampleRate,
+    ) -> Self {
+        OutputCallbackInfo {
+            timestamp,
+            budget: frames_duration(frames as u64, sample_rate),
+        }
+    }
+
     /// The timestamp associated with the call to an output stream's data callback.
     pub fn timestamp(&self) -> OutputStreamTimestamp {
         self.timestamp
     }
+
+    /// The time available to the data callback, i.e. the duration of the audio in the buffer.
+    ///
+    /// The device plays the buffer in about this long, so a callback that takes longer than its
+    /// budget will eventually cause the device to run out of samples and glitch.
+    ///
+    /// In debug builds with the `log` feature, setting the `CPAL_WARN_ON_OVERRUN` environment
+    /// variable makes streams built with the typed `DeviceTrait` methods trace the first time
+    /// their data callback exceeds its budget.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+// The duration of the given number of frames at the given sample rate, rounded down to the
+// nanosecond.
+fn frames_duration(frames: u64, sample_rate: SampleRate) -> Duration {
+    match sample_rate.0 {
+        0 => Duration::from_secs(0),
+        rate => {
+            let nanos = frames as u128 * 1_000_000_000 / rate as u128;
+            Duration::new(
+                (nanos / 1_000_000_000) as u64,
+                (nanos % 1_000_000_000) as u32,
+            )
+        }
+    }
+}
+
+// Traces when a data callback takes longer than its budget, once per stream.
+//
+// Only enabled in debug builds with the `log` feature when the `CPAL_WARN_ON_OVERRUN` environment
+// variable is set, as measuring the time spent in the callback is not free.
+pub(crate) struct OverrunCheck {
+    enabled: bool,
+}
+
+impl OverrunCheck {
+    pub(crate) fn new() -> Self {
+        // `Instant` is not available on `wasm32-unknown-unknown`.
+        let supported =
+            cfg!(debug_assertions) && cfg!(feature = "log") && !cfg!(target_arch = "wasm32");
+        OverrunCheck {
+            enabled: supported && std::env::var_os("CPAL_WARN_ON_OVERRUN").is_some(),
+        }
+    }
+
+    pub(crate) fn run<F>(&mut self, budget: Duration, callback: F)
+    where
+        F: FnOnce(),
+    {
+        if !self.enabled {
+            return callback();
+        }
+        let start = std::time::Instant::now();
+        callback();
+        let elapsed = start.elapsed();
+        if elapsed > budget {
+            trace!(
+                "the data callback took {:?}, more than its budget of {:?}",
+                elapsed,
+                budget
+            );
+            self.enabled = false;
+        }
+    }
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -536,6 +1088,21 @@ impl Data {
             None
         }
     }
+
+    /// Fill the buffer with silence, using the `Sample::EQUILIBRIUM` of its sample format.
+    pub fn write_silence(&mut self) {
+        fn fill<T: Sample>(data: &mut Data) {
+            for sample in data.as_slice_mut::<T>().unwrap() {
+                *sample = T::EQUILIBRIUM;
+            }
+        }
+        match self.sample_format {
+            SampleFormat::I16 => fill::<i16>(self),
+            SampleFormat::U16 => fill::<u16>(self),
+            SampleFormat::U8 => fill::<u8>(self),
+            SampleFormat::F32 => fill::<f32>(self),
+        }
+    }
 }
 
 impl SupportedStreamConfigRange {
@@ -584,6 +1151,33 @@ impl SupportedStreamConfigRange {
         }
     }
 
+    /// Which parts of `config` and `sample_format` fall outside this range, or `None` if a stream
+    /// may be built with them.
+    ///
+    /// A buffer size is only a mismatch if the supported range is known.
+    pub fn why_unsupported(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Option<ConfigMismatch> {
+        let buffer_size = match (&config.buffer_size, &self.buffer_size) {
+            (&BufferSize::Fixed(n), &SupportedBufferSize::Range { min, max }) => n < min || n > max,
+            _ => false,
+        };
+        let mismatch = ConfigMismatch {
+            channels: config.channels != self.channels,
+            sample_rate: config.sample_rate < self.min_sample_rate
+                || config.sample_rate > self.max_sample_rate,
+            buffer_size,
+            sample_format: sample_format != self.sample_format,
+        };
+        if mismatch == ConfigMismatch::default() {
+            None
+        } else {
+            Some(mismatch)
+        }
+    }
+
     /// A comparison function which compares two `SupportedStreamConfigRange`s in terms of their priority of
     /// use as a default stream format.
     ///
@@ -603,6 +1197,7 @@ impl SupportedStreamConfigRange {
     /// - f32
     /// - i16
     /// - u16
+    /// - u8
     ///
     /// **Sample rate**:
     ///
@@ -653,11 +1248,486 @@ impl SupportedStreamConfigRange {
 
         self.max_sample_rate.cmp(&other.max_sample_rate)
     }
+
+    /// A comparison function which compares two `SupportedStreamConfigRange`s in terms of their
+    /// suitability for low latency playback, used by
+    /// `DeviceTrait::default_low_latency_output_config`.
+    ///
+    /// SupportedStreamConfigs are prioritised by the following heuristics:
+    ///
+    /// - f32 sample format
+    /// - Stereo
+    /// - Smallest minimum buffer size, with an unknown buffer size range ranked last
+    ///
+    /// Ties are broken with `cmp_default_heuristics`.
+    pub fn cmp_low_latency_heuristics(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering::Equal;
+
+        let cmp_f32 = (self.sample_format == SampleFormat::F32)
+            .cmp(&(other.sample_format == SampleFormat::F32));
+        if cmp_f32 != Equal {
+            return cmp_f32;
+        }
+
+        let cmp_stereo = (self.channels == 2).cmp(&(other.channels == 2));
+        if cmp_stereo != Equal {
+            return cmp_stereo;
+        }
+
+        // Smaller buffers are better, so the comparison is reversed.
+        let min_buffer_size = |config: &Self| match config.buffer_size {
+            SupportedBufferSize::Range { min, .. } => min,
+            SupportedBufferSize::Unknown => FrameCount::MAX,
+        };
+        let cmp_buffer_size = min_buffer_size(other).cmp(&min_buffer_size(self));
+        if cmp_buffer_size != Equal {
+            return cmp_buffer_size;
+        }
+
+        self.cmp_default_heuristics(other)
+    }
+
+    /// Coalesce overlapping or adjacent ranges into as few ranges as possible, e.g. to list the
+    /// supported configs of a device without clutter.
+    ///
+    /// Ranges are merged into the union of their sample rates if they have the same channel
+    /// count, sample format and buffer size, and if their sample rates overlap or follow each
+    /// other without a gap, so that the merged ranges support exactly the same configs. Exact
+    /// duplicates are dropped. Ranges are grouped by channel count, sample format and buffer
+    /// size in the order in which each group first appears, and sorted by sample rate within a
+    /// group.
+    pub fn merged<I>(configs: I) -> Vec<SupportedStreamConfigRange>
+    where
+        I: IntoIterator<Item = SupportedStreamConfigRange>,
+    {
+        let same_group = |a: &Self, b: &Self| {
+            a.channels == b.channels
+                && a.sample_format == b.sample_format
+                && a.buffer_size == b.buffer_size
+        };
+        let mut groups: Vec<Vec<SupportedStreamConfigRange>> = Vec::new();
+        for config in configs {
+            match groups
+                .iter_mut()
+                .find(|group| same_group(&group[0], &config))
+            {
+                Some(group) => group.push(config),
+                None => groups.push(vec![config]),
+            }
+        }
+        let mut merged: Vec<SupportedStreamConfigRange> = Vec::new();
+        for mut group in groups {
+            group.sort_by_key(|config| config.min_sample_rate);
+            let start = merged.len();
+            for config in group {
+                match merged[start..].last_mut() {
+                    Some(last)
+                        if config.min_sample_rate.0 <= last.max_sample_rate.0.saturating_add(1) =>
+                    {
+                        last.max_sample_rate = last.max_sample_rate.max(config.max_sample_rate);
+                    }
+                    _ => merged.push(config),
+                }
+            }
+        }
+        merged
+    }
+}
+
+impl DeviceCapabilities {
+    /// The human-readable name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The supported input stream configurations, empty for output-only devices.
+    pub fn supported_input_configs(&self) -> &[SupportedStreamConfigRange] {
+        &self.supported_input_configs
+    }
+
+    /// The supported output stream configurations, empty for input-only devices.
+    pub fn supported_output_configs(&self) -> &[SupportedStreamConfigRange] {
+        &self.supported_output_configs
+    }
+
+    /// The default input stream configuration, if the device supports input.
+    pub fn default_input_config(&self) -> Option<&SupportedStreamConfig> {
+        self.default_input_config.as_ref()
+    }
+
+    /// The default output stream configuration, if the device supports output.
+    pub fn default_output_config(&self) -> Option<&SupportedStreamConfig> {
+        self.default_output_config.as_ref()
+    }
+}
+
+impl<D> DeviceInfo<D>
+where
+    D: DeviceTrait,
+{
+    // Queries the capabilities of `device`, or returns `None` if it is gone or supports neither
+    // direction.
+    pub(crate) fn new(device: D, is_default_input: bool, is_default_output: bool) -> Option<Self> {
+        let capabilities = device.capabilities().ok()?;
+        let input = !capabilities.supported_input_configs.is_empty();
+        let output = !capabilities.supported_output_configs.is_empty();
+        let direction = match (input, output) {
+            (true, true) => Direction::Duplex,
+            (true, false) => Direction::Input,
+            (false, true) => Direction::Output,
+            (false, false) => return None,
+        };
+        Some(DeviceInfo {
+            device,
+            capabilities,
+            direction,
+            is_default_input,
+            is_default_output,
+        })
+    }
+}
+
+impl<D> DeviceInfo<D> {
+    /// The device, e.g. to build a stream once it was picked.
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Take the device out of the listing.
+    pub fn into_device(self) -> D {
+        self.device
+    }
+
+    /// The name, supported configs and default configs of the device.
+    pub fn capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
+    /// The human-readable name of the device.
+    pub fn name(&self) -> &str {
+        &self.capabilities.name
+    }
+
+    /// The directions in which the device supports streams.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Whether the device was the default input device while listing.
+    pub fn is_default_input(&self) -> bool {
+        self.is_default_input
+    }
+
+    /// Whether the device was the default output device while listing.
+    pub fn is_default_output(&self) -> bool {
+        self.is_default_output
+    }
+
+    /// The largest channel count of the device's input streams, `0` if it has no inputs.
+    pub fn max_input_channels(&self) -> ChannelCount {
+        max_channels(&self.capabilities.supported_input_configs)
+    }
+
+    /// The largest channel count of the device's output streams, `0` if it has no outputs.
+    pub fn max_output_channels(&self) -> ChannelCount {
+        max_channels(&self.capabilities.supported_output_configs)
+    }
+}
+
+fn max_channels(configs: &[SupportedStreamConfigRange]) -> ChannelCount {
+    configs.iter().map(|c| c.channels).max().unwrap_or(0)
+}
+
+impl DeviceQuery {
+    /// Whether the given supported configuration satisfies the query.
+    pub fn matches(&self, config: &SupportedStreamConfigRange) -> bool {
+        let channels = match self.min_channels {
+            Some(min) => config.channels >= min,
+            None => true,
+        };
+        let sample_rate = match self.sample_rate {
+            Some(rate) => config.min_sample_rate <= rate && rate <= config.max_sample_rate,
+            None => true,
+        };
+        let sample_format = match self.sample_format {
+            Some(format) => config.sample_format == format,
+            None => true,
+        };
+        channels && sample_rate && sample_format
+    }
+
+    pub(crate) fn devices<I>(devices: I, query: &DeviceQuery, input: bool) -> QueriedDevices<I> {
+        QueriedDevices {
+            devices,
+            query: query.clone(),
+            input,
+        }
+    }
+}
+
+impl<I> Iterator for QueriedDevices<I>
+where
+    I: Iterator,
+    I::Item: DeviceTrait,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (query, input) = (&self.query, self.input);
+        self.devices.by_ref().find(|device| {
+            let configs = if input {
+                device
+                    .supported_input_configs()
+                    .map(|c| c.collect::<Vec<_>>())
+            } else {
+                device
+                    .supported_output_configs()
+                    .map(|c| c.collect::<Vec<_>>())
+            };
+            match configs {
+                Ok(configs) => configs.iter().any(|c| query.matches(c)),
+                Err(_) => false,
+            }
+        })
+    }
+}
+
+#[test]
+fn test_device_info() {
+    let device = test::TestDevice::new(2, SampleRate(48_000)).with_name("Test");
+    let info = DeviceInfo::new(device, false, true).unwrap();
+    assert_eq!(info.name(), "Test");
+    assert_eq!(info.direction(), Direction::Duplex);
+    assert!(!info.is_default_input());
+    assert!(info.is_default_output());
+    assert_eq!(info.max_input_channels(), 2);
+    assert_eq!(info.max_output_channels(), 2);
+    let default_config = info.capabilities().default_output_config().unwrap();
+    assert_eq!(default_config.sample_rate(), SampleRate(48_000));
+}
+
+#[test]
+fn test_sample_rate_parsing() {
+    let parse = |s: &str| s.parse::<SampleRate>();
+    assert_eq!(parse("48000"), Ok(SampleRate::HZ_48000));
+    assert_eq!(parse(" 48000 Hz "), Ok(SampleRate::HZ_48000));
+    assert_eq!(parse("48kHz"), Ok(SampleRate::HZ_48000));
+    assert_eq!(parse("44.1 KHZ"), Ok(SampleRate::HZ_44100));
+    assert_eq!(parse("96khz"), Ok(SampleRate::HZ_96000));
+    for &invalid in &[
+        "",
+        "0",
+        "0 kHz",
+        "-48kHz",
+        "48 MHz",
+        "4.8e100 kHz",
+        "NaN kHz",
+        "48.5",
+    ] {
+        assert_eq!(
+            parse(invalid),
+            Err(ParseSampleRateError {
+                input: invalid.to_owned()
+            })
+        );
+    }
+    for &rate in COMMON_SAMPLE_RATES {
+        assert_eq!(parse(&rate.to_string()), Ok(rate));
+        let khz = format!("{}kHz", rate.0 as f64 / 1000.0);
+        assert_eq!(parse(&khz), Ok(rate));
+    }
+    assert_eq!(SampleRate::HZ_44100.to_string(), "44100 Hz");
+    assert_eq!(SampleRate::from_khz(44.1), SampleRate::HZ_44100);
+    assert_eq!(SampleRate::from_khz(-1.0), SampleRate(0));
+}
+
+#[test]
+fn test_channel_layout() {
+    assert_eq!(ChannelLayout::for_channels(2), Some(ChannelLayout::STEREO));
+    assert_eq!(ChannelLayout::for_channels(3), None);
+    assert_eq!(ChannelLayout::SURROUND_7_1.channels(), 8);
+    assert_eq!(ChannelLayout::SURROUND_5_1.name(), Some("5.1 Surround"));
+    let layout = ChannelLayout(ChannelLayout::FRONT_LEFT | ChannelLayout::LOW_FREQUENCY);
+    assert_eq!(layout.channels(), 2);
+    assert_eq!(layout.name(), None);
+}
+
+#[test]
+fn test_merged_config_ranges() {
+    let range = |channels, min, max, sample_format| SupportedStreamConfigRange {
+        channels,
+        min_sample_rate: SampleRate(min),
+        max_sample_rate: SampleRate(max),
+        buffer_size: SupportedBufferSize::Unknown,
+        sample_format,
+    };
+    let configs = vec![
+        range(2, 44_100, 48_000, SampleFormat::F32),
+        range(1, 8_000, 8_000, SampleFormat::I16),
+        // Overlapping.
+        range(2, 32_000, 44_100, SampleFormat::F32),
+        // Adjacent.
+        range(2, 48_001, 96_000, SampleFormat::F32),
+        // An exact repeat.
+        range(1, 8_000, 8_000, SampleFormat::I16),
+        // Separated by a gap.
+        range(1, 16_000, 16_000, SampleFormat::I16),
+        // Contained in another range.
+        range(2, 44_100, 44_100, SampleFormat::F32),
+        // Differing in sample format only.
+        range(2, 44_100, 48_000, SampleFormat::I16),
+    ];
+    assert_eq!(
+        SupportedStreamConfigRange::merged(configs),
+        vec![
+            range(2, 32_000, 96_000, SampleFormat::F32),
+            range(1, 8_000, 8_000, SampleFormat::I16),
+            range(1, 16_000, 16_000, SampleFormat::I16),
+            range(2, 44_100, 48_000, SampleFormat::I16),
+        ]
+    );
+    let mut other_buffer_size = range(2, 48_000, 48_000, SampleFormat::F32);
+    other_buffer_size.buffer_size = SupportedBufferSize::Range { min: 64, max: 512 };
+    let configs = vec![
+        range(2, 48_000, 48_000, SampleFormat::F32),
+        other_buffer_size,
+    ];
+    assert_eq!(SupportedStreamConfigRange::merged(configs.clone()), configs);
+}
+
+#[test]
+fn test_why_unsupported() {
+    let range = SupportedStreamConfigRange {
+        channels: 2,
+        min_sample_rate: SampleRate(44_100),
+        max_sample_rate: SampleRate(48_000),
+        buffer_size: SupportedBufferSize::Range { min: 64, max: 512 },
+        sample_format: SampleFormat::F32,
+    };
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Fixed(256),
+    };
+    assert_eq!(range.why_unsupported(&config, SampleFormat::F32), None);
+    let config = StreamConfig {
+        sample_rate: SampleRate(96_000),
+        ..config
+    };
+    assert_eq!(
+        range.why_unsupported(&config, SampleFormat::I16),
+        Some(ConfigMismatch {
+            sample_rate: true,
+            sample_format: true,
+            ..Default::default()
+        })
+    );
+    let config = StreamConfig {
+        channels: 1,
+        sample_rate: SampleRate(44_100),
+        buffer_size: BufferSize::Fixed(32),
+    };
+    assert_eq!(
+        range.why_unsupported(&config, SampleFormat::F32),
+        Some(ConfigMismatch {
+            channels: true,
+            buffer_size: true,
+            ..Default::default()
+        })
+    );
+    let range = SupportedStreamConfigRange {
+        buffer_size: SupportedBufferSize::Unknown,
+        ..range
+    };
+    let config = StreamConfig {
+        channels: 2,
+        ..config
+    };
+    assert_eq!(range.why_unsupported(&config, SampleFormat::F32), None);
+}
+
+#[test]
+fn test_device_query_matches() {
+    let config = SupportedStreamConfigRange {
+        channels: 8,
+        min_sample_rate: SampleRate(44_100),
+        max_sample_rate: SampleRate(96_000),
+        buffer_size: SupportedBufferSize::Unknown,
+        sample_format: SampleFormat::F32,
+    };
+    assert!(DeviceQuery::default().matches(&config));
+    let query = DeviceQuery {
+        min_channels: Some(8),
+        sample_rate: Some(SampleRate(48_000)),
+        sample_format: Some(SampleFormat::F32),
+    };
+    assert!(query.matches(&config));
+    let more_channels = DeviceQuery {
+        min_channels: Some(10),
+        ..query.clone()
+    };
+    assert!(!more_channels.matches(&config));
+    let other_rate = DeviceQuery {
+        sample_rate: Some(SampleRate(192_000)),
+        ..query.clone()
+    };
+    assert!(!other_rate.matches(&config));
+    let other_format = DeviceQuery {
+        sample_format: Some(SampleFormat::I16),
+        ..query
+    };
+    assert!(!other_format.matches(&config));
+}
+
+#[test]
+fn test_data_write_silence() {
+    let mut samples = [1u16, 2, 3];
+    let mut data =
+        unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 3, SampleFormat::U16) };
+    data.write_silence();
+    assert_eq!(samples, [u16::EQUILIBRIUM; 3]);
+}
+
+#[test]
+fn test_frame_arithmetic() {
+    let config = SupportedStreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: SupportedBufferSize::Unknown,
+        sample_format: SampleFormat::I16,
+    };
+    assert_eq!(config.bytes_per_frame(), 4);
+    assert_eq!(config.frames_to_bytes(480), 1920);
+    assert_eq!(config.bytes_to_frames(1923), 480);
+    assert_eq!(config.duration_of_frames(480), Duration::from_millis(10));
+    assert_eq!(
+        config.duration_of_frames(96_000 * 3_600),
+        Duration::from_secs(7_200)
+    );
+    assert_eq!(config.frames_in_duration(Duration::from_millis(10)), 480);
+    assert_eq!(config.frames_in_duration(Duration::from_micros(30)), 1);
+    let config = StreamConfig {
+        sample_rate: SampleRate(0),
+        ..config.config()
+    };
+    assert_eq!(config.duration_of_frames(480), Duration::from_secs(0));
+    assert_eq!(config.frames_in_duration(Duration::from_secs(1)), 0);
+}
+
+#[test]
+fn test_data_as_slice_requires_matching_format() {
+    let mut samples = [0.25f32, -0.5];
+    let mut data =
+        unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 2, SampleFormat::F32) };
+    assert!(data.as_slice::<i16>().is_none());
+    assert!(data.as_slice_mut::<u16>().is_none());
+    data.as_slice_mut::<f32>().unwrap()[1] = 1.0;
+    assert_eq!(data.as_slice::<f32>(), Some(&[0.25, 1.0][..]));
 }
 
 #[test]
 fn test_cmp_default_heuristics() {
-    let mut formats = vec![
+    let mut formats = [
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
             channels: 2,
@@ -724,6 +1794,55 @@ fn test_cmp_default_heuristics() {
     assert_eq!(formats[4].channels(), 2);
 }
 
+#[test]
+fn test_cmp_low_latency_heuristics() {
+    let config = |buffer_size, channels, sample_format| SupportedStreamConfigRange {
+        buffer_size,
+        channels,
+        min_sample_rate: SampleRate(1),
+        max_sample_rate: SampleRate(96000),
+        sample_format,
+    };
+    let mut formats = [
+        config(SupportedBufferSize::Unknown, 2, SampleFormat::F32),
+        config(
+            SupportedBufferSize::Range { min: 16, max: 512 },
+            2,
+            SampleFormat::I16,
+        ),
+        config(
+            SupportedBufferSize::Range { min: 64, max: 512 },
+            2,
+            SampleFormat::F32,
+        ),
+        config(
+            SupportedBufferSize::Range { min: 32, max: 512 },
+            1,
+            SampleFormat::F32,
+        ),
+        config(
+            SupportedBufferSize::Range { min: 128, max: 512 },
+            2,
+            SampleFormat::F32,
+        ),
+    ];
+
+    formats.sort_by(|a, b| a.cmp_low_latency_heuristics(b));
+
+    // lowest-priority first:
+    assert_eq!(formats[0].sample_format(), SampleFormat::I16);
+    assert_eq!(formats[1].channels(), 1);
+    assert_eq!(*formats[2].buffer_size(), SupportedBufferSize::Unknown);
+    assert_eq!(
+        *formats[3].buffer_size(),
+        SupportedBufferSize::Range { min: 128, max: 512 }
+    );
+    assert_eq!(
+        *formats[4].buffer_size(),
+        SupportedBufferSize::Range { min: 64, max: 512 }
+    );
+}
+
 impl From<SupportedStreamConfig> for StreamConfig {
     fn from(conf: SupportedStreamConfig) -> Self {
         conf.config()
@@ -732,10 +1851,10 @@ impl From<SupportedStreamConfig> for StreamConfig {
 
 // If a backend does not provide an API for retrieving supported formats, we query it with a bunch
 // of commonly used rates. This is always the case for wasapi and is sometimes the case for alsa.
+// Continuous ranges of rates are also listed as the common rates they contain.
 //
 // If a rate you desire is missing from this list, feel free to add it!
-#[cfg(target_os = "windows")]
-const COMMON_SAMPLE_RATES: &'static [SampleRate] = &[
+const COMMON_SAMPLE_RATES: &[SampleRate] = &[
     SampleRate(5512),
     SampleRate(8000),
     SampleRate(11025),
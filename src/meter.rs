@@ -0,0 +1,354 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use traits::StreamTrait;
+use {ChannelCount, PauseStreamError, PlayStreamError, Sample, SampleRate, StreamConfig};
+
+/// The duration over which `ChannelLevels::rms` is averaged, in seconds.
+const RMS_WINDOW: f32 = 0.3;
+
+/// The absolute level from which samples count as clipped, see `MeteredStream::clip_counts`.
+const CLIP_LEVEL: f32 = 1.0;
+
+/// The levels of each channel of a stream, as measured by a `MeteredStream`.
+///
+/// Levels are linear amplitudes where `0.0` is silence and `1.0` is full scale. Values above
+/// `1.0` mean that `f32` samples outside of the nominal range, which would clip on most devices,
+/// were produced or captured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelLevels {
+    /// The largest absolute sample value of each channel in the most recent buffer.
+    pub peak: Vec<f32>,
+    /// The root mean square of each channel, averaged over roughly the last 300 milliseconds.
+    pub rms: Vec<f32>,
+}
+
+impl ChannelLevels {
+    /// The peak level of each channel in dBFS, where `0.0` is full scale and silence is
+    /// `f32::NEG_INFINITY`.
+    ///
+    /// Samples are converted to `f32` before measuring, with both ends of the range of integer
+    /// formats mapped to `±1.0`, so the largest `i16`, `u16` or `u8` sample reads `0.0` dBFS
+    /// just like an `f32` sample of `1.0`.
+    pub fn peak_dbfs(&self) -> Vec<f32> {
+        self.peak.iter().map(|&level| to_dbfs(level)).collect()
+    }
+
+    /// The RMS level of each channel in dBFS, where silence is `f32::NEG_INFINITY`.
+    ///
+    /// The level is referenced to a full scale square wave, which reads `0.0` dBFS, so that a
+    /// full scale sine wave reads about `-3.01` dBFS.
+    pub fn rms_dbfs(&self) -> Vec<f32> {
+        self.rms.iter().map(|&level| to_dbfs(level)).collect()
+    }
+}
+
+fn to_dbfs(level: f32) -> f32 {
+    20.0 * level.log10()
+}
+
+// The levels shared between the audio callback and the stream handle.
+//
+// Each level is the bit pattern of an `f32` so that they can be read without locking.
+pub(crate) struct Levels {
+    enabled: AtomicBool,
+    peak: Box<[AtomicU32]>,
+    rms: Box<[AtomicU32]>,
+    // The number of clipped samples of each channel since the last reset.
+    clipped: Box<[AtomicU64]>,
+}
+
+impl Levels {
+    fn new(channels: ChannelCount) -> Arc<Self> {
+        let zeros = || (0..channels).map(|_| AtomicU32::new(0)).collect();
+        Arc::new(Levels {
+            enabled: AtomicBool::new(true),
+            peak: zeros(),
+            rms: zeros(),
+            clipped: (0..channels).map(|_| AtomicU64::new(0)).collect(),
+        })
+    }
+}
+
+// Measures the buffers passed through an audio callback, publishing the results to `Levels`.
+pub(crate) struct Meter {
+    levels: Arc<Levels>,
+    sample_rate: SampleRate,
+    // Scratch space for the peak, sum of squares and clipped samples of each channel of the
+    // current buffer.
+    peak: Vec<f32>,
+    sum_squares: Vec<f32>,
+    clipped: Vec<u64>,
+    // The running mean of the squares of each channel.
+    mean_squares: Vec<f32>,
+}
+
+impl Meter {
+    pub(crate) fn new(config: &StreamConfig) -> Self {
+        let channels = config.channels as usize;
+        Meter {
+            levels: Levels::new(config.channels),
+            sample_rate: config.sample_rate,
+            peak: vec![0.0; channels],
+            sum_squares: vec![0.0; channels],
+            clipped: vec![0; channels],
+            mean_squares: vec![0.0; channels],
+        }
+    }
+
+    pub(crate) fn levels(&self) -> Arc<Levels> {
+        self.levels.clone()
+    }
+
+    // Measures a buffer of interleaved samples.
+    pub(crate) fn process<T>(&mut self, samples: &[T])
+    where
+        T: Sample,
+    {
+        if !self.levels.enabled.load(Ordering::Relaxed) || self.peak.is_empty() {
+            return;
+        }
+        let channels = self.peak.len();
+        for (peak, sum) in self.peak.iter_mut().zip(&mut self.sum_squares) {
+            *peak = 0.0;
+            *sum = 0.0;
+        }
+        self.clipped.iter_mut().for_each(|clipped| *clipped = 0);
+        for frame in samples.chunks(channels) {
+            for (i, sample) in frame.iter().enumerate() {
+                let sample = sample.to_f32();
+                self.peak[i] = self.peak[i].max(sample.abs());
+                self.sum_squares[i] += sample * sample;
+                if sample.abs() >= CLIP_LEVEL {
+                    self.clipped[i] += 1;
+                }
+            }
+        }
+        for (clipped, &count) in self.levels.clipped.iter().zip(&self.clipped) {
+            if count > 0 {
+                clipped.fetch_add(count, Ordering::Relaxed);
+            }
+        }
+
+        // An exponential moving average, weighing the buffer by its share of the window.
+        let frames = (samples.len() / channels) as f32;
+        if frames == 0.0 {
+            return;
+        }
+        let weight = (frames / (RMS_WINDOW * self.sample_rate.0 as f32)).min(1.0);
+        for i in 0..channels {
+            let mean = &mut self.mean_squares[i];
+            *mean += weight * (self.sum_squares[i] / frames - *mean);
+            let peak = self.peak[i].to_bits();
+            let rms = mean.sqrt().to_bits();
+            self.levels.peak[i].store(peak, Ordering::Relaxed);
+            self.levels.rms[i].store(rms, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A stream that measures the peak and RMS level of each channel of its audio, and counts its
+/// clipped samples.
+///
+/// Created via `DeviceTrait::build_input_stream_with_meter` or
+/// `DeviceTrait::build_output_stream_with_meter`. The levels are updated by the audio callback
+/// each time it runs and can be read from any thread without blocking it.
+pub struct MeteredStream<S> {
+    stream: S,
+    levels: Arc<Levels>,
+}
+
+impl<S> MeteredStream<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(stream: S, levels: Arc<Levels>) -> Self {
+        MeteredStream { stream, levels }
+    }
+
+    /// The most recently measured levels. All levels are `0.0` until the first callback.
+    pub fn levels(&self) -> ChannelLevels {
+        let load = |levels: &[AtomicU32]| {
+            levels
+                .iter()
+                .map(|level| f32::from_bits(level.load(Ordering::Relaxed)))
+                .collect()
+        };
+        ChannelLevels {
+            peak: load(&self.levels.peak),
+            rms: load(&self.levels.rms),
+        }
+    }
+
+    /// The number of clipped samples of each channel since the stream was built or
+    /// `reset_clip_counts` was last called, e.g. to light a clip indicator while recording.
+    ///
+    /// A sample is clipped when it reaches full scale, i.e. when its absolute value is at least
+    /// `1.0` once converted to `f32`. This counts samples at either end of the range of integer
+    /// formats, which is where an input stage that is driven too hard flattens the signal, and
+    /// `f32` samples at or beyond `±1.0`. Only samples measured while metering is enabled are
+    /// counted.
+    pub fn clip_counts(&self) -> Vec<u64> {
+        self.levels
+            .clipped
+            .iter()
+            .map(|clipped| clipped.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Reset the clip counts of all channels to zero, e.g. when the user acknowledges the clip
+    /// indicator.
+    pub fn reset_clip_counts(&self) {
+        for clipped in self.levels.clipped.iter() {
+            clipped.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Resume measuring levels after `disable_metering`. Metering is enabled when the stream is
+    /// built.
+    pub fn enable_metering(&self) {
+        self.levels.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Stop measuring levels, saving the cost of doing so in the audio callback. `levels` keeps
+    /// returning the last measured levels.
+    pub fn disable_metering(&self) {
+        self.levels.enabled.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether levels are currently being measured.
+    pub fn is_metering_enabled(&self) -> bool {
+        self.levels.enabled.load(Ordering::Relaxed)
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S> StreamTrait for MeteredStream<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_dbfs, Meter};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use {BufferSize, ChannelLevels, SampleRate, StreamConfig};
+
+    fn meter() -> Meter {
+        Meter::new(&StreamConfig {
+            channels: 2,
+            sample_rate: SampleRate(1_000),
+            buffer_size: BufferSize::Default,
+        })
+    }
+
+    fn load(levels: &[AtomicU32]) -> Vec<f32> {
+        levels
+            .iter()
+            .map(|level| f32::from_bits(level.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    #[test]
+    fn peak_and_clipping() {
+        let mut meter = meter();
+        meter.process(&[0.5f32, 0.0, -1.5, 0.0, 0.25, 0.0]);
+        let levels = meter.levels();
+        assert_eq!(load(&levels.peak), [1.5, 0.0]);
+        meter.process(&[0.0f32; 4]);
+        assert_eq!(load(&levels.peak), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn clip_counts() {
+        let mut meter = meter();
+        let levels = meter.levels();
+        let clipped = || -> Vec<u64> {
+            levels
+                .clipped
+                .iter()
+                .map(|clipped| clipped.load(Ordering::Relaxed))
+                .collect()
+        };
+        meter.process(&[1.0f32, 0.99, -1.5, 0.0]);
+        assert_eq!(clipped(), [2, 0]);
+        // Both ends of the range of integer formats are full scale, and counts accumulate.
+        meter.process(&[i16::MIN, i16::MAX, i16::MAX - 1, 0]);
+        assert_eq!(clipped(), [3, 1]);
+    }
+
+    #[test]
+    fn rms_window() {
+        let mut meter = meter();
+        // A full window of a square wave of amplitude 0.5 on the first channel only.
+        let samples: Vec<i16> = (0..300)
+            .flat_map(|i| vec![if i % 2 == 0 { 16_384 } else { -16_384 }, 0])
+            .collect();
+        meter.process(&samples);
+        let rms = load(&meter.levels().rms);
+        assert!((rms[0] - 0.5).abs() < 1e-3);
+        assert_eq!(rms[1], 0.0);
+        // A short buffer of silence only partially decays the level.
+        meter.process(&[0i16; 60]);
+        let rms = load(&meter.levels().rms);
+        assert!(rms[0] > 0.4 && rms[0] < 0.5);
+    }
+
+    #[test]
+    fn dbfs() {
+        let mut meter = meter();
+        let levels = meter.levels();
+        let peak_dbfs = || {
+            let levels = ChannelLevels {
+                peak: load(&levels.peak),
+                rms: load(&levels.rms),
+            };
+            levels.peak_dbfs()
+        };
+        // Both ends of the range of every format are full scale.
+        meter.process(&[i16::MAX, i16::MIN]);
+        assert_eq!(peak_dbfs(), [0.0, 0.0]);
+        meter.process(&[u16::MAX, 0]);
+        assert_eq!(peak_dbfs(), [0.0, 0.0]);
+        meter.process(&[u8::MAX, 128]);
+        assert_eq!(peak_dbfs(), [0.0, f32::NEG_INFINITY]);
+        meter.process(&[-1.0f32, 0.5]);
+        let dbfs = peak_dbfs();
+        assert_eq!(dbfs[0], 0.0);
+        assert!((dbfs[1] + 6.02).abs() < 0.01);
+        assert_eq!(to_dbfs(0.0), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut meter = meter();
+        let levels = meter.levels();
+        levels.enabled.store(false, Ordering::Relaxed);
+        meter.process(&[1.0f32, 1.0]);
+        assert_eq!(load(&levels.peak), [0.0, 0.0]);
+    }
+}
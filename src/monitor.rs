@@ -0,0 +1,279 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use channel_map;
+use duplex::{self, StreamPair};
+use ring_buffer;
+use traits::{DeviceTrait, StreamTrait};
+use {
+    BuildStreamError, ChannelMap, Data, PauseStreamError, PlayStreamError, Sample, SampleFormat,
+    SampleRate, StreamConfig, StreamError, SupportedStreamConfig, SupportedStreamConfigRange,
+};
+
+/// Route the input of `input_device` to the output of `output_device`, e.g. to let the user hear
+/// their microphone, with the given linear `gain`.
+///
+/// Both streams use the default config of their device. Captured audio is handed from the input
+/// stream to the output stream through a lock-free ring buffer, and mixed to the channels of the
+/// output with `ChannelMap::Default`. Whenever the input falls behind, silence is played.
+///
+/// There is no resampling: if the default sample rates differ, the output is opened at the rate
+/// of the input, or else the input at the rate of the output. Returns
+/// `BuildStreamError::StreamConfigNotSupported` if neither device supports the other's rate.
+///
+/// The streams are built paused on hosts that allow it: call `play` to start monitoring.
+pub fn monitor<D, E>(
+    input_device: &D,
+    output_device: &D,
+    gain: f32,
+    error_callback: E,
+) -> Result<Monitor<D::Stream>, BuildStreamError>
+where
+    D: DeviceTrait,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let mut input_config = input_device.default_input_config()?;
+    let mut output_config = output_device.default_output_config()?;
+    if input_config.sample_rate() != output_config.sample_rate() {
+        let output_configs = output_device.supported_output_configs()?;
+        let input_configs = input_device.supported_input_configs()?;
+        if let Some(config) =
+            with_sample_rate(output_configs, &output_config, input_config.sample_rate())
+        {
+            output_config = config;
+        } else if let Some(config) =
+            with_sample_rate(input_configs, &input_config, output_config.sample_rate())
+        {
+            input_config = config;
+        } else {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+    }
+    let input_format = input_config.sample_format();
+    let output_format = output_config.sample_format();
+    let input_config = input_config.config();
+    let output_config = output_config.config();
+    let matrix = ChannelMap::Default.matrix(input_config.channels, output_config.channels)?;
+
+    let gain = Arc::new(AtomicU32::new(gain.to_bits()));
+    let latency = Arc::new(AtomicU64::new(0));
+    // The delay between the capture of the last input buffer and its arrival, in nanoseconds.
+    let input_delay = Arc::new(AtomicU64::new(0));
+    let (mut producer, mut consumer) =
+        ring_buffer::ring_buffer(duplex::DEFAULT_CAPACITY_FRAMES, input_config.channels);
+    let (input_error_callback, output_error_callback) =
+        duplex::shared_error_callback(error_callback);
+
+    let input = {
+        let input_delay = input_delay.clone();
+        let mut buffer = Vec::new();
+        input_device.build_input_stream_raw(
+            &input_config,
+            input_format,
+            move |data, info| {
+                let timestamp = info.timestamp();
+                let delay = timestamp.callback.duration_since(&timestamp.capture);
+                input_delay.store(nanos(delay.unwrap_or_default()), Ordering::Relaxed);
+                read_f32(data, &mut buffer);
+                producer.push_slice(&buffer);
+            },
+            input_error_callback,
+        )?
+    };
+
+    let output = {
+        let gain = gain.clone();
+        let latency = latency.clone();
+        let input_channels = input_config.channels as usize;
+        let output_channels = output_config.channels as usize;
+        let sample_rate = input_config.sample_rate;
+        let mut input_buffer = Vec::new();
+        let mut output_buffer = Vec::new();
+        output_device.build_output_stream_raw(
+            &output_config,
+            output_format,
+            move |data, info| {
+                let timestamp = info.timestamp();
+                let delay = timestamp.playback.duration_since(&timestamp.callback);
+                let queued = (consumer.len() / input_channels) as u64;
+                let total = Duration::from_nanos(input_delay.load(Ordering::Relaxed))
+                    + duration_of_frames(queued, sample_rate)
+                    + delay.unwrap_or_default();
+                latency.store(nanos(total), Ordering::Relaxed);
+
+                let frames = data.len() / output_channels;
+                input_buffer.resize(frames * input_channels, 0.0);
+                consumer.fill(&mut input_buffer);
+                output_buffer.resize(frames * output_channels, 0.0);
+                channel_map::mix(&matrix, &input_buffer, &mut output_buffer);
+                let gain = f32::from_bits(gain.load(Ordering::Relaxed));
+                for sample in output_buffer.iter_mut() {
+                    *sample *= gain;
+                }
+                write_f32(&output_buffer, data);
+            },
+            output_error_callback,
+        )?
+    };
+
+    Ok(Monitor {
+        streams: StreamPair::new(input, output),
+        gain,
+        latency,
+    })
+}
+
+/// An input stream routed to an output stream, created via `monitor`.
+///
+/// Playing and pausing apply to both streams like for a `StreamPair`.
+pub struct Monitor<S> {
+    streams: StreamPair<S>,
+    // The bits of the `f32` gain.
+    gain: Arc<AtomicU32>,
+    // The latest measured latency, in nanoseconds.
+    latency: Arc<AtomicU64>,
+}
+
+impl<S> Monitor<S>
+where
+    S: StreamTrait,
+{
+    /// The underlying input and output streams.
+    pub fn streams(&self) -> &StreamPair<S> {
+        &self.streams
+    }
+
+    /// The linear gain applied to the input.
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    /// Change the linear gain applied to the input, from the next output buffer on.
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The round-trip latency measured by the latest output buffer, from the capture of the
+    /// input to its playback, or zero until the output stream ran.
+    ///
+    /// Made of the capture latency reported by the input stream, the audio waiting in the ring
+    /// buffer and the playback latency reported by the output stream, so it is only as accurate
+    /// as the timestamps of the host.
+    pub fn latency(&self) -> Duration {
+        Duration::from_nanos(self.latency.load(Ordering::Relaxed))
+    }
+}
+
+impl<S> StreamTrait for Monitor<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.streams.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.streams.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.streams.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.streams.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.streams.is_playing()
+    }
+}
+
+// `config` at `sample_rate`, if one of the supported configs allows it.
+fn with_sample_rate<I>(
+    mut configs: I,
+    config: &SupportedStreamConfig,
+    sample_rate: SampleRate,
+) -> Option<SupportedStreamConfig>
+where
+    I: Iterator<Item = SupportedStreamConfigRange>,
+{
+    configs
+        .find(|range| {
+            range.channels() == config.channels()
+                && range.sample_format() == config.sample_format()
+                && range.min_sample_rate() <= sample_rate
+                && sample_rate <= range.max_sample_rate()
+        })
+        .map(|range| range.with_sample_rate(sample_rate))
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+fn duration_of_frames(frames: u64, sample_rate: SampleRate) -> Duration {
+    Duration::from_nanos(frames * 1_000_000_000 / sample_rate.0 as u64)
+}
+
+fn read_f32(data: &Data, output: &mut Vec<f32>) {
+    fn read<T: Sample>(input: &[T], output: &mut Vec<f32>) {
+        output.extend(input.iter().map(|sample| sample.to_f32()));
+    }
+    output.clear();
+    match data.sample_format() {
+        SampleFormat::I16 => read::<i16>(data.as_slice().unwrap(), output),
+        SampleFormat::U16 => read::<u16>(data.as_slice().unwrap(), output),
+        SampleFormat::U8 => read::<u8>(data.as_slice().unwrap(), output),
+        SampleFormat::F32 => read::<f32>(data.as_slice().unwrap(), output),
+    }
+}
+
+fn write_f32(input: &[f32], data: &mut Data) {
+    fn write<T: Sample>(input: &[f32], output: &mut [T]) {
+        for (output, input) in output.iter_mut().zip(input) {
+            *output = T::from(input);
+        }
+    }
+    match data.sample_format() {
+        SampleFormat::I16 => write::<i16>(input, data.as_slice_mut().unwrap()),
+        SampleFormat::U16 => write::<u16>(input, data.as_slice_mut().unwrap()),
+        SampleFormat::U8 => write::<u8>(input, data.as_slice_mut().unwrap()),
+        SampleFormat::F32 => write::<f32>(input, data.as_slice_mut().unwrap()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::monitor;
+    use std::time::Duration;
+    use test::TestDevice;
+    use traits::StreamTrait;
+    use SampleRate;
+
+    #[test]
+    fn input_is_played_with_gain() {
+        let input = TestDevice::new(1, SampleRate(48_000)).with_input(vec![0.25, 0.5]);
+        let output = TestDevice::new(2, SampleRate(48_000));
+        let monitor = monitor(&input, &output, 2.0, |_| ()).unwrap();
+        monitor.play().unwrap();
+        input.process(2);
+        output.process(3);
+        assert_eq!(output.recorded_output(), vec![0.5, 0.5, 1.0, 1.0, 0.0, 0.0]);
+        // The test devices report no latency of their own, leaving the two queued frames.
+        assert_eq!(monitor.latency(), Duration::from_nanos(41_666));
+        monitor.set_gain(0.5);
+        assert_eq!(monitor.gain(), 0.5);
+    }
+
+    #[test]
+    fn mismatched_rates_are_not_supported() {
+        let input = TestDevice::new(1, SampleRate(48_000));
+        let output = TestDevice::new(1, SampleRate(44_100));
+        assert!(matches!(
+            monitor(&input, &output, 1.0, |_| ()),
+            Err(::BuildStreamError::StreamConfigNotSupported)
+        ));
+    }
+}
@@ -0,0 +1,139 @@
+//! Adapters between interleaved and planar (one buffer per channel) data callbacks, used by hosts
+//! that do not provide planar buffers natively.
+
+use {ChannelCount, InputCallbackInfo, OutputCallbackInfo, Sample};
+
+// Empties `vec` and changes the lifetime of the slices it can hold, retaining its allocation.
+//
+// This lets a `Vec` of slices into the buffers of one callback be reused by the next one without
+// allocating in the audio callback. The in-place `collect` of an empty iterator does not allocate.
+pub(crate) fn reuse_vec<'b, S>(mut vec: Vec<&S>) -> Vec<&'b S>
+where
+    S: ?Sized,
+{
+    vec.clear();
+    vec.into_iter().map(|_| unreachable!()).collect()
+}
+
+// Like `reuse_vec`, for mutable slices.
+pub(crate) fn reuse_vec_mut<'b, S>(mut vec: Vec<&mut S>) -> Vec<&'b mut S>
+where
+    S: ?Sized,
+{
+    vec.clear();
+    vec.into_iter().map(|_| unreachable!()).collect()
+}
+
+// An empty `Vec` of slices, kept between invocations of a data callback to reuse its allocation.
+struct EmptySlices<T: 'static>(Vec<&'static [T]>);
+
+// The `Vec` never holds a slice while it could be sent to another thread.
+unsafe impl<T> Send for EmptySlices<T> where T: Send {}
+
+// Wraps a planar input data callback into an interleaved one.
+pub(crate) fn deinterleave_input<T, D>(
+    channels: ChannelCount,
+    mut data_callback: D,
+) -> impl FnMut(&[T], &InputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+    D: FnMut(&[&[T]], &InputCallbackInfo) + Send + 'static,
+{
+    let channels = channels as usize;
+    let mut buffers: Vec<Vec<T>> = vec![Vec::new(); channels];
+    let mut slices = EmptySlices(Vec::with_capacity(channels));
+    move |data, info| {
+        for (channel, buffer) in buffers.iter_mut().enumerate() {
+            buffer.clear();
+            buffer.extend(data.iter().skip(channel).step_by(channels));
+        }
+        let mut planar = reuse_vec(std::mem::take(&mut slices.0));
+        planar.extend(buffers.iter().map(|buffer| &buffer[..]));
+        data_callback(&planar, info);
+        slices.0 = reuse_vec(planar);
+    }
+}
+
+// Wraps a planar output data callback into an interleaved one.
+pub(crate) fn interleave_output<T, D>(
+    channels: ChannelCount,
+    mut data_callback: D,
+) -> impl FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static
+where
+    T: Sample + Send + 'static,
+    D: FnMut(&mut [&mut [T]], &OutputCallbackInfo) + Send + 'static,
+{
+    let channels = channels as usize;
+    let mut buffers: Vec<Vec<T>> = vec![Vec::new(); channels];
+    let mut slices: Vec<&'static mut [T]> = Vec::with_capacity(channels);
+    move |data, info| {
+        let frames = data.len() / channels.max(1);
+        for buffer in &mut buffers {
+            buffer.clear();
+            buffer.resize(frames, T::EQUILIBRIUM);
+        }
+        let mut planar = reuse_vec_mut(std::mem::take(&mut slices));
+        planar.extend(buffers.iter_mut().map(|buffer| &mut buffer[..]));
+        data_callback(&mut planar, info);
+        slices = reuse_vec_mut(planar);
+        for (frame, samples) in data.chunks_mut(channels).enumerate() {
+            for (sample, buffer) in samples.iter_mut().zip(&buffers) {
+                *sample = buffer[frame];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {InputCallbackInfo, OutputCallbackInfo, SampleRate};
+
+    #[test]
+    fn output_is_interleaved() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_planar(
+                &config,
+                |data: &mut [&mut [f32]], _: &OutputCallbackInfo| {
+                    assert_eq!(data.len(), 2);
+                    data[0].iter_mut().for_each(|s| *s = 0.25);
+                    data[1].iter_mut().for_each(|s| *s = -0.5);
+                },
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(2);
+        assert_eq!(device.recorded_output(), vec![0.25, -0.5, 0.25, -0.5]);
+    }
+
+    #[test]
+    fn input_is_deinterleaved() {
+        let device = TestDevice::new(2, SampleRate(48_000)).with_input(vec![0.1, 0.2, 0.3, 0.4]);
+        let config = device.default_input_config().unwrap().config();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let received = received.clone();
+            device
+                .build_input_stream_planar(
+                    &config,
+                    move |data: &[&[f32]], _: &InputCallbackInfo| {
+                        let channels: Vec<Vec<f32>> = data.iter().map(|c| c.to_vec()).collect();
+                        received.lock().unwrap().push(channels);
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        device.process(2);
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![vec![vec![0.1, 0.3], vec![0.2, 0.4]]]
+        );
+    }
+}
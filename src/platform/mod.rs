@@ -7,6 +7,29 @@
 #[doc(inline)]
 pub use self::platform_impl::*;
 
+/// A host compiled into CPAL, as returned by `backend_info`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackendInfo {
+    /// The identifier of the host.
+    pub id: HostId,
+    /// The version of the library implementing the host where it can be queried at runtime,
+    /// currently for ALSA, JACK and PulseAudio.
+    pub version: Option<String>,
+}
+
+/// List the devices of the default host along with their capabilities and whether they are
+/// default devices, in a single pass. See `HostTrait::enumerate_with_defaults`.
+///
+/// ```no_run
+/// for info in cpal::enumerate_with_defaults().unwrap() {
+///     let default = if info.is_default_output() { " (default)" } else { "" };
+///     println!("{}{}: {:?}", info.name(), default, info.direction());
+/// }
+/// ```
+pub fn enumerate_with_defaults() -> Result<Vec<crate::DeviceInfo<Device>>, crate::DevicesError> {
+    crate::traits::HostTrait::enumerate_with_defaults(&default_host())
+}
+
 // A macro to assist with implementing a platform's dynamically dispatched `Host` type.
 //
 // These dynamically dispatched types are necessary to allow for users to switch between hosts at
@@ -52,6 +75,11 @@ macro_rules! impl_platform_host {
 
         /// The **Device** implementation associated with the platform's dynamically dispatched
         /// **Host** type.
+        ///
+        /// Devices compare equal, and hash the same, when they refer to the same endpoint of the
+        /// same host, even if they were obtained from separate enumerations. Clones refer to the
+        /// same device.
+        #[derive(Clone)]
         pub struct Device(DeviceInner);
 
         /// The **Devices** iterator associated with the platform's dynamically dispatched **Host**
@@ -83,6 +111,7 @@ macro_rules! impl_platform_host {
             )*
         }
 
+        #[derive(Clone)]
         enum DeviceInner {
             $(
                 $HostVariant(crate::host::$host_mod::Device),
@@ -129,6 +158,22 @@ macro_rules! impl_platform_host {
             }
         }
 
+        impl Device {
+            /// The identifier of the host that this device belongs to.
+            pub fn host_id(&self) -> HostId {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(_) => HostId::$HostVariant,
+                    )*
+                }
+            }
+
+            /// The name of the host that this device belongs to, e.g. "ALSA" or "WASAPI".
+            pub fn host_name(&self) -> &'static str {
+                self.host_id().name()
+            }
+        }
+
         impl Host {
             /// The unique identifier associated with this host.
             pub fn id(&self) -> HostId {
@@ -202,6 +247,32 @@ macro_rules! impl_platform_host {
             }
         }
 
+        // Devices of different hosts never compare equal.
+        impl PartialEq for Device {
+            fn eq(&self, other: &Self) -> bool {
+                #[allow(unreachable_patterns)]
+                match (&self.0, &other.0) {
+                    $(
+                        (DeviceInner::$HostVariant(ref a), DeviceInner::$HostVariant(ref b)) => a == b,
+                    )*
+                    _ => false,
+                }
+            }
+        }
+
+        impl Eq for Device {}
+
+        impl std::hash::Hash for Device {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                std::mem::discriminant(&self.0).hash(state);
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.hash(state),
+                    )*
+                }
+            }
+        }
+
         impl crate::traits::DeviceTrait for Device {
             type SupportedInputConfigs = SupportedInputConfigs;
             type SupportedOutputConfigs = SupportedOutputConfigs;
@@ -239,6 +310,101 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn volume(&self) -> Result<f32, crate::DeviceVolumeError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.volume(),
+                    )*
+                }
+            }
+
+            fn set_volume(&self, volume: f32) -> Result<(), crate::DeviceVolumeError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.set_volume(volume),
+                    )*
+                }
+            }
+
+            fn is_muted(&self) -> Result<bool, crate::DeviceVolumeError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.is_muted(),
+                    )*
+                }
+            }
+
+            fn set_muted(&self, muted: bool) -> Result<(), crate::DeviceVolumeError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.set_muted(muted),
+                    )*
+                }
+            }
+
+            fn supports_input(&self) -> bool {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.supports_input(),
+                    )*
+                }
+            }
+
+            fn supports_output(&self) -> bool {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.supports_output(),
+                    )*
+                }
+            }
+
+            fn default_input_channel_layout(
+                &self,
+            ) -> Result<Option<crate::ChannelLayout>, crate::DefaultStreamConfigError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.default_input_channel_layout(),
+                    )*
+                }
+            }
+
+            fn default_output_channel_layout(
+                &self,
+            ) -> Result<Option<crate::ChannelLayout>, crate::DefaultStreamConfigError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.default_output_channel_layout(),
+                    )*
+                }
+            }
+
+            fn default_buffer_size(
+                &self,
+                config: &crate::SupportedStreamConfig,
+            ) -> Option<crate::FrameCount> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.default_buffer_size(config),
+                    )*
+                }
+            }
+
+            fn is_in_use(&self) -> Result<bool, crate::DeviceInUseError> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.is_in_use(),
+                    )*
+                }
+            }
+
+            fn form_factor(&self) -> Option<crate::FormFactor> {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d.form_factor(),
+                    )*
+                }
+            }
+
             fn default_input_config(&self) -> Result<crate::SupportedStreamConfig, crate::DefaultStreamConfigError> {
                 match self.0 {
                     $(
@@ -255,6 +421,92 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn build_input_stream_planar<T, D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                T: crate::Sample + Send + 'static,
+                D: FnMut(&[&[T]], &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_input_stream_planar(config, data_callback, error_callback)
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
+
+            fn build_output_stream_planar<T, D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                T: crate::Sample + Send + 'static,
+                D: FnMut(&mut [&mut [T]], &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_output_stream_planar(config, data_callback, error_callback)
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
+
+            fn build_input_stream_with_options<T, D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                options: &crate::StreamOptions,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                T: crate::Sample + Send + 'static,
+                D: FnMut(&[T], &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_input_stream_with_options(config, options, data_callback, error_callback)
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
+
+            fn build_output_stream_with_options<T, D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                options: &crate::StreamOptions,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                T: crate::Sample + Send + 'static,
+                D: FnMut(&mut [T], &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_output_stream_with_options(config, options, data_callback, error_callback)
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
+
             fn build_input_stream_raw<D, E>(
                 &self,
                 config: &crate::StreamConfig,
@@ -266,6 +518,7 @@ macro_rules! impl_platform_host {
                 D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
                 E: FnMut(crate::StreamError) + Send + 'static,
             {
+                config.validate()?;
                 match self.0 {
                     $(
                         DeviceInner::$HostVariant(ref d) => d
@@ -292,6 +545,7 @@ macro_rules! impl_platform_host {
                 D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
                 E: FnMut(crate::StreamError) + Send + 'static,
             {
+                config.validate()?;
                 match self.0 {
                     $(
                         DeviceInner::$HostVariant(ref d) => d
@@ -306,6 +560,107 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+            fn build_input_stream_raw_with_periods<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                periods: u32,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                config.validate()?;
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_input_stream_raw_with_periods(
+                                config,
+                                sample_format,
+                                periods,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
+
+            fn build_output_stream_raw_with_periods<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                periods: u32,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                config.validate()?;
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_output_stream_raw_with_periods(
+                                config,
+                                sample_format,
+                                periods,
+                                data_callback,
+                                error_callback,
+                            )
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
+
+            fn build_input_stream_bit_perfect<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<(Self::Stream, crate::SampleFormat), crate::BuildStreamError>
+            where
+                D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                config.validate()?;
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_input_stream_bit_perfect(config, data_callback, error_callback)
+                            .map(|(stream, sample_format)| {
+                                (Stream::from(StreamInner::$HostVariant(stream)), sample_format)
+                            }),
+                    )*
+                }
+            }
+
+            fn build_output_stream_bit_perfect<D, E>(
+                &self,
+                config: &crate::StreamConfig,
+                data_callback: D,
+                error_callback: E,
+            ) -> Result<(Self::Stream, crate::SampleFormat), crate::BuildStreamError>
+            where
+                D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                config.validate()?;
+                match self.0 {
+                    $(
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_output_stream_bit_perfect(config, data_callback, error_callback)
+                            .map(|(stream, sample_format)| {
+                                (Stream::from(StreamInner::$HostVariant(stream)), sample_format)
+                            }),
+                    )*
+                }
+            }
         }
 
         impl crate::traits::HostTrait for Host {
@@ -345,6 +700,30 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn default_input_device_for_role(&self, role: crate::Role) -> Option<Self::Device> {
+                match self.0 {
+                    $(
+                        HostInner::$HostVariant(ref h) => {
+                            h.default_input_device_for_role(role)
+                                .map(DeviceInner::$HostVariant)
+                                .map(Device::from)
+                        }
+                    )*
+                }
+            }
+
+            fn default_output_device_for_role(&self, role: crate::Role) -> Option<Self::Device> {
+                match self.0 {
+                    $(
+                        HostInner::$HostVariant(ref h) => {
+                            h.default_output_device_for_role(role)
+                                .map(DeviceInner::$HostVariant)
+                                .map(Device::from)
+                        }
+                    )*
+                }
+            }
         }
 
         impl crate::traits::StreamTrait for Stream {
@@ -367,6 +746,36 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn config(&self) -> crate::StreamConfig {
+                match self.0 {
+                    $(
+                        StreamInner::$HostVariant(ref s) => {
+                            s.config()
+                        }
+                    )*
+                }
+            }
+
+            fn frame_position(&self) -> u64 {
+                match self.0 {
+                    $(
+                        StreamInner::$HostVariant(ref s) => {
+                            s.frame_position()
+                        }
+                    )*
+                }
+            }
+
+            fn is_playing(&self) -> bool {
+                match self.0 {
+                    $(
+                        StreamInner::$HostVariant(ref s) => {
+                            s.is_playing()
+                        }
+                    )*
+                }
+            }
         }
 
         impl From<DeviceInner> for Device {
@@ -430,6 +839,30 @@ macro_rules! impl_platform_host {
             host_ids
         }
 
+        /// The hosts compiled into CPAL on this platform, along with the version of the library
+        /// implementing each of them where it can be queried.
+        ///
+        /// Hosts are listed whether or not they are available, in the order of `ALL_HOSTS`.
+        ///
+        /// ```
+        /// for info in cpal::backend_info() {
+        ///     match info.version {
+        ///         Some(version) => println!("{} {}", info.id.name(), version),
+        ///         None => println!("{}", info.id.name()),
+        ///     }
+        /// }
+        /// ```
+        pub fn backend_info() -> Vec<crate::platform::BackendInfo> {
+            vec![
+                $(
+                    crate::platform::BackendInfo {
+                        id: HostId::$HostVariant,
+                        version: crate::host::$host_mod::Host::library_version(),
+                    },
+                )*
+            ]
+        }
+
         /// Given a unique host identifier, initialise and produce the host if it is available.
         pub fn host_from_id(id: HostId) -> Result<Host, crate::HostUnavailable> {
             match id {
@@ -445,37 +878,86 @@ macro_rules! impl_platform_host {
     };
 }
 
-// TODO: Add pulseaudio and jack here eventually.
+// The default host of Linux, dragonfly and freebsd is ALSA, unless its feature is disabled or the
+// `null-backend` feature is enabled. JACK and PulseAudio are available alongside either.
 #[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
 mod platform_impl {
+    #[cfg(all(feature = "alsa", not(feature = "null-backend")))]
     pub use crate::host::alsa::{
-        Device as AlsaDevice, Devices as AlsaDevices, Host as AlsaHost, Stream as AlsaStream,
-        SupportedInputConfigs as AlsaSupportedInputConfigs,
+        AlsaAccess, Device as AlsaDevice, Devices as AlsaDevices, Host as AlsaHost,
+        Stream as AlsaStream, SupportedInputConfigs as AlsaSupportedInputConfigs,
         SupportedOutputConfigs as AlsaSupportedOutputConfigs,
     };
     #[cfg(feature = "jack")]
     pub use crate::host::jack::{
-        Device as JackDevice, Devices as JackDevices, Host as JackHost, Stream as JackStream,
+        Device as JackDevice, Devices as JackDevices, Host as JackHost, Midi as JackMidi,
+        MidiEvent as JackMidiEvent, Stream as JackStream,
         SupportedInputConfigs as JackSupportedInputConfigs,
-        SupportedOutputConfigs as JackSupportedOutputConfigs,
+        SupportedOutputConfigs as JackSupportedOutputConfigs, Transport as JackTransport,
+        TransportState as JackTransportState,
+    };
+    #[cfg(any(not(feature = "alsa"), feature = "null-backend"))]
+    pub use crate::host::null::{
+        Device as NullDevice, Devices as NullDevices, Host as NullHost,
+        SupportedInputConfigs as NullSupportedInputConfigs,
+        SupportedOutputConfigs as NullSupportedOutputConfigs,
     };
 
-    #[cfg(feature = "jack")]
-    impl_platform_host!(Jack jack "JACK", Alsa alsa "ALSA");
+    #[cfg(feature = "pulseaudio")]
+    pub use crate::host::pulseaudio::{
+        Device as PulseAudioDevice, Devices as PulseAudioDevices, Host as PulseAudioHost,
+        Stream as PulseAudioStream, SupportedInputConfigs as PulseAudioSupportedInputConfigs,
+        SupportedOutputConfigs as PulseAudioSupportedOutputConfigs,
+    };
+
+    // Implements the platform host for the optional hosts that are enabled, followed by the given
+    // default host.
+    macro_rules! impl_linux_host {
+        ($($default_host:tt)*) => {
+            #[cfg(all(feature = "jack", feature = "pulseaudio"))]
+            impl_platform_host!(Jack jack "JACK", PulseAudio pulseaudio "PulseAudio", $($default_host)*);
+
+            #[cfg(all(feature = "jack", not(feature = "pulseaudio")))]
+            impl_platform_host!(Jack jack "JACK", $($default_host)*);
+
+            #[cfg(all(not(feature = "jack"), feature = "pulseaudio"))]
+            impl_platform_host!(PulseAudio pulseaudio "PulseAudio", $($default_host)*);
+
+            #[cfg(not(any(feature = "jack", feature = "pulseaudio")))]
+            impl_platform_host!($($default_host)*);
+        };
+    }
 
-    #[cfg(not(feature = "jack"))]
-    impl_platform_host!(Alsa alsa "ALSA");
+    #[cfg(all(feature = "alsa", not(feature = "null-backend")))]
+    impl_linux_host!(Alsa alsa "ALSA");
+
+    #[cfg(any(not(feature = "alsa"), feature = "null-backend"))]
+    impl_linux_host!(Null null "Null");
 
     /// The default host for the current compilation target platform.
+    #[cfg(all(feature = "alsa", not(feature = "null-backend")))]
     pub fn default_host() -> Host {
         AlsaHost::new()
             .expect("the default host should always be available")
             .into()
     }
+
+    /// The default host for the current compilation target platform.
+    #[cfg(any(not(feature = "alsa"), feature = "null-backend"))]
+    pub fn default_host() -> Host {
+        NullHost::new()
+            .expect("the default host should always be available")
+            .into()
+    }
 }
 
-#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[cfg(all(
+    any(target_os = "macos", target_os = "ios"),
+    not(feature = "null-backend")
+))]
 mod platform_impl {
+    #[cfg(target_os = "macos")]
+    pub use crate::host::coreaudio::AggregateDevice as CoreAudioAggregateDevice;
     pub use crate::host::coreaudio::{
         Device as CoreAudioDevice, Devices as CoreAudioDevices, Host as CoreAudioHost,
         Stream as CoreAudioStream, SupportedInputConfigs as CoreAudioSupportedInputConfigs,
@@ -492,7 +974,7 @@ mod platform_impl {
     }
 }
 
-#[cfg(target_os = "emscripten")]
+#[cfg(all(target_os = "emscripten", not(feature = "null-backend")))]
 mod platform_impl {
     pub use crate::host::emscripten::{
         Device as EmscriptenDevice, Devices as EmscriptenDevices, Host as EmscriptenHost,
@@ -510,7 +992,11 @@ mod platform_impl {
     }
 }
 
-#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+#[cfg(all(
+    target_arch = "wasm32",
+    feature = "wasm-bindgen",
+    not(feature = "null-backend")
+))]
 mod platform_impl {
     pub use crate::host::webaudio::{
         Device as WebAudioDevice, Devices as WebAudioDevices, Host as WebAudioHost,
@@ -528,6 +1014,8 @@ mod platform_impl {
     }
 }
 
+// The default host of Windows is WASAPI, unless its feature is disabled or the `null-backend`
+// feature is enabled. ASIO is available alongside either.
 #[cfg(windows)]
 mod platform_impl {
     #[cfg(feature = "asio")]
@@ -536,27 +1024,59 @@ mod platform_impl {
         SupportedInputConfigs as AsioSupportedInputConfigs,
         SupportedOutputConfigs as AsioSupportedOutputConfigs,
     };
+    #[cfg(any(not(feature = "wasapi"), feature = "null-backend"))]
+    pub use crate::host::null::{
+        Device as NullDevice, Devices as NullDevices, Host as NullHost,
+        SupportedInputConfigs as NullSupportedInputConfigs,
+        SupportedOutputConfigs as NullSupportedOutputConfigs,
+    };
+    #[cfg(all(feature = "wasapi", not(feature = "null-backend")))]
     pub use crate::host::wasapi::{
         Device as WasapiDevice, Devices as WasapiDevices, Host as WasapiHost,
         Stream as WasapiStream, SupportedInputConfigs as WasapiSupportedInputConfigs,
-        SupportedOutputConfigs as WasapiSupportedOutputConfigs,
+        SupportedOutputConfigs as WasapiSupportedOutputConfigs, WasapiServiceMode,
     };
 
-    #[cfg(feature = "asio")]
+    #[cfg(all(feature = "asio", feature = "wasapi", not(feature = "null-backend")))]
     impl_platform_host!(Asio asio "ASIO", Wasapi wasapi "WASAPI");
 
-    #[cfg(not(feature = "asio"))]
+    #[cfg(all(
+        not(feature = "asio"),
+        feature = "wasapi",
+        not(feature = "null-backend")
+    ))]
     impl_platform_host!(Wasapi wasapi "WASAPI");
 
+    #[cfg(all(
+        feature = "asio",
+        any(not(feature = "wasapi"), feature = "null-backend")
+    ))]
+    impl_platform_host!(Asio asio "ASIO", Null null "Null");
+
+    #[cfg(all(
+        not(feature = "asio"),
+        any(not(feature = "wasapi"), feature = "null-backend")
+    ))]
+    impl_platform_host!(Null null "Null");
+
     /// The default host for the current compilation target platform.
+    #[cfg(all(feature = "wasapi", not(feature = "null-backend")))]
     pub fn default_host() -> Host {
         WasapiHost::new()
             .expect("the default host should always be available")
             .into()
     }
+
+    /// The default host for the current compilation target platform.
+    #[cfg(any(not(feature = "wasapi"), feature = "null-backend"))]
+    pub fn default_host() -> Host {
+        NullHost::new()
+            .expect("the default host should always be available")
+            .into()
+    }
 }
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", not(feature = "null-backend")))]
 mod platform_impl {
     pub use crate::host::oboe::{
         Device as OboeDevice, Devices as OboeDevices, Host as OboeHost, Stream as OboeStream,
@@ -579,11 +1099,16 @@ mod platform_impl {
     target_os = "linux",
     target_os = "dragonfly",
     target_os = "freebsd",
-    target_os = "macos",
-    target_os = "ios",
-    target_os = "emscripten",
-    target_os = "android",
-    all(target_arch = "wasm32", feature = "wasm-bindgen"),
+    all(
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "emscripten",
+            target_os = "android",
+            all(target_arch = "wasm32", feature = "wasm-bindgen"),
+        ),
+        not(feature = "null-backend")
+    ),
 )))]
 mod platform_impl {
     pub use crate::host::null::{
@@ -617,3 +1142,82 @@ impl Default for NotSendSyncAcrossAllPlatforms {
         NotSendSyncAcrossAllPlatforms(std::marker::PhantomData)
     }
 }
+
+// Compile-time checks of the thread-safety guarantees documented at the crate root.
+#[cfg(test)]
+mod test {
+    use traits::HostTrait;
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn default_device_is_default() {
+        let host = super::default_host();
+        if let Some(device) = host.default_output_device() {
+            assert!(host.is_default_output_device(&device));
+        }
+        if let Some(device) = host.default_input_device() {
+            assert!(host.is_default_input_device(&device));
+        }
+    }
+
+    #[test]
+    fn devices_are_enumerated_on_a_worker_thread() {
+        use std::sync::mpsc;
+        use std::thread;
+        use traits::DeviceTrait;
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let host = super::default_host();
+            let devices = host.devices().unwrap().inspect(|device| {
+                let _ = device.supported_output_configs().map(|c| c.count());
+            });
+            sender.send(devices.collect::<Vec<_>>()).unwrap();
+        })
+        .join()
+        .unwrap();
+        // The devices can be used from the thread that received them.
+        for device in receiver.recv().unwrap() {
+            device.name().unwrap();
+        }
+    }
+
+    #[test]
+    fn host_and_device_are_send_sync() {
+        assert_send::<super::Host>();
+        assert_sync::<super::Host>();
+        assert_send::<super::Device>();
+        assert_sync::<super::Device>();
+        assert_send::<super::Devices>();
+        assert_send::<super::HostId>();
+        assert_sync::<super::HostId>();
+    }
+
+    #[test]
+    #[cfg(all(
+        any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
+        feature = "alsa",
+        not(feature = "null-backend")
+    ))]
+    fn alsa_stream_is_send_sync() {
+        assert_send::<super::AlsaStream>();
+        assert_sync::<super::AlsaStream>();
+    }
+
+    #[test]
+    #[cfg(all(
+        any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"),
+        feature = "jack"
+    ))]
+    fn jack_stream_is_send() {
+        assert_send::<super::JackStream>();
+    }
+
+    #[test]
+    #[cfg(all(windows, feature = "wasapi", not(feature = "null-backend")))]
+    fn wasapi_stream_is_send() {
+        assert_send::<super::WasapiStream>();
+    }
+}
@@ -0,0 +1,421 @@
+#[cfg(feature = "wav")]
+use std::fs::File;
+#[cfg(feature = "wav")]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[cfg(feature = "wav")]
+use channel_map;
+use traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "wav")]
+use wav;
+#[cfg(feature = "wav")]
+use ChannelMap;
+use {
+    ChannelCount, OutputCallbackInfo, PlaybackError, Sample, SampleFormat, SampleRate, Stream,
+    StreamConfig, StreamError, StreamInstant,
+};
+
+// The value of `State::seek` while no seek is pending.
+const NO_SEEK: u64 = u64::MAX;
+
+/// Play interleaved `f32` samples once on the default output device of the default host.
+///
+/// The device is opened with the given channel count and sample rate, in the sample format it
+/// supports best for them, to which the samples are converted. There is no resampling or channel
+/// mapping: returns `BuildStreamError::StreamConfigNotSupported` if the device does not support
+/// the channel count at the sample rate. The samples are copied, so `samples` need not outlive
+/// the playback.
+///
+/// ```no_run
+/// let samples: Vec<f32> = (0..48_000)
+///     .map(|i| (i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48_000.0).sin() * 0.2)
+///     .collect();
+/// cpal::play_samples(&samples, cpal::SampleRate::HZ_48000, 1)
+///     .unwrap()
+///     .wait()
+///     .unwrap();
+/// ```
+pub fn play_samples(
+    samples: &[f32],
+    sample_rate: SampleRate,
+    channels: ChannelCount,
+) -> Result<PlaybackHandle<Stream>, PlaybackError> {
+    let device = ::default_host()
+        .default_output_device()
+        .ok_or(PlaybackError::NoDevice)?;
+    play_samples_on(&device, samples.to_vec(), sample_rate, channels)
+}
+
+/// Play a WAV file once on the default output device of the default host.
+///
+/// The whole file is read and decoded up front, from 8, 16, 24 or 32-bit integer PCM or 32-bit
+/// float. If the device does not support the channel count of the file at its sample rate, the
+/// audio is converted to the default output config of the device: its channels are mixed as with
+/// `ChannelMap::Default`, and it is resampled with linear interpolation.
+///
+/// Requires the `wav` feature.
+///
+/// ```no_run
+/// let playback = cpal::play_file("sound.wav").unwrap();
+/// playback.set_looping(true);
+/// playback.wait().unwrap();
+/// ```
+#[cfg(feature = "wav")]
+pub fn play_file<P>(path: P) -> Result<PlaybackHandle<Stream>, PlaybackError>
+where
+    P: AsRef<Path>,
+{
+    let (spec, samples) = wav::read(File::open(path)?)?;
+    let device = ::default_host()
+        .default_output_device()
+        .ok_or(PlaybackError::NoDevice)?;
+    play_converted_on(&device, samples, spec.sample_rate, spec.channels)
+}
+
+/// Samples being played, created via `play_samples` or `play_file`.
+///
+/// Dropping the handle stops the playback like `stop`.
+pub struct PlaybackHandle<S> {
+    stream: S,
+    state: Arc<State>,
+    sample_rate: SampleRate,
+}
+
+// Shared with the data and error callbacks.
+struct State {
+    // `None` while playing, then what `wait` returns.
+    result: Mutex<Option<Result<(), StreamError>>>,
+    finished: Condvar,
+    // The frame from which to continue playing, or `NO_SEEK`.
+    seek: AtomicU64,
+    // Whether to start over once all samples were played.
+    looping: AtomicBool,
+}
+
+impl State {
+    fn finish(&self, result: Result<(), StreamError>) {
+        let mut current = self.result.lock().unwrap();
+        if current.is_none() {
+            *current = Some(result);
+            self.finished.notify_all();
+        }
+    }
+}
+
+impl<S> PlaybackHandle<S>
+where
+    S: StreamTrait,
+{
+    /// The underlying output stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// Whether all samples have been played, or the stream failed.
+    pub fn is_finished(&self) -> bool {
+        self.state.result.lock().unwrap().is_some()
+    }
+
+    /// Continue playing from `position`, the time from the start of the samples, or stop playing
+    /// if it is past their end.
+    ///
+    /// Samples already handed to the device are still played, and once the playback finished,
+    /// seeking has no effect.
+    pub fn seek(&self, position: Duration) {
+        let frames = position.as_secs_f64() * self.sample_rate.0 as f64;
+        let frame = (frames.round() as u64).min(NO_SEEK - 1);
+        self.state.seek.store(frame, Ordering::Relaxed);
+    }
+
+    /// Whether the samples are played again from the start once they were all played.
+    pub fn is_looping(&self) -> bool {
+        self.state.looping.load(Ordering::Relaxed)
+    }
+
+    /// Set whether the samples are played again from the start once they were all played, in
+    /// which case the playback only finishes when the stream fails. (default is `false`)
+    ///
+    /// Once the playback finished, looping has no effect.
+    pub fn set_looping(&self, looping: bool) {
+        self.state.looping.store(looping, Ordering::Relaxed);
+    }
+
+    /// Block until all samples have been played by the device, or until the stream reports an
+    /// error, which is returned.
+    pub fn wait(self) -> Result<(), StreamError> {
+        let mut result = self.state.result.lock().unwrap();
+        loop {
+            match result.take() {
+                Some(result) => return result,
+                None => result = self.state.finished.wait(result).unwrap(),
+            }
+        }
+    }
+
+    /// Stop playing, dropping the samples that were not played yet.
+    pub fn stop(self) {}
+}
+
+// Plays `samples` once on `device`.
+pub(crate) fn play_samples_on<D>(
+    device: &D,
+    samples: Vec<f32>,
+    sample_rate: SampleRate,
+    channels: ChannelCount,
+) -> Result<PlaybackHandle<D::Stream>, PlaybackError>
+where
+    D: DeviceTrait,
+{
+    let supported = device
+        .supported_output_configs()?
+        .filter(|range| {
+            range.channels() == channels
+                && range.min_sample_rate() <= sample_rate
+                && sample_rate <= range.max_sample_rate()
+        })
+        .max_by(|a, b| a.cmp_default_heuristics(b))
+        .ok_or(::BuildStreamError::StreamConfigNotSupported)?
+        .with_sample_rate(sample_rate);
+    let config = supported.config();
+    let state = Arc::new(State {
+        result: Mutex::new(None),
+        finished: Condvar::new(),
+        seek: AtomicU64::new(NO_SEEK),
+        looping: AtomicBool::new(false),
+    });
+    let stream = match supported.sample_format() {
+        SampleFormat::I16 => build::<i16, _>(device, &config, samples, &state)?,
+        SampleFormat::U16 => build::<u16, _>(device, &config, samples, &state)?,
+        SampleFormat::U8 => build::<u8, _>(device, &config, samples, &state)?,
+        SampleFormat::F32 => build::<f32, _>(device, &config, samples, &state)?,
+    };
+    stream.play()?;
+    Ok(PlaybackHandle {
+        stream,
+        state,
+        sample_rate,
+    })
+}
+
+// Plays `samples` once on `device`, converting them to its default output config should it not
+// support `channels` at `sample_rate`.
+#[cfg(feature = "wav")]
+fn play_converted_on<D>(
+    device: &D,
+    samples: Vec<f32>,
+    sample_rate: SampleRate,
+    channels: ChannelCount,
+) -> Result<PlaybackHandle<D::Stream>, PlaybackError>
+where
+    D: DeviceTrait,
+{
+    let supported = device.supported_output_configs()?.any(|range| {
+        range.channels() == channels
+            && range.min_sample_rate() <= sample_rate
+            && sample_rate <= range.max_sample_rate()
+    });
+    if supported {
+        return play_samples_on(device, samples, sample_rate, channels);
+    }
+    let config = device.default_output_config()?;
+    let matrix = ChannelMap::Default.matrix(channels, config.channels())?;
+    let mut mixed = vec![0.0; samples.len() / channels as usize * config.channels() as usize];
+    channel_map::mix(&matrix, &samples, &mut mixed);
+    let resampled = resample(&mixed, config.channels(), sample_rate, config.sample_rate());
+    play_samples_on(device, resampled, config.sample_rate(), config.channels())
+}
+
+// Resamples interleaved `samples` from `from` to `to` with linear interpolation.
+#[cfg(feature = "wav")]
+fn resample(samples: &[f32], channels: ChannelCount, from: SampleRate, to: SampleRate) -> Vec<f32> {
+    let channels = channels as usize;
+    let frames = samples.len() / channels;
+    if from == to || frames == 0 {
+        return samples.to_vec();
+    }
+    let step = from.0 as f64 / to.0 as f64;
+    let resampled_frames = (frames as f64 / step).floor() as usize;
+    let mut resampled = Vec::with_capacity(resampled_frames * channels);
+    for frame in 0..resampled_frames {
+        let position = frame as f64 * step;
+        let index = position as usize;
+        let next = (index + 1).min(frames - 1);
+        let fraction = (position - index as f64) as f32;
+        for channel in 0..channels {
+            let a = samples[index * channels + channel];
+            let b = samples[next * channels + channel];
+            resampled.push(a + (b - a) * fraction);
+        }
+    }
+    resampled
+}
+
+fn build<T, D>(
+    device: &D,
+    config: &StreamConfig,
+    samples: Vec<f32>,
+    state: &Arc<State>,
+) -> Result<D::Stream, PlaybackError>
+where
+    T: Sample + Send + 'static,
+    D: DeviceTrait,
+{
+    let mut position = 0;
+    // When the last sample is to be played, once all of them were written.
+    let mut end: Option<StreamInstant> = None;
+    let mut finished = false;
+    let sample_rate = config.sample_rate;
+    let channels = config.channels as usize;
+    let data_state = state.clone();
+    let error_state = state.clone();
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &OutputCallbackInfo| {
+            if !finished {
+                let seek = data_state.seek.swap(NO_SEEK, Ordering::Relaxed);
+                if seek != NO_SEEK {
+                    let sample = (seek as usize).saturating_mul(channels);
+                    position = sample.min(samples.len());
+                    end = None;
+                }
+            }
+            if let Some(end) = end {
+                data.iter_mut().for_each(|sample| *sample = T::EQUILIBRIUM);
+                if !finished && info.timestamp().callback >= end {
+                    finished = true;
+                    data_state.finish(Ok(()));
+                }
+                return;
+            }
+            let looping = data_state.looping.load(Ordering::Relaxed) && !samples.is_empty();
+            let mut n = 0;
+            loop {
+                let remaining = &samples[position..];
+                let written = remaining.len().min(data.len() - n);
+                for (sample, &value) in data[n..].iter_mut().zip(remaining) {
+                    *sample = T::from(&value);
+                }
+                n += written;
+                position += written;
+                if n == data.len() || !looping {
+                    break;
+                }
+                position = 0;
+            }
+            data[n..]
+                .iter_mut()
+                .for_each(|sample| *sample = T::EQUILIBRIUM);
+            if position == samples.len() && !looping {
+                let frames = (n / channels) as u64;
+                let duration = ::frames_duration(frames, sample_rate);
+                let playback = info.timestamp().playback;
+                end = Some(playback.add(duration).unwrap_or(playback));
+            }
+        },
+        move |err| error_state.finish(Err(err)),
+    )?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod test {
+    use super::play_samples_on;
+    use std::time::Duration;
+    use test::TestDevice;
+    use {BuildStreamError, PlaybackError, SampleRate};
+
+    #[test]
+    fn plays_once() {
+        let device = TestDevice::new(2, SampleRate(1_000));
+        let samples: Vec<f32> = (0..12).map(|i| i as f32 / 16.0).collect();
+        let playback = play_samples_on(&device, samples.clone(), SampleRate(1_000), 2).unwrap();
+        device.process(4);
+        device.process(4);
+        // The last samples were written, but not played yet.
+        assert!(!playback.is_finished());
+        device.process(4);
+        assert!(playback.is_finished());
+        playback.wait().unwrap();
+
+        let output = device.recorded_output();
+        assert_eq!(output[..12], samples[..]);
+        assert!(output[12..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn stop() {
+        let device = TestDevice::new(1, SampleRate(1_000));
+        let playback = play_samples_on(&device, vec![0.5; 8], SampleRate(1_000), 1).unwrap();
+        device.process(4);
+        playback.stop();
+        device.process(4);
+        assert_eq!(
+            device.recorded_output(),
+            [0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn seek() {
+        let device = TestDevice::new(1, SampleRate(1_000));
+        let samples: Vec<f32> = (0..8).map(|i| i as f32 / 8.0).collect();
+        let playback = play_samples_on(&device, samples.clone(), SampleRate(1_000), 1).unwrap();
+        device.process(2);
+        playback.seek(Duration::from_millis(5));
+        device.process(4);
+        let output = device.recorded_output();
+        assert_eq!(output[..5], [0.0, 0.125, 0.625, 0.75, 0.875]);
+        assert_eq!(output[5], 0.0);
+    }
+
+    #[test]
+    fn looping() {
+        let device = TestDevice::new(1, SampleRate(1_000));
+        let playback =
+            play_samples_on(&device, vec![0.25, 0.5, 0.75], SampleRate(1_000), 1).unwrap();
+        playback.set_looping(true);
+        device.process(4);
+        device.process(4);
+        assert!(!playback.is_finished());
+        playback.set_looping(false);
+        device.process(4);
+        device.process(4);
+        assert!(playback.is_finished());
+        let output = device.recorded_output();
+        assert_eq!(
+            output[..9],
+            [0.25, 0.5, 0.75, 0.25, 0.5, 0.75, 0.25, 0.5, 0.75]
+        );
+        assert!(output[9..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn converts_unsupported_configs() {
+        let device = TestDevice::new(2, SampleRate(1_000));
+        let samples = vec![0.0, 0.5, 1.0];
+        let playback = super::play_converted_on(&device, samples, SampleRate(500), 1).unwrap();
+        device.process(6);
+        drop(playback);
+        // Upmixed to stereo and resampled to the rate of the device, holding the last frame.
+        assert_eq!(
+            device.recorded_output(),
+            [0.0, 0.0, 0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn unsupported_config() {
+        let device = TestDevice::new(2, SampleRate(1_000));
+        let result = play_samples_on(&device, vec![0.0; 4], SampleRate(1_000), 1);
+        match result {
+            Err(PlaybackError::BuildStream {
+                err: BuildStreamError::StreamConfigNotSupported,
+            }) => (),
+            _ => panic!("expected StreamConfigNotSupported"),
+        }
+    }
+}
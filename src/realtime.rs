@@ -0,0 +1,129 @@
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+extern crate libc;
+#[cfg(target_os = "windows")]
+extern crate winapi;
+
+use BackendSpecificError;
+
+// Promotes the calling thread to realtime scheduling, with `SCHED_FIFO` at the middle of its
+// priority range. Threads that already use a realtime policy keep their priority.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+pub(crate) fn promote_current_thread() -> Result<(), BackendSpecificError> {
+    if runs_realtime_policy() {
+        return Ok(());
+    }
+    let errno = unsafe {
+        let min = libc::sched_get_priority_min(libc::SCHED_FIFO);
+        let max = libc::sched_get_priority_max(libc::SCHED_FIFO);
+        let param = libc::sched_param {
+            sched_priority: (min + max) / 2,
+        };
+        libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param)
+    };
+    if errno != 0 {
+        let err = std::io::Error::from_raw_os_error(errno);
+        let description = format!("failed to enable realtime scheduling: {}", err);
+        return Err(BackendSpecificError { description });
+    }
+    Ok(())
+}
+
+// Whether the calling thread already runs with a realtime scheduling policy.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+fn runs_realtime_policy() -> bool {
+    unsafe {
+        let mut policy = 0;
+        let mut param: libc::sched_param = std::mem::zeroed();
+        libc::pthread_getschedparam(libc::pthread_self(), &mut policy, &mut param) == 0
+            && (policy == libc::SCHED_FIFO || policy == libc::SCHED_RR)
+    }
+}
+
+// Restricts the calling thread to the given CPUs.
+#[cfg(target_os = "linux")]
+pub(crate) fn pin_current_thread(cpus: &[usize]) -> Result<(), BackendSpecificError> {
+    let errno = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            if cpu >= 8 * std::mem::size_of::<libc::cpu_set_t>() {
+                let description = format!("CPU {} is out of range of the affinity mask", cpu);
+                return Err(BackendSpecificError { description });
+            }
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::pthread_setaffinity_np(
+            libc::pthread_self(),
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &set,
+        )
+    };
+    if errno != 0 {
+        let err = std::io::Error::from_raw_os_error(errno);
+        let description = format!("failed to set the CPU affinity: {}", err);
+        return Err(BackendSpecificError { description });
+    }
+    Ok(())
+}
+
+// Registers the calling thread with the Multimedia Class Scheduler Service as a "Pro Audio" task.
+//
+// The registration ends with the thread, which is when the stream is dropped.
+#[cfg(target_os = "windows")]
+pub(crate) fn promote_current_thread() -> Result<(), BackendSpecificError> {
+    use self::winapi::um::avrt::AvSetMmThreadCharacteristicsW;
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+
+    let task: Vec<u16> = OsStr::new("Pro Audio")
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    let mut task_index = 0;
+    let handle = unsafe { AvSetMmThreadCharacteristicsW(task.as_ptr(), &mut task_index) };
+    if handle.is_null() {
+        let err = std::io::Error::last_os_error();
+        let description = format!("failed to register the thread with MMCSS: {}", err);
+        return Err(BackendSpecificError { description });
+    }
+    Ok(())
+}
+
+// Restricts the calling thread to the given CPUs.
+#[cfg(target_os = "windows")]
+pub(crate) fn pin_current_thread(cpus: &[usize]) -> Result<(), BackendSpecificError> {
+    use self::winapi::um::processthreadsapi::GetCurrentThread;
+    use self::winapi::um::winbase::SetThreadAffinityMask;
+
+    let mut mask = 0usize;
+    for &cpu in cpus {
+        if cpu >= 8 * std::mem::size_of::<usize>() {
+            let description = format!("CPU {} is out of range of the affinity mask", cpu);
+            return Err(BackendSpecificError { description });
+        }
+        mask |= 1 << cpu;
+    }
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } == 0 {
+        let err = std::io::Error::last_os_error();
+        let description = format!("failed to set the CPU affinity: {}", err);
+        return Err(BackendSpecificError { description });
+    }
+    Ok(())
+}
+
+// The threads of CoreAudio belong to the OS, and the remaining platforms are not supported.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub(crate) fn pin_current_thread(_cpus: &[usize]) -> Result<(), BackendSpecificError> {
+    Ok(())
+}
+
+// CoreAudio already runs its callbacks on realtime threads, and the remaining hosts have no
+// thread of their own to promote.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "windows"
+)))]
+pub(crate) fn promote_current_thread() -> Result<(), BackendSpecificError> {
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ring_buffer::{self, Consumer};
+use traits::{DeviceTrait, StreamTrait};
+use wav::{WavSpec, WavWriter};
+use {RecordError, Sample, SampleFormat, StreamConfig, StreamError, SupportedStreamConfig};
+
+// How long the writer thread sleeps whenever it finds the ring buffer empty.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Record the input of `device` to a WAV file at `path`, created or truncated.
+///
+/// The input stream is built with `config` and played right away. Its data callback only pushes
+/// samples to a ring buffer holding a second of audio, from which a separate thread writes them
+/// to the file, so that the audio thread never waits for the disk. Samples captured while the
+/// ring buffer is full are dropped.
+///
+/// Recording stops once `duration` worth of frames has been written, if given, or else when
+/// `WavRecording::stop` is called or the recording is dropped. `I16` and `U16` samples are stored
+/// as 16-bit PCM and `F32` samples as 32-bit float.
+///
+/// ```no_run
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use std::time::Duration;
+///
+/// let device = cpal::default_host().default_input_device().unwrap();
+/// let config = device.default_input_config().unwrap();
+/// let duration = Some(Duration::from_secs(5));
+/// let recording =
+///     cpal::record_to_wav(&device, &config, "mic.wav", duration, |err| eprintln!("{}", err))
+///         .unwrap();
+/// recording.wait().unwrap();
+/// ```
+pub fn record_to_wav<D, P, E>(
+    device: &D,
+    config: &SupportedStreamConfig,
+    path: P,
+    duration: Option<Duration>,
+    error_callback: E,
+) -> Result<WavRecording<D::Stream>, RecordError>
+where
+    D: DeviceTrait,
+    P: AsRef<Path>,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let spec = WavSpec {
+        channels: config.channels(),
+        sample_rate: config.sample_rate(),
+    };
+    let file = BufWriter::new(File::create(path)?);
+    let writer = WavWriter::new(file, spec, config.sample_format())?;
+    let limit = duration
+        .map(|duration| config.frames_in_duration(duration) as usize * config.channels() as usize);
+    let sample_format = config.sample_format();
+    let config = config.config();
+    match sample_format {
+        SampleFormat::I16 => start::<i16, _, _>(device, &config, writer, limit, error_callback),
+        SampleFormat::U16 => start::<u16, _, _>(device, &config, writer, limit, error_callback),
+        SampleFormat::U8 => start::<u8, _, _>(device, &config, writer, limit, error_callback),
+        SampleFormat::F32 => start::<f32, _, _>(device, &config, writer, limit, error_callback),
+    }
+}
+
+/// An input stream being recorded to a WAV file, created via `record_to_wav`.
+///
+/// Dropping the recording stops it like `stop`, ignoring any error.
+pub struct WavRecording<S> {
+    stream: S,
+    // Asks the writer thread to drain the ring buffer and finalize the file.
+    stop: Arc<AtomicBool>,
+    // Set by the writer thread once it returned.
+    finished: Arc<AtomicBool>,
+    // `None` once the writer thread has been joined.
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl<S> WavRecording<S>
+where
+    S: StreamTrait,
+{
+    /// The underlying input stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// Whether the requested duration has been written, or writing the file failed.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Stop recording, write the samples captured so far and finalize the file.
+    pub fn stop(mut self) -> Result<(), RecordError> {
+        self.stop.store(true, Ordering::Release);
+        self.join()
+    }
+
+    /// Block until the duration given to `record_to_wav` has been written, then finalize the
+    /// file. Without a duration, this blocks until writing the file fails.
+    pub fn wait(mut self) -> Result<(), RecordError> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<(), RecordError> {
+        match self.thread.take() {
+            Some(thread) => match thread.join() {
+                Ok(result) => Ok(result?),
+                Err(_) => Err(io::Error::other("the writer thread panicked").into()),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S> Drop for WavRecording<S> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn start<T, D, E>(
+    device: &D,
+    config: &StreamConfig,
+    writer: WavWriter<BufWriter<File>>,
+    limit: Option<usize>,
+    error_callback: E,
+) -> Result<WavRecording<D::Stream>, RecordError>
+where
+    T: Sample + Send + 'static,
+    D: DeviceTrait,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let frames = config.sample_rate.0 as usize;
+    let (producer, consumer) = ring_buffer::ring_buffer::<T>(frames, config.channels);
+    let stream = device.build_input_stream_into_producer(config, producer, error_callback)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let finished = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stop = stop.clone();
+        let finished = finished.clone();
+        thread::Builder::new()
+            .name("cpal_wav_recording".to_owned())
+            .spawn(move || {
+                let result = write(consumer, writer, limit, &stop);
+                finished.store(true, Ordering::Release);
+                result
+            })?
+    };
+    let recording = WavRecording {
+        stream,
+        stop,
+        finished,
+        thread: Some(thread),
+    };
+    recording.stream.play()?;
+    Ok(recording)
+}
+
+// Writes the samples popped from `consumer` until `limit` samples were written or `stop` is set.
+fn write<T>(
+    mut consumer: Consumer<T>,
+    mut writer: WavWriter<BufWriter<File>>,
+    mut limit: Option<usize>,
+    stop: &AtomicBool,
+) -> io::Result<()>
+where
+    T: Sample,
+{
+    let mut buffer = vec![T::EQUILIBRIUM; consumer.capacity()];
+    loop {
+        // Checked before popping, so that the samples captured before `stop` are written.
+        let stopping = stop.load(Ordering::Acquire);
+        let mut n = consumer.pop_slice(&mut buffer);
+        if let Some(ref mut limit) = limit {
+            n = n.min(*limit);
+            *limit -= n;
+        }
+        writer.write(&buffer[..n])?;
+        if stopping || limit == Some(0) {
+            break;
+        }
+        if n == 0 {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::record_to_wav;
+    use std::fs::File;
+    use std::time::Duration;
+    use test::TestDevice;
+    use traits::DeviceTrait;
+    use {wav, SampleRate};
+
+    #[test]
+    fn records_the_requested_duration() {
+        let path = std::env::temp_dir().join(format!("cpal_record_{}.wav", std::process::id()));
+        let input: Vec<f32> = (0..32).map(|i| i as f32 / 32.0).collect();
+        let device = TestDevice::new(2, SampleRate(1_000)).with_input(input.clone());
+        let config = device.default_input_config().unwrap();
+        let duration = Some(Duration::from_millis(10));
+        let recording = record_to_wav(&device, &config, &path, duration, |_| ()).unwrap();
+        device.process(16);
+        recording.wait().unwrap();
+
+        let (spec, samples) = wav::read(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, SampleRate(1_000));
+        assert_eq!(samples, &input[..20]);
+    }
+}
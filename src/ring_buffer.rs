@@ -0,0 +1,326 @@
+//! A lock-free single-producer single-consumer ring buffer for moving samples into or out of an
+//! audio callback.
+//!
+//! The audio callback runs on a high-priority thread on which blocking (e.g. on a mutex) must be
+//! avoided. Splitting a ring buffer into a `Producer` and a `Consumer` allows one thread to feed
+//! samples to, or collect samples from, the callback without any locks.
+//!
+//! ```no_run
+//! use cpal::ring_buffer;
+//! use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+//!
+//! let host = cpal::default_host();
+//! let device = host.default_output_device().unwrap();
+//! let config: cpal::StreamConfig = device.default_output_config().unwrap().into();
+//!
+//! // Room for a second of audio.
+//! let frames = config.sample_rate.0 as usize;
+//! let (mut producer, consumer) = ring_buffer::ring_buffer::<f32>(frames, config.channels);
+//! let stream = device
+//!     .build_output_stream_from_consumer(&config, consumer, |err| eprintln!("{}", err))
+//!     .unwrap();
+//! stream.play().unwrap();
+//!
+//! // Feed the stream from this thread.
+//! let samples = vec![0.0; 1024];
+//! producer.push_slice(&samples);
+//! ```
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use {ChannelCount, Sample};
+
+// The storage shared between the `Producer` and the `Consumer`.
+//
+// `head` and `tail` count the total number of samples ever popped and pushed respectively,
+// wrapping on overflow. The number of queued samples is always `tail - head`.
+//
+// The buffer has a power of two length, so that masking the counters keeps mapping them to
+// consecutive slots when they wrap. Only `capacity` of its slots are ever in use.
+struct Inner<T> {
+    buffer: Box<[UnsafeCell<T>]>,
+    capacity: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    underruns: AtomicUsize,
+}
+
+// The producer only ever writes to the slots that are free and the consumer only ever reads from
+// the slots that are queued, so the two never access the same slot at the same time.
+unsafe impl<T: Send> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    fn slot(&self, position: usize) -> *mut T {
+        self.buffer[position & (self.buffer.len() - 1)].get()
+    }
+}
+
+/// The half of a ring buffer that pushes samples.
+pub struct Producer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// The half of a ring buffer that pops samples.
+pub struct Consumer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// Create a ring buffer able to hold `frames` frames of `channels` interleaved samples each.
+///
+/// # Panics
+///
+/// Panics if the resulting capacity is zero.
+pub fn ring_buffer<T>(frames: usize, channels: ChannelCount) -> (Producer<T>, Consumer<T>)
+where
+    T: Sample,
+{
+    let capacity = frames * channels as usize;
+    assert!(capacity > 0, "ring buffer capacity must be non-zero");
+    let buffer = (0..capacity.next_power_of_two())
+        .map(|_| UnsafeCell::new(T::EQUILIBRIUM))
+        .collect();
+    let inner = Arc::new(Inner {
+        buffer,
+        capacity,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        underruns: AtomicUsize::new(0),
+    });
+    let producer = Producer {
+        inner: inner.clone(),
+    };
+    let consumer = Consumer { inner };
+    (producer, consumer)
+}
+
+impl<T> Producer<T>
+where
+    T: Sample,
+{
+    /// The total number of samples that the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// The number of samples currently queued.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether no samples are currently queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of samples that can be pushed before the ring buffer is full.
+    pub fn free_len(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// The number of times the consumer could not completely fill a buffer via `Consumer::fill`.
+    pub fn underruns(&self) -> usize {
+        self.inner.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Push a single sample, returning it back if the ring buffer is full.
+    pub fn push(&mut self, sample: T) -> Result<(), T> {
+        if self.push_slice(&[sample]) == 1 {
+            Ok(())
+        } else {
+            Err(sample)
+        }
+    }
+
+    /// Push as many of `samples` as fit, returning the number of samples pushed.
+    pub fn push_slice(&mut self, samples: &[T]) -> usize {
+        let tail = self.inner.tail.load(Ordering::Relaxed);
+        let head = self.inner.head.load(Ordering::Acquire);
+        let free = self.capacity() - tail.wrapping_sub(head);
+        let n = free.min(samples.len());
+        for (i, &sample) in samples[..n].iter().enumerate() {
+            unsafe { *self.inner.slot(tail.wrapping_add(i)) = sample };
+        }
+        self.inner
+            .tail
+            .store(tail.wrapping_add(n), Ordering::Release);
+        n
+    }
+}
+
+impl<T> Consumer<T>
+where
+    T: Sample,
+{
+    /// The total number of samples that the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// The number of samples currently queued.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether no samples are currently queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of times `fill` could not completely fill a buffer.
+    pub fn underruns(&self) -> usize {
+        self.inner.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Pop a single sample, if any are queued.
+    pub fn pop(&mut self) -> Option<T> {
+        let mut sample = [T::EQUILIBRIUM];
+        if self.pop_slice(&mut sample) == 1 {
+            Some(sample[0])
+        } else {
+            None
+        }
+    }
+
+    /// Pop as many samples as are queued into `samples`, returning the number of samples popped.
+    pub fn pop_slice(&mut self, samples: &mut [T]) -> usize {
+        let head = self.inner.head.load(Ordering::Relaxed);
+        let tail = self.inner.tail.load(Ordering::Acquire);
+        let n = tail.wrapping_sub(head).min(samples.len());
+        for (i, sample) in samples[..n].iter_mut().enumerate() {
+            *sample = unsafe { *self.inner.slot(head.wrapping_add(i)) };
+        }
+        self.inner
+            .head
+            .store(head.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    /// Fill `samples` from the ring buffer, writing silence in place of any missing samples.
+    ///
+    /// Returns the number of samples popped. If this is less than `samples.len()` the underrun
+    /// counter is incremented.
+    pub fn fill(&mut self, samples: &mut [T]) -> usize {
+        let n = self.pop_slice(samples);
+        if n < samples.len() {
+            for sample in &mut samples[n..] {
+                *sample = T::EQUILIBRIUM;
+            }
+            self.inner.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ring_buffer;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+
+    #[test]
+    fn push_pop_wraparound() {
+        let (mut producer, mut consumer) = ring_buffer::<i16>(4, 1);
+        let mut out = [0i16; 3];
+        for round in 0..10i16 {
+            let samples = [round, round + 1, round + 2];
+            assert_eq!(producer.push_slice(&samples), 3);
+            assert_eq!(consumer.len(), 3);
+            assert_eq!(consumer.pop_slice(&mut out), 3);
+            assert_eq!(out, samples);
+            assert!(consumer.is_empty());
+        }
+    }
+
+    #[test]
+    fn counters_wrap_around() {
+        // A capacity that is not a power of two, with both counters just below the overflow.
+        let (mut producer, mut consumer) = ring_buffer::<i16>(3, 2);
+        let start = usize::MAX - 7;
+        producer.inner.head.store(start, Ordering::Relaxed);
+        producer.inner.tail.store(start, Ordering::Relaxed);
+        let mut next = 0i16;
+        let mut expected = 0i16;
+        let mut out = [0i16; 4];
+        for _ in 0..10 {
+            let samples: Vec<i16> = (next..next + 5).collect();
+            next += producer.push_slice(&samples) as i16;
+            assert!(producer.len() <= producer.capacity());
+            let n = consumer.pop_slice(&mut out);
+            for &sample in &out[..n] {
+                assert_eq!(sample, expected);
+                expected += 1;
+            }
+        }
+        assert!(consumer.inner.head.load(Ordering::Relaxed) < start);
+        assert!(expected > 10);
+    }
+
+    #[test]
+    fn push_when_full() {
+        let (mut producer, mut consumer) = ring_buffer::<f32>(2, 2);
+        assert_eq!(producer.capacity(), 4);
+        assert_eq!(producer.push_slice(&[0.1, 0.2, 0.3, 0.4, 0.5]), 4);
+        assert_eq!(producer.push(0.6), Err(0.6));
+        assert_eq!(consumer.pop(), Some(0.1));
+        assert_eq!(producer.free_len(), 1);
+        assert_eq!(producer.push(0.6), Ok(()));
+        let mut out = [0.0; 4];
+        assert_eq!(consumer.pop_slice(&mut out), 4);
+        assert_eq!(out, [0.2, 0.3, 0.4, 0.6]);
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn fill_counts_underruns() {
+        let (mut producer, mut consumer) = ring_buffer::<u16>(8, 1);
+        producer.push_slice(&[1, 2]);
+        let mut out = [5u16; 4];
+        assert_eq!(consumer.fill(&mut out), 2);
+        assert_eq!(out, [1, 2, 32768, 32768]);
+        assert_eq!(producer.underruns(), 1);
+        producer.push_slice(&[3, 4, 5, 6]);
+        assert_eq!(consumer.fill(&mut out), 4);
+        assert_eq!(consumer.underruns(), 1);
+    }
+
+    #[test]
+    fn threaded_order_preserved() {
+        const COUNT: usize = 10_000;
+        let (mut producer, mut consumer) = ring_buffer::<f32>(64, 2);
+        let writer = thread::spawn(move || {
+            let mut next = 0;
+            while next < COUNT {
+                let samples: Vec<f32> = (next..COUNT.min(next + 37)).map(|i| i as f32).collect();
+                match producer.push_slice(&samples) {
+                    0 => thread::yield_now(),
+                    n => next += n,
+                }
+            }
+        });
+        let mut expected = 0;
+        let mut out = [0.0; 29];
+        while expected < COUNT {
+            let n = consumer.pop_slice(&mut out);
+            if n == 0 {
+                thread::yield_now();
+            }
+            for &sample in &out[..n] {
+                assert_eq!(sample, expected as f32);
+                expected += 1;
+            }
+        }
+        writer.join().unwrap();
+    }
+}
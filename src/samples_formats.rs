@@ -7,6 +7,8 @@ pub enum SampleFormat {
     I16,
     /// The value 0 corresponds to 32768.
     U16,
+    /// The value 0 corresponds to 128.
+    U8,
     /// The boundaries are (-1.0, 1.0).
     F32,
 }
@@ -18,31 +20,128 @@ impl SampleFormat {
         match *self {
             SampleFormat::I16 => mem::size_of::<i16>(),
             SampleFormat::U16 => mem::size_of::<u16>(),
+            SampleFormat::U8 => mem::size_of::<u8>(),
             SampleFormat::F32 => mem::size_of::<f32>(),
         }
     }
+
+    /// Returns the number of bits in a sample of this format.
+    #[inline]
+    pub fn bits(&self) -> u8 {
+        (self.sample_size() * 8) as u8
+    }
+
+    /// Returns whether samples of this format are floating point numbers.
+    #[inline]
+    pub fn is_float(&self) -> bool {
+        match *self {
+            SampleFormat::F32 => true,
+            SampleFormat::I16 | SampleFormat::U16 | SampleFormat::U8 => false,
+        }
+    }
+
+    /// Returns whether samples of this format can be negative, i.e. whether silence is `0`.
+    #[inline]
+    pub fn is_signed(&self) -> bool {
+        match *self {
+            SampleFormat::I16 | SampleFormat::F32 => true,
+            SampleFormat::U16 | SampleFormat::U8 => false,
+        }
+    }
 }
 
 /// Trait for containers that contain PCM data.
+///
+/// # Safety
+///
+/// `FORMAT` must match the in-memory representation of the implementing type, as hosts
+/// reinterpret raw sample buffers as slices of `Self` based on it.
 pub unsafe trait Sample: Copy + Clone {
     /// The `SampleFormat` corresponding to this data type.
     const FORMAT: SampleFormat;
 
+    /// The value of the sample type representing silence.
+    ///
+    /// This is `0` for signed and floating point types and the midpoint of the range for unsigned
+    /// types.
+    const EQUILIBRIUM: Self;
+
     /// Turns the sample into its equivalent as a floating-point.
     fn to_f32(&self) -> f32;
     /// Converts this sample into a standard i16 sample.
     fn to_i16(&self) -> i16;
     /// Converts this sample into a standard u16 sample.
     fn to_u16(&self) -> u16;
+    /// Converts this sample into a standard u8 sample.
+    ///
+    /// The default implementation keeps the 8 most significant bits of `to_i16`.
+    #[inline]
+    fn to_u8(&self) -> u8 {
+        ((self.to_i16() >> 8) + 128) as u8
+    }
 
-    /// Converts any sample type to this one by calling `to_i16`, `to_u16` or `to_f32`.
+    /// Converts any sample type to this one by calling `to_i16`, `to_u16`, `to_u8` or `to_f32`.
     fn from<S>(s: &S) -> Self
     where
         S: Sample;
+
+    /// Converts a floating-point sample to this type, like `Sample::from(&sample)`.
+    ///
+    /// Together with `to_f32`, this lets generic code process samples of any type as `f32`:
+    ///
+    /// ```
+    /// # use cpal::Sample;
+    /// fn halve<T: Sample>(sample: T) -> T {
+    ///     T::from_f32(sample.to_f32() * 0.5)
+    /// }
+    /// assert_eq!(halve(-16384i16), -8192);
+    /// ```
+    #[inline]
+    fn from_f32(sample: f32) -> Self {
+        Self::from(&sample)
+    }
+}
+
+unsafe impl Sample for u8 {
+    const FORMAT: SampleFormat = SampleFormat::U8;
+    const EQUILIBRIUM: Self = 128;
+
+    #[inline]
+    fn to_f32(&self) -> f32 {
+        if *self < 128 {
+            (*self as f32 - 128.0) / 128.0
+        } else {
+            (*self as f32 - 128.0) / 127.0
+        }
+    }
+
+    #[inline]
+    fn to_i16(&self) -> i16 {
+        ((*self as i16) - 128) << 8
+    }
+
+    #[inline]
+    fn to_u16(&self) -> u16 {
+        (*self as u16) << 8
+    }
+
+    #[inline]
+    fn to_u8(&self) -> u8 {
+        *self
+    }
+
+    #[inline]
+    fn from<S>(sample: &S) -> Self
+    where
+        S: Sample,
+    {
+        sample.to_u8()
+    }
 }
 
 unsafe impl Sample for u16 {
     const FORMAT: SampleFormat = SampleFormat::U16;
+    const EQUILIBRIUM: Self = 32768;
 
     #[inline]
     fn to_f32(&self) -> f32 {
@@ -74,6 +173,7 @@ unsafe impl Sample for u16 {
 
 unsafe impl Sample for i16 {
     const FORMAT: SampleFormat = SampleFormat::I16;
+    const EQUILIBRIUM: Self = 0;
 
     #[inline]
     fn to_f32(&self) -> f32 {
@@ -109,6 +209,7 @@ unsafe impl Sample for i16 {
 
 unsafe impl Sample for f32 {
     const FORMAT: SampleFormat = SampleFormat::F32;
+    const EQUILIBRIUM: Self = 0.0;
 
     #[inline]
     fn to_f32(&self) -> f32 {
@@ -129,6 +230,15 @@ unsafe impl Sample for f32 {
         (((*self + 1.0) * 0.5) * u16::MAX as f32).round() as u16
     }
 
+    #[inline]
+    fn to_u8(&self) -> u8 {
+        if *self >= 0.0 {
+            (128.0 + *self * 127.0).round() as u8
+        } else {
+            (128.0 + *self * 128.0).round() as u8
+        }
+    }
+
     #[inline]
     fn from<S>(sample: &S) -> Self
     where
@@ -138,9 +248,44 @@ unsafe impl Sample for f32 {
     }
 }
 
+// The samples of `data` as `f32` samples if `T` is `f32`, for the wrappers of `f32` data callbacks
+// to skip converting them.
+pub(crate) fn as_f32_slice_mut<T>(data: &mut [T]) -> Option<&mut [f32]>
+where
+    T: Sample,
+{
+    if T::FORMAT == SampleFormat::F32 {
+        // `Sample` guarantees that `T` is represented like `f32`.
+        unsafe {
+            Some(std::slice::from_raw_parts_mut(
+                data.as_mut_ptr() as *mut f32,
+                data.len(),
+            ))
+        }
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::Sample;
+    use super::{Sample, SampleFormat};
+
+    #[test]
+    fn sample_format_introspection() {
+        assert_eq!(SampleFormat::I16.bits(), 16);
+        assert_eq!(SampleFormat::U16.bits(), 16);
+        assert_eq!(SampleFormat::F32.bits(), 32);
+        assert_eq!(SampleFormat::U8.bits(), 8);
+        assert_eq!(SampleFormat::F32.sample_size(), 4);
+        assert!(SampleFormat::F32.is_float());
+        assert!(!SampleFormat::I16.is_float());
+        assert!(SampleFormat::I16.is_signed());
+        assert!(!SampleFormat::U16.is_signed());
+        assert!(!SampleFormat::U8.is_signed());
+        assert!(!SampleFormat::U8.is_float());
+        assert!(SampleFormat::F32.is_signed());
+    }
 
     #[test]
     fn i16_to_i16() {
@@ -204,10 +349,50 @@ mod test {
         assert_eq!(1.0f32.to_u16(), 65535);
     }
 
+    #[test]
+    fn u8_conversions() {
+        assert_eq!(128u8.to_i16(), 0);
+        assert_eq!(0u8.to_i16(), i16::MIN);
+        assert_eq!(255u8.to_u16(), 65280);
+        assert_eq!(0u8.to_f32(), -1.0);
+        assert_eq!(255u8.to_f32(), 1.0);
+        assert_eq!(0i16.to_u8(), 128);
+        assert_eq!(i16::MIN.to_u8(), 0);
+        assert_eq!(i16::MAX.to_u8(), 255);
+        assert_eq!(u16::MAX.to_u8(), 255);
+        assert_eq!((-1.0f32).to_u8(), 0);
+        assert_eq!(1.0f32.to_u8(), 255);
+        for sample in 0..=u8::MAX {
+            assert_eq!(u8::from_f32(sample.to_f32()), sample);
+            assert_eq!(<u8 as Sample>::from(&sample.to_i16()), sample);
+        }
+    }
+
     #[test]
     fn f32_to_f32() {
         assert_eq!(0.1f32.to_f32(), 0.1);
         assert_eq!((-0.7f32).to_f32(), -0.7);
         assert_eq!(1.0f32.to_f32(), 1.0);
     }
+
+    #[test]
+    fn f32_round_trips() {
+        for &sample in &[i16::MIN, -16384, -1, 0, 1, 16384, i16::MAX] {
+            assert_eq!(i16::from_f32(sample.to_f32()), sample);
+        }
+        for &sample in &[0u16, 16384, 32768, 49152, u16::MAX] {
+            assert_eq!(u16::from_f32(sample.to_f32()), sample);
+        }
+        assert_eq!(f32::from_f32(0.3), 0.3);
+    }
+
+    #[test]
+    fn equilibrium() {
+        assert_eq!(u16::EQUILIBRIUM.to_f32(), 0.0);
+        assert_eq!(i16::EQUILIBRIUM.to_f32(), 0.0);
+        assert_eq!(f32::EQUILIBRIUM.to_u16(), u16::EQUILIBRIUM);
+        assert_eq!(f32::EQUILIBRIUM.to_i16(), i16::EQUILIBRIUM);
+        assert_eq!(u8::EQUILIBRIUM.to_f32(), 0.0);
+        assert_eq!(f32::EQUILIBRIUM.to_u8(), u8::EQUILIBRIUM);
+    }
 }
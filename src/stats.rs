@@ -0,0 +1,246 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use traits::StreamTrait;
+use {FrameCount, PauseStreamError, PlayStreamError, StreamConfig, StreamInstant};
+
+/// Statistics about the invocations of the data callback of a stream, as measured by an
+/// `InstrumentedStream`.
+///
+/// Intervals are measured between the `callback` timestamps that the host passes to consecutive
+/// invocations, so they are only as accurate as the timestamps of the host. The fields are
+/// `None` until enough invocations were measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// The number of times the data callback was invoked.
+    pub callbacks: u64,
+    /// The shortest interval between two invocations.
+    pub min_interval: Option<Duration>,
+    /// The longest interval between two invocations.
+    pub max_interval: Option<Duration>,
+    /// The mean interval between two invocations.
+    pub avg_interval: Option<Duration>,
+    /// The smallest number of frames passed to the data callback.
+    pub min_buffer_frames: Option<FrameCount>,
+    /// The largest number of frames passed to the data callback.
+    pub max_buffer_frames: Option<FrameCount>,
+}
+
+// The counters shared between the audio callback and the stream handle.
+pub(crate) struct Counters {
+    callbacks: AtomicU64,
+    intervals: AtomicU64,
+    // In nanoseconds. The minimums start at `u64::MAX` and the maximums at `0`.
+    interval_sum: AtomicU64,
+    min_interval: AtomicU64,
+    max_interval: AtomicU64,
+    min_frames: AtomicU64,
+    max_frames: AtomicU64,
+}
+
+impl Counters {
+    fn new() -> Arc<Self> {
+        Arc::new(Counters {
+            callbacks: AtomicU64::new(0),
+            intervals: AtomicU64::new(0),
+            interval_sum: AtomicU64::new(0),
+            min_interval: AtomicU64::new(u64::MAX),
+            max_interval: AtomicU64::new(0),
+            min_frames: AtomicU64::new(u64::MAX),
+            max_frames: AtomicU64::new(0),
+        })
+    }
+
+    fn reset(&self) {
+        self.callbacks.store(0, Ordering::Relaxed);
+        self.intervals.store(0, Ordering::Relaxed);
+        self.interval_sum.store(0, Ordering::Relaxed);
+        self.min_interval.store(u64::MAX, Ordering::Relaxed);
+        self.max_interval.store(0, Ordering::Relaxed);
+        self.min_frames.store(u64::MAX, Ordering::Relaxed);
+        self.max_frames.store(0, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> StreamStats {
+        let callbacks = self.callbacks.load(Ordering::Relaxed);
+        let intervals = self.intervals.load(Ordering::Relaxed);
+        let min_interval = self.min_interval.load(Ordering::Relaxed);
+        let min_frames = self.min_frames.load(Ordering::Relaxed);
+        // A reset racing with the callback may leave the sentinels in place.
+        let measured = |value: u64| Some(value).filter(|&value| value != u64::MAX);
+        StreamStats {
+            callbacks,
+            min_interval: measured(min_interval).map(Duration::from_nanos),
+            max_interval: measured(min_interval)
+                .map(|_| Duration::from_nanos(self.max_interval.load(Ordering::Relaxed))),
+            avg_interval: measured(min_interval).filter(|_| intervals > 0).map(|_| {
+                Duration::from_nanos(self.interval_sum.load(Ordering::Relaxed) / intervals)
+            }),
+            min_buffer_frames: measured(min_frames).map(|frames| frames as FrameCount),
+            max_buffer_frames: measured(min_frames)
+                .map(|_| self.max_frames.load(Ordering::Relaxed) as FrameCount),
+        }
+    }
+}
+
+// Records the invocations of a data callback into `Counters`.
+pub(crate) struct Recorder {
+    counters: Arc<Counters>,
+    channels: usize,
+    last: Option<StreamInstant>,
+}
+
+impl Recorder {
+    pub(crate) fn new(config: &StreamConfig) -> Self {
+        Recorder {
+            counters: Counters::new(),
+            channels: config.channels as usize,
+            last: None,
+        }
+    }
+
+    pub(crate) fn counters(&self) -> Arc<Counters> {
+        self.counters.clone()
+    }
+
+    // Records an invocation with `samples` interleaved samples at the `callback` timestamp.
+    pub(crate) fn record(&mut self, samples: usize, callback: StreamInstant) {
+        let counters = &self.counters;
+        counters.callbacks.fetch_add(1, Ordering::Relaxed);
+        let frames = (samples / self.channels.max(1)) as u64;
+        counters.min_frames.fetch_min(frames, Ordering::Relaxed);
+        counters.max_frames.fetch_max(frames, Ordering::Relaxed);
+        if let Some(interval) = self.last.and_then(|last| callback.duration_since(&last)) {
+            let nanos = interval.as_nanos() as u64;
+            counters.intervals.fetch_add(1, Ordering::Relaxed);
+            counters.interval_sum.fetch_add(nanos, Ordering::Relaxed);
+            counters.min_interval.fetch_min(nanos, Ordering::Relaxed);
+            counters.max_interval.fetch_max(nanos, Ordering::Relaxed);
+        }
+        self.last = Some(callback);
+    }
+}
+
+/// A stream that keeps `StreamStats` about the invocations of its data callback, e.g. to tune
+/// buffer sizes.
+///
+/// Created via `DeviceTrait::build_input_stream_with_stats` or
+/// `DeviceTrait::build_output_stream_with_stats`. The statistics are updated with a few atomic
+/// operations each time the data callback runs, and can be read from any thread without
+/// blocking it.
+pub struct InstrumentedStream<S> {
+    stream: S,
+    counters: Arc<Counters>,
+}
+
+impl<S> InstrumentedStream<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(stream: S, counters: Arc<Counters>) -> Self {
+        InstrumentedStream { stream, counters }
+    }
+
+    /// The statistics since the stream was built or since the last call to `reset_stats`.
+    pub fn stats(&self) -> StreamStats {
+        self.counters.stats()
+    }
+
+    /// Start measuring anew, e.g. after changing the conditions being tuned.
+    pub fn reset_stats(&self) {
+        self.counters.reset();
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S> StreamTrait for InstrumentedStream<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {BufferSize, OutputCallbackInfo, SampleRate, StreamStats};
+
+    #[test]
+    fn callback_stats() {
+        let device = TestDevice::new(2, SampleRate(1_000));
+        let config = device.default_output_config().unwrap().config();
+        let stream = device
+            .build_output_stream_with_stats(
+                &config,
+                |_: &mut [f32], _: &OutputCallbackInfo| (),
+                |_| (),
+            )
+            .unwrap();
+        assert_eq!(stream.stats(), StreamStats::default());
+        stream.play().unwrap();
+        for &frames in &[10, 30, 20] {
+            device.process(frames);
+        }
+        let stats = stream.stats();
+        assert_eq!(stats.callbacks, 3);
+        assert_eq!(stats.min_interval, Some(Duration::from_millis(10)));
+        assert_eq!(stats.max_interval, Some(Duration::from_millis(30)));
+        assert_eq!(stats.avg_interval, Some(Duration::from_millis(20)));
+        assert_eq!(stats.min_buffer_frames, Some(10));
+        assert_eq!(stats.max_buffer_frames, Some(30));
+
+        stream.reset_stats();
+        assert_eq!(stream.stats(), StreamStats::default());
+        device.process(5);
+        let stats = stream.stats();
+        assert_eq!(stats.callbacks, 1);
+        assert_eq!(stats.min_interval, Some(Duration::from_millis(20)));
+        assert_eq!(stats.max_buffer_frames, Some(5));
+    }
+
+    #[test]
+    fn fixed_buffer_size() {
+        let device = TestDevice::new(1, SampleRate(1_000));
+        let mut config = device.default_output_config().unwrap().config();
+        config.buffer_size = BufferSize::Fixed(4);
+        let stream = device
+            .build_output_stream_with_stats(
+                &config,
+                |_: &mut [i16], _: &OutputCallbackInfo| (),
+                |_| (),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(10);
+        let stats = stream.stats();
+        assert_eq!(stats.callbacks, 3);
+        assert_eq!(stats.min_buffer_frames, Some(2));
+        assert_eq!(stats.max_buffer_frames, Some(4));
+        assert_eq!(stats.avg_interval, Some(Duration::from_millis(4)));
+    }
+}
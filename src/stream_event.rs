@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use traits::StreamTrait;
+use {PauseStreamError, PlayStreamError, StreamConfig};
+
+/// Passed to the event callback of a stream built with `DeviceTrait::build_input_stream_with_events`
+/// or `DeviceTrait::build_output_stream_with_events`.
+///
+/// Events are derived from the data callback, so they report when audio actually starts flowing
+/// rather than when `play` returns, which may be noticeably earlier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// The data callback is about to be invoked for the first time.
+    Started {
+        /// The moment the event was detected, right before the data callback.
+        at: Instant,
+    },
+    /// The data callback is about to be invoked for the first time since the stream was paused
+    /// and played again.
+    Resumed {
+        /// The moment the event was detected, right before the data callback.
+        at: Instant,
+    },
+}
+
+// The values of `PendingEvent`.
+const NO_EVENT: u8 = 0;
+const STARTED: u8 = 1;
+const RESUMED: u8 = 2;
+
+// The event that the next invocation of the data callback announces, if any.
+pub(crate) struct PendingEvent(AtomicU8);
+
+impl PendingEvent {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(PendingEvent(AtomicU8::new(STARTED)))
+    }
+
+    // Called by the audio callback before invoking the data callback.
+    pub(crate) fn take(&self) -> Option<StreamEvent> {
+        match self.0.swap(NO_EVENT, Ordering::AcqRel) {
+            NO_EVENT => None,
+            STARTED => Some(StreamEvent::Started { at: Instant::now() }),
+            _ => Some(StreamEvent::Resumed { at: Instant::now() }),
+        }
+    }
+
+    // Announce `Resumed` with the next data callback, unless `Started` is still pending.
+    fn resume(&self) {
+        let _ = self
+            .0
+            .compare_exchange(NO_EVENT, RESUMED, Ordering::AcqRel, Ordering::Acquire);
+    }
+}
+
+/// A stream that reports when audio starts flowing to an event callback, see `StreamEvent`.
+///
+/// Created via `DeviceTrait::build_input_stream_with_events` or
+/// `DeviceTrait::build_output_stream_with_events`. The event callback runs on the audio thread,
+/// right before the data callback, and should return just as quickly.
+pub struct NotifyingStream<S> {
+    stream: S,
+    pending: Arc<PendingEvent>,
+    // Whether `pause` was called since the last `play`.
+    paused: AtomicBool,
+}
+
+impl<S> NotifyingStream<S>
+where
+    S: StreamTrait,
+{
+    pub(crate) fn new(stream: S, pending: Arc<PendingEvent>) -> Self {
+        NotifyingStream {
+            stream,
+            pending,
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S> StreamTrait for NotifyingStream<S>
+where
+    S: StreamTrait,
+{
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()?;
+        if self.paused.swap(false, Ordering::AcqRel) {
+            self.pending.resume();
+        }
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()?;
+        self.paused.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.stream.config()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.stream.frame_position()
+    }
+
+    fn is_playing(&self) -> bool {
+        self.stream.is_playing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamEvent;
+    use std::sync::{Arc, Mutex};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {OutputCallbackInfo, SampleRate};
+
+    #[test]
+    fn started_then_resumed() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let events = events.clone();
+            device
+                .build_output_stream_with_events(
+                    &config,
+                    |_: &mut [f32], _: &OutputCallbackInfo| (),
+                    move |event| events.lock().unwrap().push(event),
+                    |_| (),
+                )
+                .unwrap()
+        };
+        let take = || events.lock().unwrap().drain(..).collect::<Vec<_>>();
+
+        stream.play().unwrap();
+        device.process(16);
+        device.process(16);
+        let started = take();
+        assert_eq!(started.len(), 1);
+        assert!(matches!(started[0], StreamEvent::Started { .. }));
+
+        stream.pause().unwrap();
+        stream.play().unwrap();
+        device.process(16);
+        let resumed = take();
+        assert_eq!(resumed.len(), 1);
+        assert!(matches!(resumed[0], StreamEvent::Resumed { .. }));
+
+        // Playing a stream that is not paused is no resumption.
+        stream.play().unwrap();
+        device.process(16);
+        assert!(take().is_empty());
+    }
+}
@@ -0,0 +1,915 @@
+//! A virtual device for testing code that uses cpal without any audio hardware.
+//!
+//! A `TestDevice` implements `DeviceTrait` like any other device, but its streams are only
+//! driven when `TestDevice::process` is called. Every call runs the data callbacks of the
+//! device's playing streams for the requested number of frames:
+//!
+//! - Output streams are mixed together and appended to the device's recording, which can be
+//!   retrieved with `TestDevice::recorded_output` or saved with `TestDevice::write_wav`.
+//! - Input streams are fed from the samples given to `TestDevice::with_input`, or from a WAV
+//!   file when the device was created with `file_device`, followed by silence.
+//!
+//! ```
+//! use cpal::traits::{DeviceTrait, StreamTrait};
+//!
+//! let device = cpal::test::TestDevice::new(1, cpal::SampleRate(48_000));
+//! let config = device.default_output_config().unwrap().config();
+//! let stream = device
+//!     .build_output_stream(
+//!         &config,
+//!         |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+//!             for sample in data.iter_mut() {
+//!                 *sample = 0.5;
+//!             }
+//!         },
+//!         |err| panic!("{}", err),
+//!     )
+//!     .unwrap();
+//! stream.play().unwrap();
+//! device.process(4);
+//! assert_eq!(device.recorded_output(), vec![0.5; 4]);
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use traits::{DeviceTrait, StreamTrait};
+use wav::{self, WavSpec, WavWriter};
+use {
+    BufferSize, BuildStreamError, ChannelCount, Data, DefaultStreamConfigError, DeviceNameError,
+    FrameCount, InputCallbackInfo, InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp,
+    PauseStreamError, PlayStreamError, Sample, SampleFormat, SampleRate, StreamConfig, StreamError,
+    StreamInstant, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    SupportedStreamConfigsError,
+};
+
+/// The largest fixed buffer size accepted by a `TestDevice`, in frames.
+const MAX_BUFFER_SIZE: FrameCount = 8192;
+
+const SAMPLE_FORMATS: [SampleFormat; 4] = [
+    SampleFormat::F32,
+    SampleFormat::I16,
+    SampleFormat::U16,
+    SampleFormat::U8,
+];
+
+/// A device whose streams are driven by calling `process`, recording its output to memory.
+///
+/// Clones refer to the same device. The device supports all sample formats, but only the
+/// channel count and sample rate it was created with. Its streams never report errors.
+#[derive(Clone)]
+pub struct TestDevice {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    name: String,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    // Interleaved samples fed to input streams, and the position of the next frame.
+    input: Vec<f32>,
+    input_position: usize,
+    // Interleaved samples produced by output streams.
+    recorded: Vec<f32>,
+    // The number of frames processed since the device was created.
+    frames: u64,
+    streams: Vec<Weak<StreamInner>>,
+}
+
+/// A stream created by a `TestDevice`.
+///
+/// Streams start paused and only invoke their data callback from `TestDevice::process`.
+pub struct TestStream {
+    inner: Arc<StreamInner>,
+}
+
+struct StreamInner {
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    playing: AtomicBool,
+    frames: AtomicU64,
+    callback: Mutex<Callback>,
+}
+
+type InputCallback = dyn FnMut(&Data, &InputCallbackInfo) + Send;
+type OutputCallback = dyn FnMut(&mut Data, &OutputCallbackInfo) + Send;
+
+enum Callback {
+    Input(Box<InputCallback>),
+    Output(Box<OutputCallback>),
+}
+
+/// Iterator over the configurations supported by a `TestDevice`.
+pub type SupportedConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
+
+/// Create a `TestDevice` that feeds the contents of the WAV file at `path` to its input streams.
+///
+/// The device takes its channel count and sample rate from the file.
+pub fn file_device<P>(path: P) -> io::Result<TestDevice>
+where
+    P: AsRef<Path>,
+{
+    let file = BufReader::new(File::open(path.as_ref())?);
+    let (spec, samples) = wav::read(file)?;
+    let name = path.as_ref().display().to_string();
+    Ok(TestDevice::new(spec.channels, spec.sample_rate)
+        .with_name(name)
+        .with_input(samples))
+}
+
+impl TestDevice {
+    /// Create a device with the given channel count and sample rate and no input.
+    pub fn new(channels: ChannelCount, sample_rate: SampleRate) -> Self {
+        let inner = Inner {
+            name: "test".to_owned(),
+            channels,
+            sample_rate,
+            input: Vec::new(),
+            input_position: 0,
+            recorded: Vec::new(),
+            frames: 0,
+            streams: Vec::new(),
+        };
+        TestDevice {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Set the name returned by `DeviceTrait::name`.
+    pub fn with_name<S>(self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.inner.lock().unwrap().name = name.into();
+        self
+    }
+
+    /// Set the interleaved samples fed to input streams, restarting input from the first frame.
+    pub fn with_input(self, samples: Vec<f32>) -> Self {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.input = samples;
+            inner.input_position = 0;
+        }
+        self
+    }
+
+    /// Run the data callbacks of all playing streams for `frames` frames.
+    ///
+    /// Streams built with a fixed buffer size are called back with buffers of that size, the
+    /// last one possibly being shorter. Other streams are called back once with all of the
+    /// frames. The device records silence for any frames processed while no output stream is
+    /// playing.
+    pub fn process(&self, frames: usize) {
+        // The callbacks are run without holding the lock so that they may use the device.
+        let (channels, sample_rate, position, input, streams) = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.streams.retain(|stream| stream.strong_count() > 0);
+            let channels = inner.channels as usize;
+            let start = (inner.input_position * channels).min(inner.input.len());
+            let end = ((inner.input_position + frames) * channels).min(inner.input.len());
+            let mut input = inner.input[start..end].to_vec();
+            input.resize(frames * channels, 0.0);
+            let streams: Vec<_> = inner.streams.iter().filter_map(Weak::upgrade).collect();
+            (channels, inner.sample_rate, inner.frames, input, streams)
+        };
+
+        let mut mix = vec![0.0; frames * channels];
+        for stream in streams {
+            if !stream.playing.load(Ordering::SeqCst) {
+                continue;
+            }
+            let chunk_frames = match stream.config.buffer_size {
+                BufferSize::Fixed(n) => n as usize,
+                BufferSize::Default => frames.max(1),
+            };
+            let chunk_len = chunk_frames * channels;
+            let mut callback = stream.callback.lock().unwrap();
+            for (i, offset) in (0..mix.len()).step_by(chunk_len).enumerate() {
+                let end = (offset + chunk_len).min(mix.len());
+                let instant = instant(position + (i * chunk_frames) as u64, sample_rate);
+                match *callback {
+                    Callback::Input(ref mut callback) => {
+                        let timestamp = InputStreamTimestamp {
+                            callback: instant,
+                            capture: instant,
+                        };
+                        let info = InputCallbackInfo::new(
+                            timestamp,
+                            (end - offset) / channels,
+                            sample_rate,
+                        );
+                        let input = &input[offset..end];
+                        match stream.sample_format {
+                            SampleFormat::I16 => run_input::<i16>(callback, input, &info),
+                            SampleFormat::U16 => run_input::<u16>(callback, input, &info),
+                            SampleFormat::U8 => run_input::<u8>(callback, input, &info),
+                            SampleFormat::F32 => run_input::<f32>(callback, input, &info),
+                        }
+                    }
+                    Callback::Output(ref mut callback) => {
+                        let timestamp = OutputStreamTimestamp {
+                            callback: instant,
+                            playback: instant,
+                        };
+                        let info = OutputCallbackInfo::new(
+                            timestamp,
+                            (end - offset) / channels,
+                            sample_rate,
+                        );
+                        let mix = &mut mix[offset..end];
+                        match stream.sample_format {
+                            SampleFormat::I16 => run_output::<i16>(callback, mix, &info),
+                            SampleFormat::U16 => run_output::<u16>(callback, mix, &info),
+                            SampleFormat::U8 => run_output::<u8>(callback, mix, &info),
+                            SampleFormat::F32 => run_output::<f32>(callback, mix, &info),
+                        }
+                    }
+                }
+            }
+            stream.frames.fetch_add(frames as u64, Ordering::SeqCst);
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.recorded.extend(mix);
+        inner.input_position += frames;
+        inner.frames += frames as u64;
+    }
+
+    /// The interleaved samples produced by output streams so far.
+    pub fn recorded_output(&self) -> Vec<f32> {
+        self.inner.lock().unwrap().recorded.clone()
+    }
+
+    /// Discard the recorded output.
+    pub fn clear_recorded_output(&self) {
+        self.inner.lock().unwrap().recorded.clear();
+    }
+
+    /// Save the recorded output to a 32-bit float WAV file at `path`.
+    pub fn write_wav<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let inner = self.inner.lock().unwrap();
+        let spec = WavSpec {
+            channels: inner.channels,
+            sample_rate: inner.sample_rate,
+        };
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = WavWriter::new(file, spec, SampleFormat::F32)?;
+        writer.write(&inner.recorded)?;
+        writer.finalize()?;
+        Ok(())
+    }
+
+    fn supported_configs(&self) -> SupportedConfigs {
+        let inner = self.inner.lock().unwrap();
+        SAMPLE_FORMATS
+            .iter()
+            .map(|&sample_format| SupportedStreamConfigRange {
+                channels: inner.channels,
+                min_sample_rate: inner.sample_rate,
+                max_sample_rate: inner.sample_rate,
+                buffer_size: SupportedBufferSize::Range {
+                    min: 1,
+                    max: MAX_BUFFER_SIZE,
+                },
+                sample_format,
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn default_config(&self) -> SupportedStreamConfig {
+        let inner = self.inner.lock().unwrap();
+        SupportedStreamConfig {
+            channels: inner.channels,
+            sample_rate: inner.sample_rate,
+            buffer_size: SupportedBufferSize::Range {
+                min: 1,
+                max: MAX_BUFFER_SIZE,
+            },
+            sample_format: SampleFormat::F32,
+        }
+    }
+
+    fn build_stream(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        callback: Callback,
+    ) -> Result<TestStream, BuildStreamError> {
+        let mut inner = self.inner.lock().unwrap();
+        let buffer_size_supported = match config.buffer_size {
+            BufferSize::Fixed(n) => (1..=MAX_BUFFER_SIZE).contains(&n),
+            BufferSize::Default => true,
+        };
+        if config.channels != inner.channels
+            || config.sample_rate != inner.sample_rate
+            || !buffer_size_supported
+        {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+        let stream = Arc::new(StreamInner {
+            config: config.clone(),
+            sample_format,
+            playing: AtomicBool::new(false),
+            frames: AtomicU64::new(0),
+            callback: Mutex::new(callback),
+        });
+        inner.streams.push(Arc::downgrade(&stream));
+        Ok(TestStream { inner: stream })
+    }
+}
+
+// The instant at which the device reaches the given frame.
+fn instant(frames: u64, sample_rate: SampleRate) -> StreamInstant {
+    let rate = sample_rate.0 as u64;
+    let nanos = (frames % rate) * 1_000_000_000 / rate;
+    StreamInstant::new((frames / rate) as i64, nanos as u32)
+}
+
+fn run_input<T>(callback: &mut InputCallback, input: &[f32], info: &InputCallbackInfo)
+where
+    T: Sample,
+{
+    let mut buffer: Vec<T> = input.iter().map(T::from).collect();
+    let data = unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), buffer.len(), T::FORMAT) };
+    callback(&data, info);
+}
+
+fn run_output<T>(callback: &mut OutputCallback, mix: &mut [f32], info: &OutputCallbackInfo)
+where
+    T: Sample,
+{
+    let mut buffer = vec![T::EQUILIBRIUM; mix.len()];
+    let mut data =
+        unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), buffer.len(), T::FORMAT) };
+    callback(&mut data, info);
+    for (mixed, sample) in mix.iter_mut().zip(&buffer) {
+        *mixed += sample.to_f32();
+    }
+}
+
+impl DeviceTrait for TestDevice {
+    type SupportedInputConfigs = SupportedConfigs;
+    type SupportedOutputConfigs = SupportedConfigs;
+    type Stream = TestStream;
+
+    fn name(&self) -> Result<String, DeviceNameError> {
+        Ok(self.inner.lock().unwrap().name.clone())
+    }
+
+    fn supported_input_configs(
+        &self,
+    ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
+        Ok(self.supported_configs())
+    }
+
+    fn supported_output_configs(
+        &self,
+    ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError> {
+        Ok(self.supported_configs())
+    }
+
+    fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        Ok(self.default_config())
+    }
+
+    fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        Ok(self.default_config())
+    }
+
+    fn build_input_stream_raw<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        _error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let callback = Callback::Input(Box::new(data_callback));
+        self.build_stream(config, sample_format, callback)
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        _error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let callback = Callback::Output(Box::new(data_callback));
+        self.build_stream(config, sample_format, callback)
+    }
+}
+
+impl StreamTrait for TestStream {
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.inner.playing.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.inner.playing.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn config(&self) -> StreamConfig {
+        self.inner.config.clone()
+    }
+
+    fn frame_position(&self) -> u64 {
+        self.inner.frames.load(Ordering::SeqCst)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.inner.playing.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestDevice;
+    use std::fs::File;
+    use std::io::BufWriter;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use traits::{self, CallbackThread, DeviceTrait, StreamTrait};
+    use wav::{WavSpec, WavWriter};
+    use {
+        BufferSize, BuildStreamError, InputCallbackInfo, OutputCallbackInfo, Sample, SampleFormat,
+        SampleRate, StreamConfig, StreamError, StreamOptions, SupportedBufferSize,
+        SupportedStreamConfig, SupportedStreamConfigsError,
+    };
+
+    fn config(device: &TestDevice, buffer_size: BufferSize) -> StreamConfig {
+        let mut config = device.default_output_config().unwrap().config();
+        config.buffer_size = buffer_size;
+        config
+    }
+
+    #[test]
+    fn output_is_mixed_and_recorded() {
+        let device = TestDevice::new(2, SampleRate(44_100));
+        let config = config(&device, BufferSize::Fixed(3));
+        let a = device
+            .build_output_stream(
+                &config,
+                |data: &mut [f32], _: &OutputCallbackInfo| data.iter_mut().for_each(|s| *s = 0.25),
+                |_| (),
+            )
+            .unwrap();
+        let b = device
+            .build_output_stream(
+                &config,
+                |data: &mut [i16], _: &OutputCallbackInfo| {
+                    data.iter_mut().for_each(|s| *s = i16::MAX)
+                },
+                |_| (),
+            )
+            .unwrap();
+        device.process(2);
+        assert_eq!(device.recorded_output(), vec![0.0; 4]);
+        a.play().unwrap();
+        b.play().unwrap();
+        device.process(4);
+        assert_eq!(&device.recorded_output()[4..], &[1.25; 8][..]);
+        assert_eq!(a.frame_position(), 4);
+        b.pause().unwrap();
+        device.process(1);
+        assert_eq!(&device.recorded_output()[12..], &[0.25; 2][..]);
+        assert_eq!(b.frame_position(), 4);
+    }
+
+    #[test]
+    fn input_is_fed_then_silent() {
+        let device = TestDevice::new(1, SampleRate(8_000)).with_input(vec![0.5, -0.5, 1.0]);
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let captured = captured.clone();
+            device
+                .build_input_stream(
+                    &config(&device, BufferSize::Fixed(2)),
+                    move |data: &[f32], _: &InputCallbackInfo| {
+                        captured.lock().unwrap().push(data.to_vec())
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        device.process(5);
+        let captured = captured.lock().unwrap();
+        assert_eq!(*captured, vec![vec![0.5, -0.5], vec![1.0, 0.0], vec![0.0]]);
+    }
+
+    #[test]
+    fn mono_file_capture() {
+        let path = std::env::temp_dir().join(format!("cpal_mono_{}.wav", std::process::id()));
+        {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate: SampleRate(16_000),
+            };
+            let file = BufWriter::new(File::create(&path).unwrap());
+            let mut writer = WavWriter::new(file, spec, SampleFormat::F32).unwrap();
+            writer.write(&[0.25; 7]).unwrap();
+            writer.finalize().unwrap();
+        }
+        let device = super::file_device(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let configs: Vec<_> = device.supported_input_configs().unwrap().collect();
+        assert!(!configs.is_empty());
+        assert!(configs.iter().all(|config| config.channels == 1));
+        assert_eq!(device.default_input_config().unwrap().channels, 1);
+
+        let config = config(&device, BufferSize::Fixed(4));
+        let buffers = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let buffers = buffers.clone();
+            device
+                .build_input_stream(
+                    &config,
+                    move |data: &[i16], info: &InputCallbackInfo| {
+                        buffers.lock().unwrap().push((data.len(), info.budget()))
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        device.process(7);
+        // One sample per frame, at 16 kHz.
+        let expected = vec![
+            (4, Duration::from_micros(250)),
+            (3, Duration::from_nanos(187_500)),
+        ];
+        assert_eq!(*buffers.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn raw_file_capture() {
+        let path = std::env::temp_dir().join(format!("cpal_raw_{}.wav", std::process::id()));
+        let samples = [0.5, -0.5, 0.25, -0.25, 1.0, -1.0];
+        {
+            let spec = WavSpec {
+                channels: 2,
+                sample_rate: SampleRate(8_000),
+            };
+            let file = BufWriter::new(File::create(&path).unwrap());
+            let mut writer = WavWriter::new(file, spec, SampleFormat::F32).unwrap();
+            writer.write(&samples).unwrap();
+            writer.finalize().unwrap();
+        }
+        let device = super::file_device(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config(&device, BufferSize::Fixed(2));
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let captured = captured.clone();
+            device
+                .build_input_stream_raw(
+                    &config,
+                    SampleFormat::I16,
+                    move |data, _: &InputCallbackInfo| {
+                        assert_eq!(data.sample_format(), SampleFormat::I16);
+                        let data: &[i16] = data.as_slice().unwrap();
+                        captured.lock().unwrap().extend_from_slice(data);
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        device.process(3);
+        let expected: Vec<i16> = samples.iter().map(|sample| sample.to_i16()).collect();
+        assert_eq!(*captured.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn streams_own_their_callback_state() {
+        let device = TestDevice::new(1, SampleRate(44_100));
+        let config = config(&device, BufferSize::Fixed(1));
+        let counter = |step: f32| {
+            let mut next = 0.0;
+            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                for sample in data.iter_mut() {
+                    *sample = next;
+                    next += step;
+                }
+            }
+        };
+        let a = device
+            .build_output_stream(&config, counter(0.25), |_| ())
+            .unwrap();
+        let b = device
+            .build_output_stream(&config, counter(-0.125), |_| ())
+            .unwrap();
+        a.play().unwrap();
+        b.play().unwrap();
+        device.process(3);
+        assert_eq!(device.recorded_output(), vec![0.0, 0.125, 0.25]);
+    }
+
+    #[test]
+    fn is_playing_follows_play_and_pause() {
+        let device = TestDevice::new(1, SampleRate(44_100));
+        let config = config(&device, BufferSize::Fixed(1));
+        let stream = device
+            .build_output_stream(&config, |_: &mut [f32], _: &OutputCallbackInfo| (), |_| ())
+            .unwrap();
+        assert!(!stream.is_playing());
+        stream.play().unwrap();
+        assert!(stream.is_playing());
+        stream.pause().unwrap();
+        assert!(!stream.is_playing());
+    }
+
+    #[test]
+    fn callback_budget() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let budgets = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let budgets = budgets.clone();
+            device
+                .build_output_stream(
+                    &config(&device, BufferSize::Fixed(480)),
+                    move |_: &mut [f32], info: &OutputCallbackInfo| {
+                        budgets.lock().unwrap().push(info.budget())
+                    },
+                    |_| (),
+                )
+                .unwrap()
+        };
+        stream.play().unwrap();
+        // The last buffer is only partially filled.
+        device.process(720);
+        assert_eq!(
+            *budgets.lock().unwrap(),
+            vec![Duration::from_millis(10), Duration::from_millis(5)]
+        );
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let valid = config(&device, BufferSize::Default);
+        let zero_channels = StreamConfig {
+            channels: 0,
+            ..valid.clone()
+        };
+        let zero_sample_rate = StreamConfig {
+            sample_rate: SampleRate(0),
+            ..valid
+        };
+        for config in &[zero_channels, zero_sample_rate] {
+            let input =
+                device.build_input_stream(config, |_: &[i16], _: &InputCallbackInfo| (), |_| ());
+            assert!(matches!(input, Err(BuildStreamError::InvalidStreamConfig)));
+            let output = device.build_output_stream(
+                config,
+                |_: &mut [f32], _: &OutputCallbackInfo| (),
+                |_| (),
+            );
+            assert!(matches!(output, Err(BuildStreamError::InvalidStreamConfig)));
+        }
+    }
+
+    #[test]
+    fn unsupported_config() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let mut config = config(&device, BufferSize::Default);
+        config.channels = 1;
+        let result =
+            device.build_output_stream(&config, |_: &mut [f32], _: &OutputCallbackInfo| (), |_| ());
+        assert!(matches!(
+            result,
+            Err(BuildStreamError::StreamConfigNotSupported)
+        ));
+    }
+
+    #[test]
+    fn bit_perfect_not_supported() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = config(&device, BufferSize::Default);
+        let result = device.build_output_stream_bit_perfect(&config, |_, _| (), |_| ());
+        assert!(matches!(
+            result,
+            Err(BuildStreamError::BitPerfectNotSupported)
+        ));
+    }
+
+    #[test]
+    fn period_count_not_supported() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = config(&device, BufferSize::Default);
+        let build = |periods| {
+            let options = StreamOptions {
+                periods: Some(periods),
+                ..Default::default()
+            };
+            device.build_output_stream_with_options(
+                &config,
+                &options,
+                |_: &mut [f32], _: &OutputCallbackInfo| (),
+                |_| (),
+            )
+        };
+        assert!(matches!(
+            build(2),
+            Err(BuildStreamError::PeriodCountNotSupported)
+        ));
+        assert!(matches!(build(0), Err(BuildStreamError::InvalidArgument)));
+    }
+
+    #[test]
+    fn cpu_affinity() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = config(&device, BufferSize::Default);
+        let build = |cpus: Vec<usize>, errors: Arc<Mutex<Vec<StreamError>>>| {
+            let options = StreamOptions {
+                cpu_affinity: Some(cpus),
+                ..Default::default()
+            };
+            device.build_output_stream_with_options(
+                &config,
+                &options,
+                |_: &mut [f32], _: &OutputCallbackInfo| (),
+                move |err| errors.lock().unwrap().push(err),
+            )
+        };
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        assert!(matches!(
+            build(Vec::new(), errors.clone()),
+            Err(BuildStreamError::InvalidArgument)
+        ));
+
+        // CPUs out of range of the affinity mask are reported once the callback runs.
+        let stream = build(vec![1 << 20], errors.clone()).unwrap();
+        stream.play().unwrap();
+        device.process(4);
+        device.process(4);
+        let expected = if cfg!(any(target_os = "linux", target_os = "windows")) {
+            1
+        } else {
+            0
+        };
+        assert_eq!(errors.lock().unwrap().len(), expected);
+    }
+
+    #[test]
+    fn foreign_threads_are_left_alone() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = config(&device, BufferSize::Default);
+        let options = StreamOptions {
+            realtime: true,
+            cpu_affinity: Some(vec![1 << 20]),
+            ..Default::default()
+        };
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let stream = {
+            let errors = errors.clone();
+            traits::build_output_stream_with_options(
+                &device,
+                &config,
+                &options,
+                CallbackThread::Foreign,
+                |_: &mut [f32], _: &OutputCallbackInfo| (),
+                move |err| errors.lock().unwrap().push(err),
+            )
+            .unwrap()
+        };
+        stream.play().unwrap();
+        device.process(4);
+        assert!(errors.lock().unwrap().is_empty());
+
+        // The list of CPUs is still validated.
+        let options = StreamOptions {
+            cpu_affinity: Some(Vec::new()),
+            ..Default::default()
+        };
+        let result = traits::build_output_stream_with_options(
+            &device,
+            &config,
+            &options,
+            CallbackThread::Foreign,
+            |_: &mut [f32], _: &OutputCallbackInfo| (),
+            |_| (),
+        );
+        assert!(matches!(result, Err(BuildStreamError::InvalidArgument)));
+    }
+
+    #[test]
+    fn preferred_sample_format() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = config(&device, BufferSize::Default);
+        let formats = [SampleFormat::I16, SampleFormat::F32];
+        let (_stream, sample_format) = device
+            .build_output_stream_preferring(&config, &formats, |_, _| (), |_| ())
+            .unwrap();
+        assert_eq!(sample_format, SampleFormat::I16);
+        let (_stream, sample_format) = device
+            .build_input_stream_preferring(&config, &formats[1..], |_, _| (), |_| ())
+            .unwrap();
+        assert_eq!(sample_format, SampleFormat::F32);
+
+        let mono = StreamConfig {
+            channels: 1,
+            ..config.clone()
+        };
+        let result = device.build_output_stream_preferring(&mono, &formats, |_, _| (), |_| ());
+        assert!(matches!(
+            result,
+            Err(BuildStreamError::StreamConfigNotSupported)
+        ));
+        let result = device.build_output_stream_preferring(&config, &[], |_, _| (), |_| ());
+        assert!(matches!(
+            result,
+            Err(BuildStreamError::StreamConfigNotSupported)
+        ));
+    }
+
+    #[test]
+    fn supported_buffer_size() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = device.default_output_config().unwrap();
+        let range = SupportedBufferSize::Range {
+            min: 1,
+            max: super::MAX_BUFFER_SIZE,
+        };
+        assert_eq!(device.supported_output_buffer_size(&config).unwrap(), range);
+        assert_eq!(device.supported_input_buffer_size(&config).unwrap(), range);
+        let other_rate = SupportedStreamConfig {
+            sample_rate: SampleRate(44_100),
+            ..config
+        };
+        assert!(matches!(
+            device.supported_output_buffer_size(&other_rate),
+            Err(SupportedStreamConfigsError::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn default_buffer_size_is_unknown() {
+        // Test streams built with `BufferSize::Default` are called back with whatever is processed.
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let config = device.default_output_config().unwrap();
+        assert_eq!(device.default_buffer_size(&config), None);
+    }
+
+    #[test]
+    fn discrete_sample_rates() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        let rates = vec![SampleRate(48_000)];
+        assert_eq!(device.supported_input_sample_rates().unwrap(), rates);
+        assert_eq!(device.supported_output_sample_rates().unwrap(), rates);
+    }
+
+    #[test]
+    fn max_channels() {
+        let device = TestDevice::new(6, SampleRate(48_000));
+        assert_eq!(device.max_input_channels().unwrap(), 6);
+        assert_eq!(device.max_output_channels().unwrap(), 6);
+    }
+
+    #[test]
+    fn direction() {
+        let device = TestDevice::new(2, SampleRate(48_000));
+        assert!(device.supports_input());
+        assert!(device.supports_output());
+        assert_eq!(device.direction(), Some(::Direction::Duplex));
+    }
+
+    #[test]
+    fn capabilities_snapshot() {
+        let device = TestDevice::new(2, SampleRate(48_000)).with_name("snapshot");
+        let capabilities = device.capabilities().unwrap();
+        let capabilities = std::thread::spawn(move || capabilities).join().unwrap();
+        assert_eq!(capabilities.name(), "snapshot");
+        assert_eq!(
+            capabilities.supported_output_configs(),
+            &device
+                .supported_output_configs()
+                .unwrap()
+                .collect::<Vec<_>>()[..]
+        );
+        assert_eq!(
+            capabilities.default_input_config(),
+            Some(&device.default_input_config().unwrap())
+        );
+    }
+}
@@ -1,10 +1,35 @@
 //! The suite of traits allowing CPAL to abstract over hosts, devices, event loops and stream IDs.
 
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
+#[cfg(feature = "async")]
+use async_input::{self, AsyncInputStream};
+use blocking::{self, BlockingInputStream, BlockingOutputStream};
+use blocks::Blocks;
+use channel_map::{self, SharedMatrix};
+use clip;
+use dither;
+use drift::DriftCorrector;
+use duplex::{self, StreamPair};
+use meter::Meter;
+use planar;
+use realtime;
+use ring_buffer::{self, Consumer, Producer};
+use stats::Recorder;
+use stream_event::PendingEvent;
+use underrun::UnderrunFiller;
+use unwind::PanicGuard;
 use {
-    BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, InputDevices, OutputCallbackInfo, OutputDevices, PauseStreamError,
-    PlayStreamError, Sample, SampleFormat, StreamConfig, StreamError, SupportedStreamConfig,
-    SupportedStreamConfigRange, SupportedStreamConfigsError,
+    BufferSize, BuildStreamError, CallbackControl, ChannelCount, ChannelLayout, ChannelMap,
+    ClipMode, ClippedStream, ControlledStream, Data, DefaultStreamConfigError, DeviceCapabilities,
+    DeviceInUseError, DeviceInfo, DeviceNameError, DeviceQuery, DeviceVolumeError, DevicesError,
+    Direction, Dither, DriftCorrectedStream, FormFactor, FrameCount, InputCallbackInfo,
+    InputDevices, InstrumentedStream, MeteredStream, NotifyingStream, OutputCallbackInfo,
+    OutputDevices, OverrunCheck, PauseStreamError, PlayStreamError, QueriedDevices, Role,
+    RoutedStream, Sample, SampleFormat, SampleRate, StreamConfig, StreamError, StreamEvent,
+    StreamOptions, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    SupportedStreamConfigsError, UnderrunPolicy, COMMON_SAMPLE_RATES,
 };
 
 /// A **Host** provides access to the available audio devices on the system.
@@ -50,16 +75,58 @@ pub trait HostTrait {
     /// Returns `None` if no output device is available.
     fn default_output_device(&self) -> Option<Self::Device>;
 
+    /// The default input audio device on the system for the given role, e.g. the headset
+    /// microphone for `Role::Communications`.
+    ///
+    /// Hosts that do not distinguish between roles return `default_input_device`.
+    ///
+    /// Returns `None` if no input device is available.
+    fn default_input_device_for_role(&self, role: Role) -> Option<Self::Device> {
+        let _ = role;
+        self.default_input_device()
+    }
+
+    /// The default output audio device on the system for the given role.
+    ///
+    /// Hosts that do not distinguish between roles return `default_output_device`.
+    ///
+    /// Returns `None` if no output device is available.
+    fn default_output_device_for_role(&self, role: Role) -> Option<Self::Device> {
+        let _ = role;
+        self.default_output_device()
+    }
+
+    /// Whether `device` is the current default input device, e.g. to mark it among the
+    /// enumerated devices.
+    ///
+    /// The default device is looked up again on each call, so this reflects changes to the
+    /// system's default made while the program is running.
+    fn is_default_input_device(&self, device: &Self::Device) -> bool
+    where
+        Self::Device: PartialEq,
+    {
+        self.default_input_device().as_ref() == Some(device)
+    }
+
+    /// Whether `device` is the current default output device, e.g. to mark it among the
+    /// enumerated devices.
+    ///
+    /// The default device is looked up again on each call, so this reflects changes to the
+    /// system's default made while the program is running.
+    fn is_default_output_device(&self, device: &Self::Device) -> bool
+    where
+        Self::Device: PartialEq,
+    {
+        self.default_output_device().as_ref() == Some(device)
+    }
+
     /// An iterator yielding all `Device`s currently available to the system that support one or more
     /// input stream formats.
     ///
     /// Can be empty if the system does not support audio input.
     fn input_devices(&self) -> Result<InputDevices<Self::Devices>, DevicesError> {
         fn supports_input<D: DeviceTrait>(device: &D) -> bool {
-            device
-                .supported_input_configs()
-                .map(|mut iter| iter.next().is_some())
-                .unwrap_or(false)
+            device.supports_input()
         }
         Ok(self.devices()?.filter(supports_input::<Self::Device>))
     }
@@ -70,13 +137,48 @@ pub trait HostTrait {
     /// Can be empty if the system does not support audio output.
     fn output_devices(&self) -> Result<OutputDevices<Self::Devices>, DevicesError> {
         fn supports_output<D: DeviceTrait>(device: &D) -> bool {
-            device
-                .supported_output_configs()
-                .map(|mut iter| iter.next().is_some())
-                .unwrap_or(false)
+            device.supports_output()
         }
         Ok(self.devices()?.filter(supports_output::<Self::Device>))
     }
+
+    /// An iterator yielding the `Device`s currently available to the system that support an
+    /// input stream satisfying `query`.
+    fn input_devices_supporting(
+        &self,
+        query: &DeviceQuery,
+    ) -> Result<QueriedDevices<Self::Devices>, DevicesError> {
+        Ok(DeviceQuery::devices(self.devices()?, query, true))
+    }
+
+    /// An iterator yielding the `Device`s currently available to the system that support an
+    /// output stream satisfying `query`.
+    fn output_devices_supporting(
+        &self,
+        query: &DeviceQuery,
+    ) -> Result<QueriedDevices<Self::Devices>, DevicesError> {
+        Ok(DeviceQuery::devices(self.devices()?, query, false))
+    }
+
+    /// List the devices along with their capabilities, and whether they are the default input or
+    /// output device, in a single pass.
+    ///
+    /// The default devices are looked up once, and each device is queried once. Devices that
+    /// disappear while being listed, or that support neither direction, are left out rather
+    /// than failing the whole listing.
+    fn enumerate_with_defaults(&self) -> Result<Vec<DeviceInfo<Self::Device>>, DevicesError>
+    where
+        Self::Device: PartialEq,
+    {
+        let default_input = self.default_input_device();
+        let default_output = self.default_output_device();
+        let devices = self.devices()?.filter_map(|device| {
+            let is_default_input = default_input.as_ref() == Some(&device);
+            let is_default_output = default_output.as_ref() == Some(&device);
+            DeviceInfo::new(device, is_default_input, is_default_output)
+        });
+        Ok(devices.collect())
+    }
 }
 
 /// A device that is capable of audio input and/or output.
@@ -108,17 +210,294 @@ pub trait DeviceTrait {
         &self,
     ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError>;
 
+    /// The distinct channel counts supported by the device for input streams, in ascending order.
+    ///
+    /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
+    fn supported_input_channel_counts(
+        &self,
+    ) -> Result<Vec<ChannelCount>, SupportedStreamConfigsError> {
+        Ok(channel_counts(self.supported_input_configs()?))
+    }
+
+    /// The distinct channel counts supported by the device for output streams, in ascending order.
+    ///
+    /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
+    fn supported_output_channel_counts(
+        &self,
+    ) -> Result<Vec<ChannelCount>, SupportedStreamConfigsError> {
+        Ok(channel_counts(self.supported_output_configs()?))
+    }
+
+    /// Whether the device supports input streams.
+    ///
+    /// WASAPI and CoreAudio answer from the data flow of the endpoint and from the streams of the
+    /// device respectively, without querying its configs. Other hosts check whether the device
+    /// has any supported input config, an error counting as no support.
+    fn supports_input(&self) -> bool {
+        self.supported_input_configs()
+            .map(|mut configs| configs.next().is_some())
+            .unwrap_or(false)
+    }
+
+    /// Whether the device supports output streams, determined like `supports_input`.
+    fn supports_output(&self) -> bool {
+        self.supported_output_configs()
+            .map(|mut configs| configs.next().is_some())
+            .unwrap_or(false)
+    }
+
+    /// The directions in which the device supports streams, or `None` if it supports neither,
+    /// e.g. because it has been disconnected. See `supports_input`.
+    fn direction(&self) -> Option<Direction> {
+        match (self.supports_input(), self.supports_output()) {
+            (true, true) => Some(Direction::Duplex),
+            (true, false) => Some(Direction::Input),
+            (false, true) => Some(Direction::Output),
+            (false, false) => None,
+        }
+    }
+
+    /// The largest channel count of the device's input streams, e.g. to describe it as an
+    /// 8-channel interface before picking a config. `0` if the device has no inputs.
+    ///
+    /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
+    fn max_input_channels(&self) -> Result<ChannelCount, SupportedStreamConfigsError> {
+        let configs = supported_or_empty(self.supported_input_configs())?;
+        Ok(configs.iter().map(|c| c.channels()).max().unwrap_or(0))
+    }
+
+    /// The largest channel count of the device's output streams. `0` if the device has no
+    /// outputs.
+    ///
+    /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
+    fn max_output_channels(&self) -> Result<ChannelCount, SupportedStreamConfigsError> {
+        let configs = supported_or_empty(self.supported_output_configs())?;
+        Ok(configs.iter().map(|c| c.channels()).max().unwrap_or(0))
+    }
+
+    /// The distinct sample rates supported by the device for input streams, in ascending order,
+    /// e.g. to offer a choice of rates rather than a slider over a range.
+    ///
+    /// Configs supporting a single rate contribute that rate. Configs supporting a continuous
+    /// range of rates, usually because the host resamples, contribute the common rates between
+    /// 5512 Hz and 192 kHz that lie within the range, along with its bounds.
+    ///
+    /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
+    fn supported_input_sample_rates(&self) -> Result<Vec<SampleRate>, SupportedStreamConfigsError> {
+        Ok(sample_rates(self.supported_input_configs()?))
+    }
+
+    /// The distinct sample rates supported by the device for output streams, in ascending order.
+    /// See `supported_input_sample_rates`.
+    ///
+    /// Can return an error if the device is no longer valid (e.g. it has been disconnected).
+    fn supported_output_sample_rates(
+        &self,
+    ) -> Result<Vec<SampleRate>, SupportedStreamConfigsError> {
+        Ok(sample_rates(self.supported_output_configs()?))
+    }
+
+    /// Query the name, default configs and supported configs of the device at once.
+    ///
+    /// Directions that the device does not support are left empty. Fails if the device is no
+    /// longer available, or if the host fails to report any of them.
+    fn capabilities(&self) -> Result<DeviceCapabilities, SupportedStreamConfigsError> {
+        let name = self.name().map_err(|err| match err {
+            DeviceNameError::BackendSpecific { err } => err,
+        })?;
+        Ok(DeviceCapabilities {
+            name,
+            supported_input_configs: supported_or_empty(self.supported_input_configs())?,
+            supported_output_configs: supported_or_empty(self.supported_output_configs())?,
+            default_input_config: default_or_none(self.default_input_config())?,
+            default_output_config: default_or_none(self.default_output_config())?,
+        })
+    }
+
+    /// The range of buffer sizes, in frames, that the device supports for input streams of the
+    /// given config, e.g. to let the user pick a `BufferSize::Fixed` value.
+    ///
+    /// Returns `SupportedBufferSize::Unknown` if the host does not report it, and
+    /// `SupportedStreamConfigsError::InvalidArgument` if no supported input config matches.
+    fn supported_input_buffer_size(
+        &self,
+        config: &SupportedStreamConfig,
+    ) -> Result<SupportedBufferSize, SupportedStreamConfigsError> {
+        buffer_size_of(self.supported_input_configs()?, config)
+    }
+
+    /// The range of buffer sizes, in frames, that the device supports for output streams of the
+    /// given config, e.g. to let the user pick a `BufferSize::Fixed` value.
+    ///
+    /// Returns `SupportedBufferSize::Unknown` if the host does not report it, and
+    /// `SupportedStreamConfigsError::InvalidArgument` if no supported output config matches.
+    fn supported_output_buffer_size(
+        &self,
+        config: &SupportedStreamConfig,
+    ) -> Result<SupportedBufferSize, SupportedStreamConfigsError> {
+        buffer_size_of(self.supported_output_configs()?, config)
+    }
+
+    /// The volume of the device set in the system, from `0.0` (silent) to `1.0` (full volume).
+    ///
+    /// This is the volume of the device itself, shared by every application using it, rather than
+    /// that of the streams of this application. Its scope and the permissions needed depend on
+    /// the host:
+    ///
+    /// - ALSA: the `Master` or `PCM` playback control of the card's mixer, or else its `Capture`
+    ///   control, scaled linearly over the range of the control. PCMs that are not bound to a
+    ///   card, such as `default`, use the default mixer. Changing it requires write access to the
+    ///   card's control device, usually granted by the `audio` group.
+    /// - WASAPI: the master volume of the endpoint, as shown in the sound settings. No special
+    ///   permissions are needed, but the change applies to every session of the endpoint.
+    /// - CoreAudio on macOS: the master volume of the device's output scope, or of its input scope
+    ///   for input-only devices. Devices that only expose per-channel volumes are not supported.
+    ///
+    /// Other hosts return `DeviceVolumeError::NotSupported`.
+    fn volume(&self) -> Result<f32, DeviceVolumeError> {
+        Err(DeviceVolumeError::NotSupported)
+    }
+
+    /// Set the volume of the device in the system, from `0.0` (silent) to `1.0` (full volume),
+    /// see `volume`. Values outside of this range are clamped.
+    fn set_volume(&self, volume: f32) -> Result<(), DeviceVolumeError> {
+        let _ = volume;
+        Err(DeviceVolumeError::NotSupported)
+    }
+
+    /// Whether the device is muted in the system, see `volume` for the scope of the control.
+    fn is_muted(&self) -> Result<bool, DeviceVolumeError> {
+        Err(DeviceVolumeError::NotSupported)
+    }
+
+    /// Mute or unmute the device in the system, see `volume` for the scope of the control.
+    fn set_muted(&self, muted: bool) -> Result<(), DeviceVolumeError> {
+        let _ = muted;
+        Err(DeviceVolumeError::NotSupported)
+    }
+
+    /// Whether another application, or another stream of this one, holds the device so that it
+    /// cannot be opened exclusively, e.g. with `build_output_stream_bit_perfect`.
+    ///
+    /// This is a hint for presenting devices to the user, not a guarantee: the device may be
+    /// taken or released right after the check. How it is checked depends on the host:
+    ///
+    /// - ALSA: the PCM is opened in each direction, and is in use if that fails as busy. Devices
+    ///   shared through the plug or dmix layers, such as `default`, are never in use.
+    /// - WASAPI: the endpoint is briefly opened in exclusive mode with its default format, and is
+    ///   in use if this fails because the endpoint is in use. Endpoints without an exclusive
+    ///   format for their default format are reported as not in use.
+    /// - CoreAudio on macOS: the device is in use if another process holds it in hog mode.
+    ///
+    /// Other hosts return `DeviceInUseError::NotSupported`.
+    fn is_in_use(&self) -> Result<bool, DeviceInUseError> {
+        Err(DeviceInUseError::NotSupported)
+    }
+
+    /// The physical kind of the device, e.g. to show an icon for it in a device picker, or `None`
+    /// if the host does not know it.
+    ///
+    /// - WASAPI: the form factor of the endpoint (`PKEY_AudioEndpoint_FormFactor`).
+    /// - CoreAudio on macOS: derived from the transport type of the device, and for built-in
+    ///   devices from their data source, e.g. the internal speakers or the headphone jack.
+    ///
+    /// Other hosts return `None`.
+    fn form_factor(&self) -> Option<FormFactor> {
+        None
+    }
+
     /// The default input stream format for the device.
+    ///
+    /// On WASAPI, this is the format at which the system mixer currently runs in shared mode.
+    /// Other hosts choose among the supported configs when the OS reports no such format.
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError>;
 
     /// The default output stream format for the device.
+    ///
+    /// On WASAPI, this is the format at which the system mixer currently runs in shared mode.
+    /// Other hosts choose among the supported configs when the OS reports no such format.
     fn default_output_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError>;
 
+    /// The speaker positions of the channels of the default input config, e.g. to show
+    /// "Stereo (2ch)" to users.
+    ///
+    /// WASAPI reports the channel mask of the format of the system mixer. Other hosts assume the
+    /// usual layout of the default number of channels, see `ChannelLayout::for_channels`, and
+    /// return `None` if there is none.
+    fn default_input_channel_layout(
+        &self,
+    ) -> Result<Option<ChannelLayout>, DefaultStreamConfigError> {
+        let config = self.default_input_config()?;
+        Ok(ChannelLayout::for_channels(config.channels()))
+    }
+
+    /// The speaker positions of the channels of the default output config. Like
+    /// `default_input_channel_layout`.
+    fn default_output_channel_layout(
+        &self,
+    ) -> Result<Option<ChannelLayout>, DefaultStreamConfigError> {
+        let config = self.default_output_config()?;
+        Ok(ChannelLayout::for_channels(config.channels()))
+    }
+
+    /// The buffer size in frames that the host prefers for streams of the given config, e.g. as
+    /// the starting point of a buffer size setting within `SupportedBufferSize::Range`.
+    ///
+    /// This is the size of the buffers passed to the data callback when building the stream with
+    /// `BufferSize::Default`, where the host knows it in advance:
+    ///
+    /// - ALSA: the period closest to the 25 milliseconds that cpal requests by default.
+    /// - CoreAudio: the current buffer frame size of the device.
+    /// - WASAPI: the default device period, converted to frames at the sample rate of `config`.
+    ///
+    /// Returns `None` on other hosts, or if the device could not be queried.
+    fn default_buffer_size(&self, config: &SupportedStreamConfig) -> Option<FrameCount> {
+        let _ = config;
+        None
+    }
+
+    /// An output stream format suited to low latency playback, e.g. for games or instruments.
+    ///
+    /// Unlike `default_output_config`, which is often chosen by the OS to save power, this picks
+    /// the supported config that ranks greatest with
+    /// `SupportedStreamConfigRange::cmp_low_latency_heuristics`, preferring f32 stereo formats
+    /// with the smallest minimum buffer size. The sample rate of the default output config is
+    /// kept if possible, otherwise the maximum supported sample rate is used.
+    ///
+    /// The returned buffer size range is left to the caller: request its minimum with
+    /// `BufferSize::Fixed` to get the lowest latency the device allows.
+    fn default_low_latency_output_config(
+        &self,
+    ) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+        let config = self
+            .supported_output_configs()
+            .map_err(default_config_error)?
+            .max_by(|a, b| a.cmp_low_latency_heuristics(b))
+            .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)?;
+        let default_sample_rate = self.default_output_config().map(|c| c.sample_rate());
+        match default_sample_rate {
+            Ok(rate) if config.min_sample_rate() <= rate && rate <= config.max_sample_rate() => {
+                Ok(config.with_sample_rate(rate))
+            }
+            _ => Ok(config.with_max_sample_rate()),
+        }
+    }
+
     /// Create an input stream.
+    ///
+    /// The stream is opened in the sample format of `T`, and the data callback is handed the
+    /// buffer of the host as is, without converting or copying the samples.
+    ///
+    /// Returns `BuildStreamError::InvalidStreamConfig` if `config` has zero channels or a zero
+    /// sample rate, and `BuildStreamError::StreamConfigNotSupported` if `config.channels` does
+    /// not match the channel count of any of the device's supported input configs.
+    ///
+    /// Should the data callback panic, the panic is caught before it reaches the host and
+    /// reported as `StreamError::CallbackPanic`, and the data callback is not invoked anymore.
     fn build_input_stream<T, D, E>(
         &self,
         config: &StreamConfig,
-        mut data_callback: D,
+        data_callback: D,
         error_callback: E,
     ) -> Result<Self::Stream, BuildStreamError>
     where
@@ -126,25 +505,24 @@ pub trait DeviceTrait {
         D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        self.build_input_stream_raw(
-            config,
-            T::FORMAT,
-            move |data, info| {
-                data_callback(
-                    data.as_slice()
-                        .expect("host supplied incorrect sample type"),
-                    info,
-                )
-            },
-            error_callback,
-        )
+        build_typed_input_stream(self, config, None, data_callback, error_callback)
     }
 
     /// Create an output stream.
+    ///
+    /// The stream is opened in the sample format of `T`, and the data callback fills the buffer
+    /// of the host directly, without converting or copying the samples.
+    ///
+    /// Returns `BuildStreamError::InvalidStreamConfig` if `config` has zero channels or a zero
+    /// sample rate, and `BuildStreamError::StreamConfigNotSupported` if `config.channels` does
+    /// not match the channel count of any of the device's supported output configs.
+    ///
+    /// Should the data callback panic, the panic is caught before it reaches the host and
+    /// reported as `StreamError::CallbackPanic`, and the stream plays silence from then on.
     fn build_output_stream<T, D, E>(
         &self,
         config: &StreamConfig,
-        mut data_callback: D,
+        data_callback: D,
         error_callback: E,
     ) -> Result<Self::Stream, BuildStreamError>
     where
@@ -152,57 +530,1333 @@ pub trait DeviceTrait {
         D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        self.build_output_stream_raw(
+        build_typed_output_stream(self, config, None, data_callback, error_callback)
+    }
+
+    /// Create an input stream like `build_input_stream`, with the given `StreamOptions`.
+    fn build_input_stream_with_options<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        options: &StreamOptions,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        build_input_stream_with_options(
+            self,
             config,
-            T::FORMAT,
-            move |data, info| {
-                data_callback(
-                    data.as_slice_mut()
-                        .expect("host supplied incorrect sample type"),
-                    info,
-                )
-            },
+            options,
+            CallbackThread::Owned,
+            data_callback,
             error_callback,
         )
     }
 
-    /// Create a dynamically typed input stream.
-    fn build_input_stream_raw<D, E>(
+    /// Create an output stream like `build_output_stream`, with the given `StreamOptions`.
+    fn build_output_stream_with_options<T, D, E>(
         &self,
         config: &StreamConfig,
-        sample_format: SampleFormat,
+        options: &StreamOptions,
         data_callback: D,
         error_callback: E,
     ) -> Result<Self::Stream, BuildStreamError>
     where
-        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
-        E: FnMut(StreamError) + Send + 'static;
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        build_output_stream_with_options(
+            self,
+            config,
+            options,
+            CallbackThread::Owned,
+            data_callback,
+            error_callback,
+        )
+    }
 
-    /// Create a dynamically typed output stream.
-    fn build_output_stream_raw<D, E>(
+    /// Create an input stream whose data callback receives one buffer per channel rather than
+    /// interleaved samples.
+    ///
+    /// Hosts whose buffers are planar (currently JACK) pass them through directly. Other hosts
+    /// de-interleave each buffer before invoking the data callback.
+    fn build_input_stream_planar<T, D, E>(
         &self,
         config: &StreamConfig,
-        sample_format: SampleFormat,
         data_callback: D,
         error_callback: E,
     ) -> Result<Self::Stream, BuildStreamError>
     where
-        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
-        E: FnMut(StreamError) + Send + 'static;
-}
+        T: Sample + Send + 'static,
+        D: FnMut(&[&[T]], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = planar::deinterleave_input(config.channels, data_callback);
+        self.build_input_stream(config, data_callback, error_callback)
+    }
 
-/// A stream created from `Device`, with methods to control playback.
-pub trait StreamTrait {
-    /// Run the stream.
+    /// Create an output stream whose data callback fills one buffer per channel rather than
+    /// interleaved samples.
     ///
-    /// Note: Not all platforms automatically run the stream upon creation, so it is important to
-    /// call `play` after creation if it is expected that the stream should run immediately.
-    fn play(&self) -> Result<(), PlayStreamError>;
+    /// Hosts whose buffers are planar (currently JACK) pass them through directly. Other hosts
+    /// interleave the buffers after the data callback returns.
+    fn build_output_stream_planar<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [&mut [T]], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = planar::interleave_output(config.channels, data_callback);
+        self.build_output_stream(config, data_callback, error_callback)
+    }
 
-    /// Some devices support pausing the audio stream. This can be useful for saving energy in
-    /// moments of silence.
+    /// Create an output stream of samples of type `T` whose data callback produces `f32` samples.
     ///
-    /// Note: Not all devices support suspending the stream at the hardware level. This method may
-    /// fail in these cases.
-    fn pause(&self) -> Result<(), PauseStreamError>;
+    /// The samples are quantized to `T` with the given `dither` after the data callback returns,
+    /// which avoids quantization distortion when the device requires an integer format. If `T` is
+    /// `f32`, the data callback fills the buffer of the host directly instead.
+    fn build_output_stream_dithered<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        dither: Dither,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let data_callback = dither::dither_output::<T, D>(dither, data_callback);
+        self.build_output_stream(config, data_callback, error_callback)
+    }
+
+    /// Create an output stream of samples of type `T` whose data callback produces `f32` samples,
+    /// converting those outside of `[-1.0, 1.0]` according to `clip_mode`.
+    ///
+    /// The returned stream counts the out-of-range samples, e.g. for a clip indicator. If `T` is
+    /// `f32`, the data callback fills the buffer of the host, which is then clipped in place.
+    fn build_output_stream_clipped<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        clip_mode: ClipMode,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<ClippedStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let clipped = Arc::new(AtomicU64::new(0));
+        let data_callback = clip::clip_output::<T, D>(clip_mode, clipped.clone(), data_callback);
+        let stream = self.build_output_stream(config, data_callback, error_callback)?;
+        Ok(ClippedStream::new(stream, clipped))
+    }
+
+    /// Create an input stream whose data callback can stop the stream by returning
+    /// `CallbackControl::Stop`, after which the stream is paused.
+    ///
+    /// The stream is built and owned by a dedicated thread, see `ControlledStream`. On targets
+    /// without threads this returns `BuildStreamError::BackendSpecific`.
+    fn build_input_stream_with_control<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<ControlledStream<Self::Stream>, BuildStreamError>
+    where
+        Self: Clone + Send + 'static,
+        Self::Stream: 'static,
+        T: Sample + 'static,
+        D: FnMut(&[T], &InputCallbackInfo) -> CallbackControl + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let device = self.clone();
+        let config = config.clone();
+        ControlledStream::spawn(move |finished| {
+            device.build_input_stream(
+                &config,
+                move |data: &[T], info: &InputCallbackInfo| {
+                    if finished.is_set() {
+                        return;
+                    }
+                    if let CallbackControl::Stop = data_callback(data, info) {
+                        finished.set();
+                    }
+                },
+                error_callback,
+            )
+        })
+    }
+
+    /// Create an output stream whose data callback can stop the stream by returning
+    /// `CallbackControl::Stop`, after which the stream is paused.
+    ///
+    /// The buffer passed to the callback that returns `Stop` is still played in full, so a
+    /// callback finishing partway through a buffer should fill the remainder with silence. Until
+    /// the stream is paused, it plays silence.
+    ///
+    /// The stream is built and owned by a dedicated thread, see `ControlledStream`. On targets
+    /// without threads this returns `BuildStreamError::BackendSpecific`.
+    fn build_output_stream_with_control<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<ControlledStream<Self::Stream>, BuildStreamError>
+    where
+        Self: Clone + Send + 'static,
+        Self::Stream: 'static,
+        T: Sample + 'static,
+        D: FnMut(&mut [T], &OutputCallbackInfo) -> CallbackControl + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let device = self.clone();
+        let config = config.clone();
+        ControlledStream::spawn(move |finished| {
+            device.build_output_stream(
+                &config,
+                move |data: &mut [T], info: &OutputCallbackInfo| {
+                    if finished.is_set() {
+                        for sample in data.iter_mut() {
+                            *sample = T::EQUILIBRIUM;
+                        }
+                        return;
+                    }
+                    if let CallbackControl::Stop = data_callback(data, info) {
+                        finished.set();
+                    }
+                },
+                error_callback,
+            )
+        })
+    }
+
+    /// Create an input stream that measures the peak and RMS level of each channel of the
+    /// captured audio, see `MeteredStream::levels`.
+    fn build_input_stream_with_meter<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<MeteredStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mut meter = Meter::new(config);
+        let levels = meter.levels();
+        let stream = self.build_input_stream(
+            config,
+            move |data: &[T], info: &InputCallbackInfo| {
+                meter.process(data);
+                data_callback(data, info);
+            },
+            error_callback,
+        )?;
+        Ok(MeteredStream::new(stream, levels))
+    }
+
+    /// Create an output stream that measures the peak and RMS level of each channel of the audio
+    /// written by the data callback, see `MeteredStream::levels`.
+    fn build_output_stream_with_meter<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<MeteredStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mut meter = Meter::new(config);
+        let levels = meter.levels();
+        let stream = self.build_output_stream(
+            config,
+            move |data: &mut [T], info: &OutputCallbackInfo| {
+                data_callback(data, info);
+                meter.process(data);
+            },
+            error_callback,
+        )?;
+        Ok(MeteredStream::new(stream, levels))
+    }
+
+    /// Create an input stream that keeps statistics about the invocations of its data callback,
+    /// see `InstrumentedStream::stats`.
+    fn build_input_stream_with_stats<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<InstrumentedStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mut recorder = Recorder::new(config);
+        let counters = recorder.counters();
+        let stream = self.build_input_stream(
+            config,
+            move |data: &[T], info: &InputCallbackInfo| {
+                recorder.record(data.len(), info.timestamp().callback);
+                data_callback(data, info);
+            },
+            error_callback,
+        )?;
+        Ok(InstrumentedStream::new(stream, counters))
+    }
+
+    /// Create an output stream that keeps statistics about the invocations of its data callback,
+    /// see `InstrumentedStream::stats`.
+    fn build_output_stream_with_stats<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<InstrumentedStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mut recorder = Recorder::new(config);
+        let counters = recorder.counters();
+        let stream = self.build_output_stream(
+            config,
+            move |data: &mut [T], info: &OutputCallbackInfo| {
+                recorder.record(data.len(), info.timestamp().callback);
+                data_callback(data, info);
+            },
+            error_callback,
+        )?;
+        Ok(InstrumentedStream::new(stream, counters))
+    }
+
+    /// Create an input stream that passes a `StreamEvent` to `event_callback` when audio starts
+    /// flowing, and again each time it resumes flowing after the stream was paused.
+    fn build_input_stream_with_events<T, D, N, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        mut event_callback: N,
+        error_callback: E,
+    ) -> Result<NotifyingStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        N: FnMut(StreamEvent) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let pending = PendingEvent::new();
+        let stream = {
+            let pending = pending.clone();
+            self.build_input_stream(
+                config,
+                move |data: &[T], info: &InputCallbackInfo| {
+                    if let Some(event) = pending.take() {
+                        event_callback(event);
+                    }
+                    data_callback(data, info);
+                },
+                error_callback,
+            )?
+        };
+        Ok(NotifyingStream::new(stream, pending))
+    }
+
+    /// Create an output stream that passes a `StreamEvent` to `event_callback` when audio starts
+    /// flowing, and again each time it resumes flowing after the stream was paused.
+    fn build_output_stream_with_events<T, D, N, E>(
+        &self,
+        config: &StreamConfig,
+        mut data_callback: D,
+        mut event_callback: N,
+        error_callback: E,
+    ) -> Result<NotifyingStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        N: FnMut(StreamEvent) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let pending = PendingEvent::new();
+        let stream = {
+            let pending = pending.clone();
+            self.build_output_stream(
+                config,
+                move |data: &mut [T], info: &OutputCallbackInfo| {
+                    if let Some(event) = pending.take() {
+                        event_callback(event);
+                    }
+                    data_callback(data, info);
+                },
+                error_callback,
+            )?
+        };
+        Ok(NotifyingStream::new(stream, pending))
+    }
+
+    /// Create an input and an output stream on this device, whose single data callback receives
+    /// the captured audio along with the output buffer to fill.
+    ///
+    /// The input buffer holds as many frames as the output buffer, with `input_config.channels`
+    /// channels each. See `StreamPair` for details.
+    ///
+    /// This is not a native duplex stream sharing one device clock, such as a single AudioUnit
+    /// with input and output on CoreAudio or linked PCMs on ALSA. The two streams are separate
+    /// and unsynchronized, joined by a ring buffer, so that their clocks may drift apart. Use
+    /// `build_duplex_stream_with_drift_correction` to compensate for this.
+    ///
+    /// Returns `BuildStreamError::InvalidArgument` if the two configurations have different
+    /// sample rates or if either has no channels.
+    fn build_unsynchronized_duplex_stream<T, D, E>(
+        &self,
+        input_config: &StreamConfig,
+        output_config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<StreamPair<Self::Stream>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        if input_config.sample_rate != output_config.sample_rate
+            || input_config.channels == 0
+            || output_config.channels == 0
+        {
+            return Err(BuildStreamError::InvalidArgument);
+        }
+        let frames = match input_config.buffer_size {
+            BufferSize::Fixed(frames) => (frames as usize * 4).max(duplex::DEFAULT_CAPACITY_FRAMES),
+            BufferSize::Default => duplex::DEFAULT_CAPACITY_FRAMES,
+        };
+        let (producer, mut consumer) = ring_buffer::ring_buffer(frames, input_config.channels);
+        let (input_error_callback, output_error_callback) =
+            duplex::shared_error_callback(error_callback);
+        let input =
+            self.build_input_stream_into_producer(input_config, producer, input_error_callback)?;
+        let input_channels = input_config.channels as usize;
+        let output_channels = output_config.channels as usize;
+        // Output buffers that exceed the ring buffer are not expected, but grow the scratch
+        // buffer if they do.
+        let mut buffer = vec![T::EQUILIBRIUM; frames * input_channels];
+        let output = self.build_output_stream(
+            output_config,
+            move |data: &mut [T], info: &OutputCallbackInfo| {
+                let len = data.len() / output_channels * input_channels;
+                if buffer.len() < len {
+                    buffer.resize(len, T::EQUILIBRIUM);
+                }
+                let input = &mut buffer[..len];
+                consumer.fill(input);
+                data_callback(input, data, info);
+            },
+            output_error_callback,
+        )?;
+        Ok(StreamPair::new(input, output))
+    }
+
+    /// Create an input and an output stream like `build_unsynchronized_duplex_stream`, whose
+    /// input is resampled to follow the clock of the output, e.g. for live monitoring. See
+    /// `DriftCorrectedStream` for details.
+    ///
+    /// Unlike with `build_unsynchronized_duplex_stream`, the input and output may have different
+    /// sample rates.
+    /// Returns `BuildStreamError::InvalidArgument` if either configuration has no channels.
+    fn build_duplex_stream_with_drift_correction<T, D, E>(
+        &self,
+        input_config: &StreamConfig,
+        output_config: &StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<DriftCorrectedStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        if input_config.channels == 0 || output_config.channels == 0 {
+            return Err(BuildStreamError::InvalidArgument);
+        }
+        let frames = match input_config.buffer_size {
+            BufferSize::Fixed(frames) => (frames as usize * 4).max(duplex::DEFAULT_CAPACITY_FRAMES),
+            BufferSize::Default => duplex::DEFAULT_CAPACITY_FRAMES,
+        };
+        let (producer, consumer) = ring_buffer::ring_buffer(frames, input_config.channels);
+        let (input_error_callback, output_error_callback) =
+            duplex::shared_error_callback(error_callback);
+        let input =
+            self.build_input_stream_into_producer(input_config, producer, input_error_callback)?;
+        let input_channels = input_config.channels as usize;
+        let output_channels = output_config.channels as usize;
+        let mut corrector = DriftCorrector::new(
+            consumer,
+            input_channels,
+            input_config.sample_rate,
+            output_config.sample_rate,
+        );
+        let drift = corrector.drift();
+        // Output buffers that exceed the ring buffer are not expected, but grow the scratch
+        // buffer if they do.
+        let mut buffer = vec![T::EQUILIBRIUM; frames * input_channels];
+        let output = self.build_output_stream(
+            output_config,
+            move |data: &mut [T], info: &OutputCallbackInfo| {
+                let len = data.len() / output_channels * input_channels;
+                if buffer.len() < len {
+                    buffer.resize(len, T::EQUILIBRIUM);
+                }
+                let input = &mut buffer[..len];
+                corrector.process(input);
+                data_callback(input, data, info);
+            },
+            output_error_callback,
+        )?;
+        Ok(DriftCorrectedStream::new(
+            StreamPair::new(input, output),
+            drift,
+        ))
+    }
+
+    /// Create an input stream whose data callback receives audio with `channels` channels, mixed
+    /// from the channels of `config` as described by `channel_map`.
+    ///
+    /// Returns `BuildStreamError::InvalidArgument` if `channel_map` cannot mix between the two
+    /// channel counts.
+    fn build_input_stream_with_channel_map<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        channels: ChannelCount,
+        channel_map: ChannelMap,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let matrix = channel_map.matrix(config.channels, channels)?;
+        let mut buffer = mix_buffer(config, channels);
+        let device_channels = config.channels as usize;
+        self.build_input_stream(
+            config,
+            move |data: &[T], info: &InputCallbackInfo| {
+                let frames = data.len() / device_channels;
+                buffer.resize(frames * channels as usize, T::EQUILIBRIUM);
+                channel_map::mix(&matrix, data, &mut buffer);
+                data_callback(&buffer, info);
+            },
+            error_callback,
+        )
+    }
+
+    /// Create an output stream whose data callback produces audio with `channels` channels,
+    /// mixed into the channels of `config` as described by `channel_map`.
+    ///
+    /// Returns `BuildStreamError::InvalidArgument` if `channel_map` cannot mix between the two
+    /// channel counts.
+    fn build_output_stream_with_channel_map<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        channels: ChannelCount,
+        channel_map: ChannelMap,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let matrix = channel_map.matrix(channels, config.channels)?;
+        let mut buffer = mix_buffer(config, channels);
+        let device_channels = config.channels as usize;
+        self.build_output_stream(
+            config,
+            move |data: &mut [T], info: &OutputCallbackInfo| {
+                let frames = data.len() / device_channels;
+                buffer.resize(frames * channels as usize, T::EQUILIBRIUM);
+                data_callback(&mut buffer, info);
+                channel_map::mix(&matrix, &buffer, data);
+            },
+            error_callback,
+        )
+    }
+
+    /// Create an output stream whose data callback produces `f32` audio with `channels` channels,
+    /// mixed into the channels of `config` through a routing matrix.
+    ///
+    /// The matrix starts out as that of `ChannelMap::Default` and can be replaced while the
+    /// stream runs with `RoutedStream::set_output_matrix`. Mixing is performed before converting
+    /// to `T`.
+    fn build_output_stream_routed<T, D, E>(
+        &self,
+        config: &StreamConfig,
+        channels: ChannelCount,
+        mut data_callback: D,
+        error_callback: E,
+    ) -> Result<RoutedStream<Self::Stream>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mut matrix = ChannelMap::Default.matrix(channels, config.channels)?;
+        let shared = SharedMatrix::new();
+        let mut buffer = mix_buffer(config, channels);
+        let device_channels = config.channels as usize;
+        let stream = {
+            let shared = shared.clone();
+            self.build_output_stream(
+                config,
+                move |data: &mut [T], info: &OutputCallbackInfo| {
+                    shared.update(&mut matrix);
+                    let frames = data.len() / device_channels;
+                    buffer.resize(frames * channels as usize, 0.0);
+                    data_callback(&mut buffer, info);
+                    channel_map::mix(&matrix, &buffer, data);
+                },
+                error_callback,
+            )?
+        };
+        Ok(RoutedStream::new(stream, shared, channels))
+    }
+
+    /// Create an input stream whose samples are read from the calling thread.
+    ///
+    /// See the `blocking` module for details.
+    fn build_blocking_input_stream<T, E>(
+        &self,
+        config: &StreamConfig,
+        error_callback: E,
+    ) -> Result<BlockingInputStream<T, Self::Stream>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let shared = blocking::Shared::new(config);
+        let stream = {
+            let shared = shared.clone();
+            self.build_input_stream(
+                config,
+                move |data: &[T], _: &InputCallbackInfo| shared.push_from(data),
+                error_callback,
+            )?
+        };
+        Ok(BlockingInputStream::new(stream, shared))
+    }
+
+    /// Create an output stream that is fed by writing samples to it from the calling thread.
+    ///
+    /// See the `blocking` module for details.
+    fn build_blocking_output_stream<T, E>(
+        &self,
+        config: &StreamConfig,
+        error_callback: E,
+    ) -> Result<BlockingOutputStream<T, Self::Stream>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let shared = blocking::Shared::new(config);
+        let stream = {
+            let shared = shared.clone();
+            self.build_output_stream(
+                config,
+                move |data: &mut [T], _: &OutputCallbackInfo| shared.pop_into(data),
+                error_callback,
+            )?
+        };
+        Ok(BlockingOutputStream::new(stream, shared))
+    }
+
+    /// Create an input stream that pushes all captured samples to the given `Producer`.
+    ///
+    /// Samples captured while the ring buffer is full are discarded.
+    fn build_input_stream_into_producer<T, E>(
+        &self,
+        config: &StreamConfig,
+        mut producer: Producer<T>,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_input_stream(
+            config,
+            move |data: &[T], _: &InputCallbackInfo| {
+                producer.push_slice(data);
+            },
+            error_callback,
+        )
+    }
+
+    /// Create an input stream whose captured samples are awaited as a `futures_core::Stream`,
+    /// e.g. from a tokio or async-std task. See `AsyncInputStream` for how the consumer of the
+    /// samples falling behind is handled.
+    ///
+    /// Only available with the `async` feature.
+    #[cfg(feature = "async")]
+    fn build_input_stream_async<T, E>(
+        &self,
+        config: &StreamConfig,
+        error_callback: E,
+    ) -> Result<AsyncInputStream<Self::Stream, T>, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let frames = config.sample_rate.0 as usize;
+        let (producer, consumer) = ring_buffer::ring_buffer(frames, config.channels);
+        let shared = async_input::Shared::new();
+        let data_callback =
+            async_input::input_callback(producer, shared.clone(), config.channels as usize);
+        let stream = self.build_input_stream(config, data_callback, error_callback)?;
+        Ok(AsyncInputStream::new(stream, consumer, shared))
+    }
+
+    /// Create an output stream that plays the samples popped from the given `Consumer`.
+    ///
+    /// Silence is played whenever the ring buffer runs dry, in which case the ring buffer's
+    /// underrun counter is incremented.
+    fn build_output_stream_from_consumer<T, E>(
+        &self,
+        config: &StreamConfig,
+        consumer: Consumer<T>,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let policy = UnderrunPolicy::Silence;
+        self.build_output_stream_from_consumer_with_policy(config, consumer, policy, error_callback)
+    }
+
+    /// Create an output stream like `build_output_stream_from_consumer`, which plays according
+    /// to `policy` whenever the ring buffer runs dry.
+    fn build_output_stream_from_consumer_with_policy<T, E>(
+        &self,
+        config: &StreamConfig,
+        consumer: Consumer<T>,
+        policy: UnderrunPolicy,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: Sample + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (mut underrun_error_callback, error_callback) =
+            duplex::shared_error_callback(error_callback);
+        let mut filler = UnderrunFiller::new(consumer, policy);
+        self.build_output_stream(
+            config,
+            move |data: &mut [T], _: &OutputCallbackInfo| {
+                filler.fill(data, &mut underrun_error_callback);
+            },
+            error_callback,
+        )
+    }
+
+    /// Create a dynamically typed input stream.
+    ///
+    /// Unlike `build_input_stream`, panics of the data callback are not caught.
+    fn build_input_stream_raw<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static;
+
+    /// Create a dynamically typed output stream.
+    ///
+    /// Unlike `build_output_stream`, panics of the data callback are not caught.
+    fn build_output_stream_raw<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static;
+
+    /// Create a dynamically typed input stream whose device buffer is split into `periods`
+    /// periods, see `StreamOptions::periods`.
+    ///
+    /// Hosts that cannot control the number of periods return
+    /// `BuildStreamError::PeriodCountNotSupported`.
+    fn build_input_stream_raw_with_periods<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        periods: u32,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let _ = (
+            config,
+            sample_format,
+            periods,
+            data_callback,
+            error_callback,
+        );
+        Err(BuildStreamError::PeriodCountNotSupported)
+    }
+
+    /// Create a dynamically typed output stream whose device buffer is split into `periods`
+    /// periods, see `StreamOptions::periods`.
+    ///
+    /// Hosts that cannot control the number of periods return
+    /// `BuildStreamError::PeriodCountNotSupported`.
+    fn build_output_stream_raw_with_periods<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        periods: u32,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let _ = (
+            config,
+            sample_format,
+            periods,
+            data_callback,
+            error_callback,
+        );
+        Err(BuildStreamError::PeriodCountNotSupported)
+    }
+
+    /// Create a dynamically typed input stream with the first of `sample_formats` that the device
+    /// supports along with `config`, e.g. `&[SampleFormat::F32, SampleFormat::I16]` to prefer
+    /// `f32` but accept `i16`.
+    ///
+    /// The chosen sample format is returned along with the stream and is the format of the
+    /// `Data` passed to the data callback. Returns `BuildStreamError::StreamConfigNotSupported`
+    /// if none of the formats is supported.
+    fn build_input_stream_preferring<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_formats: &[SampleFormat],
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let supported: Vec<_> = self.supported_input_configs()?.collect();
+        let sample_format = preferred_format(&supported, config, sample_formats)?;
+        let stream =
+            self.build_input_stream_raw(config, sample_format, data_callback, error_callback)?;
+        Ok((stream, sample_format))
+    }
+
+    /// Create a dynamically typed output stream with the first of `sample_formats` that the
+    /// device supports along with `config`.
+    ///
+    /// The returned sample format is the one of the `Data` that the data callback must write.
+    /// Otherwise this behaves like `build_input_stream_preferring`.
+    fn build_output_stream_preferring<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_formats: &[SampleFormat],
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let supported: Vec<_> = self.supported_output_configs()?.collect();
+        let sample_format = preferred_format(&supported, config, sample_formats)?;
+        let stream =
+            self.build_output_stream_raw(config, sample_format, data_callback, error_callback)?;
+        Ok((stream, sample_format))
+    }
+
+    /// Create a dynamically typed input stream that captures the samples of the device exactly as
+    /// the hardware produces them, without any conversion by CPAL or the system.
+    ///
+    /// The stream uses a sample format that the device supports natively with the channels and
+    /// sample rate of `config`. It is returned along with the stream and is the format of the
+    /// `Data` passed to the data callback. Rather than converting silently, building fails with:
+    ///
+    /// - `BuildStreamError::BitPerfectNotSupported` if the host cannot guarantee it for the device.
+    /// - `BuildStreamError::StreamConfigNotSupported` if the device has no native format for
+    ///   `config`, including when it would run at another sample rate.
+    ///
+    /// Support depends on the host:
+    ///
+    /// - ALSA: `hw:` devices are opened as is, and `plughw:` devices through the corresponding
+    ///   `hw:` device, bypassing the plug layer. Other PCMs, such as `default`, are not supported.
+    ///   The hardware device cannot be shared with other streams.
+    /// - WASAPI: the stream is opened in exclusive mode, bypassing the system mixer, which requires
+    ///   exclusive mode to be allowed in the device's settings. Loopback capture of output devices
+    ///   is not supported.
+    ///
+    /// Other hosts, including CoreAudio, return `BuildStreamError::BitPerfectNotSupported`.
+    fn build_input_stream_bit_perfect<D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let _ = (config, data_callback, error_callback);
+        Err(BuildStreamError::BitPerfectNotSupported)
+    }
+
+    /// Create a dynamically typed output stream whose samples are played exactly as written,
+    /// without any conversion by CPAL or the system.
+    ///
+    /// The returned sample format is the one of the `Data` that the data callback must write.
+    /// Otherwise this behaves like `build_input_stream_bit_perfect`, including which hosts
+    /// support it.
+    fn build_output_stream_bit_perfect<D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<(Self::Stream, SampleFormat), BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let _ = (config, data_callback, error_callback);
+        Err(BuildStreamError::BitPerfectNotSupported)
+    }
+}
+
+// Whether a host runs the data callback on a thread owned by cpal, to which the thread options of
+// `StreamOptions` apply, or on a thread it does not own, like the process thread of a JACK server,
+// which is left alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CallbackThread {
+    Owned,
+    // Only used by the JACK and ASIO hosts.
+    #[allow(dead_code)]
+    Foreign,
+}
+
+// Builds the stream of `build_input_stream_with_options`, for hosts running the data callback on
+// the given kind of thread.
+pub(crate) fn build_input_stream_with_options<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    options: &StreamOptions,
+    thread: CallbackThread,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample + Send + 'static,
+    D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    validate_options(options)?;
+    let mut thread_options = ThreadOptions::new(options, thread);
+    if thread_options.is_empty() && options.callback_buffer_size.is_none() {
+        let periods = options.periods;
+        return build_typed_input_stream(device, config, periods, data_callback, error_callback);
+    }
+    let (mut promotion_error_callback, error_callback) =
+        duplex::shared_error_callback(error_callback);
+    let mut blocks = Blocks::new(config.channels, options.callback_buffer_size);
+    build_typed_input_stream(
+        device,
+        config,
+        options.periods,
+        move |data: &[T], info: &InputCallbackInfo| {
+            thread_options.apply(&mut promotion_error_callback);
+            blocks.input(data, info, &mut data_callback)
+        },
+        error_callback,
+    )
+}
+
+// Builds the stream of `build_output_stream_with_options`, for hosts running the data callback on
+// the given kind of thread.
+pub(crate) fn build_output_stream_with_options<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    options: &StreamOptions,
+    thread: CallbackThread,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample + Send + 'static,
+    D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    validate_options(options)?;
+    let mut thread_options = ThreadOptions::new(options, thread);
+    if thread_options.is_empty() && options.callback_buffer_size.is_none() {
+        let periods = options.periods;
+        return build_typed_output_stream(device, config, periods, data_callback, error_callback);
+    }
+    let (mut promotion_error_callback, error_callback) =
+        duplex::shared_error_callback(error_callback);
+    let mut blocks = Blocks::new(config.channels, options.callback_buffer_size);
+    build_typed_output_stream(
+        device,
+        config,
+        options.periods,
+        move |data: &mut [T], info: &OutputCallbackInfo| {
+            thread_options.apply(&mut promotion_error_callback);
+            blocks.output(data, info, &mut data_callback)
+        },
+        error_callback,
+    )
+}
+
+fn validate_options(options: &StreamOptions) -> Result<(), BuildStreamError> {
+    if options.callback_buffer_size == Some(0)
+        || options.periods == Some(0)
+        || options.cpu_affinity == Some(Vec::new())
+    {
+        return Err(BuildStreamError::InvalidArgument);
+    }
+    Ok(())
+}
+
+// The `StreamOptions` applied to the thread running the data callback when it is first invoked.
+struct ThreadOptions {
+    realtime: bool,
+    cpu_affinity: Option<Vec<usize>>,
+    applied: bool,
+}
+
+impl ThreadOptions {
+    // The options of threads that cpal does not own are ignored.
+    fn new(options: &StreamOptions, thread: CallbackThread) -> Self {
+        let owned = thread == CallbackThread::Owned;
+        ThreadOptions {
+            realtime: owned && options.realtime,
+            cpu_affinity: options.cpu_affinity.clone().filter(|_| owned),
+            applied: false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.realtime && self.cpu_affinity.is_none()
+    }
+
+    // Applies the options unless they already were, reporting failures to `error_callback`.
+    fn apply<E>(&mut self, error_callback: &mut E)
+    where
+        E: FnMut(StreamError),
+    {
+        if self.applied {
+            return;
+        }
+        self.applied = true;
+        if let Some(ref cpus) = self.cpu_affinity {
+            if let Err(err) = realtime::pin_current_thread(cpus) {
+                error_callback(err.into());
+            }
+        }
+        if self.realtime {
+            if let Err(err) = realtime::promote_current_thread() {
+                error_callback(err.into());
+            }
+        }
+    }
+}
+
+// Builds the typed input stream of `build_input_stream`, with the given number of periods if any.
+fn build_typed_input_stream<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    periods: Option<u32>,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample,
+    D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    config.validate()?;
+    if !channels_supported(device.supported_input_configs(), config.channels) {
+        return Err(BuildStreamError::StreamConfigNotSupported);
+    }
+    let (mut panic_error_callback, error_callback) = duplex::shared_error_callback(error_callback);
+    let mut panic_guard = PanicGuard::new();
+    let mut overrun_check = OverrunCheck::new();
+    let data_callback = move |data: &Data, info: &InputCallbackInfo| {
+        let data = data
+            .as_slice()
+            .expect("host supplied incorrect sample type");
+        panic_guard.run(
+            || overrun_check.run(info.budget(), || data_callback(data, info)),
+            &mut panic_error_callback,
+        );
+    };
+    match periods {
+        None => device.build_input_stream_raw(config, T::FORMAT, data_callback, error_callback),
+        Some(periods) => device.build_input_stream_raw_with_periods(
+            config,
+            T::FORMAT,
+            periods,
+            data_callback,
+            error_callback,
+        ),
+    }
+}
+
+// Builds the typed output stream of `build_output_stream`, with the given number of periods if
+// any.
+fn build_typed_output_stream<Dev, T, D, E>(
+    device: &Dev,
+    config: &StreamConfig,
+    periods: Option<u32>,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<Dev::Stream, BuildStreamError>
+where
+    Dev: DeviceTrait + ?Sized,
+    T: Sample,
+    D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    config.validate()?;
+    if !channels_supported(device.supported_output_configs(), config.channels) {
+        return Err(BuildStreamError::StreamConfigNotSupported);
+    }
+    let (mut panic_error_callback, error_callback) = duplex::shared_error_callback(error_callback);
+    let mut panic_guard = PanicGuard::new();
+    let mut overrun_check = OverrunCheck::new();
+    let data_callback = move |data: &mut Data, info: &OutputCallbackInfo| {
+        let data = data
+            .as_slice_mut()
+            .expect("host supplied incorrect sample type");
+        let completed = panic_guard.run(
+            || overrun_check.run(info.budget(), || data_callback(data, info)),
+            &mut panic_error_callback,
+        );
+        if !completed {
+            data.iter_mut().for_each(|sample| *sample = T::EQUILIBRIUM);
+        }
+    };
+    match periods {
+        None => device.build_output_stream_raw(config, T::FORMAT, data_callback, error_callback),
+        Some(periods) => device.build_output_stream_raw_with_periods(
+            config,
+            T::FORMAT,
+            periods,
+            data_callback,
+            error_callback,
+        ),
+    }
+}
+
+// Creates the intermediate buffer used when mixing between channel layouts, with enough capacity
+// to avoid allocating in the callback if the stream's buffer size is known.
+fn mix_buffer<T>(config: &StreamConfig, channels: ChannelCount) -> Vec<T> {
+    match config.buffer_size {
+        BufferSize::Fixed(frames) => Vec::with_capacity(frames as usize * channels as usize),
+        BufferSize::Default => Vec::new(),
+    }
+}
+
+// The first of `sample_formats` that one of the `supported` configs allows along with `config`.
+fn preferred_format(
+    supported: &[SupportedStreamConfigRange],
+    config: &StreamConfig,
+    sample_formats: &[SampleFormat],
+) -> Result<SampleFormat, BuildStreamError> {
+    sample_formats
+        .iter()
+        .cloned()
+        .find(|&sample_format| {
+            supported
+                .iter()
+                .any(|range| range.why_unsupported(config, sample_format).is_none())
+        })
+        .ok_or(BuildStreamError::StreamConfigNotSupported)
+}
+
+// Maps an error enumerating the supported configs to the error returned when choosing a default
+// config from them.
+fn default_config_error(err: SupportedStreamConfigsError) -> DefaultStreamConfigError {
+    match err {
+        SupportedStreamConfigsError::DeviceNotAvailable => {
+            DefaultStreamConfigError::DeviceNotAvailable
+        }
+        SupportedStreamConfigsError::InvalidArgument => {
+            DefaultStreamConfigError::StreamTypeNotSupported
+        }
+        SupportedStreamConfigsError::BackendSpecific { err } => err.into(),
+    }
+}
+
+// Collects the sorted, deduplicated channel counts from an iterator of supported configs.
+fn channel_counts<I>(configs: I) -> Vec<ChannelCount>
+where
+    I: Iterator<Item = SupportedStreamConfigRange>,
+{
+    let mut counts: Vec<_> = configs.map(|c| c.channels()).collect();
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+// The distinct sample rates of the given configs, listing continuous ranges as the common rates
+// within them.
+fn sample_rates<I>(configs: I) -> Vec<SampleRate>
+where
+    I: Iterator<Item = SupportedStreamConfigRange>,
+{
+    let mut rates = Vec::new();
+    for config in configs {
+        let (min, max) = (config.min_sample_rate(), config.max_sample_rate());
+        rates.push(min);
+        rates.push(max);
+        let common = COMMON_SAMPLE_RATES.iter().cloned();
+        rates.extend(common.filter(|&rate| min < rate && rate < max));
+    }
+    rates.sort_unstable();
+    rates.dedup();
+    rates
+}
+
+// Collects the supported configs of a device, treating an unsupported direction as empty.
+fn supported_or_empty<I>(
+    configs: Result<I, SupportedStreamConfigsError>,
+) -> Result<Vec<SupportedStreamConfigRange>, SupportedStreamConfigsError>
+where
+    I: Iterator<Item = SupportedStreamConfigRange>,
+{
+    match configs {
+        Ok(configs) => Ok(configs.collect()),
+        Err(SupportedStreamConfigsError::InvalidArgument) => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+// The default config of a device, treating an unsupported direction as `None`.
+fn default_or_none(
+    config: Result<SupportedStreamConfig, DefaultStreamConfigError>,
+) -> Result<Option<SupportedStreamConfig>, SupportedStreamConfigsError> {
+    match config {
+        Ok(config) => Ok(Some(config)),
+        Err(DefaultStreamConfigError::StreamTypeNotSupported) => Ok(None),
+        Err(DefaultStreamConfigError::DeviceNotAvailable) => {
+            Err(SupportedStreamConfigsError::DeviceNotAvailable)
+        }
+        Err(DefaultStreamConfigError::BackendSpecific { err }) => Err(err.into()),
+    }
+}
+
+// The buffer size range of the first of the given supported configs that includes `config`.
+fn buffer_size_of<I>(
+    mut configs: I,
+    config: &SupportedStreamConfig,
+) -> Result<SupportedBufferSize, SupportedStreamConfigsError>
+where
+    I: Iterator<Item = SupportedStreamConfigRange>,
+{
+    configs
+        .find(|c| {
+            c.channels() == config.channels()
+                && c.sample_format() == config.sample_format()
+                && c.min_sample_rate() <= config.sample_rate()
+                && config.sample_rate() <= c.max_sample_rate()
+        })
+        .map(|c| c.buffer_size().clone())
+        .ok_or(SupportedStreamConfigsError::InvalidArgument)
+}
+
+// Whether `channels` appears in any of the given supported configs.
+//
+// If the configs cannot be enumerated the check is skipped and the host gets to decide whether
+// the requested config can be used.
+fn channels_supported<I>(
+    configs: Result<I, SupportedStreamConfigsError>,
+    channels: ChannelCount,
+) -> bool
+where
+    I: Iterator<Item = SupportedStreamConfigRange>,
+{
+    match configs {
+        Ok(mut configs) => configs.any(|c| c.channels() == channels),
+        Err(_) => true,
+    }
+}
+
+/// A stream created from `Device`, with methods to control playback.
+pub trait StreamTrait {
+    /// Run the stream.
+    ///
+    /// Note: Not all platforms automatically run the stream upon creation, so it is important to
+    /// call `play` after creation if it is expected that the stream should run immediately.
+    fn play(&self) -> Result<(), PlayStreamError>;
+
+    /// Some devices support pausing the audio stream. This can be useful for saving energy in
+    /// moments of silence.
+    ///
+    /// Note: Not all devices support suspending the stream at the hardware level. Hosts then keep
+    /// the device running, writing silence to output streams and discarding the audio captured by
+    /// input streams, without invoking the data callback.
+    fn pause(&self) -> Result<(), PauseStreamError>;
+
+    /// The configuration with which the stream is running.
+    ///
+    /// Some hosts settle on a configuration that differs from the one requested when building
+    /// the stream, e.g. by picking the nearest sample rate supported by the device. Where the host
+    /// exposes it, `BufferSize::Default` is also replaced with the buffer size that was chosen.
+    fn config(&self) -> StreamConfig;
+
+    /// The number of frames passed to (for output streams) or received from (for input streams)
+    /// the data callback since the stream was built.
+    ///
+    /// The position is monotonic. It does not advance while the stream is paused, as the data
+    /// callback is not invoked, and it is not reset by `play`. For output streams, the frames
+    /// most recently written are yet to be heard: use `OutputStreamTimestamp::playback` to find out
+    /// when they will be played.
+    fn frame_position(&self) -> u64;
+
+    /// Whether the stream is playing, as opposed to paused.
+    ///
+    /// This reflects the most recent call to `play` or `pause`. Some hosts start running streams
+    /// as soon as they are built, in which case this is `true` before `play` is first called.
+    fn is_playing(&self) -> bool;
 }
@@ -0,0 +1,134 @@
+use ring_buffer::Consumer;
+use {Sample, StreamError};
+
+/// What an output stream created via `DeviceTrait::build_output_stream_from_consumer_with_policy`
+/// plays when its ring buffer runs dry.
+///
+/// The underrun counter of the ring buffer is incremented whatever the policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnderrunPolicy {
+    /// Play silence in place of the missing samples, e.g. for a synthesizer, where repeating
+    /// anything would be heard as a glitch.
+    Silence,
+    /// Play the missing samples of the previous buffer again, which masks short gaps in e.g.
+    /// music. Only one buffer is repeated: should the ring buffer stay dry, silence is played
+    /// until it is refilled.
+    RepeatLast,
+    /// Play silence and report the underrun to the error callback as `StreamError::Xrun`.
+    Report,
+}
+
+// Fills output buffers from a ring buffer, handling underruns according to an `UnderrunPolicy`.
+pub(crate) struct UnderrunFiller<T> {
+    consumer: Consumer<T>,
+    policy: UnderrunPolicy,
+    // The previous buffer, for `UnderrunPolicy::RepeatLast`, or empty if it was repeated.
+    previous: Vec<T>,
+}
+
+impl<T> UnderrunFiller<T>
+where
+    T: Sample,
+{
+    pub(crate) fn new(consumer: Consumer<T>, policy: UnderrunPolicy) -> Self {
+        UnderrunFiller {
+            consumer,
+            policy,
+            previous: Vec::new(),
+        }
+    }
+
+    pub(crate) fn fill<E>(&mut self, data: &mut [T], error_callback: &mut E)
+    where
+        E: FnMut(StreamError),
+    {
+        let n = self.consumer.fill(data);
+        let underrun = n < data.len();
+        match self.policy {
+            UnderrunPolicy::Silence => (),
+            UnderrunPolicy::RepeatLast => {
+                if underrun {
+                    // Buffers may differ in length, in which case the previous one is looped.
+                    let len = self.previous.len();
+                    if len > 0 {
+                        for (i, sample) in data.iter_mut().enumerate().skip(n) {
+                            *sample = self.previous[i % len];
+                        }
+                    }
+                }
+                self.previous.clear();
+                if !underrun {
+                    // Grows to the largest buffer, after which it is not reallocated anymore.
+                    self.previous.extend_from_slice(data);
+                }
+            }
+            UnderrunPolicy::Report => {
+                if underrun {
+                    error_callback(StreamError::Xrun);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ring_buffer;
+    use std::sync::{Arc, Mutex};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {SampleRate, StreamError, UnderrunPolicy};
+
+    fn play(policy: UnderrunPolicy, samples: &[f32]) -> (Vec<f32>, Vec<StreamError>, usize) {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let (mut producer, consumer) = ring_buffer::ring_buffer(16, 1);
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = errors.clone();
+        let stream = device
+            .build_output_stream_from_consumer_with_policy(&config, consumer, policy, move |err| {
+                recorded.lock().unwrap().push(err)
+            })
+            .unwrap();
+        stream.play().unwrap();
+        producer.push_slice(samples);
+        for _ in 0..3 {
+            device.process(4);
+        }
+        let errors = errors.lock().unwrap().drain(..).collect();
+        (device.recorded_output(), errors, producer.underruns())
+    }
+
+    #[test]
+    fn silence() {
+        let (output, errors, underruns) = play(UnderrunPolicy::Silence, &[0.5; 6]);
+        assert_eq!(
+            output,
+            [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert!(errors.is_empty());
+        assert_eq!(underruns, 2);
+    }
+
+    #[test]
+    fn repeat_last() {
+        let samples = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let (output, errors, underruns) = play(UnderrunPolicy::RepeatLast, &samples);
+        // The gap of the second buffer is filled from the first, but that is not repeated.
+        assert_eq!(
+            output,
+            [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.3, 0.4, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert!(errors.is_empty());
+        assert_eq!(underruns, 2);
+    }
+
+    #[test]
+    fn report() {
+        let (output, errors, underruns) = play(UnderrunPolicy::Report, &[0.5; 6]);
+        assert_eq!(output[6..], [0.0; 6]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|err| matches!(err, StreamError::Xrun)));
+        assert_eq!(underruns, 2);
+    }
+}
@@ -0,0 +1,124 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use StreamError;
+
+// Keeps panics of the data callback from unwinding into the host, which may abort the process or
+// leave its locks poisoned.
+//
+// A panic is reported once to the error callback as `StreamError::CallbackPanic`, after which the
+// data callback is no longer invoked, as it may have been left in an inconsistent state.
+pub(crate) struct PanicGuard {
+    panicked: bool,
+}
+
+impl PanicGuard {
+    pub(crate) fn new() -> Self {
+        PanicGuard { panicked: false }
+    }
+
+    // Runs `f` unless it panicked before, returning whether it ran to completion.
+    pub(crate) fn run<F, E>(&mut self, f: F, error_callback: &mut E) -> bool
+    where
+        F: FnOnce(),
+        E: FnMut(StreamError),
+    {
+        if self.panicked {
+            return false;
+        }
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(()) => true,
+            Err(payload) => {
+                self.panicked = true;
+                let message = panic_message(&*payload);
+                error_callback(StreamError::CallbackPanic { message });
+                false
+            }
+        }
+    }
+}
+
+// The message passed to `panic!`, if any.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use test::TestDevice;
+    use traits::{DeviceTrait, StreamTrait};
+    use {InputCallbackInfo, OutputCallbackInfo, SampleRate, StreamError};
+
+    fn recorded_errors() -> (Arc<Mutex<Vec<StreamError>>>, impl FnMut(StreamError) + Send) {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = errors.clone();
+        (errors, move |err| recorded.lock().unwrap().push(err))
+    }
+
+    #[test]
+    fn output_callback_panic() {
+        let device = TestDevice::new(1, SampleRate(48_000));
+        let config = device.default_output_config().unwrap().config();
+        let (errors, error_callback) = recorded_errors();
+        let mut calls = 0;
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _: &OutputCallbackInfo| {
+                    calls += 1;
+                    data.iter_mut().for_each(|sample| *sample = 0.5);
+                    if calls == 2 {
+                        panic!("callback {} failed", calls);
+                    }
+                },
+                error_callback,
+            )
+            .unwrap();
+        stream.play().unwrap();
+        for _ in 0..3 {
+            device.process(4);
+        }
+        assert_eq!(
+            device.recorded_output(),
+            [0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        let errors = errors.lock().unwrap();
+        match errors[..] {
+            [StreamError::CallbackPanic { ref message }] => {
+                assert_eq!(message, "callback 2 failed")
+            }
+            ref errors => panic!("unexpected errors {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn input_callback_panic() {
+        let device = TestDevice::new(1, SampleRate(48_000)).with_input(vec![0.25; 16]);
+        let config = device.default_input_config().unwrap().config();
+        let (errors, error_callback) = recorded_errors();
+        let calls = Arc::new(Mutex::new(0));
+        let counted = calls.clone();
+        let stream = device
+            .build_input_stream(
+                &config,
+                move |_: &[f32], _: &InputCallbackInfo| {
+                    *counted.lock().unwrap() += 1;
+                    panic!("input failed");
+                },
+                error_callback,
+            )
+            .unwrap();
+        stream.play().unwrap();
+        device.process(4);
+        device.process(4);
+        assert_eq!(*calls.lock().unwrap(), 1);
+        assert_eq!(errors.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,217 @@
+//! A minimal WAV reader and writer, enough to move PCM and floating point audio in and out of
+//! files without pulling in another dependency.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use {ChannelCount, Sample, SampleFormat, SampleRate};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// The size of the header written by `WavWriter`, up to and including the `data` chunk header.
+const HEADER_LEN: u32 = 44;
+
+/// The layout of the audio in a WAV file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct WavSpec {
+    pub(crate) channels: ChannelCount,
+    pub(crate) sample_rate: SampleRate,
+}
+
+// Writes interleaved samples to a WAV file.
+//
+// The RIFF and data chunk sizes are only known once all samples have been written, so they are
+// patched in by `finalize`.
+pub(crate) struct WavWriter<W: Write + Seek> {
+    inner: W,
+    format: SampleFormat,
+    data_len: u32,
+}
+
+impl<W> WavWriter<W>
+where
+    W: Write + Seek,
+{
+    // Writes the header. `I16` and `U16` are both stored as 16-bit signed PCM, `U8` as 8-bit
+    // unsigned PCM and `F32` as 32-bit IEEE float.
+    pub(crate) fn new(mut inner: W, spec: WavSpec, format: SampleFormat) -> io::Result<Self> {
+        let (tag, bits) = match format {
+            SampleFormat::I16 | SampleFormat::U16 => (WAVE_FORMAT_PCM, 16u16),
+            SampleFormat::U8 => (WAVE_FORMAT_PCM, 8),
+            SampleFormat::F32 => (WAVE_FORMAT_IEEE_FLOAT, 32),
+        };
+        let block_align = spec.channels * bits / 8;
+        inner.write_all(b"RIFF")?;
+        inner.write_all(&(HEADER_LEN - 8).to_le_bytes())?;
+        inner.write_all(b"WAVEfmt ")?;
+        inner.write_all(&16u32.to_le_bytes())?;
+        inner.write_all(&tag.to_le_bytes())?;
+        inner.write_all(&spec.channels.to_le_bytes())?;
+        inner.write_all(&spec.sample_rate.0.to_le_bytes())?;
+        inner.write_all(&(spec.sample_rate.0 * block_align as u32).to_le_bytes())?;
+        inner.write_all(&block_align.to_le_bytes())?;
+        inner.write_all(&bits.to_le_bytes())?;
+        inner.write_all(b"data")?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter {
+            inner,
+            format,
+            data_len: 0,
+        })
+    }
+
+    // Appends interleaved samples, converting them to the format given to `new`.
+    pub(crate) fn write<T>(&mut self, samples: &[T]) -> io::Result<()>
+    where
+        T: Sample,
+    {
+        for sample in samples {
+            match self.format {
+                SampleFormat::I16 | SampleFormat::U16 => {
+                    self.inner.write_all(&sample.to_i16().to_le_bytes())?
+                }
+                SampleFormat::U8 => self.inner.write_all(&[sample.to_u8()])?,
+                SampleFormat::F32 => self.inner.write_all(&sample.to_f32().to_le_bytes())?,
+            }
+        }
+        self.data_len += (samples.len() * self.format.sample_size()) as u32;
+        Ok(())
+    }
+
+    // Patches the chunk sizes into the header and flushes the writer.
+    pub(crate) fn finalize(mut self) -> io::Result<W> {
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.inner
+            .write_all(&(HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(HEADER_LEN as u64 - 4))?;
+        self.inner.write_all(&self.data_len.to_le_bytes())?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+// Reads a whole WAV file, converting its samples to `f32`.
+//
+// Supports 8, 16, 24 and 32-bit integer PCM as well as 32-bit float, including when described
+// by a `WAVE_FORMAT_EXTENSIBLE` header.
+pub(crate) fn read<R>(mut reader: R) -> io::Result<(WavSpec, Vec<f32>)>
+where
+    R: Read,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid_data("not a RIFF WAVE file"));
+    }
+
+    let mut format = None;
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let id = &bytes[position..position + 4];
+        let len = read_u32(&bytes[position + 4..]) as usize;
+        let body = position + 8;
+        let end = body.saturating_add(len).min(bytes.len());
+        let chunk = &bytes[body..end];
+        if id == b"fmt " {
+            if chunk.len() < 16 {
+                return Err(invalid_data("truncated fmt chunk"));
+            }
+            let mut tag = read_u16(chunk);
+            if tag == WAVE_FORMAT_EXTENSIBLE {
+                if chunk.len() < 26 {
+                    return Err(invalid_data("truncated fmt chunk"));
+                }
+                // The first two bytes of the sub-format GUID hold the actual format tag.
+                tag = read_u16(&chunk[24..]);
+            }
+            let spec = WavSpec {
+                channels: read_u16(&chunk[2..]),
+                sample_rate: SampleRate(read_u32(&chunk[4..])),
+            };
+            format = Some((tag, read_u16(&chunk[14..]), spec));
+        } else if id == b"data" {
+            let (tag, bits, spec) = format.ok_or_else(|| invalid_data("data before fmt chunk"))?;
+            if spec.channels == 0 {
+                return Err(invalid_data("zero channels"));
+            }
+            let samples = match (tag, bits) {
+                (WAVE_FORMAT_PCM, 8) => chunk.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+                (WAVE_FORMAT_PCM, 16) => chunk
+                    .chunks_exact(2)
+                    .map(|b| (read_u16(b) as i16).to_f32())
+                    .collect(),
+                (WAVE_FORMAT_PCM, 24) => chunk
+                    .chunks_exact(3)
+                    .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0)
+                    .collect(),
+                (WAVE_FORMAT_PCM, 32) => chunk
+                    .chunks_exact(4)
+                    .map(|b| read_u32(b) as i32 as f32 / 2_147_483_648.0)
+                    .collect(),
+                (WAVE_FORMAT_IEEE_FLOAT, 32) => chunk
+                    .chunks_exact(4)
+                    .map(|b| f32::from_bits(read_u32(b)))
+                    .collect(),
+                _ => return Err(invalid_data("unsupported sample format")),
+            };
+            return Ok((spec, samples));
+        }
+        // Chunks are padded to an even number of bytes.
+        position = body.saturating_add(len + (len & 1));
+    }
+    Err(invalid_data("missing data chunk"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read, WavSpec, WavWriter};
+    use std::io::Cursor;
+    use {SampleFormat, SampleRate};
+
+    const SPEC: WavSpec = WavSpec {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+    };
+
+    #[test]
+    fn round_trip_f32() {
+        let samples = [0.0f32, 0.25, -0.5, 1.0];
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), SPEC, SampleFormat::F32).unwrap();
+        writer.write(&samples).unwrap();
+        let bytes = writer.finalize().unwrap().into_inner();
+        assert_eq!(bytes.len(), 44 + 16);
+        let (spec, read_samples) = read(&bytes[..]).unwrap();
+        assert_eq!(spec, SPEC);
+        assert_eq!(read_samples, samples);
+    }
+
+    #[test]
+    fn round_trip_i16() {
+        let samples = [0i16, i16::MAX, i16::MIN, 0];
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), SPEC, SampleFormat::I16).unwrap();
+        writer.write(&samples).unwrap();
+        let bytes = writer.finalize().unwrap().into_inner();
+        let (_, read_samples) = read(&bytes[..]).unwrap();
+        assert_eq!(read_samples, [0.0, 1.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(read(&b"RIFF\0\0\0\0AVI LIST"[..]).is_err());
+        assert!(read(&b"RIFF\x04\0\0\0WAVE"[..]).is_err());
+    }
+}